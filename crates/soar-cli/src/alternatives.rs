@@ -0,0 +1,113 @@
+use nu_ansi_term::Color::{Blue, Cyan, Green, Magenta, Yellow};
+use soar_core::SoarResult;
+use soar_operations::{alternatives, AlternativeGroup, AlternativeProvider, SoarContext};
+use tabled::{
+    builder::Builder,
+    settings::{peaker::PriorityMax, themes::BorderCorrection, Style, Width},
+};
+use tracing::info;
+
+use crate::{
+    cli::AlternativesAction,
+    json_output::{self, AlternativeJson, Listing},
+    utils::{icon_or, json_enabled, term_width, Colored, Icons},
+};
+
+pub fn handle_alternatives_action(ctx: &SoarContext, action: AlternativesAction) -> SoarResult<()> {
+    match action {
+        AlternativesAction::List {
+            name,
+        } => display_alternatives(&alternatives::list_alternatives(ctx, name.as_deref())?),
+        AlternativesAction::Set {
+            name,
+            package,
+        } => {
+            let group = alternatives::set_alternative(ctx, &name, &package)?;
+            report(&group);
+        }
+        AlternativesAction::Auto {
+            name,
+        } => {
+            let group = alternatives::auto_alternative(ctx, &name)?;
+            report(&group);
+        }
+    }
+    Ok(())
+}
+
+fn display_alternatives(groups: &[AlternativeGroup]) {
+    if json_enabled() {
+        let items = groups.iter().map(AlternativeJson::from).collect::<Vec<_>>();
+        let total = items.len();
+        json_output::emit(&Listing::new(items, total));
+        return;
+    }
+
+    if groups.is_empty() {
+        info!("No command is provided by more than one package");
+        return;
+    }
+
+    let mut builder = Builder::new();
+    builder.push_record([
+        "Command", "Mode", "", "Package", "Version", "Priority", "Runs",
+    ]);
+    for group in groups {
+        for (idx, provider) in group.providers.iter().enumerate() {
+            let (command, mode) = if idx == 0 {
+                (
+                    format!("{}", Colored(Cyan, &group.name)),
+                    if group.manual {
+                        format!("{}", Colored(Yellow, "manual"))
+                    } else {
+                        "auto".to_string()
+                    },
+                )
+            } else {
+                (String::new(), String::new())
+            };
+            builder.push_record([
+                command,
+                mode,
+                if idx == 0 {
+                    format!("{}", Colored(Green, icon_or(Icons::CHECK, "*")))
+                } else {
+                    String::new()
+                },
+                package_name(provider),
+                format!("{}", Colored(Magenta, &provider.package.version)),
+                provider.priority.to_string(),
+                provider.target.display().to_string(),
+            ]);
+        }
+    }
+
+    let table = builder
+        .build()
+        .with(Style::rounded())
+        .with(BorderCorrection {})
+        .with(Width::wrap(term_width()).priority(PriorityMax::default()))
+        .to_string();
+    info!("\n{table}");
+}
+
+fn report(group: &AlternativeGroup) {
+    let Some(provider) = group.providers.first() else {
+        return;
+    };
+    info!(
+        "{} is provided by {} ({})",
+        Colored(Cyan, &group.name),
+        package_name(provider),
+        if group.manual { "manual" } else { "auto" }
+    );
+}
+
+/// The package's name, with its pkg_id when it has one.
+fn package_name(provider: &AlternativeProvider) -> String {
+    let package = &provider.package;
+    match &package.pkg_id {
+        Some(pkg_id) => format!("{}#{pkg_id}", Colored(Blue, &package.pkg_name)),
+        None => format!("{}", Colored(Blue, &package.pkg_name)),
+    }
+}
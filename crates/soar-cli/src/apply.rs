@@ -13,7 +13,7 @@ use tracing::{info, warn};
 use crate::{
     json_output::{self, ApplyDiffJson},
     progress::create_wait_job,
-    utils::{display_settings, icon_or, json_enabled, Colored, Icons},
+    utils::{cancel_on_interrupt, display_settings, icon_or, json_enabled, Colored, Icons},
 };
 
 pub async fn apply_packages(
@@ -86,6 +86,7 @@ pub async fn apply_packages(
         }
     }
 
+    cancel_on_interrupt(ctx);
     let report = apply::execute_apply(ctx, diff, no_verify).await?;
     display_apply_report(&report);
 
@@ -98,6 +99,7 @@ fn display_diff(diff: &ApplyDiff, prune: bool) {
 
     if !diff.to_install.is_empty()
         || !diff.to_update.is_empty()
+        || !diff.to_relaunch.is_empty()
         || (prune && !diff.to_remove.is_empty())
     {
         let mut builder = Builder::new();
@@ -131,6 +133,15 @@ fn display_diff(diff: &ApplyDiff, prune: bool) {
             ]);
         }
 
+        for (pkg, _) in &diff.to_relaunch {
+            builder.push_record([
+                format!("{}", Colored(Cyan, icon_or("*", "*"))),
+                format!("{}", Colored(Blue, &pkg.pkg_name),),
+                format!("{} (launch settings)", Colored(Green, &pkg.version)),
+                format!("{}", Colored(Magenta, &pkg.repo_name)),
+            ]);
+        }
+
         if prune {
             for pkg in &diff.to_remove {
                 builder.push_record([
@@ -173,6 +184,12 @@ fn display_diff(diff: &ApplyDiff, prune: bool) {
             format!("{}", Colored(Yellow, diff.to_update.len())),
         ]);
     }
+    if !diff.to_relaunch.is_empty() {
+        summary_builder.push_record([
+            format!("{} To Relaunch", icon_or("*", "*")),
+            format!("{}", Colored(Cyan, diff.to_relaunch.len())),
+        ]);
+    }
     if prune && !diff.to_remove.is_empty() {
         summary_builder.push_record([
             format!("{} To Remove", icon_or("-", "-")),
@@ -212,6 +229,9 @@ fn display_diff(diff: &ApplyDiff, prune: bool) {
                 diff.in_sync.len()
             );
         }
+        if !diff.to_relaunch.is_empty() {
+            info!("{} with new launch settings", diff.to_relaunch.len());
+        }
     }
 }
 
@@ -220,6 +240,9 @@ fn display_apply_report(report: &ApplyReport) {
     info!("  Installed: {}", report.installed_count);
     info!("  Updated:   {}", report.updated_count);
     info!("  Removed:   {}", report.removed_count);
+    if report.relaunched_count > 0 {
+        info!("  Relaunched: {}", report.relaunched_count);
+    }
     if report.failed_count > 0 {
         warn!("  Failed:    {}", report.failed_count);
     }
@@ -0,0 +1,130 @@
+use nu_ansi_term::Color::{Blue, Green, Red, Yellow};
+use soar_core::{
+    audit::{AuditKind, AuditRecord},
+    SoarResult,
+};
+use soar_operations::{audit, AuditQuery, SoarContext};
+use tabled::{
+    builder::Builder,
+    settings::{peaker::PriorityMax, themes::BorderCorrection, Style, Width},
+};
+use tracing::info;
+
+use crate::{
+    json_output::{self, Listing},
+    utils::{icon_or, json_enabled, term_width, Colored, Icons},
+};
+
+pub fn display_audit(ctx: &SoarContext, query: &AuditQuery, output: bool) -> SoarResult<()> {
+    let records = audit::query_audit(ctx, query)?;
+
+    if json_enabled() {
+        let total = records.len();
+        json_output::emit(&Listing::new(records, total));
+        return Ok(());
+    }
+
+    if records.is_empty() {
+        info!("No recorded runs");
+        return Ok(());
+    }
+
+    let mut builder = Builder::new();
+    builder.push_record([
+        "", "Time", "Package", "Run", "Result", "Duration", "Command",
+    ]);
+    for record in &records {
+        builder.push_record([
+            status_icon(record),
+            record
+                .timestamp
+                .split_once('.')
+                .map_or(record.timestamp.as_str(), |(time, _)| time)
+                .replace('T', " "),
+            package_name(record),
+            format!("{} {}", kind_name(record.kind), record.name),
+            result(record),
+            format!("{:.1}s", record.duration_ms as f64 / 1000.0),
+            record.command.clone(),
+        ]);
+    }
+
+    let table = builder
+        .build()
+        .with(Style::rounded())
+        .with(BorderCorrection {})
+        .with(Width::wrap(term_width()).priority(PriorityMax::default()))
+        .to_string();
+    info!("\n{table}");
+
+    if output {
+        for record in &records {
+            show_output(record);
+        }
+    }
+
+    Ok(())
+}
+
+/// The package's name, with its pkg_id when it has one.
+fn package_name(record: &AuditRecord) -> String {
+    if record.pkg_id.is_empty() {
+        format!("{}", Colored(Blue, &record.pkg_name))
+    } else {
+        format!("{}#{}", Colored(Blue, &record.pkg_name), record.pkg_id)
+    }
+}
+
+fn kind_name(kind: AuditKind) -> &'static str {
+    match kind {
+        AuditKind::Hook => "hook",
+        AuditKind::Build => "build",
+    }
+}
+
+fn status_icon(record: &AuditRecord) -> String {
+    if record.violation.is_some() {
+        format!("{}", Colored(Red, icon_or(Icons::WARNING, "!")))
+    } else if record.failed() {
+        format!("{}", Colored(Red, icon_or(Icons::CROSS, "x")))
+    } else {
+        format!("{}", Colored(Green, icon_or(Icons::CHECK, "OK")))
+    }
+}
+
+fn result(record: &AuditRecord) -> String {
+    if let Some(violation) = &record.violation {
+        return format!("{}", Colored(Red, violation));
+    }
+    match (record.exit_code, record.signal) {
+        (Some(0), _) => "exit 0".to_string(),
+        (Some(code), _) => format!("{}", Colored(Red, format!("exit {code}"))),
+        (None, Some(signal)) => format!("{}", Colored(Red, format!("signal {signal}"))),
+        (None, None) => "unknown".to_string(),
+    }
+}
+
+fn show_output(record: &AuditRecord) {
+    info!(
+        "\n{} {} {} {} ({})",
+        Icons::LOG,
+        package_name(record),
+        kind_name(record.kind),
+        record.name,
+        record.timestamp
+    );
+    if record.truncated {
+        info!(
+            "{} output was cut to its last {} KiB",
+            Colored(Yellow, icon_or(Icons::WARNING, "!")),
+            soar_core::audit::OUTPUT_LIMIT / 1024
+        );
+    }
+    for (stream, text) in [("stdout", &record.stdout), ("stderr", &record.stderr)] {
+        if text.is_empty() {
+            continue;
+        }
+        info!("{}:", Colored(Green, stream));
+        info!("{}", text.trim_end());
+    }
+}
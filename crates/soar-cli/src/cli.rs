@@ -72,6 +72,40 @@ pub struct Args {
     pub command: Commands,
 }
 
+#[derive(Subcommand)]
+pub enum AlternativesAction {
+    /// Show the packages providing a command, or every command more than one
+    /// package provides
+    List {
+        /// The command
+        #[arg(required = false)]
+        name: Option<String>,
+    },
+    /// Give a command to one package, whatever the priorities say
+    Set {
+        /// The command
+        name: String,
+        /// Package to run it from, as pkg_name, pkg_id or pkg_name#pkg_id
+        package: String,
+    },
+    /// Give a command back to the package with the highest priority
+    Auto {
+        /// The command
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum MimeAction {
+    /// Make a package the default application for a MIME type
+    SetDefault {
+        /// Installed package whose desktop entry should open the type
+        package: String,
+        /// MIME type, or x-scheme-handler/<scheme> for a URL scheme
+        mime_type: String,
+    },
+}
+
 #[derive(Subcommand)]
 pub enum RepoAction {
     /// Add a new repository
@@ -80,6 +114,9 @@ pub enum RepoAction {
         name: String,
         /// Repository metadata URL
         url: String,
+        /// Delta manifest URL, for syncing only what changed
+        #[arg(long)]
+        delta_url: Option<String>,
         /// Base64-encoded public key for signature verification
         #[arg(long)]
         pubkey: Option<String>,
@@ -103,6 +140,9 @@ pub enum RepoAction {
         /// Repository metadata URL
         #[arg(long)]
         url: Option<String>,
+        /// Delta manifest URL, for syncing only what changed
+        #[arg(long)]
+        delta_url: Option<String>,
         /// Base64-encoded public key for signature verification
         #[arg(long)]
         pubkey: Option<String>,
@@ -128,6 +168,14 @@ pub enum RepoAction {
     /// List configured repositories
     #[clap(visible_alias = "ls")]
     List,
+    /// Check a metadata index for entries clients would skip or fail on
+    Lint {
+        /// Path or http(s) URL of the JSON index, optionally zstd-compressed
+        source: String,
+        /// Fail on warnings as well as errors
+        #[arg(long)]
+        strict: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -255,7 +303,8 @@ pub enum Commands {
     #[command(arg_required_else_help = true)]
     #[clap(name = "search", visible_alias = "s", visible_alias = "find")]
     Search {
-        /// Query to search
+        /// Query to search, optionally narrowed with category:, type:,
+        /// license: and repo: filters
         #[arg(required = true)]
         query: String,
 
@@ -275,6 +324,10 @@ pub enum Commands {
         /// Package to query
         #[arg(required = true)]
         query: String,
+
+        /// List the builds that can be installed with `pkg@<version|build-id>`
+        #[arg(required = false, long)]
+        versions: bool,
     },
 
     /// Remove packages
@@ -351,13 +404,36 @@ pub enum Commands {
         package: String,
     },
 
-    /// Inspect package build script
+    /// Inspect package build script, or the files bundled in a package
     #[command(arg_required_else_help = true)]
     #[clap(name = "inspect")]
     Inspect {
-        /// Package to view build script for
+        /// Package to view build script for, or with --contents or --extract,
+        /// an installed package or a path to a package file
         #[arg(required = true)]
         package: String,
+
+        /// List the files bundled in an onelf, AppImage, FlatImage, RunImage or Wrappe
+        #[arg(required = false, long)]
+        contents: bool,
+
+        /// List the bundled files, checking each against its recorded checksum
+        #[arg(required = false, long)]
+        verify: bool,
+
+        /// Extract the bundled files into a directory
+        #[arg(
+            required = false,
+            long,
+            value_name = "DIR",
+            value_hint = ValueHint::DirPath,
+            conflicts_with_all = ["contents", "verify"]
+        )]
+        extract: Option<String>,
+
+        /// Paths inside the package to extract, instead of the whole tree
+        #[arg(required = false, requires = "extract")]
+        paths: Vec<String>,
     },
 
     /// Run packages without installing to PATH
@@ -372,6 +448,11 @@ pub enum Commands {
         #[arg(required = false, long)]
         no_verify: bool,
 
+        /// Run inside the package's runtime sandbox from packages.toml, or a
+        /// strict default one
+        #[arg(required = false, long)]
+        sandbox: bool,
+
         /// Command to execute
         #[arg(required = true)]
         command: Vec<String>,
@@ -385,15 +466,50 @@ pub enum Commands {
         repo_name: Option<String>,
     },
 
+    /// Start a shell, or run a command, with packages on PATH without
+    /// installing them
+    #[command(arg_required_else_help = true)]
+    #[clap(name = "shell")]
+    Shell {
+        /// Skip all prompts and use first
+        #[arg(required = false, short, long)]
+        yes: bool,
+
+        /// Skip checksum verification before running
+        #[arg(required = false, long)]
+        no_verify: bool,
+
+        /// Remove the packages this shell downloaded from the cache on exit
+        #[arg(required = false, long)]
+        clean: bool,
+
+        /// Packages to make available
+        #[arg(required = true)]
+        packages: Vec<String>,
+
+        /// Command to run instead of $SHELL
+        #[arg(required = false, last = true)]
+        command: Vec<String>,
+    },
+
     /// Use package from different family
     #[command(arg_required_else_help = true)]
     #[clap(name = "use")]
     Use {
-        /// The package name to use alternative package for
+        /// The package name to use alternative package for, or name@version
+        /// to make that installed version the default
         #[arg(required = true)]
         package_name: String,
     },
 
+    /// Choose which installed package provides a command
+    #[command(arg_required_else_help = true)]
+    #[clap(name = "alternatives")]
+    Alternatives {
+        #[clap(subcommand)]
+        action: AlternativesAction,
+    },
+
     /// Download arbitrary files
     #[command(arg_required_else_help = true)]
     #[clap(name = "download", visible_alias = "dl")]
@@ -464,6 +580,30 @@ pub enum Commands {
     #[clap(name = "health")]
     Health,
 
+    /// Show recorded runs of hook and build commands
+    #[clap(name = "audit")]
+    Audit {
+        /// Only runs for this package
+        #[arg(required = false)]
+        package: Option<String>,
+
+        /// Only hooks, or only build commands
+        #[arg(required = false, long, value_parser = ["hook", "build"])]
+        kind: Option<String>,
+
+        /// Only runs that failed
+        #[arg(required = false, long)]
+        failed: bool,
+
+        /// Show at most this many of the most recent runs
+        #[arg(required = false, short = 'n', long)]
+        limit: Option<usize>,
+
+        /// Also print what each run wrote to stdout and stderr
+        #[arg(required = false, long)]
+        output: bool,
+    },
+
     /// Generate default config
     #[clap(name = "defconfig")]
     DefConfig {
@@ -495,6 +635,22 @@ pub enum Commands {
     #[clap(name = "plugin-manifest")]
     PluginManifest,
 
+    /// Serve soar to frontends as JSON-RPC over a unix socket
+    #[clap(name = "daemon")]
+    Daemon {
+        /// Socket to listen on (default: $XDG_RUNTIME_DIR/soar/daemon.sock)
+        #[arg(required = false, long, value_hint = ValueHint::FilePath)]
+        socket: Option<String>,
+    },
+
+    /// Choose which installed package opens a file type or URL scheme
+    #[command(arg_required_else_help = true)]
+    #[clap(name = "mime")]
+    Mime {
+        #[clap(subcommand)]
+        action: MimeAction,
+    },
+
     /// Act on a soar:// link, or register soar as its handler
     #[command(arg_required_else_help = true)]
     #[clap(name = "url")]
@@ -0,0 +1,800 @@
+//! `soar daemon`: soar's operations as JSON-RPC 2.0 over a unix socket.
+//!
+//! Each line a client writes is one request, and each line the daemon writes
+//! is one response or notification. While a request runs, the events it emits
+//! arrive as `event` notifications naming it, so a frontend can show progress
+//! without parsing a child's stdout. The databases stay open between
+//! requests, which is the cost a frontend shelling out pays every time.
+
+use std::{
+    collections::HashMap,
+    env,
+    fs::{self, DirBuilder},
+    os::unix::fs::{DirBuilderExt as _, MetadataExt as _},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use nix::{
+    sys::stat::{umask, Mode},
+    unistd::getuid,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use soar_config::packages::PackagesConfig;
+use soar_core::{
+    error::{ErrorContext, SoarError},
+    SoarResult,
+};
+use soar_events::{EventSink, EventSinkHandle, LogLevel, SoarEvent};
+use soar_operations::{
+    apply, install, remove, search, sync, update, InstallOptions, RemoveResolveResult,
+    ResolveResult, SoarContext,
+};
+use soar_utils::{cancel::CancellationToken, lock::FileLock};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::{UnixListener, UnixStream},
+    sync::{
+        mpsc::{self, error::TrySendError},
+        watch,
+    },
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    json_output::{
+        AmbiguousJson, ApplyDiffJson, ApplyReportJson, ChangedJson, InstallReportJson, Listing,
+        PackageDetailJson, PackageJson, RemoveReportJson, UpdateJson, UpdateReportJson,
+    },
+    utils::OPERATIONS_LOCK,
+};
+
+/// The methods the daemon serves, besides cancellation.
+pub const METHODS: [&str; 7] = [
+    "search", "query", "install", "remove", "update", "apply", "sync",
+];
+
+/// The notification that cancels a request, named as LSP names it.
+pub const CANCEL_METHOD: &str = "$/cancelRequest";
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+/// The operation ran and failed; the message is soar's error.
+const OPERATION_FAILED: i64 = -32000;
+const REQUEST_CANCELLED: i64 = -32800;
+
+/// Messages queued for a client before it counts as no longer reading.
+const QUEUE_LIMIT: usize = 1024;
+
+/// Where the daemon listens unless told otherwise: beside soar's lock files,
+/// or in a directory of this user's own under the temporary directory.
+fn default_socket() -> PathBuf {
+    let dir = match env::var_os("XDG_RUNTIME_DIR") {
+        Some(runtime) => PathBuf::from(runtime).join("soar"),
+        None => env::temp_dir().join(format!("soar-{}", getuid())),
+    };
+    dir.join("daemon.sock")
+}
+
+/// Create `dir` for the socket, accessible to this user only, or check that
+/// one already there is this user's alone.
+///
+/// Whoever can write to it could put their own socket in the daemon's place.
+fn prepare_socket_dir(dir: &Path) -> SoarResult<()> {
+    DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .with_context(|| format!("creating directory {}", dir.display()))?;
+    let meta =
+        fs::symlink_metadata(dir).with_context(|| format!("reading {}", dir.display()))?;
+    if !meta.is_dir() || meta.uid() != getuid().as_raw() {
+        return Err(SoarError::Custom(format!(
+            "{} is not a directory of this user's own, refusing to listen in it",
+            dir.display()
+        )));
+    }
+    if meta.mode() & 0o022 != 0 {
+        return Err(SoarError::Custom(format!(
+            "{} is writable by other users, refusing to listen in it",
+            dir.display()
+        )));
+    }
+    Ok(())
+}
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl RpcError {
+    fn cancelled() -> Self {
+        Self::new(REQUEST_CANCELLED, "request cancelled")
+    }
+}
+
+impl From<SoarError> for RpcError {
+    fn from(err: SoarError) -> Self {
+        if err.is_cancelled() {
+            return Self::cancelled();
+        }
+        Self::new(OPERATION_FAILED, err.to_string())
+    }
+}
+
+type RpcResult = Result<Value, RpcError>;
+
+#[derive(Deserialize)]
+struct Request {
+    jsonrpc: String,
+    #[serde(default)]
+    id: Option<Value>,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct SearchParams {
+    query: String,
+    #[serde(default)]
+    case_sensitive: bool,
+    #[serde(default)]
+    limit: Option<usize>,
+}
+
+#[derive(Deserialize)]
+struct QueryParams {
+    query: String,
+}
+
+#[derive(Deserialize)]
+struct InstallParams {
+    packages: Vec<String>,
+    #[serde(default)]
+    force: bool,
+    #[serde(default)]
+    binary_only: bool,
+    #[serde(default)]
+    no_verify: bool,
+}
+
+#[derive(Deserialize)]
+struct RemoveParams {
+    packages: Vec<String>,
+    #[serde(default)]
+    all: bool,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct UpdateParams {
+    /// Every updatable package when absent.
+    packages: Option<Vec<String>>,
+    keep: bool,
+    no_verify: bool,
+    check: bool,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ApplyParams {
+    packages_config: Option<String>,
+    prune: bool,
+    dry_run: bool,
+    no_verify: bool,
+}
+
+/// A request the daemon understood, with its parameters read.
+enum Call {
+    Search(SearchParams),
+    Query(QueryParams),
+    Install(InstallParams),
+    Remove(RemoveParams),
+    Update(UpdateParams),
+    Apply(ApplyParams),
+    Sync,
+}
+
+impl Call {
+    fn parse(method: &str, params: Value) -> Result<Self, RpcError> {
+        // Parameters that are all optional may be left out altogether.
+        let params = if params.is_null() { json!({}) } else { params };
+
+        fn read<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+            serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+        }
+
+        Ok(match method {
+            "search" => Call::Search(read(params)?),
+            "query" => Call::Query(read(params)?),
+            "install" => Call::Install(read(params)?),
+            "remove" => Call::Remove(read(params)?),
+            "update" => Call::Update(read(params)?),
+            "apply" => Call::Apply(read(params)?),
+            "sync" => Call::Sync,
+            _ => {
+                return Err(RpcError::new(
+                    METHOD_NOT_FOUND,
+                    format!("no method named {method}"),
+                ))
+            }
+        })
+    }
+
+    /// Whether this call changes what is installed or synced, and so waits
+    /// for any other that does.
+    fn mutates(&self) -> bool {
+        match self {
+            Call::Search(_) | Call::Query(_) => false,
+            Call::Update(params) => !params.check,
+            Call::Apply(params) => !params.dry_run,
+            Call::Install(_) | Call::Remove(_) | Call::Sync => true,
+        }
+    }
+}
+
+/// Forwards a request's events to its client as `event` notifications.
+struct NotificationSink {
+    request: Value,
+    connection: Arc<Connection>,
+}
+
+impl EventSink for NotificationSink {
+    fn emit(&self, event: SoarEvent) {
+        self.connection.send(json!({
+            "jsonrpc": "2.0",
+            "method": "event",
+            "params": { "request": self.request, "event": event },
+        }));
+    }
+}
+
+/// One client: where its messages go, and how to cancel what it asked for.
+struct Connection {
+    outgoing: mpsc::Sender<String>,
+    pending: Mutex<HashMap<String, CancellationToken>>,
+    /// Set once the client is let go for not reading what it is sent.
+    hangup: watch::Sender<bool>,
+}
+
+impl Connection {
+    /// Queue a message. A client that has gone away is not an error; what it
+    /// asked for still runs to the end.
+    ///
+    /// A client that leaves [`QUEUE_LIMIT`] messages unread is disconnected,
+    /// rather than queueing for it without end.
+    fn send(&self, message: Value) {
+        if let Err(TrySendError::Full(_)) = self.outgoing.try_send(message.to_string()) {
+            if !self.hangup.send_replace(true) {
+                warn!("Disconnecting a client that stopped reading its messages");
+            }
+        }
+    }
+
+    fn respond(&self, id: Value, result: RpcResult) {
+        let message = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(err) => {
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": err.code, "message": err.message },
+                })
+            }
+        };
+        self.send(message);
+    }
+
+    fn cancel(&self, params: &Value) {
+        let Some(id) = params.get("id") else {
+            return;
+        };
+        if let Some(cancel) = self.pending.lock().unwrap().get(&id.to_string()) {
+            debug!(id = %id, "cancelling request");
+            cancel.cancel();
+        }
+    }
+}
+
+struct Daemon {
+    ctx: Mutex<SoarContext>,
+}
+
+impl Daemon {
+    fn context(&self) -> SoarContext {
+        self.ctx.lock().unwrap().clone()
+    }
+
+    /// Drop the databases the context holds open, so requests after a change
+    /// read what it left rather than what was there before.
+    fn reload(&self) {
+        let mut ctx = self.ctx.lock().unwrap();
+        *ctx = SoarContext::new(ctx.config().clone(), ctx.events().clone());
+    }
+
+    async fn call(
+        &self,
+        call: Call,
+        events: EventSinkHandle,
+        cancel: CancellationToken,
+    ) -> RpcResult {
+        let _change = if call.mutates() {
+            Some(Change {
+                daemon: self,
+                _lock: wait_for_lock(&events, &cancel).await?,
+            })
+        } else {
+            None
+        };
+
+        let ctx = self.context().with_events(events).with_cancellation(cancel);
+        match call {
+            Call::Search(params) => search_packages(&ctx, params).await,
+            Call::Query(params) => query_package(&ctx, params).await,
+            Call::Install(params) => install_packages(&ctx, params).await,
+            Call::Remove(params) => remove_packages(&ctx, params).await,
+            Call::Update(params) => update_packages(&ctx, params).await,
+            Call::Apply(params) => apply_packages(&ctx, params).await,
+            Call::Sync => {
+                sync::sync_repos(&ctx).await?;
+                Ok(Value::Null)
+            }
+        }
+    }
+}
+
+/// Held while a request changes what is installed. Letting go reloads the
+/// context before the lock passes on, even when the request failed.
+struct Change<'a> {
+    daemon: &'a Daemon,
+    _lock: FileLock,
+}
+
+impl Drop for Change<'_> {
+    fn drop(&mut self) {
+        self.daemon.reload();
+    }
+}
+
+/// Take the daemon's lock, yielding while another request holds it so the
+/// wait can itself be cancelled.
+async fn wait_for_lock(
+    events: &EventSinkHandle,
+    cancel: &CancellationToken,
+) -> Result<FileLock, RpcError> {
+    let mut announced = false;
+    loop {
+        if cancel.is_cancelled() {
+            return Err(RpcError::cancelled());
+        }
+        if let Some(lock) = FileLock::try_acquire(OPERATIONS_LOCK)
+            .map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))?
+        {
+            return Ok(lock);
+        }
+        if !announced {
+            events.emit(SoarEvent::Log {
+                level: LogLevel::Info,
+                message: "Waiting for another operation to finish".into(),
+            });
+            announced = true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+fn answer<T: Serialize>(value: T) -> RpcResult {
+    serde_json::to_value(value).map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))
+}
+
+async fn search_packages(ctx: &SoarContext, params: SearchParams) -> RpcResult {
+    let result =
+        search::search_packages(ctx, &params.query, params.case_sensitive, params.limit).await?;
+    let items: Vec<PackageJson> = result.packages.iter().map(Into::into).collect();
+    answer(Listing::new(items, result.total_count))
+}
+
+async fn query_package(ctx: &SoarContext, params: QueryParams) -> RpcResult {
+    let packages = search::query_package(ctx, &params.query).await?;
+    let items: Vec<PackageDetailJson> = packages.iter().map(Into::into).collect();
+    let total = items.len();
+    answer(Listing::new(items, total))
+}
+
+/// Install what resolves to one package. A query naming several is answered
+/// with its candidates rather than a guess, for the caller to ask again.
+async fn install_packages(ctx: &SoarContext, params: InstallParams) -> RpcResult {
+    let options = InstallOptions {
+        force: params.force,
+        binary_only: params.binary_only,
+        no_verify: params.no_verify,
+        ..Default::default()
+    };
+
+    let mut report = InstallReportJson::default();
+    let mut targets = Vec::new();
+    for result in install::resolve_packages(ctx, &params.packages, &options).await? {
+        match result {
+            ResolveResult::Resolved(resolved) => targets.extend(resolved),
+            ResolveResult::Ambiguous(ambiguous) => {
+                report.ambiguous.push(AmbiguousJson {
+                    query: ambiguous.query,
+                    candidates: ambiguous.candidates.iter().map(Into::into).collect(),
+                });
+            }
+            ResolveResult::NotFound(query) => report.not_found.push(query),
+            ResolveResult::AlreadyInstalled {
+                pkg_name,
+                repo_name,
+                version,
+            } => {
+                report.already_installed.push(ChangedJson {
+                    name: pkg_name,
+                    family: None,
+                    repo: repo_name,
+                    version,
+                });
+            }
+        }
+    }
+
+    if !targets.is_empty() {
+        let done = install::perform_installation(ctx, targets, &options).await?;
+        report.installed = done.installed.iter().map(Into::into).collect();
+        report.failed = done.failed.iter().map(Into::into).collect();
+        report.warnings = done.warnings;
+    }
+
+    answer(report)
+}
+
+async fn remove_packages(ctx: &SoarContext, params: RemoveParams) -> RpcResult {
+    let mut report = RemoveReportJson::default();
+    let mut packages = Vec::new();
+    for result in remove::resolve_removals(ctx, &params.packages, params.all)? {
+        match result {
+            RemoveResolveResult::Resolved(resolved) => packages.extend(resolved),
+            RemoveResolveResult::Ambiguous {
+                query,
+                candidates,
+            } => {
+                report.ambiguous.push(AmbiguousJson {
+                    query,
+                    candidates: candidates.iter().map(Into::into).collect(),
+                });
+            }
+            RemoveResolveResult::NotInstalled(query) => report.not_installed.push(query),
+        }
+    }
+
+    if !packages.is_empty() {
+        let done = remove::perform_removal(ctx, packages).await?;
+        report.removed = done.removed.iter().map(Into::into).collect();
+        report.failed = done.failed.iter().map(Into::into).collect();
+    }
+
+    answer(report)
+}
+
+async fn update_packages(ctx: &SoarContext, params: UpdateParams) -> RpcResult {
+    let updates = update::check_updates(ctx, params.packages.as_deref()).await?;
+
+    if params.check {
+        let items: Vec<UpdateJson> = updates.iter().map(Into::into).collect();
+        let total = items.len();
+        return answer(Listing::new(items, total));
+    }
+
+    let mut report = UpdateReportJson::default();
+    if !updates.is_empty() {
+        let done = update::perform_update(ctx, updates, params.keep, params.no_verify).await?;
+        report.updated = done.updated.iter().map(Into::into).collect();
+        report.failed = done.failed.iter().map(Into::into).collect();
+    }
+
+    answer(report)
+}
+
+async fn apply_packages(ctx: &SoarContext, params: ApplyParams) -> RpcResult {
+    let config =
+        PackagesConfig::load(params.packages_config.as_deref()).map_err(SoarError::from)?;
+    let diff = apply::compute_diff(ctx, &config.resolved_packages(), params.prune).await?;
+
+    if params.dry_run {
+        return answer(ApplyDiffJson::new(&diff));
+    }
+
+    let report = apply::execute_apply(ctx, diff, params.no_verify).await?;
+    answer(ApplyReportJson::from(&report))
+}
+
+/// Read one line from a client and start what it asks for.
+fn dispatch(daemon: &Arc<Daemon>, connection: &Arc<Connection>, line: &str) {
+    let request: Request = match serde_json::from_str::<Value>(line) {
+        Ok(value) => {
+            match serde_json::from_value(value) {
+                Ok(request) => request,
+                Err(err) => {
+                    connection.respond(
+                        Value::Null,
+                        Err(RpcError::new(INVALID_REQUEST, err.to_string())),
+                    );
+                    return;
+                }
+            }
+        }
+        Err(err) => {
+            connection.respond(
+                Value::Null,
+                Err(RpcError::new(PARSE_ERROR, err.to_string())),
+            );
+            return;
+        }
+    };
+
+    if request.jsonrpc != "2.0" {
+        connection.respond(
+            request.id.unwrap_or(Value::Null),
+            Err(RpcError::new(
+                INVALID_REQUEST,
+                "only JSON-RPC 2.0 is spoken",
+            )),
+        );
+        return;
+    }
+
+    if request.method == CANCEL_METHOD {
+        connection.cancel(&request.params);
+        return;
+    }
+
+    // A notification asks for no answer, and none of the methods is worth
+    // running without one.
+    let Some(id) = request.id else {
+        return;
+    };
+
+    let call = match Call::parse(&request.method, request.params) {
+        Ok(call) => call,
+        Err(err) => {
+            connection.respond(id, Err(err));
+            return;
+        }
+    };
+
+    let cancel = CancellationToken::new();
+    let key = id.to_string();
+    connection
+        .pending
+        .lock()
+        .unwrap()
+        .insert(key.clone(), cancel.clone());
+
+    debug!(id = %id, method = request.method, "starting request");
+    let daemon = daemon.clone();
+    let connection = connection.clone();
+    tokio::spawn(async move {
+        let events: EventSinkHandle = Arc::new(NotificationSink {
+            request: id.clone(),
+            connection: connection.clone(),
+        });
+
+        // A cancelled request stops at its next step and undoes what it had
+        // started before answering.
+        let result = daemon.call(call, events, cancel).await;
+
+        connection.pending.lock().unwrap().remove(&key);
+        connection.respond(id, result);
+    });
+}
+
+/// Wait until the client is let go, or its connection is gone.
+async fn hung_up_on(hangup: &mut watch::Receiver<bool>) {
+    let _ = hangup.wait_for(|hung_up| *hung_up).await;
+}
+
+async fn serve_connection(daemon: Arc<Daemon>, stream: UnixStream) {
+    let (read, mut write) = stream.into_split();
+    let (outgoing, mut queued) = mpsc::channel::<String>(QUEUE_LIMIT);
+    let (hangup, mut hung_up) = watch::channel(false);
+    let mut writer_hung_up = hangup.subscribe();
+    let connection = Arc::new(Connection {
+        outgoing,
+        pending: Mutex::new(HashMap::new()),
+        hangup,
+    });
+
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                // Whatever is queued still goes out once the last request is
+                // answered and the connection is gone.
+                biased;
+                message = queued.recv() => {
+                    let Some(mut message) = message else {
+                        break;
+                    };
+                    message.push('\n');
+                    if write.write_all(message.as_bytes()).await.is_err() {
+                        break;
+                    }
+                }
+                _ = hung_up_on(&mut writer_hung_up) => break,
+            }
+        }
+    });
+
+    let mut lines = BufReader::new(read).lines();
+    loop {
+        tokio::select! {
+            line = lines.next_line() => {
+                let Ok(Some(line)) = line else {
+                    break;
+                };
+                if !line.trim().is_empty() {
+                    dispatch(&daemon, &connection, &line);
+                }
+            }
+            _ = hung_up_on(&mut hung_up) => break,
+        }
+    }
+    debug!("client disconnected");
+}
+
+/// Listen on `socket` until interrupted.
+pub async fn serve(ctx: &SoarContext, socket: Option<PathBuf>) -> SoarResult<()> {
+    let socket = socket.unwrap_or_else(default_socket);
+    if let Some(parent) = socket.parent().filter(|p| !p.as_os_str().is_empty()) {
+        prepare_socket_dir(parent)?;
+    }
+
+    if socket.exists() {
+        if UnixStream::connect(&socket).await.is_ok() {
+            return Err(SoarError::Custom(format!(
+                "a daemon is already listening on {}",
+                socket.display()
+            )));
+        }
+        // Left behind by a daemon that did not get to clean up.
+        fs::remove_file(&socket)
+            .with_context(|| format!("removing stale socket {}", socket.display()))?;
+    }
+
+    // Whoever can connect can install packages as this user, so the socket is
+    // never open to anyone else, not even between binding and a chmod.
+    let previous = umask(Mode::from_bits_truncate(0o177));
+    let listener = UnixListener::bind(&socket);
+    umask(previous);
+    let listener = listener.with_context(|| format!("listening on {}", socket.display()))?;
+    info!("Listening on {}", socket.display());
+
+    let daemon = Arc::new(Daemon {
+        ctx: Mutex::new(ctx.clone()),
+    });
+
+    let result = loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                match accepted {
+                    Ok((stream, _)) => {
+                        // Checked too in case the socket's permissions were
+                        // loosened after it was created.
+                        match stream.peer_cred() {
+                            Ok(cred) if cred.uid() == getuid().as_raw() => {
+                                debug!("client connected");
+                                tokio::spawn(serve_connection(daemon.clone(), stream));
+                            }
+                            Ok(cred) => warn!("Refused a connection from uid {}", cred.uid()),
+                            Err(err) => warn!("Refused a connection of unknown origin: {}", err),
+                        }
+                    }
+                    Err(err) => {
+                        break Err(SoarError::Custom(format!("accepting a connection: {err}")));
+                    }
+                }
+            }
+            _ = tokio::signal::ctrl_c() => break Ok(()),
+        }
+    };
+
+    fs::remove_file(&socket).ok();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn omitted_params_read_as_the_defaults() {
+        assert!(matches!(Call::parse("sync", Value::Null), Ok(Call::Sync)));
+        let Ok(Call::Update(params)) = Call::parse("update", Value::Null) else {
+            panic!("update takes no required parameters");
+        };
+        assert!(params.packages.is_none());
+        assert!(!params.check);
+    }
+
+    #[test]
+    fn a_required_param_left_out_is_invalid() {
+        let err = Call::parse("search", json!({ "limit": 5 })).err().unwrap();
+        assert_eq!(err.code, INVALID_PARAMS);
+
+        let err = Call::parse("frobnicate", Value::Null).err().unwrap();
+        assert_eq!(err.code, METHOD_NOT_FOUND);
+    }
+
+    #[test]
+    fn only_changes_wait_for_the_lock() {
+        let mutates =
+            |method: &str, params: Value| Call::parse(method, params).ok().unwrap().mutates();
+
+        assert!(!mutates("search", json!({ "query": "jq" })));
+        assert!(!mutates("update", json!({ "check": true })));
+        assert!(!mutates("apply", json!({ "dry_run": true })));
+        assert!(mutates("update", Value::Null));
+        assert!(mutates("install", json!({ "packages": ["jq"] })));
+    }
+
+    #[test]
+    fn socket_dirs_must_be_the_users_alone() {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let dir = tempfile::tempdir().unwrap();
+        let fresh = dir.path().join("run").join("soar");
+
+        prepare_socket_dir(&fresh).unwrap();
+        assert_eq!(fs::metadata(&fresh).unwrap().mode() & 0o777, 0o700);
+
+        fs::set_permissions(&fresh, fs::Permissions::from_mode(0o777)).unwrap();
+        assert!(prepare_socket_dir(&fresh).is_err());
+
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&fresh, &link).unwrap();
+        assert!(prepare_socket_dir(&link).is_err());
+    }
+
+    #[test]
+    fn a_client_that_stops_reading_is_let_go() {
+        let (outgoing, _queued) = mpsc::channel(QUEUE_LIMIT);
+        let (hangup, hung_up) = watch::channel(false);
+        let connection = Connection {
+            outgoing,
+            pending: Mutex::new(HashMap::new()),
+            hangup,
+        };
+
+        for _ in 0..QUEUE_LIMIT {
+            connection.send(Value::Null);
+        }
+        assert!(!*hung_up.borrow());
+
+        connection.send(Value::Null);
+        assert!(*hung_up.borrow());
+    }
+
+    #[test]
+    fn every_method_is_served() {
+        for method in METHODS {
+            let err = Call::parse(method, json!({ "query": "", "packages": [] })).err();
+            assert!(err.is_none_or(|e| e.code != METHOD_NOT_FOUND), "{method}");
+        }
+    }
+}
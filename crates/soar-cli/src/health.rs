@@ -62,6 +62,38 @@ pub async fn display_health(ctx: &SoarContext) -> SoarResult<()> {
     };
     builder.push_record(["Broken Symlinks".to_string(), sym_status]);
 
+    // Only shown where FUSE is missing, since every AppImage runs otherwise.
+    if !report.unmountable_appimages.is_empty() {
+        let fuse_status = format!(
+            "{} {} AppImage(s) cannot run",
+            Colored(Red, icon_or(Icons::CROSS, "!")),
+            Colored(Red, report.unmountable_appimages.len())
+        );
+        builder.push_record(["FUSE".to_string(), fuse_status]);
+    }
+
+    let lib_status = if report.unresolved_libraries.is_empty() {
+        format!("{} None", Colored(Green, icon_or(Icons::CHECK, "OK")))
+    } else {
+        format!(
+            "{} {} package(s) affected",
+            Colored(Yellow, icon_or(Icons::WARNING, "!")),
+            Colored(Yellow, report.unresolved_libraries.len())
+        )
+    };
+    builder.push_record(["Missing Libraries".to_string(), lib_status]);
+
+    // Only shown once a script has lost its interpreter, since most packages
+    // are native binaries and have none to lose.
+    if !report.missing_runtimes.is_empty() {
+        let runtime_status = format!(
+            "{} {} package(s) cannot run",
+            Colored(Red, icon_or(Icons::CROSS, "!")),
+            Colored(Red, report.missing_runtimes.len())
+        );
+        builder.push_record(["Missing Interpreters".to_string(), runtime_status]);
+    }
+
     let table = builder
         .build()
         .with(Panel::header("System Health Check"))
@@ -96,6 +128,48 @@ pub async fn display_health(ctx: &SoarContext) -> SoarResult<()> {
         );
     }
 
+    if !report.unmountable_appimages.is_empty() {
+        info!("\nAppImages needing FUSE, which this system lacks:");
+        for pkg in &report.unmountable_appimages {
+            info!(
+                "  {} {}: {}",
+                Icons::ARROW,
+                Colored(Blue, &pkg.pkg_name),
+                Colored(Yellow, &pkg.installed_path)
+            );
+        }
+        info!(
+            "Set {} and reinstall them to run without FUSE",
+            Colored(Green, "appimage_mode = \"extract\"")
+        );
+    }
+
+    if !report.unresolved_libraries.is_empty() {
+        info!("\nPackages needing shared libraries this system lacks:");
+        for pkg in &report.unresolved_libraries {
+            info!(
+                "  {} {}: {}",
+                Icons::ARROW,
+                Colored(Blue, &pkg.pkg_name),
+                Colored(Yellow, pkg.libraries.join(", "))
+            );
+        }
+        info!("Install them with the system package manager, or use a static build");
+    }
+
+    if !report.missing_runtimes.is_empty() {
+        info!("\nPackages whose interpreter is no longer installed:");
+        for pkg in &report.missing_runtimes {
+            info!(
+                "  {} {}: {}",
+                Icons::ARROW,
+                Colored(Blue, &pkg.pkg_name),
+                Colored(Yellow, pkg.runtimes.join(", "))
+            );
+        }
+        info!("Install the interpreter with soar or the system package manager");
+    }
+
     Ok(())
 }
 
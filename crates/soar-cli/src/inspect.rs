@@ -1,8 +1,13 @@
-use std::{fmt::Display, fs, path::PathBuf};
+use std::{
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+};
 
+use nu_ansi_term::Color::{Blue, Cyan, Green, Red};
 use soar_core::{
     database::{connection::DieselDatabase, models::Package},
-    error::ErrorContext,
+    error::{ErrorContext, SoarError},
     package::query::PackageQuery,
     SoarResult,
 };
@@ -11,14 +16,21 @@ use soar_db::repository::{
     metadata::MetadataRepository,
 };
 use soar_dl::http_client::SHARED_AGENT;
-use soar_operations::search;
+use soar_operations::{inspect, search, SoarContext};
+use soar_package::{ContentKind, PackageFormat};
 use soar_utils::bytes::format_bytes;
+use tabled::{
+    builder::Builder,
+    settings::{themes::BorderCorrection, Panel, Style},
+};
 use tracing::{error, info};
 use ureq::http::header::CONTENT_LENGTH;
 
 use crate::{
     progress::create_spinner_job,
-    utils::{display_settings, interactive_ask, select_package_interactively},
+    utils::{
+        display_settings, icon_or, interactive_ask, select_package_interactively, Colored, Icons,
+    },
 };
 
 pub enum InspectType {
@@ -234,3 +246,129 @@ pub async fn inspect_log(package: &str, inspect_type: InspectType) -> SoarResult
 
     Ok(())
 }
+
+fn format_name(format: PackageFormat) -> &'static str {
+    match format {
+        PackageFormat::AppImage => "AppImage",
+        PackageFormat::FlatImage => "FlatImage",
+        PackageFormat::RunImage => "RunImage",
+        PackageFormat::Wrappe => "Wrappe",
+        PackageFormat::Onelf => "onelf",
+        PackageFormat::ELF => "ELF",
+        PackageFormat::Unknown => "unknown",
+    }
+}
+
+/// Lists the files bundled in a package, and with `verify`, fails when any
+/// does not match its recorded checksum.
+pub fn inspect_contents(ctx: &SoarContext, package: &str, verify: bool) -> SoarResult<()> {
+    let contents = inspect::inspect_contents(ctx, package, verify)?;
+
+    let mut builder = Builder::new();
+    let mut header = vec!["Mode", "Size", "Path"];
+    if verify {
+        header.insert(0, "");
+    }
+    builder.push_record(header);
+
+    let mut total = 0;
+    let mut corrupted = Vec::new();
+    for entry in &contents.entries {
+        let path = match &entry.kind {
+            ContentKind::Directory => format!("{}/", Colored(Blue, entry.path.display())),
+            ContentKind::Symlink(target) => {
+                format!(
+                    "{} {} {}",
+                    Colored(Cyan, entry.path.display()),
+                    Icons::ARROW,
+                    target.display()
+                )
+            }
+            _ => entry.path.display().to_string(),
+        };
+        let size = if entry.kind == ContentKind::File {
+            total += entry.size;
+            format_bytes(entry.size, 2)
+        } else {
+            String::new()
+        };
+        let mode = entry
+            .mode
+            .map(|mode| format!("{mode:04o}"))
+            .unwrap_or_default();
+
+        let mut record = vec![mode, size, path];
+        if verify {
+            let status = match entry.verified {
+                Some(true) => format!("{}", Colored(Green, icon_or(Icons::CHECK, "OK"))),
+                Some(false) => {
+                    corrupted.push(&entry.path);
+                    format!("{}", Colored(Red, icon_or(Icons::CROSS, "!")))
+                }
+                None => String::new(),
+            };
+            record.insert(0, status);
+        }
+        builder.push_record(record);
+    }
+
+    let table = builder
+        .build()
+        .with(Panel::header(format!(
+            "{} ({})",
+            Colored(Blue, contents.path.display()),
+            format_name(contents.format)
+        )))
+        .with(Style::rounded())
+        .with(BorderCorrection {})
+        .to_string();
+    info!("\n{table}");
+    info!(
+        "{} entries, {} of files",
+        contents.entries.len(),
+        format_bytes(total, 2)
+    );
+
+    if verify {
+        if contents.format != PackageFormat::Onelf {
+            info!(
+                "{} records no per-file checksums, so nothing was verified",
+                format_name(contents.format)
+            );
+        } else if !corrupted.is_empty() {
+            return Err(SoarError::Custom(format!(
+                "{} file(s) do not match their recorded checksum: {}",
+                corrupted.len(),
+                corrupted
+                    .iter()
+                    .map(|p| p.display().to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        } else {
+            info!(
+                "{} Every file matches its recorded checksum",
+                Colored(Green, icon_or(Icons::CHECK, "OK"))
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Extracts the files bundled in a package into `dest`.
+pub fn extract_contents(
+    ctx: &SoarContext,
+    package: &str,
+    dest: &str,
+    paths: &[String],
+) -> SoarResult<()> {
+    let extracted = inspect::extract_bundle(ctx, package, Path::new(dest), paths)?;
+    info!(
+        "{} Extracted {} entries from {} into {}",
+        Colored(Green, icon_or(Icons::CHECK, "OK")),
+        extracted.count,
+        Colored(Blue, extracted.path.display()),
+        Colored(Cyan, extracted.dest.display())
+    );
+    Ok(())
+}
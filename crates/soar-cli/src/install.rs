@@ -10,8 +10,8 @@ use tracing::{debug, error, info, warn};
 use crate::{
     progress::create_wait_job,
     utils::{
-        ask_target_action, display_settings, icon_or, select_package_interactively,
-        select_package_interactively_with_installed, Colored, Icons,
+        ask_target_action, cancel_on_interrupt, display_settings, icon_or,
+        select_package_interactively, select_package_interactively_with_installed, Colored, Icons,
     },
 };
 
@@ -124,6 +124,7 @@ pub async fn install_packages(
         ask_target_action(&install_targets, "install")?;
     }
 
+    cancel_on_interrupt(ctx);
     let report = install::perform_installation(ctx, install_targets, &options).await?;
     display_install_report(&report, no_notes);
 
@@ -385,6 +386,7 @@ async fn install_with_show(
         ask_target_action(&install_targets, "install")?;
     }
 
+    cancel_on_interrupt(ctx);
     let report = install::perform_installation(ctx, install_targets, options).await?;
     display_install_report(&report, no_notes);
 
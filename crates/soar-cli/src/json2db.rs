@@ -92,6 +92,11 @@ pub fn json_to_db(input_path: &str, output_path: &str, repo_name: Option<&str>)
         warn!(skipped, "some packages were rejected during import");
     }
 
+    // What a delta manifest names as this revision's hash.
+    let hash = MetadataRepository::revision_hash(conn.conn())
+        .map_err(|e| SoarError::Custom(format!("hashing packages: {}", e)))?;
+    info!(hash, "Revision hash: {hash}");
+
     drop(conn);
     fs::rename(&tmp_path, output_path).with_context(|| {
         format!(
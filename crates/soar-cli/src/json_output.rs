@@ -1,4 +1,4 @@
-//! The shapes `--json` reports for the query commands.
+//! The shapes `--json` and `soar daemon` answer with.
 //!
 //! Written out rather than derived from the internal models: what a caller can
 //! read is a contract, and the models carry fields meant for the installer.
@@ -10,7 +10,10 @@ use soar_core::{
     database::models::{InstalledPackage, Package},
     package::install::InstallTarget,
 };
-use soar_operations::{ApplyDiff, InstalledEntry, PackageListEntry, SearchEntry, UpdateInfo};
+use soar_operations::{
+    AlternativeGroup, ApplyDiff, ApplyReport, FailedInfo, InstalledEntry, InstalledInfo,
+    PackageListEntry, RemovedInfo, SearchEntry, UpdateInfo,
+};
 
 /// A package as published by a repository.
 #[derive(Serialize)]
@@ -148,6 +151,20 @@ impl From<&Package> for PackageDetailJson {
     }
 }
 
+/// One build of a package, as `query --versions` lists it.
+#[derive(Serialize)]
+pub struct BuildJson {
+    pub name: String,
+    pub family: Option<String>,
+    pub repo: String,
+    pub version: String,
+    pub build_id: Option<String>,
+    pub build_date: Option<String>,
+    pub size: Option<u64>,
+    /// Whether this is the build an unversioned install gets.
+    pub current: bool,
+}
+
 /// A package with a newer version waiting for it.
 #[derive(Serialize)]
 pub struct UpdateJson {
@@ -205,6 +222,7 @@ pub struct EnvJson {
     pub cache: String,
     pub packages: String,
     pub repositories: String,
+    pub audit_log: String,
 }
 
 /// One package the declarative configuration would change.
@@ -224,6 +242,9 @@ pub struct ApplyDiffJson {
     pub to_install: Vec<ApplyChangeJson>,
     pub to_update: Vec<ApplyChangeJson>,
     pub to_remove: Vec<ApplyChangeJson>,
+    /// Installed packages whose launchers are written again for new launch
+    /// settings.
+    pub to_relaunch: Vec<ApplyChangeJson>,
     pub in_sync: Vec<String>,
     pub not_found: Vec<String>,
 }
@@ -240,25 +261,192 @@ impl ApplyDiffJson {
                 current_version: target.existing_install.as_ref().map(|e| e.version.clone()),
             }
         };
+        let installed = |package: &InstalledPackage| {
+            ApplyChangeJson {
+                name: package.pkg_name.clone(),
+                family: package.pkg_family.clone(),
+                repo: package.repo_name.clone(),
+                version: package.version.clone(),
+                current_version: Some(package.version.clone()),
+            }
+        };
 
         Self {
             to_install: diff.to_install.iter().map(|(_, t)| change(t)).collect(),
             to_update: diff.to_update.iter().map(|(_, t)| change(t)).collect(),
-            to_remove: diff
-                .to_remove
+            to_remove: diff.to_remove.iter().map(installed).collect(),
+            to_relaunch: diff.to_relaunch.iter().map(|(p, _)| installed(p)).collect(),
+            in_sync: diff.in_sync.clone(),
+            not_found: diff.not_found.clone(),
+        }
+    }
+}
+
+/// A command and the installed packages providing it.
+#[derive(Serialize)]
+pub struct AlternativeJson {
+    pub name: String,
+    /// Whether the user chose the provider, rather than the priorities.
+    pub manual: bool,
+    /// Best first; the first holds the command.
+    pub providers: Vec<AlternativeProviderJson>,
+}
+
+#[derive(Serialize)]
+pub struct AlternativeProviderJson {
+    pub name: String,
+    pub pkg_id: Option<String>,
+    pub repo: String,
+    pub version: String,
+    pub priority: i32,
+    pub target: String,
+}
+
+impl From<&AlternativeGroup> for AlternativeJson {
+    fn from(group: &AlternativeGroup) -> Self {
+        Self {
+            name: group.name.clone(),
+            manual: group.manual,
+            providers: group
+                .providers
                 .iter()
-                .map(|package| {
-                    ApplyChangeJson {
-                        name: package.pkg_name.clone(),
-                        family: package.pkg_family.clone(),
-                        repo: package.repo_name.clone(),
-                        version: package.version.clone(),
-                        current_version: Some(package.version.clone()),
+                .map(|provider| {
+                    AlternativeProviderJson {
+                        name: provider.package.pkg_name.clone(),
+                        pkg_id: provider.package.pkg_id.clone(),
+                        repo: provider.package.repo_name.clone(),
+                        version: provider.package.version.clone(),
+                        priority: provider.priority,
+                        target: provider.target.display().to_string(),
                     }
                 })
                 .collect(),
-            in_sync: diff.in_sync.clone(),
-            not_found: diff.not_found.clone(),
+        }
+    }
+}
+
+/// A package an operation installed, replaced or removed.
+#[derive(Serialize)]
+pub struct ChangedJson {
+    pub name: String,
+    pub family: Option<String>,
+    pub repo: String,
+    pub version: String,
+}
+
+impl From<&InstalledInfo> for ChangedJson {
+    fn from(info: &InstalledInfo) -> Self {
+        Self {
+            name: info.pkg_name.clone(),
+            family: info.pkg_family.clone(),
+            repo: info.repo_name.clone(),
+            version: info.version.clone(),
+        }
+    }
+}
+
+impl From<&RemovedInfo> for ChangedJson {
+    fn from(info: &RemovedInfo) -> Self {
+        Self {
+            name: info.pkg_name.clone(),
+            family: None,
+            repo: info.repo_name.clone(),
+            version: info.version.clone(),
+        }
+    }
+}
+
+impl From<&Package> for ChangedJson {
+    fn from(package: &Package) -> Self {
+        Self {
+            name: package.pkg_name.clone(),
+            family: package.pkg_family.clone(),
+            repo: package.repo_name.clone(),
+            version: package.version.clone(),
+        }
+    }
+}
+
+impl From<&InstalledPackage> for ChangedJson {
+    fn from(package: &InstalledPackage) -> Self {
+        Self {
+            name: package.pkg_name.clone(),
+            family: package.pkg_family.clone(),
+            repo: package.repo_name.clone(),
+            version: package.version.clone(),
+        }
+    }
+}
+
+/// A package an operation could not finish.
+#[derive(Serialize)]
+pub struct FailedJson {
+    pub name: String,
+    pub error: String,
+}
+
+impl From<&FailedInfo> for FailedJson {
+    fn from(info: &FailedInfo) -> Self {
+        Self {
+            name: info.pkg_name.clone(),
+            error: info.error.clone(),
+        }
+    }
+}
+
+/// A query that named more than one package. Nothing is done for it; the
+/// caller asks again for one of the candidates.
+#[derive(Serialize)]
+pub struct AmbiguousJson {
+    pub query: String,
+    pub candidates: Vec<ChangedJson>,
+}
+
+/// What an install did, and what it left for the caller to decide.
+#[derive(Serialize, Default)]
+pub struct InstallReportJson {
+    pub installed: Vec<ChangedJson>,
+    pub failed: Vec<FailedJson>,
+    pub warnings: Vec<String>,
+    pub already_installed: Vec<ChangedJson>,
+    pub ambiguous: Vec<AmbiguousJson>,
+    pub not_found: Vec<String>,
+}
+
+/// What a removal did, and what it left for the caller to decide.
+#[derive(Serialize, Default)]
+pub struct RemoveReportJson {
+    pub removed: Vec<ChangedJson>,
+    pub failed: Vec<FailedJson>,
+    pub ambiguous: Vec<AmbiguousJson>,
+    pub not_installed: Vec<String>,
+}
+
+/// What an update did.
+#[derive(Serialize, Default)]
+pub struct UpdateReportJson {
+    pub updated: Vec<ChangedJson>,
+    pub failed: Vec<FailedJson>,
+}
+
+/// How many packages applying the declarative configuration changed.
+#[derive(Serialize)]
+pub struct ApplyReportJson {
+    pub installed: usize,
+    pub updated: usize,
+    pub removed: usize,
+    pub relaunched: usize,
+    pub failed: usize,
+}
+
+impl From<&ApplyReport> for ApplyReportJson {
+    fn from(report: &ApplyReport) -> Self {
+        Self {
+            installed: report.installed_count,
+            updated: report.updated_count,
+            removed: report.removed_count,
+            relaunched: report.relaunched_count,
+            failed: report.failed_count,
         }
     }
 }
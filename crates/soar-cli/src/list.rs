@@ -11,7 +11,7 @@ use tabled::{
 use tracing::{debug, info};
 
 use crate::{
-    json_output::{self, InstalledJson, Listing, PackageDetailJson, PackageJson},
+    json_output::{self, BuildJson, InstalledJson, Listing, PackageDetailJson, PackageJson},
     utils::{
         display_settings, icon_or, json_enabled, pretty_package_size, term_width, vec_string,
         Colored, Icons,
@@ -301,6 +301,84 @@ pub async fn query_package(ctx: &SoarContext, query_str: String) -> SoarResult<(
     Ok(())
 }
 
+pub async fn list_package_versions(ctx: &SoarContext, query_str: String) -> SoarResult<()> {
+    debug!(query = query_str, "listing package versions");
+
+    let packages = search::query_package(ctx, &query_str).await?;
+
+    if json_enabled() {
+        let items: Vec<BuildJson> = packages
+            .iter()
+            .flat_map(|package| {
+                package
+                    .builds()
+                    .into_iter()
+                    .enumerate()
+                    .map(|(idx, build)| {
+                        BuildJson {
+                            name: package.pkg_name.clone(),
+                            family: package.pkg_family.clone(),
+                            repo: package.repo_name.clone(),
+                            version: build.version,
+                            build_id: build.id,
+                            build_date: build.date,
+                            size: build.size,
+                            current: idx == 0,
+                        }
+                    })
+            })
+            .collect();
+        let total = items.len();
+        json_output::emit(&Listing::new(items, total));
+        return Ok(());
+    }
+
+    for package in packages {
+        let mut builder = Builder::new();
+        builder.push_record(["", "Version", "Build", "Date", "Size"]);
+
+        for (idx, build) in package.builds().into_iter().enumerate() {
+            let marker = if idx == 0 {
+                format!("{}", Colored(Green, icon_or(Icons::INSTALLED, "*")))
+            } else {
+                String::new()
+            };
+            builder.push_record([
+                marker,
+                format!("{}", Colored(Blue, &build.version)),
+                build
+                    .id
+                    .map(|id| Colored(Yellow, id).to_string())
+                    .unwrap_or_default(),
+                build.date.unwrap_or_default(),
+                build
+                    .size
+                    .map(|size| format_bytes(size, 2))
+                    .unwrap_or_default(),
+            ]);
+        }
+
+        let table = builder
+            .build()
+            .with(Panel::header(format!(
+                "{}:{}",
+                Colored(Blue, &package.pkg_name),
+                Colored(Green, &package.repo_name)
+            )))
+            .with(Style::rounded())
+            .with(BorderCorrection {})
+            .to_string();
+
+        info!(
+            pkg_name = package.pkg_name,
+            repo_name = package.repo_name,
+            "\n{table}"
+        );
+    }
+
+    Ok(())
+}
+
 pub async fn list_packages(ctx: &SoarContext, repo_name: Option<String>) -> SoarResult<()> {
     debug!(repo = ?repo_name, "listing packages");
 
@@ -170,6 +170,16 @@ pub fn setup_logging(args: &Args) {
         Level::INFO
     };
 
+    init_logging(filter_level, args.json);
+}
+
+/// Set up logging for a sandbox launcher, which takes no flags: only what
+/// goes wrong is reported, so the program's own output stays its own.
+pub fn setup_launcher_logging() {
+    init_logging(Level::WARN, false);
+}
+
+fn init_logging(filter_level: Level, json: bool) {
     let builder = fmt::Subscriber::builder()
         .with_env_filter(format!("soar={filter_level}"))
         .with_target(false)
@@ -178,11 +188,11 @@ pub fn setup_logging(args: &Args) {
         .with_file(false)
         .with_line_number(false)
         .with_span_events(FmtSpan::NONE)
-        .with_writer(WriterBuilder::new(args.json))
+        .with_writer(WriterBuilder::new(json))
         .compact()
         .without_time();
 
-    let subscriber: Box<dyn Subscriber + Send + Sync> = if args.json {
+    let subscriber: Box<dyn Subscriber + Send + Sync> = if json {
         Box::new(builder.json().flatten_event(true).finish())
     } else {
         Box::new(builder.event_format(CustomFormatter).finish())
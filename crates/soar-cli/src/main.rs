@@ -6,40 +6,47 @@ use std::{
 };
 
 use apply::apply_packages;
+use audit::display_audit;
 use clap::{CommandFactory, Parser};
 use cli::Args;
 use download::{create_regex_patterns, download, DownloadContext};
 use health::{display_health, remove_broken_packages};
 use indicatif::ProgressBar;
-use inspect::{inspect_log, InspectType};
+use inspect::{extract_contents, inspect_contents, inspect_log, InspectType};
 use install::install_packages;
 use json2db::json_to_db;
-use list::{list_installed_packages, list_packages, query_package, search_packages};
-use logging::setup_logging;
+use list::{
+    list_installed_packages, list_package_versions, list_packages, query_package, search_packages,
+};
+use logging::{setup_launcher_logging, setup_logging};
 use progress::{create_download_job, handle_download_progress, spawn_event_handler, ProgressGuard};
 use remove::remove_packages;
-use run::run_package;
+use run::{run_package, run_sandboxed, run_shell};
 use soar_config::config::{
     self, enable_system_mode, generate_default_config, get_config, set_current_profile, Config,
     CONFIG_PATH,
 };
 use soar_core::{
+    audit::AuditKind,
     error::{ErrorContext, SoarError},
     utils::{cleanup_cache, remove_broken_symlinks, setup_required_paths},
     SoarResult,
 };
 use soar_dl::http_client::configure_http_client;
 use soar_events::EventSinkHandle;
-use soar_operations::SoarContext;
-use soar_utils::path::resolve_path;
+use soar_operations::{AuditQuery, SoarContext};
+use soar_utils::{entrypoint::SANDBOX_EXEC, path::resolve_path};
 use tracing::{debug, info, warn};
 use update::update_packages;
 use ureq::{config::IpFamily, Proxy};
 use use_package::use_alternate_package;
 use utils::{progress_enabled, COLOR};
 
+mod alternatives;
 mod apply;
+mod audit;
 mod cli;
+mod daemon;
 mod download;
 mod health;
 mod inspect;
@@ -48,6 +55,7 @@ mod json2db;
 mod json_output;
 mod list;
 mod logging;
+mod mime;
 mod plugin_manifest;
 mod progress;
 mod remove;
@@ -106,6 +114,10 @@ fn answers_with_document(command: &cli::Commands) -> bool {
             | cli::Commands::Search { .. }
             | cli::Commands::Query { .. }
             | cli::Commands::Env
+            | cli::Commands::Audit { .. }
+            | cli::Commands::Alternatives {
+                action: cli::AlternativesAction::List { .. },
+            }
             | cli::Commands::Update {
                 check: true,
                 ..
@@ -115,11 +127,35 @@ fn answers_with_document(command: &cli::Commands) -> bool {
                 ..
             }
             | cli::Commands::Repo {
-                action: cli::RepoAction::List,
+                action: cli::RepoAction::List | cli::RepoAction::Lint { .. },
             }
     )
 }
 
+/// Whether this command changes what is installed or synced, and so waits for
+/// a daemon or another soar doing the same.
+fn changes_installation(command: &cli::Commands) -> bool {
+    match command {
+        cli::Commands::Install { .. }
+        | cli::Commands::Remove { .. }
+        | cli::Commands::Sync
+        | cli::Commands::Use { .. }
+        | cli::Commands::Clean { .. } => true,
+        cli::Commands::Update {
+            check,
+            ..
+        } => !check,
+        cli::Commands::Apply {
+            dry_run,
+            ..
+        } => !dry_run,
+        cli::Commands::Alternatives {
+            action,
+        } => !matches!(action, cli::AlternativesAction::List { .. }),
+        _ => false,
+    }
+}
+
 /// Handle system mode - check for root privileges and re-exec with sudo/doas if needed
 fn handle_system_mode() -> SoarResult<()> {
     if nix::unistd::geteuid().is_root() {
@@ -156,6 +192,14 @@ fn handle_system_mode() -> SoarResult<()> {
 }
 
 async fn handle_cli() -> SoarResult<()> {
+    // A sandbox launcher hands over the program's own arguments, which are
+    // neither soar's to parse nor to splice stdin into.
+    let args_os = env::args_os().collect::<Vec<_>>();
+    if args_os.get(1).is_some_and(|arg| arg == SANDBOX_EXEC) {
+        setup_launcher_logging();
+        return run_sandboxed(&args_os[2..]);
+    }
+
     let mut args = env::args().collect::<Vec<_>>();
 
     let mut i = 0;
@@ -263,6 +307,11 @@ async fn handle_cli() -> SoarResult<()> {
 
             let (ctx, progress_guard) = create_context_for(answers_with_document(&command));
             let mut run_exit_code = None;
+            let _operations_lock = if changes_installation(&command) {
+                Some(utils::lock_operations()?)
+            } else {
+                None
+            };
 
             match command {
                 cli::Commands::Install {
@@ -321,8 +370,13 @@ async fn handle_cli() -> SoarResult<()> {
                 }
                 cli::Commands::Query {
                     query,
+                    versions,
                 } => {
-                    query_package(&ctx, query).await?;
+                    if versions {
+                        list_package_versions(&ctx, query).await?;
+                    } else {
+                        query_package(&ctx, query).await?;
+                    }
                 }
                 cli::Commands::Remove {
                     packages,
@@ -332,6 +386,7 @@ async fn handle_cli() -> SoarResult<()> {
                     remove_packages(&ctx, &packages, yes, all).await?;
                 }
                 cli::Commands::Sync => {
+                    utils::cancel_on_interrupt(&ctx);
                     ctx.sync().await?;
                 }
                 cli::Commands::Update {
@@ -359,10 +414,23 @@ async fn handle_cli() -> SoarResult<()> {
                 } => inspect_log(&package, InspectType::BuildLog).await?,
                 cli::Commands::Inspect {
                     package,
-                } => inspect_log(&package, InspectType::BuildScript).await?,
+                    contents,
+                    verify,
+                    extract,
+                    paths,
+                } => {
+                    if let Some(dest) = extract {
+                        extract_contents(&ctx, &package, &dest, &paths)?;
+                    } else if contents || verify {
+                        inspect_contents(&ctx, &package, verify)?;
+                    } else {
+                        inspect_log(&package, InspectType::BuildScript).await?;
+                    }
+                }
                 cli::Commands::Run {
                     yes,
                     no_verify,
+                    sandbox,
                     command,
                     pkg_id,
                     repo_name,
@@ -372,6 +440,7 @@ async fn handle_cli() -> SoarResult<()> {
                         command.as_ref(),
                         yes,
                         no_verify,
+                        sandbox,
                         repo_name.as_deref(),
                         pkg_id.as_deref(),
                     )
@@ -380,11 +449,28 @@ async fn handle_cli() -> SoarResult<()> {
                         run_exit_code = Some(code);
                     }
                 }
+                cli::Commands::Shell {
+                    yes,
+                    no_verify,
+                    clean,
+                    packages,
+                    command,
+                } => {
+                    let code = run_shell(&ctx, &packages, &command, yes, no_verify, clean).await?;
+                    if code != 0 {
+                        run_exit_code = Some(code);
+                    }
+                }
                 cli::Commands::Use {
                     package_name,
                 } => {
                     use_alternate_package(&ctx, &package_name).await?;
                 }
+                cli::Commands::Alternatives {
+                    action,
+                } => {
+                    alternatives::handle_alternatives_action(&ctx, action)?;
+                }
                 cli::Commands::Download {
                     links,
                     yes,
@@ -433,6 +519,27 @@ async fn handle_cli() -> SoarResult<()> {
                     download(context, links, github, gitlab, ghcr).await?;
                 }
                 cli::Commands::Health => display_health(&ctx).await?,
+                cli::Commands::Audit {
+                    package,
+                    kind,
+                    failed,
+                    limit,
+                    output,
+                } => {
+                    let kind = kind.map(|kind| {
+                        match kind.as_str() {
+                            "build" => AuditKind::Build,
+                            _ => AuditKind::Hook,
+                        }
+                    });
+                    let query = AuditQuery {
+                        package,
+                        kind,
+                        failed,
+                        limit,
+                    };
+                    display_audit(&ctx, &query, output)?;
+                }
                 cli::Commands::Repo {
                     action,
                 } => {
@@ -442,6 +549,16 @@ async fn handle_cli() -> SoarResult<()> {
                     let profiles: Vec<String> = get_config().profile.keys().cloned().collect();
                     print!("{}", plugin_manifest::manifest(&profiles));
                 }
+                cli::Commands::Daemon {
+                    socket,
+                } => {
+                    daemon::serve(&ctx, socket.map(Into::into)).await?;
+                }
+                cli::Commands::Mime {
+                    action,
+                } => {
+                    mime::handle_mime_action(&ctx, action)?;
+                }
                 cli::Commands::Url {
                     url,
                     register,
@@ -461,6 +578,7 @@ async fn handle_cli() -> SoarResult<()> {
                         cache: config.get_cache_path()?.display().to_string(),
                         packages: config.get_packages_path(None)?.display().to_string(),
                         repositories: config.get_repositories_path()?.display().to_string(),
+                        audit_log: config.get_audit_log_path()?.display().to_string(),
                     };
 
                     if utils::json_enabled() {
@@ -473,6 +591,7 @@ async fn handle_cli() -> SoarResult<()> {
                         info!("SOAR_CACHE={}", paths.cache);
                         info!("SOAR_PACKAGES={}", paths.packages);
                         info!("SOAR_REPOSITORIES={}", paths.repositories);
+                        info!("SOAR_AUDIT_LOG={}", paths.audit_log);
                     }
                 }
                 #[cfg(feature = "self")]
@@ -597,6 +716,12 @@ async fn main() {
     .ok();
 
     if let Err(err) = handle_cli().await {
+        // Interrupted, not failed: what was started has been undone, and the
+        // exit code says so the way a shell's own ^C does.
+        if err.is_cancelled() {
+            eprintln!("{err}");
+            std::process::exit(130);
+        }
         // Use miette's error display for Diagnostic errors
         eprintln!("{:?}", miette::Report::new(err));
         // Anything driving soar reads the exit code to know whether the work
@@ -0,0 +1,25 @@
+use nu_ansi_term::Color::{Blue, Cyan};
+use soar_core::SoarResult;
+use soar_operations::{mime, SoarContext};
+use tracing::info;
+
+use crate::{cli::MimeAction, utils::Colored};
+
+pub fn handle_mime_action(ctx: &SoarContext, action: MimeAction) -> SoarResult<()> {
+    match action {
+        MimeAction::SetDefault {
+            package,
+            mime_type,
+        } => {
+            let default = mime::set_default_application(ctx, &package, &mime_type)?;
+            info!(
+                "{} now opens {} ({}, recorded in {})",
+                Colored(Blue, &package),
+                Colored(Cyan, &mime_type),
+                default.desktop_id,
+                default.mimeapps.display()
+            );
+        }
+    }
+    Ok(())
+}
@@ -4,11 +4,13 @@
 //! how to read what comes back. Shipping that description in the binary keeps
 //! the two from falling out of step.
 
+use crate::daemon;
+
 /// The manifest format. A reader should refuse a version it does not know.
 const SCHEMA_VERSION: u32 = 1;
 
-/// The manifest, with `@schema_version@`, `@version@` and `@profiles@` filled
-/// in by [`manifest`].
+/// The manifest, with `@schema_version@`, `@version@`, `@profiles@`,
+/// `@methods@` and `@cancel@` filled in by [`manifest`].
 const TEMPLATE: &str = r#"# Generated by `soar plugin-manifest`. This describes the soar that
 # emitted it, so it is worth regenerating rather than copying between machines.
 schema_version = @schema_version@
@@ -229,6 +231,18 @@ output = { format = "ndjson" }
 [ops.set_repo_enabled]
 args = ["repo", "update", "{name}", "--enabled", "{enabled}"]
 output = { format = "ndjson" }
+
+# A frontend that stays open can keep one soar running instead of starting one
+# per operation. It speaks JSON-RPC 2.0, one message per line, on a unix
+# socket. A method answers with the document its command's `--json` does, and
+# its events arrive meanwhile as `event` notifications carrying the objects
+# the ndjson streams above are made of.
+[daemon]
+args = ["daemon"]
+socket = "$XDG_RUNTIME_DIR/soar/daemon.sock"
+protocol = "jsonrpc"
+methods = [@methods@]
+cancel = "@cancel@"
 "#;
 
 /// Describes soar's commands as this build accepts them.
@@ -244,10 +258,18 @@ pub fn manifest(profiles: &[String]) -> String {
         .collect::<Vec<_>>()
         .join(", ");
 
+    let methods = daemon::METHODS
+        .iter()
+        .map(|method| format!("{method:?}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
     TEMPLATE
         .replace("@schema_version@", &SCHEMA_VERSION.to_string())
         .replace("@version@", env!("CARGO_PKG_VERSION"))
         .replace("@profiles@", &options)
+        .replace("@methods@", &methods)
+        .replace("@cancel@", daemon::CANCEL_METHOD)
 }
 
 #[cfg(test)]
@@ -286,4 +308,25 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn the_daemon_section_lists_what_the_daemon_serves() {
+        use clap::CommandFactory;
+
+        let parsed: toml::Value = toml::from_str(&manifest(&["default".into()])).unwrap();
+        let daemon = &parsed["daemon"];
+
+        let subcommand = daemon["args"][0].as_str().expect("daemon subcommand");
+        assert!(crate::cli::Args::command()
+            .find_subcommand(subcommand)
+            .is_some());
+        let methods: Vec<&str> = daemon["methods"]
+            .as_array()
+            .expect("methods array")
+            .iter()
+            .filter_map(|m| m.as_str())
+            .collect();
+        assert_eq!(methods, crate::daemon::METHODS);
+        assert_eq!(daemon["cancel"].as_str(), Some("$/cancelRequest"));
+    }
 }
@@ -179,7 +179,8 @@ fn create_op_spinner(msg: &str) -> ProgressBar {
 ///
 /// Each operation (`op_id`) gets a **single bar** for its entire lifecycle: it starts as a
 /// download progress bar and is converted to a spinner for verification / install stages.
-/// The bar is cleared on terminal events (`OperationComplete` / `OperationFailed`).
+/// The bar is cleared on terminal events (`OperationComplete` / `OperationFailed` /
+/// `OperationCancelled`).
 pub fn spawn_event_handler(receiver: Receiver<SoarEvent>) -> ProgressGuard {
     let handle = std::thread::spawn(move || {
         let mut jobs: HashMap<OperationId, ProgressBar> = HashMap::new();
@@ -419,6 +420,9 @@ pub fn spawn_event_handler(receiver: Receiver<SoarEvent>) -> ProgressGuard {
                         }
                         BuildStage::CommandComplete {
                             ..
+                        }
+                        | BuildStage::Failed {
+                            ..
                         } => {}
                     }
                 }
@@ -513,6 +517,9 @@ pub fn spawn_event_handler(receiver: Receiver<SoarEvent>) -> ProgressGuard {
                                 SyncStage::Fetching => format!("{repo_name}: fetching metadata"),
                                 SyncStage::Decompressing => format!("{repo_name}: decompressing"),
                                 SyncStage::WritingDatabase => format!("{repo_name}: writing db"),
+                                SyncStage::ApplyingDeltas {
+                                    count,
+                                } => format!("{repo_name}: applying {count} deltas"),
                                 SyncStage::Validating => format!("{repo_name}: validating"),
                                 _ => unreachable!(),
                             };
@@ -594,6 +601,23 @@ pub fn spawn_event_handler(receiver: Receiver<SoarEvent>) -> ProgressGuard {
                         pb.finish_and_clear();
                     }
                 }
+                SoarEvent::OperationCancelled {
+                    op_id,
+                    pkg_name,
+                } => {
+                    remove_ops.remove(&op_id);
+                    MULTI.suspend(|| {
+                        eprintln!(
+                            " {} {}: {}",
+                            Yellow.paint("-"),
+                            Cyan.paint(&pkg_name),
+                            Yellow.paint("cancelled")
+                        );
+                    });
+                    if let Some(pb) = jobs.remove(&op_id) {
+                        pb.finish_and_clear();
+                    }
+                }
 
                 // Emitted for things that fail without aborting the run, a
                 // repository that could not be synced most of all. Without a
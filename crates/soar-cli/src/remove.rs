@@ -3,7 +3,7 @@ use soar_core::SoarResult;
 use soar_operations::{remove, RemoveResolveResult, SoarContext};
 use tracing::{debug, error, info, warn};
 
-use crate::utils::{confirm_action, select_package_interactively, Colored};
+use crate::utils::{cancel_on_interrupt, confirm_action, select_package_interactively, Colored};
 
 pub async fn remove_packages(
     ctx: &SoarContext,
@@ -68,6 +68,7 @@ pub async fn remove_packages(
         return Ok(());
     }
 
+    cancel_on_interrupt(ctx);
     let report = remove::perform_removal(ctx, to_remove).await?;
 
     for removed in &report.removed {
@@ -1,12 +1,14 @@
+use nu_ansi_term::Color::{Blue, Red, Yellow};
 use soar_config::repository::Repository;
-use soar_core::SoarResult;
+use soar_core::{error::SoarError, SoarResult};
 use soar_operations::{repo::RepoUpdate, SoarContext};
+use soar_registry::{LintReport, Severity};
 use tracing::info;
 
 use crate::{
     cli::RepoAction,
     json_output::{self, Listing, RepositoryJson},
-    utils::json_enabled,
+    utils::{json_enabled, Colored},
 };
 
 pub fn handle_repo_action(ctx: &SoarContext, action: RepoAction) -> SoarResult<()> {
@@ -14,6 +16,7 @@ pub fn handle_repo_action(ctx: &SoarContext, action: RepoAction) -> SoarResult<(
         RepoAction::Add {
             name,
             url,
+            delta_url,
             pubkey,
             enabled,
             desktop_integration,
@@ -23,17 +26,21 @@ pub fn handle_repo_action(ctx: &SoarContext, action: RepoAction) -> SoarResult<(
             ctx.add_repository(Repository {
                 name: name.clone(),
                 url,
+                delta_url,
                 pubkey,
                 enabled,
                 desktop_integration,
                 signature_verification,
                 sync_interval,
+                channels: None,
+                channel: None,
             })?;
             info!("Repository '{}' added successfully.", name);
         }
         RepoAction::Update {
             name,
             url,
+            delta_url,
             pubkey,
             enabled,
             desktop_integration,
@@ -44,6 +51,7 @@ pub fn handle_repo_action(ctx: &SoarContext, action: RepoAction) -> SoarResult<(
                 &name,
                 RepoUpdate {
                     url,
+                    delta_url,
                     pubkey,
                     enabled,
                     desktop_integration,
@@ -82,6 +90,51 @@ pub fn handle_repo_action(ctx: &SoarContext, action: RepoAction) -> SoarResult<(
                 }
             }
         }
+        RepoAction::Lint {
+            source,
+            strict,
+        } => {
+            let report = soar_registry::read_index_source(&source)
+                .and_then(|bytes| soar_registry::lint_index(&bytes))
+                .map_err(|err| SoarError::Custom(format!("linting {source}: {err}")))?;
+            print_lint_report(&report);
+
+            let failing = report.errors() + if strict { report.warnings() } else { 0 };
+            if failing > 0 {
+                return Err(SoarError::Custom(format!(
+                    "{source} has {failing} problem(s) to fix"
+                )));
+            }
+        }
     }
     Ok(())
 }
+
+fn print_lint_report(report: &LintReport) {
+    if json_enabled() {
+        json_output::emit(report);
+        return;
+    }
+
+    for issue in &report.issues {
+        let severity = match issue.severity {
+            Severity::Error => Colored(Red, "error"),
+            Severity::Warning => Colored(Yellow, "warning"),
+        };
+        info!(
+            pointer = issue.pointer,
+            "{severity} {}: {}",
+            Colored(Blue, &issue.pointer),
+            issue.message
+        );
+    }
+
+    let (errors, warnings) = (report.errors(), report.warnings());
+    info!(
+        packages = report.packages,
+        errors,
+        warnings,
+        "Checked {} packages: {errors} errors, {warnings} warnings",
+        report.packages
+    );
+}
@@ -1,5 +1,11 @@
-use soar_core::SoarResult;
-use soar_operations::{run, PrepareRunResult, SoarContext};
+use std::{
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use soar_core::{error::SoarError, SoarResult};
+use soar_operations::{run, shell, PrepareRunResult, SoarContext};
+use tracing::info;
 
 use crate::utils::select_package_interactively;
 
@@ -8,6 +14,7 @@ pub async fn run_package(
     command: &[String],
     yes: bool,
     no_verify: bool,
+    sandbox: bool,
     repo_name: Option<&str>,
     pkg_id: Option<&str>,
 ) -> SoarResult<i32> {
@@ -18,17 +25,86 @@ pub async fn run_package(
         &[]
     };
 
+    let Some((output_path, downloaded)) =
+        prepare_package(ctx, package_name, yes, no_verify, repo_name, pkg_id).await?
+    else {
+        return Ok(0);
+    };
+
+    // The progress bar leaves the cursor mid-line, so a program that writes
+    // straight to stdout would start where the bar stopped.
+    if downloaded {
+        eprintln!();
+    }
+
+    let profile = if sandbox {
+        Some(run::runtime_profile(package_name)?)
+    } else {
+        None
+    };
+    let run_result = run::execute_binary(&output_path, args, profile.as_ref())?;
+
+    Ok(run_result.exit_code)
+}
+
+/// Fetch `packages` into the cache and run `command`, or the user's shell,
+/// with all of them on `PATH`.
+///
+/// Nothing is installed: the commands are linked into a temporary directory
+/// that is removed on exit. With `clean`, what this shell downloaded is
+/// removed from the cache too.
+pub async fn run_shell(
+    ctx: &SoarContext,
+    packages: &[String],
+    command: &[String],
+    yes: bool,
+    no_verify: bool,
+    clean: bool,
+) -> SoarResult<i32> {
+    // Whatever happens from here on, what this shell downloaded goes again
+    // with `clean`.
+    let mut downloads = shell::Downloads::new(ctx, !clean)?;
+    let mut binaries = Vec::new();
+    for package in packages {
+        let Some((path, fetched)) =
+            prepare_package(ctx, package, yes, no_verify, None, None).await?
+        else {
+            return Err(SoarError::Custom(format!(
+                "No package chosen for {package}, not starting the shell"
+            )));
+        };
+        if fetched {
+            eprintln!();
+            downloads.push(path.clone());
+        }
+        binaries.push(path);
+    }
+
+    let shell_bin = shell::make_shell_bin(ctx, &binaries)?;
+    info!("Commands available: {}", shell_bin.commands.join(", "));
+    Ok(shell::run_shell(&shell_bin, packages, command)?.exit_code)
+}
+
+/// Resolve a package and fetch it into the cache, asking which one is meant
+/// when the name is ambiguous.
+///
+/// Returns the cached binary and whether it was downloaded, or `None` when
+/// nothing was chosen.
+async fn prepare_package(
+    ctx: &SoarContext,
+    package_name: &str,
+    yes: bool,
+    no_verify: bool,
+    repo_name: Option<&str>,
+    pkg_id: Option<&str>,
+) -> SoarResult<Option<(PathBuf, bool)>> {
     let result = run::prepare_run(ctx, package_name, repo_name, pkg_id, no_verify).await?;
 
-    let downloaded;
-    let output_path = match result {
+    match result {
         PrepareRunResult::Ready {
             path,
-            downloaded: d,
-        } => {
-            downloaded = d;
-            path
-        }
+            downloaded,
+        } => Ok(Some((path, downloaded))),
         PrepareRunResult::Ambiguous(amb) => {
             let pkg = if yes {
                 amb.candidates.into_iter().next()
@@ -37,7 +113,7 @@ pub async fn run_package(
             };
 
             let Some(pkg) = pkg else {
-                return Ok(0);
+                return Ok(None);
             };
 
             // Run what was chosen. Resolving it by name again would pose
@@ -63,23 +139,24 @@ pub async fn run_package(
             match result {
                 PrepareRunResult::Ready {
                     path,
-                    downloaded: d,
-                } => {
-                    downloaded = d;
-                    path
-                }
-                _ => return Ok(0),
+                    downloaded,
+                } => Ok(Some((path, downloaded))),
+                _ => Ok(None),
             }
         }
-    };
-
-    // The progress bar leaves the cursor mid-line, so a program that writes
-    // straight to stdout would start where the bar stopped.
-    if downloaded {
-        eprintln!();
     }
+}
 
-    let run_result = run::execute_binary(&output_path, args)?;
-
-    Ok(run_result.exit_code)
+/// Run a program the way its sandbox launcher asks: `<profile> -- <program>
+/// [args...]`.
+pub fn run_sandboxed(args: &[OsString]) -> SoarResult<()> {
+    let (profile, program, args) = match args {
+        [profile, separator, program, args @ ..] if separator == "--" => (profile, program, args),
+        _ => {
+            return Err(SoarError::Custom(
+                "usage: soar sandbox-exec <profile> -- <program> [args...]".into(),
+            ))
+        }
+    };
+    match run::exec_sandboxed(Path::new(profile), Path::new(program), args)? {}
 }
@@ -10,7 +10,10 @@ use tracing::{error, info};
 use crate::{
     json_output::{self, Listing, UpdateJson},
     progress::create_wait_job,
-    utils::{ask_target_action, display_settings, icon_or, json_enabled, Colored, Icons},
+    utils::{
+        ask_target_action, cancel_on_interrupt, display_settings, icon_or, json_enabled, Colored,
+        Icons,
+    },
 };
 
 pub async fn update_packages(
@@ -43,6 +46,7 @@ pub async fn update_packages(
         ask_target_action(&install_targets, "update")?;
     }
 
+    cancel_on_interrupt(ctx);
     let report = update::perform_update(ctx, updates, keep, no_verify).await?;
     display_update_report(&report);
 
@@ -20,7 +20,11 @@ use soar_core::{
     SoarResult,
 };
 use soar_operations::SoarContext;
-use soar_utils::path::xdg_data_home;
+use soar_package::{
+    desktop::quote_exec_arg,
+    mime::{refresh_desktop_database, set_default},
+};
+use soar_utils::path::{mimeapps_list, xdg_data_home};
 use tracing::info;
 
 use crate::{
@@ -129,41 +133,10 @@ fn validate_spec(spec: &str) -> SoarResult<()> {
     Ok(())
 }
 
-/// Encode a path for the `Exec` key.
-///
-/// Two layers apply: `%` starts a field code, so a literal one doubles, and
-/// inside quotes a backslash guards `"`, a backtick, `$` and itself, which the
-/// desktop-entry string layer then escapes a second time.
-fn escape_exec(exe: &str) -> String {
-    const RESERVED: &str = " \t\n\"'\\><~|&;$*?#()`";
-
-    let exe = exe.replace('%', "%%");
-    // Quoted only when it has to be: xdg-open's generic fallback resolves the
-    // Exec path with a plain `command -v` and finds nothing behind quotes.
-    if !exe.contains(|c| RESERVED.contains(c)) {
-        return exe;
-    }
-
-    let mut out = String::with_capacity(exe.len() + 2);
-    out.push('"');
-    for c in exe.chars() {
-        match c {
-            '\\' => out.push_str(r"\\\\"),
-            '"' | '`' | '$' => {
-                out.push_str(r"\\");
-                out.push(c);
-            }
-            _ => out.push(c),
-        }
-    }
-    out.push('"');
-    out
-}
-
 /// The shipped entry, pointed at the soar doing the registering rather than at
 /// whichever one `PATH` happens to hold.
 fn desktop_entry(exe: &str) -> String {
-    let exec = format!("Exec={} url %u", escape_exec(exe));
+    let exec = format!("Exec={} url %u", quote_exec_arg(exe));
     DESKTOP_ENTRY.replace(PACKAGED_EXEC, &exec)
 }
 
@@ -248,11 +221,9 @@ pub fn register() -> SoarResult<PathBuf> {
     fs::write(&path, desktop_entry(&exe.to_string_lossy()))
         .with_context(|| format!("writing {}", path.display()))?;
 
-    // Best-effort: the entry is already in place without them.
-    let _ = Command::new("update-desktop-database").arg(&dir).status();
-    let _ = Command::new("xdg-mime")
-        .args(["default", DESKTOP_FILE_NAME, SCHEME_MIME])
-        .status();
+    // Best-effort: the entry is already in place without it.
+    refresh_desktop_database(&dir);
+    set_default(&mimeapps_list(false), SCHEME_MIME, DESKTOP_FILE_NAME)?;
 
     Ok(path)
 }
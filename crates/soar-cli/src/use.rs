@@ -7,6 +7,16 @@ use tracing::info;
 use crate::utils::{get_valid_selection, Colored};
 
 pub async fn use_alternate_package(ctx: &SoarContext, name: &str) -> SoarResult<()> {
+    if let Some((name, version)) = name.split_once('@') {
+        let package = switch::switch_version(ctx, name, version).await?;
+        info!(
+            "Switched to {}@{}",
+            Colored(Blue, &package.pkg_name),
+            Colored(Magenta, &package.version)
+        );
+        return Ok(());
+    }
+
     let variants = switch::list_variants(ctx, name)?;
 
     if variants.is_empty() {
@@ -1,9 +1,16 @@
 use std::{
     fmt::Display,
     io::Write,
-    sync::{LazyLock, RwLock},
+    os::raw::c_int,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        LazyLock, Once, RwLock,
+    },
+    thread,
+    time::Duration,
 };
 
+use nix::sys::signal::{signal, SigHandler, Signal};
 use nu_ansi_term::Color::{self, Blue, Green, LightRed, Magenta, Red};
 use serde::Serialize;
 use soar_config::{
@@ -14,9 +21,10 @@ use soar_core::{
     package::install::InstallTarget,
     SoarResult,
 };
+use soar_operations::SoarContext;
 use soar_package::PackageExt;
-use soar_utils::{bytes::format_bytes, system::platform};
-use tracing::{error, info};
+use soar_utils::{bytes::format_bytes, lock::FileLock, system::platform};
+use tracing::{error, info, warn};
 
 pub struct Icons;
 
@@ -173,6 +181,55 @@ pub fn select_package_interactively_with_installed<T: PackageExt>(
     Ok(pkgs.into_iter().nth(selection))
 }
 
+/// Let Ctrl-C cancel the operation `ctx` is about to run, which then undoes
+/// what it had started. A second Ctrl-C quits without waiting for that.
+///
+/// Called once the prompts are behind, which Ctrl-C still simply quits.
+pub fn cancel_on_interrupt(ctx: &SoarContext) {
+    static ARMED: Once = Once::new();
+    static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+    // Only async-signal-safe work in here: note the interrupt and let the
+    // next Ctrl-C take the default path and kill the process.
+    extern "C" fn on_interrupt(_: c_int) {
+        INTERRUPTED.store(true, Ordering::SeqCst);
+        let _ = unsafe { signal(Signal::SIGINT, SigHandler::SigDfl) };
+    }
+
+    let token = ctx.cancellation().clone();
+    ARMED.call_once(|| {
+        if unsafe { signal(Signal::SIGINT, SigHandler::Handler(on_interrupt)) }.is_err() {
+            return;
+        }
+        // A plain thread rather than a task: the operation may be holding
+        // every runtime worker in blocking downloads.
+        thread::spawn(move || {
+            while !INTERRUPTED.load(Ordering::SeqCst) {
+                thread::sleep(Duration::from_millis(50));
+            }
+            token.cancel();
+            warn!("Cancelling; press Ctrl-C again to quit now");
+        });
+    });
+}
+
+/// Held while soar changes what is installed or synced, by a command or a
+/// daemon request, so they run one at a time.
+pub const OPERATIONS_LOCK: &str = ".operations";
+
+/// Take [`OPERATIONS_LOCK`] for a command, waiting while a daemon or another
+/// soar holds it.
+pub fn lock_operations() -> SoarResult<FileLock> {
+    let locked = |e: soar_utils::error::LockError| {
+        SoarError::Custom(format!("Failed to acquire operations lock: {e}"))
+    };
+    if let Some(lock) = FileLock::try_acquire(OPERATIONS_LOCK).map_err(locked)? {
+        return Ok(lock);
+    }
+    info!("Waiting for another operation to finish");
+    FileLock::acquire(OPERATIONS_LOCK).map_err(locked)
+}
+
 pub fn pretty_package_size(ghcr_size: Option<u64>, size: Option<u64>) -> String {
     ghcr_size
         .map(|size| format!("{}", Colored(Magenta, format_bytes(size, 2))))
@@ -19,7 +19,7 @@ use crate::{
     display::DisplaySettings,
     error::{ConfigError, Result},
     profile::Profile,
-    repository::{get_platform_repositories, Repository, SOARPKGS_PUBKEY},
+    repository::{get_platform_repositories, Repository, DEFAULT_CHANNEL, SOARPKGS_PUBKEY},
     utils::default_install_patterns,
 };
 
@@ -99,6 +99,11 @@ pub struct Config {
     /// Global override for desktop integration
     pub desktop_integration: Option<bool>,
 
+    /// How AppImages are run: "mount" runs the file as published, "extract"
+    /// unpacks it at install time for systems without FUSE.
+    /// Default: "mount"
+    pub appimage_mode: Option<AppImageMode>,
+
     /// Global override for sync interval
     pub sync_interval: Option<String>,
 
@@ -112,6 +117,17 @@ pub struct Config {
     pub system_mode: bool,
 }
 
+/// How an installed AppImage is run.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AppImageMode {
+    /// Run the AppImage itself, which mounts its filesystem through FUSE.
+    #[default]
+    Mount,
+    /// Unpack the AppDir at install time and run it through a launcher.
+    Extract,
+}
+
 pub static CONFIG: LazyLock<RwLock<Option<Config>>> = LazyLock::new(|| RwLock::new(None));
 pub static CURRENT_PROFILE: LazyLock<RwLock<Option<String>>> = LazyLock::new(|| RwLock::new(None));
 pub static SYSTEM_MODE: LazyLock<RwLock<bool>> = LazyLock::new(|| RwLock::new(false));
@@ -234,11 +250,16 @@ impl Config {
             repositories.push(Repository {
                 name: repo_info.name.to_string(),
                 url: repo_info.url_template.replace("{}", &current_platform),
+                delta_url: repo_info
+                    .delta_url_template
+                    .map(|url| url.replace("{}", &current_platform)),
                 pubkey: repo_info.pubkey.map(String::from),
                 desktop_integration: repo_info.desktop_integration,
                 enabled: repo_info.enabled,
                 signature_verification: repo_info.signature_verification,
                 sync_interval: repo_info.sync_interval.map(String::from),
+                channels: None,
+                channel: None,
             });
         }
 
@@ -286,6 +307,7 @@ impl Config {
 
             signature_verification: None,
             desktop_integration: None,
+            appimage_mode: None,
             sync_interval: None,
             display: None,
             system_mode: is_system_mode(),
@@ -330,11 +352,16 @@ impl Config {
             repositories.push(Repository {
                 name: repo_info.name.to_string(),
                 url: repo_info.url_template.replace("{}", &current_platform),
+                delta_url: repo_info
+                    .delta_url_template
+                    .map(|url| url.replace("{}", &current_platform)),
                 pubkey: repo_info.pubkey.map(String::from),
                 desktop_integration: repo_info.desktop_integration,
                 enabled: repo_info.enabled,
                 signature_verification: repo_info.signature_verification,
                 sync_interval: repo_info.sync_interval.map(String::from),
+                channels: None,
+                channel: None,
             });
         }
 
@@ -380,6 +407,7 @@ impl Config {
 
             signature_verification: None,
             desktop_integration: None,
+            appimage_mode: None,
             sync_interval: None,
             display: None,
             system_mode,
@@ -458,6 +486,19 @@ impl Config {
                 return Err(ConfigError::DuplicateRepositoryName(repo.name.clone()));
             }
 
+            let mut seen_channels = HashSet::new();
+            for channel in repo.channels.iter().flatten() {
+                if !is_safe_component(&channel.name)
+                    || channel.name == DEFAULT_CHANNEL
+                    || !seen_channels.insert(&channel.name)
+                {
+                    return Err(ConfigError::InvalidRepositoryChannel {
+                        repo: repo.name.clone(),
+                        channel: channel.name.clone(),
+                    });
+                }
+            }
+
             repo.enabled.get_or_insert(true);
 
             if repo.pubkey.is_none() && repo.name.as_str() == "soarpkgs" {
@@ -590,6 +631,14 @@ impl Config {
         self.default_profile()?.get_repositories_path()
     }
 
+    /// Where hook and build commands are recorded.
+    pub fn get_audit_log_path(&self) -> Result<PathBuf> {
+        if let Ok(env_path) = std::env::var("SOAR_AUDIT_LOG") {
+            return Ok(resolve_path(&env_path)?);
+        }
+        self.default_profile()?.get_audit_log_path()
+    }
+
     pub fn get_portable_dirs(&self) -> Result<PathBuf> {
         if let Ok(env_path) = std::env::var("SOAR_PORTABLE_DIRS") {
             return Ok(resolve_path(&env_path)?);
@@ -615,6 +664,10 @@ impl Config {
             .is_some_and(|repo| repo.desktop_integration.unwrap_or(true))
     }
 
+    pub fn appimage_mode(&self) -> AppImageMode {
+        self.appimage_mode.unwrap_or_default()
+    }
+
     pub fn display(&self) -> DisplaySettings {
         self.display.clone().unwrap_or_default()
     }
@@ -683,7 +736,7 @@ pub fn generate_default_config<T: AsRef<str>>(repos: &[T]) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{error::ConfigError, test_utils::with_env};
+    use crate::{error::ConfigError, repository::RepositoryChannel, test_utils::with_env};
 
     #[test]
     fn test_default_config_creation() {
@@ -719,11 +772,14 @@ mod tests {
         Repository {
             name: name.to_string(),
             url: "https://example.com".to_string(),
+            delta_url: None,
             desktop_integration: None,
             pubkey: None,
             enabled: Some(true),
             signature_verification: None,
             sync_interval: None,
+            channels: None,
+            channel: None,
         }
     }
 
@@ -741,6 +797,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_config_resolve_rejects_bad_channels() {
+        for names in [vec!["../x"], vec!["stable"], vec!["testing", "testing"]] {
+            let mut config = Config::default_config::<&str>(&[]);
+            let mut repo = repo_named("my-repo");
+            repo.channels = Some(
+                names
+                    .iter()
+                    .map(|name| {
+                        RepositoryChannel {
+                            name: name.to_string(),
+                            url: "https://example.com/testing.sdb".to_string(),
+                            delta_url: None,
+                        }
+                    })
+                    .collect(),
+            );
+            config.repositories.push(repo);
+
+            assert!(
+                matches!(
+                    config.resolve(),
+                    Err(ConfigError::InvalidRepositoryChannel { .. })
+                ),
+                "channels {names:?} should be rejected"
+            );
+        }
+    }
+
     #[test]
     fn test_config_resolve_accepts_normal_repo_name() {
         let mut config = Config::default_config::<&str>(&[]);
@@ -755,11 +840,14 @@ mod tests {
         config.repositories.push(Repository {
             name: "local".to_string(),
             url: "https://example.com".to_string(),
+            delta_url: None,
             desktop_integration: None,
             pubkey: None,
             enabled: Some(true),
             signature_verification: None,
             sync_interval: None,
+            channels: None,
+            channel: None,
         });
 
         let result = config.resolve();
@@ -772,11 +860,14 @@ mod tests {
         config.repositories.push(Repository {
             name: "needs-key".to_string(),
             url: "https://example.com".to_string(),
+            delta_url: None,
             desktop_integration: None,
             pubkey: None,
             enabled: Some(true),
             signature_verification: Some(true),
             sync_interval: None,
+            channels: None,
+            channel: None,
         });
 
         let result = config.resolve();
@@ -789,20 +880,26 @@ mod tests {
         config.repositories.push(Repository {
             name: "duplicate".to_string(),
             url: "https://example.com".to_string(),
+            delta_url: None,
             desktop_integration: None,
             pubkey: None,
             enabled: Some(true),
             signature_verification: None,
             sync_interval: None,
+            channels: None,
+            channel: None,
         });
         config.repositories.push(Repository {
             name: "duplicate".to_string(),
             url: "https://example2.com".to_string(),
+            delta_url: None,
             desktop_integration: None,
             pubkey: None,
             enabled: Some(true),
             signature_verification: None,
             sync_interval: None,
+            channels: None,
+            channel: None,
         });
 
         let result = config.resolve();
@@ -868,11 +965,14 @@ mod tests {
         config.repositories.push(Repository {
             name: "test_repo".to_string(),
             url: "https://example.com".to_string(),
+            delta_url: None,
             desktop_integration: Some(true),
             pubkey: None,
             enabled: Some(true),
             signature_verification: None,
             sync_interval: None,
+            channels: None,
+            channel: None,
         });
         assert!(config.has_desktop_integration("test_repo"));
     }
@@ -97,6 +97,17 @@ pub enum ConfigError {
     )]
     DuplicateRepositoryName(String),
 
+    #[error("Repository '{repo}' has an invalid channel '{channel}'")]
+    #[diagnostic(
+        code(soar_config::invalid_channel),
+        help(
+            "A channel name is part of its database's file name, so it must be a single path \
+             component. Each channel is listed once, and `stable` is the one at the \
+             repository's own `url`."
+        )
+    )]
+    InvalidRepositoryChannel { repo: String, channel: String },
+
     #[error("Repository '{0}' has signature verification enabled but no pubkey configured")]
     #[diagnostic(
         code(soar_config::missing_pubkey),
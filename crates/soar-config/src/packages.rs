@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
     fs,
     path::PathBuf,
     sync::{LazyLock, RwLock},
@@ -13,7 +13,9 @@ use tracing::info;
 
 use crate::{
     annotations::annotate_toml_table,
+    config::AppImageMode,
     error::{ConfigError, Result},
+    repository::DEFAULT_CHANNEL,
 };
 
 /// Path to the packages configuration file
@@ -142,6 +144,64 @@ pub struct SandboxConfig {
 
     /// Whether to allow network access (requires Landlock V4+, kernel 6.7+).
     pub network: Option<bool>,
+
+    /// How commands are isolated. `namespace` also runs them in their own
+    /// user, mount, PID and network namespaces, falling back to `landlock`
+    /// where unprivileged user namespaces are disabled.
+    pub backend: Option<SandboxBackend>,
+
+    /// System calls to forbid on top of the built-in deny-list (`ptrace`,
+    /// `mount`, `bpf`, `kexec_load` and the like). A command making one is
+    /// killed.
+    #[serde(default)]
+    pub deny_syscalls: Vec<String>,
+
+    /// Resource limits for each command. Unset fields keep the defaults.
+    pub limits: Option<SandboxLimits>,
+}
+
+/// Resource limits for sandboxed hook and build commands. `0` lifts a limit.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SandboxLimits {
+    /// CPU time per process, in seconds (default: 3600).
+    pub cpu_time: Option<u64>,
+    /// Address space per process, in MiB (default: unlimited).
+    pub address_space: Option<u64>,
+    /// Largest file a process may write, in MiB (default: 4096).
+    pub file_size: Option<u64>,
+    /// Processes the user may run at once (default: unlimited).
+    pub processes: Option<u64>,
+}
+
+impl SandboxLimits {
+    /// Field-level merge, `package` fields overriding `global` ones.
+    fn merge(global: Option<&SandboxLimits>, package: Option<&SandboxLimits>) -> Option<Self> {
+        match (global, package) {
+            (None, None) => None,
+            (Some(l), None) | (None, Some(l)) => Some(l.clone()),
+            (Some(g), Some(p)) => {
+                Some(SandboxLimits {
+                    cpu_time: p.cpu_time.or(g.cpu_time),
+                    address_space: p.address_space.or(g.address_space),
+                    file_size: p.file_size.or(g.file_size),
+                    processes: p.processes.or(g.processes),
+                })
+            }
+        }
+    }
+}
+
+/// How a sandboxed hook or build command is isolated.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SandboxBackend {
+    /// Restrict filesystem and TCP access with Landlock.
+    #[default]
+    Landlock,
+    /// Landlock, inside unprivileged user and mount namespaces: a read-only
+    /// system, private `/tmp`, a process table of its own and a scrubbed
+    /// environment.
+    Namespace,
 }
 
 impl SandboxConfig {
@@ -161,8 +221,13 @@ impl SandboxConfig {
         self.network.unwrap_or(false)
     }
 
-    /// Field-level merge: per-package fields override globals; path lists are
-    /// concatenated (globals first, then per-package).
+    /// The isolation backend, Landlock unless set.
+    pub fn backend(&self) -> SandboxBackend {
+        self.backend.unwrap_or_default()
+    }
+
+    /// Field-level merge: per-package fields override globals; path and
+    /// system call lists are concatenated (globals first, then per-package).
     pub fn merge(
         global: Option<&SandboxConfig>,
         package: Option<&SandboxConfig>,
@@ -176,18 +241,120 @@ impl SandboxConfig {
                 fs_read.extend(p.fs_read.iter().cloned());
                 let mut fs_write = g.fs_write.clone();
                 fs_write.extend(p.fs_write.iter().cloned());
+                let mut deny_syscalls = g.deny_syscalls.clone();
+                deny_syscalls.extend(p.deny_syscalls.iter().cloned());
                 Some(SandboxConfig {
                     enabled: p.enabled.or(g.enabled),
                     require: p.require.or(g.require),
                     fs_read,
                     fs_write,
                     network: p.network.or(g.network),
+                    backend: p.backend.or(g.backend),
+                    deny_syscalls,
+                    limits: SandboxLimits::merge(g.limits.as_ref(), p.limits.as_ref()),
                 })
             }
         }
     }
 }
 
+/// Runtime sandbox profile for an installed package's commands.
+///
+/// Uses Landlock (Linux 5.13+). The package itself and the system's libraries
+/// are always readable; everything else the program may touch is listed here.
+#[derive(
+    Clone, Debug, Default, PartialEq, Deserialize, Serialize, Documented, DocumentedFields,
+)]
+pub struct RuntimeSandbox {
+    /// Whether the profile applies. Defaults to `true`; set to `false` to drop
+    /// a profile an earlier install of the package recorded.
+    pub enabled: Option<bool>,
+
+    /// Refuse to run the package when Landlock is not available, instead of
+    /// running it unsandboxed with a warning.
+    pub require: Option<bool>,
+
+    /// Additional paths that can be read.
+    #[serde(default)]
+    pub fs_read: Vec<String>,
+
+    /// Paths that can be written (beyond /tmp).
+    #[serde(default)]
+    pub fs_write: Vec<String>,
+
+    /// Whether the user's config, data and cache directories can be written.
+    pub user_dirs: Option<bool>,
+
+    /// Whether to allow all TCP connections and listening sockets. When off,
+    /// only the ports below are (requires Landlock V4+, kernel 6.7+).
+    pub network: Option<bool>,
+
+    /// TCP ports the package may connect to when `network` is off.
+    #[serde(default)]
+    pub connect_ports: Vec<u16>,
+
+    /// TCP ports the package may listen on when `network` is off.
+    #[serde(default)]
+    pub bind_ports: Vec<u16>,
+}
+
+impl RuntimeSandbox {
+    /// Whether the profile applies; only an explicit `Some(false)` turns it off.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.unwrap_or(true)
+    }
+
+    /// Whether the package must not run without Landlock.
+    pub fn is_required(&self) -> bool {
+        self.require.unwrap_or(false)
+    }
+
+    /// Whether the user's own directories are writable.
+    pub fn allows_user_dirs(&self) -> bool {
+        self.user_dirs.unwrap_or(false)
+    }
+
+    /// Whether all TCP network access is permitted.
+    pub fn allows_network(&self) -> bool {
+        self.network.unwrap_or(false)
+    }
+}
+
+/// How a package's commands are started: variables set for them, arguments
+/// put before the user's, and a command run first.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LaunchConfig {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_launch: Option<String>,
+}
+
+impl LaunchConfig {
+    /// Whether the commands start as they are.
+    pub fn is_empty(&self) -> bool {
+        self.env.is_empty() && self.args.is_empty() && self.pre_launch.is_none()
+    }
+
+    /// These settings on top of `base`: variables are merged, these winning,
+    /// and `args` and `pre_launch` replace the base's when given.
+    pub fn over(&self, base: &LaunchConfig) -> LaunchConfig {
+        let mut env = base.env.clone();
+        env.extend(self.env.clone());
+        LaunchConfig {
+            env,
+            args: if self.args.is_empty() {
+                base.args.clone()
+            } else {
+                self.args.clone()
+            },
+            pre_launch: self.pre_launch.clone().or_else(|| base.pre_launch.clone()),
+        }
+    }
+}
+
 /// Full package options for detailed specification.
 #[derive(Clone, Debug, Default, Deserialize, Serialize, Documented, DocumentedFields)]
 pub struct PackageOptions {
@@ -210,6 +377,10 @@ pub struct PackageOptions {
     /// Repository to install from.
     pub repo: Option<String>,
 
+    /// Repository channel to follow, such as `testing`. The package gets the
+    /// newest version in either this channel or the default `stable` one.
+    pub channel: Option<String>,
+
     /// Direct URL to download the package from (makes it a "local" package).
     pub url: Option<String>,
 
@@ -283,6 +454,34 @@ pub struct PackageOptions {
     /// Portable directory configuration.
     pub portable: Option<PortableConfig>,
 
+    /// How an AppImage is run: "mount" or "extract" (overrides the global setting).
+    pub appimage_mode: Option<AppImageMode>,
+
+    /// Commands printing the package's shell completions, by shell, or its
+    /// manual page under `man` (e.g. `{ zsh = "app completions zsh" }`).
+    /// Each one overrides the repository's for the same shell.
+    pub completion_commands: Option<HashMap<String, String>>,
+
+    /// Sandbox the package's commands run in, every time they run.
+    pub runtime_sandbox: Option<RuntimeSandbox>,
+
+    /// Environment variables set whenever the package's commands start
+    /// (e.g. `{ QT_QPA_PLATFORM = "xcb" }`). Values may refer to other
+    /// variables and to `$INSTALL_DIR`. Merged over the repository's.
+    pub env: Option<HashMap<String, String>>,
+
+    /// Arguments put before the user's whenever the package's commands
+    /// start. Replaces the repository's.
+    pub args: Option<Vec<String>>,
+
+    /// Shell command run before the package's commands start; when it fails
+    /// the command does not start. Replaces the repository's.
+    pub pre_launch: Option<String>,
+
+    /// Priority of the package's commands over those of the same name from
+    /// other packages; the highest holds the command (default: 0).
+    pub priority: Option<i32>,
+
     /// Glob patterns filtering which files an install keeps, overriding the default.
     #[deprecated(
         since = "0.13.0",
@@ -330,6 +529,7 @@ pub struct ResolvedPackage {
     pub pkg_id: Option<String>,
     pub version: Option<String>,
     pub repo: Option<String>,
+    pub channel: Option<String>,
     pub url: Option<String>,
     pub bsum: Option<String>,
     pub github: Option<String>,
@@ -349,6 +549,11 @@ pub struct ResolvedPackage {
     pub pinned: bool,
     pub profile: Option<String>,
     pub portable: Option<PortableConfig>,
+    pub appimage_mode: Option<AppImageMode>,
+    pub completion_commands: Option<HashMap<String, String>>,
+    pub runtime_sandbox: Option<RuntimeSandbox>,
+    pub launch: Option<LaunchConfig>,
+    pub priority: Option<i32>,
     #[deprecated(
         since = "0.13.0",
         note = "only the OCI download path applies these; the declarative format does not use it"
@@ -358,6 +563,23 @@ pub struct ResolvedPackage {
     pub arch_map: Option<HashMap<String, String>>,
 }
 
+impl PackageOptions {
+    /// The launch settings declared for the package, if any.
+    fn launch(&self) -> Option<LaunchConfig> {
+        let launch = LaunchConfig {
+            env: self
+                .env
+                .iter()
+                .flatten()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+            args: self.args.clone().unwrap_or_default(),
+            pre_launch: self.pre_launch.clone(),
+        };
+        (!launch.is_empty()).then_some(launch)
+    }
+}
+
 impl PackageSpec {
     /// Resolve the package specification with defaults applied.
     // Still populated while the OCI path exists; see the field's deprecation.
@@ -377,6 +599,7 @@ impl PackageSpec {
                     pkg_id: None,
                     version,
                     repo: None,
+                    channel: None,
                     url: None,
                     bsum: None,
                     github: None,
@@ -396,6 +619,11 @@ impl PackageSpec {
                     pinned,
                     profile: defaults.and_then(|d| d.profile.clone()),
                     portable: None,
+                    appimage_mode: None,
+                    completion_commands: None,
+                    runtime_sandbox: None,
+                    launch: None,
+                    priority: None,
                     install_patterns: defaults.and_then(|d| d.install_patterns.clone()),
                     binary_only: defaults.and_then(|d| d.binary_only).unwrap_or(false),
                     arch_map: None,
@@ -415,6 +643,7 @@ impl PackageSpec {
                     pkg_id: opts.pkg_id.clone(),
                     version,
                     repo: opts.repo.clone(),
+                    channel: opts.channel.clone().filter(|c| c != DEFAULT_CHANNEL),
                     url: opts.url.clone(),
                     bsum: opts.bsum.clone(),
                     github: opts.github.clone(),
@@ -440,6 +669,11 @@ impl PackageSpec {
                         .clone()
                         .or_else(|| defaults.and_then(|d| d.profile.clone())),
                     portable: opts.portable.clone(),
+                    appimage_mode: opts.appimage_mode,
+                    completion_commands: opts.completion_commands.clone(),
+                    runtime_sandbox: opts.runtime_sandbox.clone(),
+                    launch: opts.launch(),
+                    priority: opts.priority,
                     install_patterns: opts
                         .install_patterns
                         .clone()
@@ -511,9 +745,16 @@ impl PackagesConfig {
 # Package format:
 #   package_name = "*"                    # Latest version
 #   package_name = "1.2.3"                # Specific version (pinned)
+#   package_name = "2025-01-14-a1b2c3"    # Specific build id (pinned)
 #   package_name = { version = "1.2" }    # Same as above
 #   package_name = { family = "pkg", repo = "bincache" }
+#   package_name = { channel = "testing" }  # Follow a repository channel
 #   package_name = { pinned = true, portable = { home = "~/.pkg" } }
+#   package_name = { appimage_mode = "extract" }  # Run without FUSE
+#   package_name = { completion_commands = { bash = "pkg completions bash" } }
+#   package_name = { runtime_sandbox = { fs_read = ["~/Documents"] } }
+#   package_name = { env = { QT_QPA_PLATFORM = "xcb" }, args = ["--no-sandbox"] }
+#   package_name = { priority = 10 }     # Wins commands other packages also provide
 
 "#;
         doc.as_table_mut().decor_mut().set_prefix(header);
@@ -721,6 +962,23 @@ special = { profile = "isolated" }
         assert_eq!(resolved[0].profile, Some("isolated".to_string()));
     }
 
+    #[test]
+    fn test_channel_resolved() {
+        let toml_str = r#"
+[packages]
+neovim = { channel = "testing" }
+curl = { channel = "stable" }
+"#;
+        let config: PackagesConfig = toml::from_str(toml_str).unwrap();
+        let resolved = config.resolved_packages();
+
+        let neovim = resolved.iter().find(|p| p.name == "neovim").unwrap();
+        let curl = resolved.iter().find(|p| p.name == "curl").unwrap();
+
+        assert_eq!(neovim.channel.as_deref(), Some("testing"));
+        assert_eq!(curl.channel, None);
+    }
+
     #[test]
     fn test_bsum_pin_resolved() {
         let toml_str = r#"
@@ -763,6 +1021,137 @@ firefox = { portable = { home = "~/.firefox-home", config = "~/.firefox-config"
         assert_eq!(portable.config, Some("~/.firefox-config".to_string()));
     }
 
+    #[test]
+    fn test_appimage_mode_resolved() {
+        let toml_str = r#"
+[packages]
+krita = { appimage_mode = "extract" }
+gimp = "*"
+"#;
+        let config: PackagesConfig = toml::from_str(toml_str).unwrap();
+        let resolved = config.resolved_packages();
+
+        let mode = |name: &str| {
+            resolved
+                .iter()
+                .find(|p| p.name == name)
+                .unwrap()
+                .appimage_mode
+        };
+        assert_eq!(mode("krita"), Some(AppImageMode::Extract));
+        assert_eq!(mode("gimp"), None);
+    }
+
+    #[test]
+    fn test_runtime_sandbox_resolved() {
+        let toml_str = r#"
+[packages.browser]
+runtime_sandbox = { fs_write = ["~/Downloads"], connect_ports = [443] }
+"#;
+        let config: PackagesConfig = toml::from_str(toml_str).unwrap();
+        let resolved = config.resolved_packages();
+
+        let profile = resolved[0].runtime_sandbox.as_ref().unwrap();
+        assert!(profile.is_enabled());
+        assert!(!profile.allows_network());
+        assert_eq!(profile.fs_write, ["~/Downloads"]);
+        assert_eq!(profile.connect_ports, [443]);
+    }
+
+    #[test]
+    fn test_launch_settings_resolved_and_merged() {
+        let toml_str = r#"
+[packages.editor]
+env = { QT_QPA_PLATFORM = "xcb", API = "https://example.com" }
+args = ["--ozone-platform=x11"]
+
+[packages.plain]
+version = "1.0"
+"#;
+        let config: PackagesConfig = toml::from_str(toml_str).unwrap();
+        let resolved = config.resolved_packages();
+        let editor = resolved.iter().find(|p| p.name == "editor").unwrap();
+        let plain = resolved.iter().find(|p| p.name == "plain").unwrap();
+        assert!(plain.launch.is_none());
+
+        let repository = LaunchConfig {
+            env: BTreeMap::from([
+                ("API".into(), "https://repo.example".into()),
+                ("LD_LIBRARY_PATH".into(), "$INSTALL_DIR/lib".into()),
+            ]),
+            args: vec!["--repo-flag".into()],
+            pre_launch: Some("mkdir -p ~/.editor".into()),
+        };
+        let launch = editor.launch.as_ref().unwrap().over(&repository);
+        assert_eq!(launch.env["API"], "https://example.com");
+        assert_eq!(launch.env["LD_LIBRARY_PATH"], "$INSTALL_DIR/lib");
+        assert_eq!(launch.env["QT_QPA_PLATFORM"], "xcb");
+        assert_eq!(launch.args, ["--ozone-platform=x11"]);
+        assert_eq!(launch.pre_launch.as_deref(), Some("mkdir -p ~/.editor"));
+    }
+
+    #[test]
+    fn test_priority_resolved() {
+        let toml_str = r#"
+[packages]
+vim = "*"
+neovim = { priority = 20 }
+"#;
+        let config: PackagesConfig = toml::from_str(toml_str).unwrap();
+        let resolved = config.resolved_packages();
+        let priority = |name: &str| resolved.iter().find(|p| p.name == name).unwrap().priority;
+        assert_eq!(priority("vim"), None);
+        assert_eq!(priority("neovim"), Some(20));
+    }
+
+    #[test]
+    fn test_sandbox_backend_merged() {
+        let toml_str = r#"
+[defaults]
+sandbox = { backend = "namespace" }
+
+[packages]
+tool = { sandbox = { network = true } }
+legacy = { sandbox = { backend = "landlock" } }
+"#;
+        let config: PackagesConfig = toml::from_str(toml_str).unwrap();
+        let backend = |name: &str| {
+            config
+                .resolved_packages()
+                .into_iter()
+                .find(|p| p.name == name)
+                .and_then(|p| p.sandbox)
+                .map(|s| s.backend())
+        };
+
+        assert_eq!(backend("tool"), Some(SandboxBackend::Namespace));
+        assert_eq!(backend("legacy"), Some(SandboxBackend::Landlock));
+    }
+
+    #[test]
+    fn test_sandbox_limits_merged_per_field() {
+        let toml_str = r#"
+[defaults.sandbox]
+deny_syscalls = ["ptrace"]
+limits = { cpu_time = 600, file_size = 100 }
+
+[packages]
+tool = { sandbox = { deny_syscalls = ["socket"], limits = { cpu_time = 0 } } }
+"#;
+        let config: PackagesConfig = toml::from_str(toml_str).unwrap();
+        let sandbox = config.resolved_packages().remove(0).sandbox.unwrap();
+
+        assert_eq!(sandbox.deny_syscalls, ["ptrace", "socket"]);
+        assert_eq!(
+            sandbox.limits,
+            Some(SandboxLimits {
+                cpu_time: Some(0),
+                file_size: Some(100),
+                ..Default::default()
+            })
+        );
+    }
+
     #[test]
     fn test_annotated_document() {
         let config = PackagesConfig::default_config();
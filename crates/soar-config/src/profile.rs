@@ -45,6 +45,10 @@ impl Profile {
         Ok(self.get_root_path()?.join("repos"))
     }
 
+    pub(crate) fn get_audit_log_path(&self) -> Result<PathBuf> {
+        Ok(self.get_root_path()?.join("audit.jsonl"))
+    }
+
     pub(crate) fn get_portable_dirs(&self) -> Result<PathBuf> {
         Ok(self.get_root_path()?.join("portable-dirs"))
     }
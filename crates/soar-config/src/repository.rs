@@ -15,6 +15,11 @@ pub struct Repository {
     /// URL to the repository's metadata file.
     pub url: String,
 
+    /// URL to the repository's delta manifest. When set, a sync downloads
+    /// only the changes since the last sync instead of the whole metadata.
+    /// Default: none
+    pub delta_url: Option<String>,
+
     /// Enables desktop integration for packages from this repository.
     /// Default: true
     pub desktop_integration: Option<bool>,
@@ -33,13 +38,82 @@ pub struct Repository {
     /// Optional sync interval (e.g., "1h", "12h", "1d").
     /// Default: "3h"
     pub sync_interval: Option<String>,
+
+    /// Further channels the repository publishes beside the one at `url`,
+    /// which is the `stable` channel. Each is synced into its own database,
+    /// and a package follows one with `channel = "<name>"` in packages.toml.
+    /// Default: none
+    pub channels: Option<Vec<RepositoryChannel>>,
+
+    /// The channel this value describes, for the views [`Repository::for_channel`]
+    /// makes. Always `None` for a configured repository.
+    #[serde(skip)]
+    pub channel: Option<String>,
+}
+
+/// A channel a repository publishes beside its stable metadata.
+#[derive(Clone, Deserialize, Serialize, Documented, DocumentedFields)]
+pub struct RepositoryChannel {
+    /// Name packages follow the channel by, such as `testing`.
+    pub name: String,
+
+    /// URL to the channel's metadata file.
+    pub url: String,
+
+    /// URL to the channel's delta manifest.
+    /// Default: none
+    pub delta_url: Option<String>,
 }
 
+/// The channel a repository's `url` publishes.
+pub const DEFAULT_CHANNEL: &str = "stable";
+
 impl Repository {
     pub fn get_path(&self) -> Result<PathBuf> {
         Ok(get_config().get_repositories_path()?.join(&self.name))
     }
 
+    /// Path of the metadata database for the channel this value describes.
+    pub fn metadata_db_path(&self) -> Result<PathBuf> {
+        let file = match &self.channel {
+            Some(channel) => format!("metadata-{channel}.db"),
+            None => "metadata.db".to_string(),
+        };
+        Ok(self.get_path()?.join(file))
+    }
+
+    /// The repository as seen through one of its channels: the channel's URLs,
+    /// with everything else, signing key included, shared.
+    ///
+    /// Returns `None` for a channel the repository does not publish. The
+    /// default channel is the repository itself.
+    pub fn for_channel(&self, channel: &str) -> Option<Repository> {
+        if channel == DEFAULT_CHANNEL {
+            return Some(Repository {
+                channel: None,
+                channels: None,
+                ..self.clone()
+            });
+        }
+        let published = self.channels.iter().flatten().find(|c| c.name == channel)?;
+        Some(Repository {
+            url: published.url.clone(),
+            delta_url: published.delta_url.clone(),
+            channels: None,
+            channel: Some(channel.to_string()),
+            ..self.clone()
+        })
+    }
+
+    /// Views of every channel besides the default one.
+    pub fn channel_views(&self) -> Vec<Repository> {
+        self.channels
+            .iter()
+            .flatten()
+            .filter_map(|c| self.for_channel(&c.name))
+            .collect()
+    }
+
     pub fn is_enabled(&self) -> bool {
         self.enabled.unwrap_or(true)
     }
@@ -75,6 +149,7 @@ impl Repository {
 pub struct DefaultRepositoryInfo {
     pub name: &'static str,
     pub url_template: &'static str,
+    pub delta_url_template: Option<&'static str>,
     pub pubkey: Option<&'static str>,
     pub desktop_integration: Option<bool>,
     pub enabled: Option<bool>,
@@ -107,11 +182,14 @@ mod tests {
         let repo = Repository {
             name: "test".to_string(),
             url: "https://example.com".to_string(),
+            delta_url: None,
             desktop_integration: None,
             pubkey: None,
             enabled: Some(true),
             signature_verification: None,
             sync_interval: None,
+            channels: None,
+            channel: None,
         };
 
         assert!(repo.is_enabled());
@@ -134,16 +212,53 @@ mod tests {
         let repo = Repository {
             name: "test".to_string(),
             url: "https://example.com".to_string(),
+            delta_url: None,
             desktop_integration: None,
             pubkey: None,
             enabled: Some(true),
             signature_verification: None,
             sync_interval: Some("always".to_string()),
+            channels: None,
+            channel: None,
         };
 
         assert_eq!(repo.sync_interval(), 0);
     }
 
+    #[test]
+    fn test_repository_for_channel() {
+        let repo = Repository {
+            name: "test".to_string(),
+            url: "https://example.com/stable.sdb".to_string(),
+            delta_url: Some("https://example.com/stable.delta".to_string()),
+            desktop_integration: None,
+            pubkey: Some("key".to_string()),
+            enabled: Some(true),
+            signature_verification: None,
+            sync_interval: None,
+            channels: Some(vec![RepositoryChannel {
+                name: "testing".to_string(),
+                url: "https://example.com/testing.sdb".to_string(),
+                delta_url: None,
+            }]),
+            channel: None,
+        };
+
+        let testing = repo.for_channel("testing").unwrap();
+        assert_eq!(testing.name, "test");
+        assert_eq!(testing.url, "https://example.com/testing.sdb");
+        assert_eq!(testing.delta_url, None);
+        assert_eq!(testing.pubkey.as_deref(), Some("key"));
+        assert_eq!(testing.channel.as_deref(), Some("testing"));
+
+        let stable = repo.for_channel(DEFAULT_CHANNEL).unwrap();
+        assert_eq!(stable.url, repo.url);
+        assert_eq!(stable.channel, None);
+
+        assert!(repo.for_channel("nightly").is_none());
+        assert_eq!(repo.channel_views().len(), 1);
+    }
+
     #[test]
     fn test_get_platform_repositories() {
         let repos = get_platform_repositories();
@@ -0,0 +1,213 @@
+//! Commands provided by more than one package.
+//!
+//! Every command an install links into the bin directory is registered as an
+//! alternative, with the priority packages.toml gives the package. The link
+//! then goes to the provider [`AlternativeRepository::providers`] ranks first,
+//! rather than to whichever package was installed last, and removing that
+//! provider hands the command to the next one.
+
+use std::{
+    fs,
+    os::unix,
+    path::{Path, PathBuf},
+};
+
+use soar_db::{
+    models::core::{Alternative, NewAlternative},
+    repository::alternatives::AlternativeRepository,
+};
+use tracing::{debug, info, warn};
+
+use crate::{
+    database::{connection::DieselDatabase, models::InstalledPackage},
+    error::ErrorContext,
+    SoarResult,
+};
+
+/// A command's providers, best first.
+pub struct Providers {
+    pub name: String,
+    pub providers: Vec<(Alternative, InstalledPackage)>,
+}
+
+impl Providers {
+    pub fn load(db: &DieselDatabase, name: &str) -> SoarResult<Self> {
+        let providers = db
+            .with_conn(|conn| AlternativeRepository::providers(conn, name))?
+            .into_iter()
+            .map(|(alternative, package)| (alternative, package.into()))
+            .collect();
+        Ok(Self {
+            name: name.to_string(),
+            providers,
+        })
+    }
+
+    /// The provider that holds the command.
+    pub fn best(&self) -> Option<&(Alternative, InstalledPackage)> {
+        self.providers.first()
+    }
+
+    /// Whether the user chose the provider rather than the priorities.
+    pub fn is_manual(&self) -> bool {
+        self.best()
+            .is_some_and(|(alternative, _)| alternative.selected)
+    }
+}
+
+/// Register the commands an install linked into `bin_dir`, then point each at
+/// the provider that should hold it.
+///
+/// `links` pairs what each link runs with the link, and `replaces` is the
+/// install an update replaces, whose place among the providers it takes. When
+/// another package holds a command, its link is put back and the install is
+/// told how to take the command over.
+pub fn claim(
+    db: &DieselDatabase,
+    bin_dir: &Path,
+    package_id: i32,
+    replaces: Option<i32>,
+    links: &[(PathBuf, PathBuf)],
+    priority: i32,
+) -> SoarResult<()> {
+    if let Some(replaced) = replaces.filter(|&id| id != package_id) {
+        db.with_conn(|conn| AlternativeRepository::transfer(conn, replaced, package_id))?;
+    }
+    for (target, link) in links {
+        if link.parent() != Some(bin_dir) {
+            continue;
+        }
+        let Some(name) = link.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let target = target.to_string_lossy();
+        db.with_conn(|conn| {
+            AlternativeRepository::register(
+                conn,
+                &NewAlternative {
+                    name,
+                    package_id,
+                    target: &target,
+                    priority,
+                },
+            )
+        })?;
+
+        let providers = Providers::load(db, name)?;
+        let Some((_, holder)) = providers.best() else {
+            continue;
+        };
+        let others = providers
+            .providers
+            .iter()
+            .filter(|(alternative, _)| alternative.package_id != package_id)
+            .map(|(_, package)| package.pkg_name.as_str())
+            .collect::<Vec<_>>();
+        if others.is_empty() {
+            continue;
+        }
+
+        if holder.id as i32 == package_id {
+            info!(
+                "{name} is also provided by {}; this package's takes it",
+                others.join(", ")
+            );
+        } else if let Some((_, package)) = providers
+            .providers
+            .iter()
+            .find(|(alternative, _)| alternative.package_id == package_id)
+        {
+            warn!(
+                "{name} is already provided by {}, which keeps it; run `soar alternatives set \
+                 {name} {}` to use this package's",
+                holder.pkg_name, package.pkg_name
+            );
+        }
+        activate(bin_dir, &providers)?;
+    }
+    Ok(())
+}
+
+/// Record what a package's commands run now that their launchers were
+/// written again, and relink those it holds.
+///
+/// Unlike [`claim`], this registers nothing new, so a package installed
+/// before alternatives were tracked is left as it is.
+pub fn retarget(
+    db: &DieselDatabase,
+    bin_dir: &Path,
+    package_id: i32,
+    links: &[(PathBuf, PathBuf)],
+) -> SoarResult<()> {
+    for (target, link) in links {
+        let Some(name) = link.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let target = target.to_string_lossy();
+        let updated = db
+            .with_conn(|conn| AlternativeRepository::set_target(conn, name, package_id, &target))?;
+        if updated > 0 {
+            activate(bin_dir, &Providers::load(db, name)?)?;
+        }
+    }
+    Ok(())
+}
+
+/// Point a command's link at its best provider. A file that is not a link is
+/// never replaced.
+///
+/// # Returns
+///
+/// Whether the link now goes somewhere.
+pub fn activate(bin_dir: &Path, providers: &Providers) -> SoarResult<bool> {
+    let link = bin_dir.join(&providers.name);
+    let existing = fs::symlink_metadata(&link).ok();
+    if existing.as_ref().is_some_and(|meta| !meta.is_symlink()) {
+        warn!(
+            "{} is not a symlink; leaving it in place of {}'s providers",
+            link.display(),
+            providers.name
+        );
+        return Ok(false);
+    }
+
+    let Some((alternative, package)) = providers.best() else {
+        return Ok(false);
+    };
+    let target = Path::new(&alternative.target);
+    if fs::read_link(&link).is_ok_and(|current| current == target) {
+        return Ok(true);
+    }
+    if existing.is_some() {
+        fs::remove_file(&link).with_context(|| format!("removing symlink {}", link.display()))?;
+    }
+    unix::fs::symlink(target, &link)
+        .with_context(|| format!("creating symlink {}", link.display()))?;
+    debug!(
+        name = providers.name,
+        pkg_name = package.pkg_name,
+        target = %target.display(),
+        "linked alternative"
+    );
+    Ok(true)
+}
+
+/// Hand the commands a removed package held to their next providers.
+///
+/// `names` are the commands it registered. Those whose link is gone, or no
+/// longer leads anywhere, are linked again.
+pub fn restore(db: &DieselDatabase, bin_dir: &Path, names: &[String]) -> SoarResult<()> {
+    for name in names {
+        let link = bin_dir.join(name);
+        if link.exists() {
+            continue;
+        }
+        let providers = Providers::load(db, name)?;
+        if activate(bin_dir, &providers)? {
+            if let Some((_, package)) = providers.best() {
+                info!("{name} is now provided by {}", package.pkg_name);
+            }
+        }
+    }
+    Ok(())
+}
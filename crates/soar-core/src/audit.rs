@@ -0,0 +1,489 @@
+//! Audit log of hook and build commands.
+//!
+//! Hooks and build commands come from `packages.toml` files that may be
+//! shared, and run arbitrary shell. Each run is appended to a JSON-lines log
+//! in the data directory: what ran, where, under which sandbox rules, and
+//! how it ended, with the end of its output.
+//!
+//! Once the log reaches [`LOG_LIMIT`] it is moved aside to `<log>.1`,
+//! replacing the one moved aside before, so it takes at most twice that.
+
+use std::{
+    collections::BTreeMap,
+    fs::{self, OpenOptions},
+    io::{self, BufRead as _, BufReader, Read, Write},
+    os::{
+        fd::AsFd,
+        unix::{fs::MetadataExt as _, process::ExitStatusExt as _},
+    },
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use nix::{
+    fcntl::{fcntl, FcntlArg, Flock, FlockArg, OFlag},
+    sys::signal::{kill, Signal},
+    unistd::Pid,
+};
+use serde::{Deserialize, Serialize};
+use soar_config::config::get_config;
+use soar_utils::cancel::CancellationToken;
+use tracing::{debug, warn};
+
+use crate::{
+    error::{ErrorContext, SoarError},
+    sandbox::{SandboxRules, Violation},
+    SoarResult,
+};
+
+/// How much of each output stream a record keeps, from the end.
+pub const OUTPUT_LIMIT: usize = 64 * 1024;
+
+/// The size at which the log is moved aside for a fresh one.
+pub const LOG_LIMIT: u64 = 16 * 1024 * 1024;
+
+/// What a recorded command was run for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditKind {
+    Hook,
+    Build,
+}
+
+/// One run of a hook or build command.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct AuditRecord {
+    /// When the command finished, in RFC 3339.
+    pub timestamp: String,
+    pub pkg_name: String,
+    pub pkg_id: String,
+    pub pkg_version: String,
+    pub kind: AuditKind,
+    /// The hook's name, or which build command this was (`build 2/3`).
+    pub name: String,
+    pub command: String,
+    pub working_dir: PathBuf,
+    /// Variables soar set for the command. It inherited soar's others too,
+    /// unless it ran in namespaces.
+    pub env: BTreeMap<String, String>,
+    /// The sandbox it ran in, or `None` when it ran without one.
+    pub sandbox: Option<SandboxRules>,
+    pub exit_code: Option<i32>,
+    /// The signal that ended the command, if one did.
+    pub signal: Option<i32>,
+    /// The sandbox limit that stopped the command, if one did.
+    pub violation: Option<String>,
+    pub duration_ms: u64,
+    pub stdout: String,
+    pub stderr: String,
+    /// Whether output was cut to the last [`OUTPUT_LIMIT`] bytes.
+    pub truncated: bool,
+}
+
+impl AuditRecord {
+    /// Whether the command failed.
+    pub fn failed(&self) -> bool {
+        self.exit_code != Some(0)
+    }
+}
+
+/// A command run by [`capture`].
+#[derive(Debug)]
+pub struct Captured {
+    pub status: ExitStatus,
+    /// Variables set on the command.
+    pub env: BTreeMap<String, String>,
+    pub duration: Duration,
+    /// The last [`OUTPUT_LIMIT`] bytes of standard output.
+    pub stdout: Vec<u8>,
+    /// The last [`OUTPUT_LIMIT`] bytes of standard error.
+    pub stderr: Vec<u8>,
+    /// Whether either stream was longer than what was kept.
+    pub truncated: bool,
+}
+
+/// Run `cmd`, passing what it prints through to soar's own output and keeping
+/// the end of each stream.
+///
+/// Output is read until the command itself exits. Something it left running
+/// in the background may still hold its output open, and is not waited for.
+///
+/// Once `cancel` is cancelled the command is sent `SIGTERM`; it still runs to
+/// its end, which the returned status records.
+pub fn capture(mut cmd: Command, cancel: Option<&CancellationToken>) -> io::Result<Captured> {
+    let env = cmd
+        .get_envs()
+        .filter_map(|(key, value)| {
+            Some((
+                key.to_string_lossy().into_owned(),
+                value?.to_string_lossy().into_owned(),
+            ))
+        })
+        .collect();
+
+    let started = Instant::now();
+    let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    for fd in [
+        stdout.as_ref().map(AsFd::as_fd),
+        stderr.as_ref().map(AsFd::as_fd),
+    ]
+    .into_iter()
+    .flatten()
+    {
+        fcntl(fd, FcntlArg::F_SETFL(OFlag::O_NONBLOCK))?;
+    }
+    let exited = AtomicBool::new(false);
+    let (status, (stdout, out_truncated), (stderr, err_truncated)) = thread::scope(|scope| {
+        let out = scope.spawn(|| tee(stdout, io::stdout(), &exited));
+        let err = scope.spawn(|| tee(stderr, io::stderr(), &exited));
+        let status = wait(&mut child, cancel);
+        exited.store(true, Ordering::SeqCst);
+        (
+            status,
+            out.join().unwrap_or_default(),
+            err.join().unwrap_or_default(),
+        )
+    });
+
+    Ok(Captured {
+        status: status?,
+        env,
+        duration: started.elapsed(),
+        stdout,
+        stderr,
+        truncated: out_truncated || err_truncated,
+    })
+}
+
+/// Wait for `child`, sending it `SIGTERM` once `cancel` is cancelled.
+fn wait(child: &mut Child, cancel: Option<&CancellationToken>) -> io::Result<ExitStatus> {
+    let Some(cancel) = cancel else {
+        return child.wait();
+    };
+    let mut signalled = false;
+    loop {
+        // Checked here rather than from another thread, so the signal cannot
+        // reach a pid reused after the child was reaped.
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        if !signalled && cancel.is_cancelled() {
+            let _ = kill(Pid::from_raw(child.id() as i32), Signal::SIGTERM);
+            signalled = true;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+/// Copy `from` to `to` until it ends, or has nothing more to give once
+/// `exited` is set, returning the last [`OUTPUT_LIMIT`] bytes and whether
+/// there was more.
+fn tee(from: Option<impl Read>, mut to: impl Write, exited: &AtomicBool) -> (Vec<u8>, bool) {
+    let Some(mut from) = from else {
+        return (Vec::new(), false);
+    };
+    let mut kept = Vec::new();
+    let mut truncated = false;
+    let mut buf = [0u8; 8192];
+    loop {
+        // Checked before the read, so whatever the command wrote before exiting
+        // is in the pipe by the time an empty one counts as the end.
+        let done = exited.load(Ordering::SeqCst);
+        let n = match from.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => {
+                if done {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(10));
+                continue;
+            }
+            Err(_) => break,
+        };
+        // Whoever reads soar's output going away is no reason to stop the
+        // command.
+        let _ = to.write_all(&buf[..n]).and_then(|_| to.flush());
+        kept.extend_from_slice(&buf[..n]);
+        if kept.len() > 2 * OUTPUT_LIMIT {
+            kept.drain(..kept.len() - OUTPUT_LIMIT);
+            truncated = true;
+        }
+    }
+    if kept.len() > OUTPUT_LIMIT {
+        kept.drain(..kept.len() - OUTPUT_LIMIT);
+        truncated = true;
+    }
+    (kept, truncated)
+}
+
+/// Who ran a recorded command, and what for.
+pub struct AuditEntry<'a> {
+    pub pkg_name: &'a str,
+    pub pkg_id: &'a str,
+    pub pkg_version: &'a str,
+    pub kind: AuditKind,
+    pub name: String,
+    pub command: &'a str,
+    pub working_dir: &'a Path,
+}
+
+impl AuditEntry<'_> {
+    /// The record of the run `captured`, under `sandbox`.
+    pub fn record(self, sandbox: Option<SandboxRules>, captured: &Captured) -> AuditRecord {
+        let violation = sandbox
+            .is_some()
+            .then(|| Violation::of(&captured.status))
+            .flatten();
+        AuditRecord {
+            timestamp: Utc::now().to_rfc3339(),
+            pkg_name: self.pkg_name.to_string(),
+            pkg_id: self.pkg_id.to_string(),
+            pkg_version: self.pkg_version.to_string(),
+            kind: self.kind,
+            name: self.name,
+            command: self.command.to_string(),
+            working_dir: self.working_dir.to_path_buf(),
+            env: captured.env.clone(),
+            sandbox,
+            exit_code: captured.status.code(),
+            signal: captured.status.signal(),
+            violation: violation.map(|v| v.to_string()),
+            duration_ms: captured.duration.as_millis() as u64,
+            stdout: String::from_utf8_lossy(&captured.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&captured.stderr).into_owned(),
+            truncated: captured.truncated,
+        }
+    }
+}
+
+/// Append `record` to the audit log.
+///
+/// A record that cannot be written is a warning; the command has already run.
+pub fn log(record: &AuditRecord) {
+    if let Err(err) = get_config()
+        .get_audit_log_path()
+        .map_err(Into::into)
+        .and_then(|path| append(&path, record))
+    {
+        warn!("Failed to write audit record: {}", err);
+    }
+}
+
+/// Append `record` to the log at `path`.
+pub fn append(path: &Path, record: &AuditRecord) -> SoarResult<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("creating directory {}", parent.display()))?;
+    }
+    let mut line = serde_json::to_vec(record)
+        .map_err(|e| SoarError::Custom(format!("Failed to serialize audit record: {e}")))?;
+    line.push(b'\n');
+    rotate(path, LOG_LIMIT).with_context(|| format!("rotating {}", path.display()))?;
+    // One write per record, so concurrent soar processes never interleave.
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(&line))
+        .with_context(|| format!("writing {}", path.display()))?;
+    debug!(path = %path.display(), "recorded {} {}", record.pkg_name, record.name);
+    Ok(())
+}
+
+/// Where the log at `path` is moved aside to.
+pub(crate) fn rotated(path: &Path) -> PathBuf {
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(".1");
+    PathBuf::from(rotated)
+}
+
+/// Move the log at `path` aside once it holds `limit` bytes or more.
+fn rotate(path: &Path, limit: u64) -> io::Result<()> {
+    match fs::metadata(path) {
+        Ok(meta) if meta.len() >= limit => {}
+        Ok(_) => return Ok(()),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    }
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    let file = Flock::lock(file, FlockArg::LockExclusive).map_err(|(_, errno)| errno)?;
+    // Another soar process may have moved it aside while this one waited.
+    let meta = file.metadata()?;
+    let current = fs::metadata(path).map(|current| current.ino());
+    if meta.len() >= limit && current.is_ok_and(|ino| ino == meta.ino()) {
+        fs::rename(path, rotated(path))?;
+    }
+    Ok(())
+}
+
+/// Read every record in the log at `path`, and the one moved aside before it,
+/// oldest first. A missing log has none; a line that is not a record is
+/// skipped with a warning.
+pub fn read(path: &Path) -> SoarResult<Vec<AuditRecord>> {
+    let mut records = read_file(&rotated(path))?;
+    records.extend(read_file(path)?);
+    Ok(records)
+}
+
+fn read_file(path: &Path) -> SoarResult<Vec<AuditRecord>> {
+    let file = match fs::File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err).with_context(|| format!("reading {}", path.display())),
+    };
+    let mut records = Vec::new();
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.with_context(|| format!("reading {}", path.display()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(record),
+            Err(err) => warn!("{}:{}: not an audit record: {}", path.display(), i + 1, err),
+        }
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_tee_keeps_only_the_end_of_long_output() {
+        let output = (0..3 * OUTPUT_LIMIT)
+            .map(|i| (i % 251) as u8)
+            .collect::<Vec<_>>();
+        let mut passed = Vec::new();
+
+        let (kept, truncated) = tee(
+            Some(output.as_slice()),
+            &mut passed,
+            &AtomicBool::new(false),
+        );
+
+        assert_eq!(passed, output);
+        assert!(truncated);
+        assert_eq!(kept, output[output.len() - OUTPUT_LIMIT..]);
+    }
+
+    #[test]
+    fn test_cancelling_stops_a_captured_command() {
+        let cancel = CancellationToken::new();
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg("exec sleep 30");
+
+        let captured = thread::scope(|scope| {
+            scope.spawn(|| {
+                thread::sleep(Duration::from_millis(100));
+                cancel.cancel();
+            });
+            capture(cmd, Some(&cancel)).unwrap()
+        });
+
+        assert_eq!(captured.status.signal(), Some(Signal::SIGTERM as i32));
+        assert!(captured.duration < Duration::from_secs(10));
+    }
+
+    #[test]
+    fn test_capture_does_not_wait_for_background_processes() {
+        // What a post_install hook starting a daemon looks like.
+        for hook in ["sleep 30 & echo started", "nohup sleep 30 & echo started"] {
+            let mut cmd = Command::new("sh");
+            cmd.arg("-c").arg(hook);
+
+            let captured = capture(cmd, None).unwrap();
+
+            assert!(captured.status.success(), "{hook}");
+            assert_eq!(captured.stdout, b"started\n", "{hook}");
+            assert!(captured.duration < Duration::from_secs(10), "{hook}");
+        }
+    }
+
+    #[test]
+    fn test_records_round_trip_through_the_log() {
+        let dir = tempdir().unwrap();
+        let log = dir.path().join("audit.jsonl");
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
+            .arg("echo out; echo err >&2; exit 3")
+            .env("PKG_NAME", "tool");
+        let captured = capture(cmd, None).unwrap();
+        let record = AuditEntry {
+            pkg_name: "tool",
+            pkg_id: "tool",
+            pkg_version: "1.0",
+            kind: AuditKind::Hook,
+            name: "post_install".into(),
+            command: "echo out; echo err >&2; exit 3",
+            working_dir: dir.path(),
+        }
+        .record(None, &captured);
+
+        append(&log, &record).unwrap();
+        fs::write(
+            &log,
+            format!("{}not json\n", fs::read_to_string(&log).unwrap()),
+        )
+        .unwrap();
+        append(&log, &record).unwrap();
+
+        let records = read(&log).unwrap();
+        assert_eq!(records, [record.clone(), record]);
+        assert_eq!(records[0].exit_code, Some(3));
+        assert_eq!(records[0].stdout, "out\n");
+        assert_eq!(records[0].stderr, "err\n");
+        assert_eq!(records[0].env["PKG_NAME"], "tool");
+        assert!(records[0].failed());
+        assert!(read(&dir.path().join("missing.jsonl")).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_a_full_log_is_moved_aside() {
+        let dir = tempdir().unwrap();
+        let log = dir.path().join("audit.jsonl");
+        let mut cmd = Command::new("true");
+        cmd.env("PKG_NAME", "tool");
+        let captured = capture(cmd, None).unwrap();
+        let record = |name: &str| {
+            AuditEntry {
+                pkg_name: "tool",
+                pkg_id: "tool",
+                pkg_version: "1.0",
+                kind: AuditKind::Hook,
+                name: name.into(),
+                command: "true",
+                working_dir: dir.path(),
+            }
+            .record(None, &captured)
+        };
+
+        append(&log, &record("first")).unwrap();
+        rotate(&log, 1).unwrap();
+        append(&log, &record("second")).unwrap();
+        rotate(&log, 1).unwrap();
+        append(&log, &record("third")).unwrap();
+        rotate(&log, u64::MAX).unwrap();
+
+        let names = read(&log)
+            .unwrap()
+            .into_iter()
+            .map(|record| record.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["second", "third"]);
+    }
+}
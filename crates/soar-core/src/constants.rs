@@ -11,3 +11,9 @@ pub const CAP_MKNOD: i32 = 27;
 
 /// Marker file stored in install directory to track partial installs
 pub const INSTALL_MARKER_FILE: &str = ".soar_install";
+
+/// Runtime sandbox profile an install recorded, read by its launchers
+pub const RUNTIME_SANDBOX_FILE: &str = ".soar_sandbox.toml";
+
+/// Launch settings an install wrote its commands' launchers with
+pub const LAUNCH_FILE: &str = ".soar_launch.toml";
@@ -101,12 +101,17 @@ impl Clone for DieselDatabase {
 /// Replaces the ATTACH DATABASE pattern with separate connections.
 pub struct MetadataManager {
     databases: Vec<(String, DieselDatabase)>,
+    /// Databases of channels besides a repository's default one, as
+    /// (repository, channel, database). Only queried by name, so searches
+    /// see the default channels alone.
+    channels: Vec<(String, String, DieselDatabase)>,
 }
 
 impl MetadataManager {
     pub fn new() -> Self {
         Self {
             databases: Vec::new(),
+            channels: Vec::new(),
         }
     }
 
@@ -136,6 +141,55 @@ impl MetadataManager {
         Ok(())
     }
 
+    /// Adds the metadata database of a repository's non-default channel.
+    pub fn add_channel<P: AsRef<Path>>(
+        &mut self,
+        repo_name: &str,
+        channel: &str,
+        path: P,
+    ) -> Result<()> {
+        debug!(repo_name = repo_name, channel = channel, path = %path.as_ref().display(), "adding channel database to manager");
+        let db = DieselDatabase::open_metadata(path)?;
+        self.channels
+            .push((repo_name.to_string(), channel.to_string(), db));
+        Ok(())
+    }
+
+    /// Adds the metadata database of a repository's non-default channel in
+    /// read-only mode.
+    pub fn add_channel_readonly<P: AsRef<Path>>(
+        &mut self,
+        repo_name: &str,
+        channel: &str,
+        path: P,
+    ) -> Result<()> {
+        debug!(repo_name = repo_name, channel = channel, path = %path.as_ref().display(), "adding channel database to manager (readonly)");
+        let db = DieselDatabase::open_metadata_readonly(path)?;
+        self.channels
+            .push((repo_name.to_string(), channel.to_string(), db));
+        Ok(())
+    }
+
+    /// Queries one non-default channel of a repository.
+    ///
+    /// Returns `None` when the channel has not been synced.
+    pub fn query_channel<F, T>(&self, repo_name: &str, channel: &str, f: F) -> Result<Option<T>>
+    where
+        F: FnOnce(&mut diesel::SqliteConnection) -> diesel::QueryResult<T>,
+    {
+        trace!(
+            repo_name = repo_name,
+            channel = channel,
+            "querying repository channel"
+        );
+        for (name, channel_name, db) in &self.channels {
+            if name == repo_name && channel_name == channel {
+                return db.with_conn(f).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
     /// Executes a query function across all repositories and collects results.
     pub fn query_all<F, T>(&self, f: F) -> Result<Vec<(String, T)>>
     where
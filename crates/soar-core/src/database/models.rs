@@ -1,10 +1,10 @@
 //! Database models for soar-core.
 
-use std::fmt::Display;
+use std::{collections::BTreeMap, fmt::Display};
 
 use serde::{Deserialize, Serialize};
 use soar_db::{
-    models::types::{PackageExtra, PackageFile, PackageProvide},
+    models::types::{PackageExtra, PackageFile, PackageLaunch, PackageProvide, PackageSnapshot},
     repository::core::InstalledPackageWithPortable,
 };
 use soar_package::PackageExt;
@@ -58,7 +58,7 @@ pub struct Package {
     pub build_script: Option<String>,
     pub build_log: Option<String>,
     pub provides: Option<Vec<PackageProvide>>,
-    pub snapshots: Option<Vec<String>>,
+    pub snapshots: Option<Vec<PackageSnapshot>>,
     pub repology: Option<Vec<String>>,
     pub maintainers: Option<Vec<Maintainer>>,
     pub replaces: Option<Vec<String>>,
@@ -71,6 +71,13 @@ pub struct Package {
     pub extra: Option<Vec<PackageExtra>>,
     /// What the package takes out of its artifact. Absent means all of it.
     pub files: Option<Vec<PackageFile>>,
+    /// Commands printing the package's completions, by shell or `man`.
+    pub completion_commands: Option<BTreeMap<String, String>>,
+    /// How the package's commands are started.
+    pub launch: Option<PackageLaunch>,
+    /// Set when this was pointed at a snapshot's own build, which publishes
+    /// no checksum to verify it by.
+    pub unverified_snapshot: bool,
 }
 
 impl PackageExt for Package {
@@ -105,26 +112,68 @@ fn resolve_version_placeholder_opt(s: Option<&str>, version: &str) -> Option<Str
     s.map(|s| resolve_version_placeholder(s, version))
 }
 
+/// Point an OCI reference at another tag of the same repository.
+fn with_oci_tag(reference: &str, tag: &str) -> String {
+    let reference = reference.split_once('@').map_or(reference, |(r, _)| r);
+    let name_start = reference.rfind('/').map_or(0, |i| i + 1);
+    let repository = match reference[name_start..].rfind(':') {
+        Some(colon) => &reference[..name_start + colon],
+        None => reference,
+    };
+    format!("{repository}:{tag}")
+}
+
 impl Package {
     /// Check if a version is available for this package.
     ///
     /// Returns true if the version matches the package's current version
-    /// or is present in the snapshots array.
+    /// or names one of its snapshots, by version or by build id.
     pub fn has_version(&self, version: &str) -> bool {
-        if self.version == version {
+        if self.version == version || self.build_id.as_deref() == Some(version) {
             return true;
         }
+        self.snapshot(version).is_some()
+    }
+
+    /// The snapshot `version` names, unless it names the current build.
+    pub fn snapshot(&self, version: &str) -> Option<&PackageSnapshot> {
+        if self.version == version || self.build_id.as_deref() == Some(version) {
+            return None;
+        }
         self.snapshots
-            .as_ref()
-            .is_some_and(|s| s.iter().any(|v| v == version))
+            .as_ref()?
+            .iter()
+            .find(|snapshot| snapshot.matches(version))
+    }
+
+    /// Every build that can be installed: the current one first, then the
+    /// snapshots in the order the repository lists them.
+    pub fn builds(&self) -> Vec<PackageSnapshot> {
+        let current = PackageSnapshot {
+            version: self.version.clone(),
+            id: self.build_id.clone(),
+            date: self.build_date.clone(),
+            bsum: self.bsum.clone(),
+            size: self.ghcr_size.or(self.size),
+            ..Default::default()
+        };
+        std::iter::once(current)
+            .chain(self.snapshots.iter().flatten().cloned())
+            .collect()
     }
 
     /// Create a copy of this package with all `{{version}}` placeholders resolved.
     ///
     /// If `version` is provided, uses that version; otherwise uses the package's version.
-    /// This is useful when installing a specific snapshot version.
+    /// This is useful when installing a specific snapshot version. `version` may also be
+    /// a snapshot's build id, and a snapshot that says where its build is and what its
+    /// checksum is replaces the current build's.
     pub fn resolve(&self, version: Option<&str>) -> Self {
-        let ver = version.unwrap_or(&self.version);
+        let snapshot = version.and_then(|v| self.snapshot(v));
+        let ver = snapshot
+            .map(|s| s.version.as_str())
+            .or(version.filter(|v| self.build_id.as_deref() != Some(*v)))
+            .unwrap_or(&self.version);
         let mut pkg = self.clone();
         pkg.download_url = resolve_version_placeholder(&self.download_url, ver);
         pkg.ghcr_pkg = resolve_version_placeholder_opt(self.ghcr_pkg.as_deref(), ver);
@@ -133,12 +182,48 @@ impl Package {
         if version.is_some() {
             pkg.version = ver.to_string();
         }
+        if let Some(snapshot) = snapshot {
+            pkg.apply_snapshot(snapshot);
+        }
         pkg
     }
+
+    /// Point this package at a snapshot's build.
+    fn apply_snapshot(&mut self, snapshot: &PackageSnapshot) {
+        if snapshot.id.is_some() {
+            self.build_id = snapshot.id.clone();
+            self.build_date = snapshot.date.clone();
+        }
+
+        let mut relocated = false;
+        if let (Some(tag), Some(reference)) = (&snapshot.ghcr_tag, &self.ghcr_pkg) {
+            self.ghcr_pkg = Some(with_oci_tag(reference, tag));
+            // Both name the current build's blob, not this one's.
+            self.ghcr_blob = None;
+            self.ghcr_url = None;
+            self.ghcr_size = snapshot.size;
+            relocated = true;
+        } else if let Some(ref url) = snapshot.download_url {
+            self.download_url = url.clone();
+            self.ghcr_pkg = None;
+            self.ghcr_blob = None;
+            self.ghcr_url = None;
+            self.ghcr_size = None;
+            self.size = snapshot.size;
+            relocated = true;
+        }
+
+        // The current build's checksum cannot vouch for another build, so a
+        // relocated snapshot carries its own or none at all.
+        if relocated || snapshot.bsum.is_some() {
+            self.bsum = snapshot.bsum.clone();
+        }
+        self.unverified_snapshot = relocated && self.bsum.is_none();
+    }
 }
 
 /// Installed package record.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct InstalledPackage {
     pub id: u64,
     pub repo_name: String,
@@ -167,6 +252,30 @@ pub struct InstalledPackage {
     pub download_url: Option<String>,
     /// The AppImage `.upd_info` string, which names a zsync feed.
     pub update_info: Option<String>,
+    /// The repository build it came from, when the repository names one.
+    pub build_id: Option<String>,
+}
+
+impl InstalledPackage {
+    /// Whether this is the build of `pkg` that `version` asked for, or any
+    /// build of its version when nothing was asked for.
+    ///
+    /// A version string covers every build of that version, but a build id
+    /// names one of them. The build id recorded at install decides then, or
+    /// failing that the checksum; an install that has neither is not taken
+    /// for the build asked for.
+    pub fn is_build(&self, pkg: &Package, version: Option<&str>) -> bool {
+        if self.version != pkg.version {
+            return false;
+        }
+        let Some(version) = version.filter(|v| pkg.build_id.as_deref() == Some(*v)) else {
+            return true;
+        };
+        if let Some(ref build_id) = self.build_id {
+            return build_id == version;
+        }
+        matches!((&self.checksum, &pkg.bsum), (Some(installed), Some(wanted)) if installed == wanted)
+    }
 }
 
 impl PackageExt for InstalledPackage {
@@ -220,6 +329,7 @@ impl From<InstalledPackageWithPortable> for InstalledPackage {
             install_patterns: pkg.install_patterns,
             download_url: pkg.download_url,
             update_info: pkg.update_info,
+            build_id: pkg.build_id,
         }
     }
 }
@@ -253,6 +363,7 @@ impl From<soar_db::repository::core::InstalledPackage> for InstalledPackage {
             install_patterns: pkg.install_patterns,
             download_url: pkg.download_url,
             update_info: pkg.update_info,
+            build_id: pkg.build_id,
         }
     }
 }
@@ -304,6 +415,9 @@ impl From<soar_db::models::metadata::Package> for Package {
             portable: pkg.portable,
             extra: pkg.extra,
             files: pkg.files,
+            completion_commands: pkg.completion_commands,
+            launch: pkg.launch,
+            unverified_snapshot: false,
         }
     }
 }
@@ -321,6 +435,39 @@ impl From<soar_db::models::metadata::PackageWithRepo> for Package {
 mod tests {
     use super::*;
 
+    fn version_only(version: &str) -> PackageSnapshot {
+        PackageSnapshot {
+            version: version.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn oci_package() -> Package {
+        Package {
+            version: "1.1.0".to_string(),
+            build_id: Some("b42".to_string()),
+            ghcr_pkg: Some("ghcr.io/org/cache/bat:1.1.0-b42".to_string()),
+            ghcr_blob: Some("ghcr.io/org/cache/bat@sha256:current".to_string()),
+            bsum: Some("current".to_string()),
+            snapshots: Some(vec![
+                PackageSnapshot {
+                    version: "1.0.0".to_string(),
+                    id: Some("b41".to_string()),
+                    date: Some("2026-09-01".to_string()),
+                    ghcr_tag: Some("1.0.0-b41".to_string()),
+                    bsum: Some("older".to_string()),
+                    ..Default::default()
+                },
+                PackageSnapshot {
+                    version: "0.9.0".to_string(),
+                    download_url: Some("https://example.com/bat-0.9.0".to_string()),
+                    ..Default::default()
+                },
+            ]),
+            ..Default::default()
+        }
+    }
+
     #[test]
     fn test_resolve_version_placeholder() {
         assert_eq!(
@@ -408,9 +555,9 @@ mod tests {
         let pkg = Package {
             version: "v1.0.0".to_string(),
             snapshots: Some(vec![
-                "v0.9.0".to_string(),
-                "v0.8.0".to_string(),
-                "v0.7.0".to_string(),
+                version_only("v0.9.0"),
+                version_only("v0.8.0"),
+                version_only("v0.7.0"),
             ]),
             ..Default::default()
         };
@@ -432,4 +579,102 @@ mod tests {
         assert!(pkg.has_version("v1.0.0"));
         assert!(!pkg.has_version("v0.9.0"));
     }
+
+    #[test]
+    fn test_has_version_by_build_id() {
+        let pkg = oci_package();
+
+        assert!(pkg.has_version("b41"));
+        assert!(pkg.has_version("b42"));
+        assert!(!pkg.has_version("b40"));
+    }
+
+    #[test]
+    fn test_resolve_snapshot_by_id_uses_its_tag_and_checksum() {
+        let pkg = oci_package().resolve(Some("b41"));
+
+        assert_eq!(pkg.version, "1.0.0");
+        assert_eq!(
+            pkg.ghcr_pkg.as_deref(),
+            Some("ghcr.io/org/cache/bat:1.0.0-b41")
+        );
+        assert_eq!(pkg.ghcr_blob, None);
+        assert_eq!(pkg.bsum.as_deref(), Some("older"));
+        assert_eq!(pkg.build_date.as_deref(), Some("2026-09-01"));
+        assert!(!pkg.unverified_snapshot);
+    }
+
+    #[test]
+    fn test_resolve_snapshot_by_url_drops_the_current_checksum() {
+        let pkg = oci_package().resolve(Some("0.9.0"));
+
+        assert_eq!(pkg.download_url, "https://example.com/bat-0.9.0");
+        assert_eq!(pkg.ghcr_pkg, None);
+        assert_eq!(pkg.bsum, None);
+        assert!(pkg.unverified_snapshot);
+    }
+
+    #[test]
+    fn test_resolve_current_build_id_is_the_current_build() {
+        let pkg = oci_package().resolve(Some("b42"));
+
+        assert_eq!(pkg.version, "1.1.0");
+        assert_eq!(pkg.bsum.as_deref(), Some("current"));
+    }
+
+    #[test]
+    fn test_is_build_tells_rebuilds_of_one_version_apart() {
+        let mut pkg = oci_package();
+        pkg.snapshots.as_mut().unwrap().push(PackageSnapshot {
+            version: "1.0.0".to_string(),
+            id: Some("b40".to_string()),
+            ghcr_tag: Some("1.0.0-b40".to_string()),
+            bsum: Some("oldest".to_string()),
+            ..Default::default()
+        });
+        let installed = InstalledPackage {
+            version: "1.0.0".to_string(),
+            build_id: Some("b41".to_string()),
+            checksum: Some("older".to_string()),
+            ..Default::default()
+        };
+
+        assert!(installed.is_build(&pkg.resolve(Some("b41")), Some("b41")));
+        assert!(!installed.is_build(&pkg.resolve(Some("b40")), Some("b40")));
+        assert!(installed.is_build(&pkg.resolve(Some("1.0.0")), Some("1.0.0")));
+        assert!(installed.is_build(&pkg.resolve(Some("b41")), None));
+
+        // Recorded before build ids were, so only the checksum tells.
+        let unrecorded = InstalledPackage {
+            build_id: None,
+            ..installed
+        };
+        assert!(unrecorded.is_build(&pkg.resolve(Some("b41")), Some("b41")));
+        assert!(!unrecorded.is_build(&pkg.resolve(Some("b40")), Some("b40")));
+    }
+
+    #[test]
+    fn test_builds_list_current_first() {
+        let builds = oci_package().builds();
+
+        assert_eq!(builds.len(), 3);
+        assert_eq!(builds[0].id.as_deref(), Some("b42"));
+        assert_eq!(builds[1].version, "1.0.0");
+    }
+
+    #[test]
+    fn test_with_oci_tag() {
+        assert_eq!(
+            with_oci_tag("ghcr.io/org/cache/bat:1.1.0", "1.0.0"),
+            "ghcr.io/org/cache/bat:1.0.0"
+        );
+        assert_eq!(
+            with_oci_tag("ghcr.io/org/cache/bat@sha256:abc", "1.0.0"),
+            "ghcr.io/org/cache/bat:1.0.0"
+        );
+        assert_eq!(
+            with_oci_tag("localhost:5000/bat", "1.0.0"),
+            "localhost:5000/bat:1.0.0"
+        );
+    }
 }
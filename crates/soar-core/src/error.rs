@@ -124,6 +124,10 @@ pub enum SoarError {
     )]
     InvalidPackageQuery(String),
 
+    #[error("Operation cancelled")]
+    #[diagnostic(code(soar::cancelled))]
+    Cancelled,
+
     #[error("{0}")]
     #[diagnostic(code(soar::error))]
     Custom(String),
@@ -177,6 +181,13 @@ pub enum SoarError {
         help("Check the command and sandbox configuration")
     )]
     SandboxExecution(String),
+
+    #[error("{command} was stopped by the sandbox: it {reason}")]
+    #[diagnostic(
+        code(soar::sandbox::violation),
+        help("Raise the package's sandbox.limits or drop the system call from sandbox.deny_syscalls if this is expected")
+    )]
+    SandboxViolation { command: String, reason: String },
 }
 
 impl SoarError {
@@ -197,6 +208,15 @@ impl SoarError {
             _ => self.to_string(),
         }
     }
+
+    /// Whether this is the work stopping because it was cancelled, however
+    /// deep that was noticed.
+    pub fn is_cancelled(&self) -> bool {
+        matches!(
+            self,
+            Self::Cancelled | Self::DownloadError(soar_dl::error::DownloadError::Cancelled)
+        )
+    }
 }
 
 impl<T> From<std::sync::PoisonError<T>> for SoarError {
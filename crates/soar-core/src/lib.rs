@@ -1,5 +1,7 @@
 use error::SoarError;
 
+pub mod alternatives;
+pub mod audit;
 pub mod constants;
 pub mod database;
 pub mod error;
@@ -0,0 +1,353 @@
+//! Whether an installed binary can run on this host.
+//!
+//! A binary built for another architecture, or against a libc this system does
+//! not have, fails at launch with an unhelpful "No such file or directory", and
+//! so does a script whose interpreter is missing. [`check`] finds those
+//! problems up front from what the binary asks for and what the host provides.
+
+use std::{
+    cmp::Ordering,
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+use soar_config::config::get_config;
+use soar_utils::{
+    elf::{self, host_machine, machine_name},
+    entrypoint::{self, Entrypoint},
+    version::compare_versions,
+};
+
+/// Where the dynamic loader looks when nothing else names a directory.
+const DEFAULT_LIBRARY_DIRS: &[&str] = &["/lib", "/usr/lib", "/lib64", "/usr/lib64"];
+
+/// Something about a binary that keeps it from running, or may.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Incompatibility {
+    /// Built for another architecture.
+    Architecture { found: String, host: String },
+    /// Its dynamic loader is missing, typically a glibc binary on a musl
+    /// system or the reverse.
+    MissingInterpreter(String),
+    /// Shared libraries no search path provides.
+    UnresolvedLibraries(Vec<String>),
+    /// Needs symbols from a newer glibc than the host has.
+    GlibcTooOld { required: String, host: String },
+    /// A script, zipapp or jar whose interpreter, such as `python3` or `java`,
+    /// is not installed.
+    MissingRuntime(String),
+}
+
+impl Incompatibility {
+    /// Whether the binary cannot start at all, as opposed to possibly failing
+    /// once it tries to use what is missing.
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            Incompatibility::Architecture { .. }
+                | Incompatibility::MissingInterpreter(_)
+                | Incompatibility::MissingRuntime(_)
+        )
+    }
+}
+
+impl fmt::Display for Incompatibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Incompatibility::Architecture {
+                found,
+                host,
+            } => write!(f, "is built for {found}, but this system is {host}"),
+            Incompatibility::MissingInterpreter(path) => {
+                write!(
+                    f,
+                    "needs the dynamic loader {path}, which this system lacks"
+                )
+            }
+            Incompatibility::UnresolvedLibraries(libraries) => {
+                write!(f, "needs {}, which this system lacks", libraries.join(", "))
+            }
+            Incompatibility::GlibcTooOld {
+                required,
+                host,
+            } => write!(f, "needs glibc {required}, but this system has {host}"),
+            Incompatibility::MissingRuntime(program) => {
+                write!(f, "needs {program} to run, which is not installed")
+            }
+        }
+    }
+}
+
+/// What keeps the binary at `path` from running here.
+///
+/// Empty when it looks runnable, and for anything that is neither an ELF nor
+/// run by an interpreter, which has nothing to check.
+pub fn check(path: &Path) -> Vec<Incompatibility> {
+    let Some(info) = elf::inspect(path) else {
+        // A launcher soar wrote stands in for the file it runs.
+        if let Some(file) = fs::read_to_string(path)
+            .ok()
+            .and_then(|script| entrypoint::launched_file(&script))
+        {
+            return check(&file);
+        }
+        return check_interpreter(path).into_iter().collect();
+    };
+
+    let mut issues = Vec::new();
+    if let Some(host) = host_machine().filter(|host| *host != info.machine) {
+        let name = |machine| {
+            machine_name(machine)
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("machine {machine}"))
+        };
+        issues.push(Incompatibility::Architecture {
+            found: name(info.machine),
+            host: name(host),
+        });
+        // Nothing else about a foreign binary is worth reporting.
+        return issues;
+    }
+
+    if let Some(interpreter) = &info.interpreter {
+        if !Path::new(interpreter).exists() {
+            issues.push(Incompatibility::MissingInterpreter(interpreter.clone()));
+        }
+    }
+
+    let origin = fs::canonicalize(path)
+        .ok()
+        .and_then(|p| p.parent().map(Path::to_path_buf))
+        .unwrap_or_default();
+    let search: Vec<PathBuf> = info
+        .search_paths
+        .iter()
+        .map(|p| {
+            PathBuf::from(
+                p.replace("${ORIGIN}", &origin.to_string_lossy())
+                    .replace("$ORIGIN", &origin.to_string_lossy()),
+            )
+        })
+        .chain(
+            std::env::var("LD_LIBRARY_PATH")
+                .unwrap_or_default()
+                .split(':')
+                .filter(|p| !p.is_empty())
+                .map(PathBuf::from),
+        )
+        .chain(host_library_dirs().iter().cloned())
+        .collect();
+    let unresolved: Vec<String> = info
+        .needed
+        .iter()
+        .filter(|library| !resolves(library, &search))
+        .cloned()
+        .collect();
+    if !unresolved.is_empty() {
+        issues.push(Incompatibility::UnresolvedLibraries(unresolved));
+    }
+
+    if let (Some(required), Some(host)) = (
+        minimum_glibc(info.required_versions.iter().map(|(_, v)| v.as_str())),
+        host_glibc(),
+    ) {
+        if compare_versions(&required, host) == Ordering::Greater {
+            issues.push(Incompatibility::GlibcTooOld {
+                required,
+                host: host.clone(),
+            });
+        }
+    }
+
+    issues
+}
+
+/// A script, zipapp or jar whose interpreter is nowhere to be found.
+///
+/// One installed by soar counts even when soar's bin directory is not on
+/// `PATH`, since that is a setup problem `soar health` reports by itself.
+fn check_interpreter(path: &Path) -> Option<Incompatibility> {
+    let entry = entrypoint::classify(path).filter(|e| *e != Entrypoint::Elf)?;
+    let soar_bin: Vec<PathBuf> = get_config().get_bin_path().into_iter().collect();
+    if entry.locate_interpreter(&soar_bin).is_some() {
+        return None;
+    }
+    Some(Incompatibility::MissingRuntime(
+        entry.interpreter_name()?.to_string(),
+    ))
+}
+
+/// Whether the dynamic loader would find `library` in `dirs`.
+fn resolves(library: &str, dirs: &[PathBuf]) -> bool {
+    if library.contains('/') {
+        return Path::new(library).exists();
+    }
+    dirs.iter().any(|dir| dir.join(library).exists())
+}
+
+/// The newest glibc a set of symbol versions asks for, such as `2.34`.
+///
+/// `GLIBC_PRIVATE` is not a release and is skipped.
+fn minimum_glibc<'a>(versions: impl Iterator<Item = &'a str>) -> Option<String> {
+    versions
+        .filter_map(|v| v.strip_prefix("GLIBC_"))
+        .filter(|v| v.starts_with(|c: char| c.is_ascii_digit()))
+        .max_by(|a, b| compare_versions(a, b))
+        .map(str::to_string)
+}
+
+/// The glibc release this host provides, or `None` on a system without one.
+fn host_glibc() -> Option<&'static String> {
+    static GLIBC: OnceLock<Option<String>> = OnceLock::new();
+    GLIBC
+        .get_or_init(|| {
+            let libc = host_library_dirs()
+                .iter()
+                .map(|dir| dir.join("libc.so.6"))
+                .find(|path| path.exists())?;
+            let info = elf::inspect(&libc)?;
+            minimum_glibc(info.defined_versions.iter().map(String::as_str))
+        })
+        .as_ref()
+}
+
+/// The directories the host's dynamic loader searches: those configured in
+/// `/etc/ld.so.conf`, then the defaults.
+fn host_library_dirs() -> &'static [PathBuf] {
+    static DIRS: OnceLock<Vec<PathBuf>> = OnceLock::new();
+    DIRS.get_or_init(|| {
+        let mut dirs = Vec::new();
+        read_ld_so_conf(Path::new("/etc/ld.so.conf"), &mut dirs, 0);
+
+        let arch = std::env::consts::ARCH;
+        for dir in DEFAULT_LIBRARY_DIRS {
+            dirs.push(PathBuf::from(dir));
+            // Debian's multiarch layout.
+            dirs.push(Path::new(dir).join(format!("{arch}-linux-gnu")));
+        }
+        // musl keeps its search path in a file of its own.
+        if let Ok(paths) = fs::read_to_string(format!("/etc/ld-musl-{arch}.path")) {
+            dirs.extend(
+                paths
+                    .split([':', '\n'])
+                    .filter(|p| !p.trim().is_empty())
+                    .map(|p| PathBuf::from(p.trim())),
+            );
+        }
+
+        dirs.dedup();
+        dirs
+    })
+}
+
+/// Collect the directories listed in an `ld.so.conf`, following `include`.
+fn read_ld_so_conf(path: &Path, dirs: &mut Vec<PathBuf>, depth: usize) {
+    // Includes can name each other; real configurations nest once.
+    if depth > 4 {
+        return;
+    }
+    let Ok(content) = fs::read_to_string(path) else {
+        return;
+    };
+    for line in content.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() || line.starts_with("hwcap ") {
+            continue;
+        }
+        let Some(pattern) = line.strip_prefix("include ") else {
+            dirs.push(PathBuf::from(line));
+            continue;
+        };
+
+        let pattern = pattern.trim();
+        let pattern = if Path::new(pattern).is_absolute() {
+            PathBuf::from(pattern)
+        } else {
+            path.parent().unwrap_or(Path::new("/")).join(pattern)
+        };
+        let Some(parent) = pattern.parent() else {
+            continue;
+        };
+        let pattern = pattern.to_string_lossy().into_owned();
+        let mut included: Vec<PathBuf> = fs::read_dir(parent)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| fast_glob::glob_match(&pattern, p.to_string_lossy().as_ref()))
+            .collect();
+        included.sort();
+        for file in included {
+            read_ld_so_conf(&file, dirs, depth + 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_minimum_glibc_takes_the_newest_release() {
+        let versions = ["GLIBC_2.2.5", "GLIBC_2.34", "GLIBC_2.17", "GLIBC_PRIVATE"];
+        assert_eq!(minimum_glibc(versions.into_iter()).as_deref(), Some("2.34"));
+        assert_eq!(minimum_glibc(["GCC_3.0"].into_iter()), None);
+    }
+
+    #[test]
+    fn test_check_accepts_scripts_with_an_interpreter() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("run.sh");
+        fs::write(&script, "#!/bin/sh\necho hi\n").unwrap();
+        assert!(check(&script).is_empty());
+
+        let notes = dir.path().join("notes.txt");
+        fs::write(&notes, "not a program\n").unwrap();
+        assert!(check(&notes).is_empty());
+    }
+
+    #[test]
+    fn test_check_reports_a_missing_script_interpreter() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("run");
+        fs::write(&script, "#!/usr/bin/env soar-no-such-interpreter\n").unwrap();
+        let issues = check(&script);
+        assert_eq!(
+            issues,
+            vec![Incompatibility::MissingRuntime(
+                "soar-no-such-interpreter".into()
+            )]
+        );
+        assert!(issues[0].is_fatal());
+    }
+
+    #[test]
+    fn test_resolves_through_search_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("libfoo.so.1"), "").unwrap();
+        let dirs = [PathBuf::from("/nonexistent"), dir.path().to_path_buf()];
+
+        assert!(resolves("libfoo.so.1", &dirs));
+        assert!(!resolves("libbar.so.1", &dirs));
+    }
+
+    #[test]
+    fn test_read_ld_so_conf_follows_includes() {
+        let dir = tempfile::tempdir().unwrap();
+        let conf_d = dir.path().join("ld.so.conf.d");
+        fs::create_dir_all(&conf_d).unwrap();
+        fs::write(
+            dir.path().join("ld.so.conf"),
+            "# comment\n/opt/a\ninclude ld.so.conf.d/*.conf\n",
+        )
+        .unwrap();
+        fs::write(conf_d.join("b.conf"), "/opt/b\n").unwrap();
+        fs::write(conf_d.join("ignored.txt"), "/opt/c\n").unwrap();
+
+        let mut dirs = Vec::new();
+        read_ld_so_conf(&dir.path().join("ld.so.conf"), &mut dirs, 0);
+
+        assert_eq!(dirs, [PathBuf::from("/opt/a"), PathBuf::from("/opt/b")]);
+    }
+}
@@ -0,0 +1,327 @@
+//! Completions and manual pages printed by the package itself.
+//!
+//! Plenty of command-line tools ship no completion files but print them on
+//! request (`tool completions zsh`). A package can declare those commands in
+//! `completion_commands`; an install runs them sandboxed in the package
+//! directory and writes what they print where a shipped file would be, so
+//! linking treats both the same.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use soar_config::{config::get_config, packages::SandboxConfig};
+use tracing::{debug, warn};
+
+use super::hooks::HookEnv;
+use crate::{error::ErrorContext, sandbox, SoarResult};
+
+/// Where the output of a completion command goes, relative to the install
+/// directory.
+///
+/// `kind` is a shell, or `man` for a manual page. `None` for anything else.
+pub fn generated_path(kind: &str, name: &str) -> Option<PathBuf> {
+    let path = match kind {
+        "bash" => format!("share/bash-completion/completions/{name}"),
+        "zsh" => format!("share/zsh/site-functions/_{name}"),
+        "fish" => format!("share/fish/vendor_completions.d/{name}.fish"),
+        "man" => format!("share/man/man1/{name}.1"),
+        _ => return None,
+    };
+    Some(PathBuf::from(path))
+}
+
+/// The name completions are generated for: the command a package links first,
+/// or the package name when it links none.
+pub fn completion_name<'a>(linked: Option<&'a Path>, pkg_name: &'a str) -> &'a str {
+    linked
+        .and_then(|link| link.file_name())
+        .and_then(|name| name.to_str())
+        .unwrap_or(pkg_name)
+}
+
+/// The completion commands an install runs: a repository's, overridden
+/// shell by shell by the ones packages.toml declares.
+///
+/// A repository's commands are shell strings from remote metadata, so they
+/// only ever run sandboxed. When the sandbox is off or no backend for it is
+/// usable, they are left out with a warning and only the declared ones run.
+pub fn completion_commands(
+    repository: Option<&BTreeMap<String, String>>,
+    declared: Option<&HashMap<String, String>>,
+    sandbox_config: Option<&SandboxConfig>,
+    pkg_name: &str,
+) -> BTreeMap<String, String> {
+    let mut commands = BTreeMap::new();
+    if let Some(repository) = repository.filter(|r| !r.is_empty()) {
+        let sandboxed = sandbox_config.is_none_or(|s| s.is_enabled())
+            && sandbox::is_supported(sandbox_config.map(|s| s.backend()).unwrap_or_default());
+        if sandboxed {
+            commands.extend(repository.clone());
+        } else {
+            warn!(
+                "{pkg_name}: not running the repository's completion commands without a sandbox"
+            );
+        }
+    }
+    commands.extend(declared.cloned().unwrap_or_default());
+    commands
+}
+
+/// Run a package's completion commands and write what they print into its
+/// install directory.
+///
+/// `name` is the command the completions are for. Only the shells in
+/// `shells` are generated, plus the manual page; one the package already
+/// ships is left as it is. A command that fails or prints nothing is skipped
+/// with a warning, since missing completions should not fail an install.
+///
+/// # Returns
+///
+/// The files written.
+pub fn generate_completions(
+    commands: &BTreeMap<String, String>,
+    name: &str,
+    shells: &[String],
+    env: &HookEnv,
+    sandbox_config: Option<&SandboxConfig>,
+) -> SoarResult<Vec<PathBuf>> {
+    let mut written = Vec::new();
+    for (kind, command) in commands {
+        if kind != "man" && !shells.contains(kind) {
+            continue;
+        }
+        let Some(relative) = generated_path(kind, name) else {
+            warn!("{}: no completions for `{}`, skipping", env.pkg_name, kind);
+            continue;
+        };
+        let path = env.install_dir.join(relative);
+        if path.exists() {
+            debug!(path = %path.display(), "package ships its own {kind} completions");
+            continue;
+        }
+
+        let Some(output) = run_completion_command(kind, command, env, sandbox_config)? else {
+            continue;
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("creating directory {}", parent.display()))?;
+        }
+        fs::write(&path, output).with_context(|| format!("writing {}", path.display()))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+/// Runs one completion command, returning what it printed or `None` when it
+/// produced nothing usable.
+fn run_completion_command(
+    kind: &str,
+    command: &str,
+    env: &HookEnv,
+    sandbox_config: Option<&SandboxConfig>,
+) -> SoarResult<Option<Vec<u8>>> {
+    debug!("generating {} completions: {}", kind, command);
+
+    let sandbox_enabled = sandbox_config.is_none_or(|s| s.is_enabled());
+    if sandbox_enabled
+        && sandbox_config.is_some_and(|s| s.is_required())
+        && !sandbox::is_supported(sandbox_config.map(|s| s.backend()).unwrap_or_default())
+    {
+        warn!(
+            "{}: {} completions need the sandbox, but Landlock is not available; skipping",
+            env.pkg_name, kind
+        );
+        return Ok(None);
+    }
+
+    let bin_dir = get_config().get_bin_path()?;
+    // The package's own binaries come first, so a command can name the tool
+    // it belongs to whether or not it is linked yet.
+    let path = [
+        env.install_dir.to_path_buf(),
+        env.install_dir.join("bin"),
+        bin_dir.clone(),
+    ]
+    .into_iter()
+    .map(|dir| dir.display().to_string())
+    .chain(std::env::var("PATH").ok())
+    .collect::<Vec<_>>()
+    .join(":");
+
+    let mut cmd = sandbox::SandboxedCommand::new(command)
+        .working_dir(env.install_dir)
+        .read_path(&bin_dir)
+        .envs([
+            ("INSTALL_DIR", env.install_dir.to_str().unwrap_or("")),
+            ("BIN_DIR", bin_dir.to_str().unwrap_or("")),
+            ("PKG_NAME", env.pkg_name),
+            ("PKG_ID", env.pkg_id),
+            ("PKG_VERSION", env.pkg_version),
+            ("PATH", &path),
+        ]);
+    if !sandbox_enabled {
+        cmd = cmd.no_sandbox();
+    } else if let Some(s) = sandbox_config {
+        cmd = cmd.config(sandbox::SandboxConfig::for_package(s));
+        for path in &s.fs_read {
+            cmd = cmd.read_path(path);
+        }
+        for path in &s.fs_write {
+            cmd = cmd.write_path(path);
+        }
+    }
+
+    let output = match cmd.output() {
+        Ok(output) => output,
+        Err(err) => {
+            warn!(
+                "{}: could not run `{}`, skipping {} completions: {}",
+                env.pkg_name, command, kind, err
+            );
+            return Ok(None);
+        }
+    };
+    if let Some(violation) = sandbox_enabled
+        .then(|| sandbox::Violation::of(&output.status))
+        .flatten()
+    {
+        warn!(
+            "{}: `{}` was stopped by the sandbox ({}), skipping {} completions",
+            env.pkg_name, command, violation, kind
+        );
+        return Ok(None);
+    }
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        warn!(
+            "{}: `{}` exited with code {}, skipping {} completions{}",
+            env.pkg_name,
+            command,
+            output.status.code().unwrap_or(-1),
+            kind,
+            stderr
+                .lines()
+                .next()
+                .map(|line| format!(": {line}"))
+                .unwrap_or_default()
+        );
+        return Ok(None);
+    }
+    if output.stdout.iter().all(u8::is_ascii_whitespace) {
+        warn!(
+            "{}: `{}` printed nothing, skipping {} completions",
+            env.pkg_name, command, kind
+        );
+        return Ok(None);
+    }
+    Ok(Some(output.stdout))
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_generated_path_follows_each_shell_convention() {
+        assert_eq!(
+            generated_path("bash", "bat").unwrap(),
+            Path::new("share/bash-completion/completions/bat")
+        );
+        assert_eq!(
+            generated_path("zsh", "bat").unwrap(),
+            Path::new("share/zsh/site-functions/_bat")
+        );
+        assert_eq!(
+            generated_path("fish", "bat").unwrap(),
+            Path::new("share/fish/vendor_completions.d/bat.fish")
+        );
+        assert_eq!(
+            generated_path("man", "bat").unwrap(),
+            Path::new("share/man/man1/bat.1")
+        );
+        assert!(generated_path("nu", "bat").is_none());
+    }
+
+    #[test]
+    fn test_completion_name_prefers_the_linked_command() {
+        assert_eq!(
+            completion_name(Some(Path::new("/bin/batcat")), "bat"),
+            "batcat"
+        );
+        assert_eq!(completion_name(None, "bat"), "bat");
+    }
+
+    #[test]
+    fn test_repository_completion_commands_only_run_sandboxed() {
+        let repository = BTreeMap::from([
+            ("bash".to_string(), "repo bash".to_string()),
+            ("zsh".to_string(), "repo zsh".to_string()),
+        ]);
+        let declared = HashMap::from([("zsh".to_string(), "mine zsh".to_string())]);
+        let unsandboxed = SandboxConfig {
+            enabled: Some(false),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            completion_commands(
+                Some(&repository),
+                Some(&declared),
+                Some(&unsandboxed),
+                "tool"
+            ),
+            BTreeMap::from([("zsh".to_string(), "mine zsh".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_generate_completions_captures_output_and_keeps_shipped_files() {
+        let dir = tempdir().unwrap();
+        let install_dir = dir.path();
+        let shipped = install_dir.join("share/zsh/site-functions/_tool");
+        fs::create_dir_all(shipped.parent().unwrap()).unwrap();
+        fs::write(&shipped, "shipped").unwrap();
+
+        let commands = BTreeMap::from([
+            (
+                "bash".to_string(),
+                "echo \"complete $PKG_NAME\"".to_string(),
+            ),
+            ("zsh".to_string(), "echo generated".to_string()),
+            ("fish".to_string(), "echo unused".to_string()),
+            ("man".to_string(), "exit 3".to_string()),
+        ]);
+        let env = HookEnv {
+            install_dir,
+            pkg_name: "tool",
+            pkg_id: "tool",
+            pkg_version: "1.0",
+        };
+        let sandbox = SandboxConfig {
+            enabled: Some(false),
+            ..Default::default()
+        };
+
+        let written = generate_completions(
+            &commands,
+            "tool",
+            &["bash".to_string(), "zsh".to_string()],
+            &env,
+            Some(&sandbox),
+        )
+        .unwrap();
+
+        let bash = install_dir.join("share/bash-completion/completions/tool");
+        assert_eq!(fs::read_to_string(&bash).unwrap(), "complete tool\n");
+        assert_eq!(written, [bash]);
+        assert_eq!(fs::read_to_string(shipped).unwrap(), "shipped");
+        assert!(!install_dir.join("share/fish").exists());
+        assert!(!install_dir.join("share/man").exists());
+    }
+}
@@ -1,9 +1,14 @@
 use std::{path::Path, process::Command};
 
 use soar_config::{config::get_config, packages::SandboxConfig};
+use soar_utils::cancel::CancellationToken;
 use tracing::{debug, warn};
 
-use crate::{error::ErrorContext, sandbox, SoarError, SoarResult};
+use crate::{
+    audit::{self, AuditKind},
+    error::ErrorContext,
+    sandbox, SoarError, SoarResult,
+};
 
 /// Environment variables passed to hook commands.
 pub struct HookEnv<'a> {
@@ -16,13 +21,18 @@ pub struct HookEnv<'a> {
 /// Run a hook command with environment variables set.
 ///
 /// This is the shared hook execution logic used by both package installation
-/// and removal operations.
+/// and removal operations. A hook that `cancel` stops fails with
+/// [`SoarError::Cancelled`] whatever it exited with.
 pub fn run_hook(
     hook_name: &str,
     command: &str,
     env: &HookEnv,
     sandbox_config: Option<&SandboxConfig>,
+    cancel: Option<&CancellationToken>,
 ) -> SoarResult<()> {
+    if cancel.is_some_and(CancellationToken::is_cancelled) {
+        return Err(SoarError::Cancelled);
+    }
     debug!("running {} hook: {}", hook_name, command);
 
     let bin_dir = get_config().get_bin_path()?;
@@ -36,21 +46,18 @@ pub fn run_hook(
     ];
 
     let sandbox_enabled = sandbox_config.is_none_or(|s| s.is_enabled());
-    let use_sandbox = sandbox_enabled && sandbox::is_landlock_supported();
+    let backend = sandbox_config.map(|s| s.backend()).unwrap_or_default();
+    let use_sandbox = sandbox_enabled && sandbox::is_supported(backend);
 
-    let status = if use_sandbox {
-        debug!("running {} hook with Landlock sandbox", hook_name);
+    let (captured, rules) = if use_sandbox {
+        debug!("running {} hook with {:?} sandbox", hook_name, backend);
         let mut cmd = sandbox::SandboxedCommand::new(command)
             .working_dir(env.install_dir)
             .read_path(&bin_dir)
             .envs(env_vars);
 
         if let Some(s) = sandbox_config {
-            let config = sandbox::SandboxConfig::new().with_network(if s.allows_network() {
-                sandbox::NetworkConfig::allow_all()
-            } else {
-                sandbox::NetworkConfig::default()
-            });
+            let config = sandbox::SandboxConfig::for_package(s);
             cmd = cmd.config(config);
             for path in &s.fs_read {
                 cmd = cmd.read_path(path);
@@ -59,7 +66,10 @@ pub fn run_hook(
                 cmd = cmd.write_path(path);
             }
         }
-        cmd.run()?
+        if let Some(token) = cancel {
+            cmd = cmd.cancel(token.clone());
+        }
+        cmd.run_captured()?
     } else {
         if sandbox_enabled && sandbox_config.is_some_and(|s| s.is_required()) {
             return Err(SoarError::Custom(format!(
@@ -80,20 +90,48 @@ pub fn run_hook(
                 hook_name
             );
         }
-        Command::new("sh")
-            .arg("-c")
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
             .arg(command)
             .env("INSTALL_DIR", env.install_dir)
             .env("BIN_DIR", &bin_dir)
             .env("PKG_NAME", env.pkg_name)
             .env("PKG_ID", env.pkg_id)
             .env("PKG_VERSION", env.pkg_version)
-            .current_dir(env.install_dir)
-            .status()
-            .with_context(|| format!("executing {} hook", hook_name))?
+            .current_dir(env.install_dir);
+        let captured =
+            audit::capture(cmd, cancel).with_context(|| format!("executing {} hook", hook_name))?;
+        (captured, None)
     };
 
+    audit::log(
+        &audit::AuditEntry {
+            pkg_name: env.pkg_name,
+            pkg_id: env.pkg_id,
+            pkg_version: env.pkg_version,
+            kind: AuditKind::Hook,
+            name: hook_name.to_string(),
+            command,
+            working_dir: env.install_dir,
+        }
+        .record(rules, &captured),
+    );
+
+    if cancel.is_some_and(CancellationToken::is_cancelled) {
+        return Err(SoarError::Cancelled);
+    }
+
+    let status = captured.status;
     if !status.success() {
+        if let Some(violation) = use_sandbox
+            .then(|| sandbox::Violation::of(&status))
+            .flatten()
+        {
+            return Err(SoarError::SandboxViolation {
+                command: format!("{} hook", hook_name),
+                reason: violation.to_string(),
+            });
+        }
         return Err(SoarError::Custom(format!(
             "{} hook failed with exit code: {}",
             hook_name,
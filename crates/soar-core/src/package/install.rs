@@ -12,8 +12,10 @@ use std::{
 use chrono::Utc;
 use serde_json::json;
 use soar_config::{
-    config::Config,
-    packages::{BinaryMapping, BuildConfig, PackageHooks, SandboxConfig},
+    config::{AppImageMode, Config},
+    packages::{
+        BinaryMapping, BuildConfig, LaunchConfig, PackageHooks, RuntimeSandbox, SandboxConfig,
+    },
 };
 use soar_db::{
     models::types::PackageFile,
@@ -26,12 +28,15 @@ use soar_dl::{
     oci::OciDownload,
     types::{OverwriteMode, Progress},
 };
-use soar_events::{BuildStage, EventSinkHandle, InstallStage, OperationId, SoarEvent};
+use soar_events::{BuildStage, EventSinkHandle, HookStage, InstallStage, OperationId, SoarEvent};
+use soar_package::formats::{common::refresh_icon_cache, flatpak};
 use soar_utils::{
+    cancel::CancellationToken,
+    entrypoint,
     error::FileSystemResult,
     fs::{safe_remove, walk_dir},
     hash::calculate_checksum,
-    path::is_safe_component,
+    path::{is_safe_component, is_safe_relative, metainfo_dir, mime_dir},
 };
 use tracing::{debug, trace, warn};
 
@@ -40,7 +45,10 @@ use crate::{
     database::{connection::DieselDatabase, models::Package},
     error::{ErrorContext, SoarError},
     package::{
-        local::local_path_from_url, remove::remove_provide_symlinks, update_info::UpdateInfo,
+        local::local_path_from_url,
+        native,
+        remove::{make_tree_writable, remove_provide_symlinks},
+        update_info::UpdateInfo,
     },
     utils::get_extract_dir,
     SoarResult,
@@ -52,7 +60,11 @@ use crate::{
 /// the artifact. A licence publishes none, because it is served from a branch
 /// and is documentation rather than something that runs: pinning it would turn
 /// an upstream copyright-year edit into a failed download.
-async fn install_extras(package: &Package, install_dir: &Path) -> SoarResult<()> {
+async fn install_extras(
+    package: &Package,
+    install_dir: &Path,
+    cancel: Option<&CancellationToken>,
+) -> SoarResult<()> {
     let Some(extras) = &package.extra else {
         return Ok(());
     };
@@ -71,6 +83,9 @@ async fn install_extras(package: &Package, install_dir: &Path) -> SoarResult<()>
         if let Some(sum) = e.blake3.as_ref() {
             dl = dl.checksum(sum);
         }
+        if let Some(token) = cancel {
+            dl = dl.cancel(token.clone());
+        }
         match dl.execute() {
             Ok(_) => {
                 // A side file is usually a licence, but it can be a binary an
@@ -81,6 +96,7 @@ async fn install_extras(package: &Package, install_dir: &Path) -> SoarResult<()>
                 }
                 debug!(file = %dest.display(), "installed side file")
             }
+            Err(DownloadError::Cancelled) => return Err(SoarError::Cancelled),
             Err(err) => {
                 // A missing licence should not abandon a working install, but
                 // it must not pass unnoticed either.
@@ -254,15 +270,6 @@ fn relative_to(link: &str, target: &str) -> Option<PathBuf> {
     (!out.as_os_str().is_empty()).then_some(out)
 }
 
-/// Whether a path stays inside the directory it is joined to.
-fn is_safe_relative(path: &str) -> bool {
-    !path.is_empty()
-        && !Path::new(path).is_absolute()
-        && Path::new(path)
-            .components()
-            .all(|c| matches!(c, std::path::Component::Normal(_)))
-}
-
 /// Every regular file under `dir`, skipping the staging directory.
 fn walk_dir_files(dir: &Path, skip: &Path) -> Vec<PathBuf> {
     let mut out = Vec::new();
@@ -326,13 +333,13 @@ fn resolve_source(
         .cloned()
 }
 
-/// Give every ELF under `dir` the executable bit.
+/// Give every ELF and `#!` script under `dir` the executable bit.
 ///
 /// Archive members carry whatever permissions the upstream tarball recorded,
 /// and some ship binaries as 0644. The downloaded file is chmodded on the way
 /// in, but files that only appear after extraction were being left
-/// unexecutable.
-fn mark_elfs_executable(dir: &Path) {
+/// unexecutable, and a script is never chmodded by the download at all.
+fn mark_executables(dir: &Path) {
     let Ok(entries) = fs::read_dir(dir) else {
         return;
     };
@@ -342,9 +349,21 @@ fn mark_elfs_executable(dir: &Path) {
             continue;
         }
         if path.is_dir() {
-            mark_elfs_executable(&path);
-        } else if is_elf(&path) {
-            fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).ok();
+            mark_executables(&path);
+        } else {
+            mark_executable(&path);
+        }
+    }
+}
+
+/// Give `path` the executable bit if it is an ELF or a `#!` script.
+fn mark_executable(path: &Path) {
+    if entrypoint::classify(path).is_some_and(|e| e.is_self_executing()) {
+        let mode = fs::metadata(path)
+            .map(|m| m.permissions().mode())
+            .unwrap_or(0);
+        if mode & 0o111 != 0o111 {
+            fs::set_permissions(path, std::fs::Permissions::from_mode(mode | 0o755)).ok();
         }
     }
 }
@@ -453,6 +472,7 @@ pub struct PackageInstaller {
     zsync: Option<ZsyncSeed>,
     events: EventSinkHandle,
     op_id: OperationId,
+    cancel: Option<CancellationToken>,
 }
 
 /// A zsync feed and the installed copy to rebuild the new artifact from.
@@ -472,6 +492,9 @@ pub struct InstallTarget {
     pub package: Package,
     pub existing_install: Option<crate::database::models::InstalledPackage>,
     pub pinned: bool,
+    /// Keep the package's other installed versions, as for a version named
+    /// on its own, instead of removing them once this one is in.
+    pub keep_versions: bool,
     pub profile: Option<String>,
     pub portable: Option<String>,
     pub portable_home: Option<String>,
@@ -486,6 +509,17 @@ pub struct InstallTarget {
     pub build: Option<BuildConfig>,
     pub sandbox: Option<SandboxConfig>,
     pub arch_map: Option<std::collections::HashMap<String, String>>,
+    /// How an AppImage is run, when packages.toml chooses for this package.
+    pub appimage_mode: Option<AppImageMode>,
+    /// Commands printing completions, when packages.toml declares them.
+    pub completion_commands: Option<std::collections::HashMap<String, String>>,
+    /// How the package's commands are started, when packages.toml says.
+    pub launch: Option<LaunchConfig>,
+    /// Priority over other packages providing the same commands, when
+    /// packages.toml gives one.
+    pub priority: Option<i32>,
+    /// Sandbox the package's commands run in, when packages.toml gives one.
+    pub runtime_sandbox: Option<RuntimeSandbox>,
     /// Set when the new artifact can be rebuilt from the installed one.
     pub zsync: Option<ZsyncSeed>,
 }
@@ -595,6 +629,7 @@ impl PackageInstaller {
                 install_patterns: Some(json!(globs)),
                 download_url: None,
                 update_info: None,
+                build_id: package.build_id.as_deref(),
             };
 
             db.with_conn(|conn| CoreRepository::insert(conn, &new_package))?;
@@ -616,11 +651,67 @@ impl PackageInstaller {
             zsync: target.zsync.clone(),
             events,
             op_id,
+            cancel: None,
         })
     }
 
+    /// Stop between steps once `cancel` is cancelled. What was installed up
+    /// to then is left for [`roll_back`](Self::roll_back) to undo.
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    fn check_cancelled(&self) -> SoarResult<()> {
+        match self.cancel {
+            Some(ref token) if token.is_cancelled() => Err(SoarError::Cancelled),
+            _ => Ok(()),
+        }
+    }
+
+    /// Undo an install that stopped before it was recorded: its directory,
+    /// and the pending row standing for it along with any other left over
+    /// for the package.
+    pub fn roll_back(&self) -> SoarResult<()> {
+        debug!(
+            pkg_name = self.package.pkg_name,
+            install_dir = %self.install_dir.display(),
+            "rolling back partial install"
+        );
+        let package = &self.package;
+        let pending = self.db.with_conn(|conn| {
+            CoreRepository::delete_pending_installs(
+                conn,
+                package.pkg_id.as_deref(),
+                &package.pkg_name,
+                &package.repo_name,
+            )
+        })?;
+        for path in pending
+            .iter()
+            .map(PathBuf::from)
+            .chain(std::iter::once(self.install_dir.clone()))
+        {
+            if path.exists() {
+                // A Flatpak bundle's app lives outside the directory.
+                if let Err(err) = flatpak::uninstall_recorded(&path, self.config.is_system()) {
+                    warn!("{err}");
+                }
+                make_tree_writable(&path);
+                fs::remove_dir_all(&path)
+                    .with_context(|| format!("removing {}", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+
     /// Run a hook command with environment variables set.
-    fn run_hook(&self, hook_name: &str, command: &str) -> SoarResult<()> {
+    fn run_hook(
+        &self,
+        hook_name: &str,
+        command: &str,
+        cancel: Option<&CancellationToken>,
+    ) -> SoarResult<()> {
         use super::hooks::{run_hook, HookEnv};
 
         let env = HookEnv {
@@ -636,14 +727,29 @@ impl PackageInstaller {
             stage: InstallStage::RunningHook(hook_name.to_string()),
         });
 
-        run_hook(hook_name, command, &env, self.sandbox.as_ref())
+        let result = run_hook(hook_name, command, &env, self.sandbox.as_ref(), cancel);
+        if let Err(SoarError::SandboxViolation {
+            reason, ..
+        }) = &result
+        {
+            self.events.emit(SoarEvent::Hook {
+                op_id: self.op_id,
+                pkg_name: self.package.pkg_name.clone(),
+                hook_name: hook_name.to_string(),
+                stage: HookStage::Failed {
+                    exit_code: None,
+                    violation: Some(reason.clone()),
+                },
+            });
+        }
+        result
     }
 
     /// Run post_download hook if configured.
     pub fn run_post_download_hook(&self) -> SoarResult<()> {
         if let Some(ref hooks) = self.hooks {
             if let Some(ref cmd) = hooks.post_download {
-                self.run_hook("post_download", cmd)?;
+                self.run_hook("post_download", cmd, self.cancel.as_ref())?;
             }
         }
         Ok(())
@@ -653,17 +759,20 @@ impl PackageInstaller {
     pub fn run_post_extract_hook(&self) -> SoarResult<()> {
         if let Some(ref hooks) = self.hooks {
             if let Some(ref cmd) = hooks.post_extract {
-                self.run_hook("post_extract", cmd)?;
+                self.run_hook("post_extract", cmd, self.cancel.as_ref())?;
             }
         }
         Ok(())
     }
 
     /// Run post_install hook if configured.
+    ///
+    /// It runs once the package is recorded, when there is nothing left to
+    /// roll back, so cancelling does not stop it.
     pub fn run_post_install_hook(&self) -> SoarResult<()> {
         if let Some(ref hooks) = self.hooks {
             if let Some(ref cmd) = hooks.post_install {
-                self.run_hook("post_install", cmd)?;
+                self.run_hook("post_install", cmd, None)?;
             }
         }
         Ok(())
@@ -691,7 +800,10 @@ impl PackageInstaller {
 
     /// Run build commands if configured.
     pub fn run_build(&self) -> SoarResult<()> {
-        use crate::sandbox;
+        use crate::{
+            audit::{self, AuditKind},
+            sandbox,
+        };
 
         let build_config = match &self.build {
             Some(config) if !config.commands.is_empty() => config,
@@ -714,10 +826,15 @@ impl PackageInstaller {
             .unwrap_or_else(|_| "1".to_string());
 
         let sandbox_enabled = self.sandbox.as_ref().is_none_or(|s| s.is_enabled());
-        let use_sandbox = sandbox_enabled && sandbox::is_landlock_supported();
+        let backend = self
+            .sandbox
+            .as_ref()
+            .map(|s| s.backend())
+            .unwrap_or_default();
+        let use_sandbox = sandbox_enabled && sandbox::is_supported(backend);
 
         if use_sandbox {
-            debug!("running build with Landlock sandbox");
+            debug!("running build with {:?} sandbox", backend);
         } else if !sandbox_enabled {
             debug!(
                 "sandbox explicitly disabled, running build without sandbox ({} commands)",
@@ -748,6 +865,7 @@ impl PackageInstaller {
         }
 
         for (i, cmd) in build_config.commands.iter().enumerate() {
+            self.check_cancelled()?;
             debug!(
                 "running build command {}/{}: {}",
                 i + 1,
@@ -764,7 +882,7 @@ impl PackageInstaller {
                 },
             });
 
-            let status = if use_sandbox {
+            let (captured, rules) = if use_sandbox {
                 let env_vars: Vec<(&str, String)> = vec![
                     (
                         "INSTALL_DIR",
@@ -783,12 +901,7 @@ impl PackageInstaller {
                     .envs(env_vars);
 
                 if let Some(s) = &self.sandbox {
-                    let config =
-                        sandbox::SandboxConfig::new().with_network(if s.allows_network() {
-                            sandbox::NetworkConfig::allow_all()
-                        } else {
-                            sandbox::NetworkConfig::default()
-                        });
+                    let config = sandbox::SandboxConfig::for_package(s);
                     sandbox_cmd = sandbox_cmd.config(config);
                     for path in &s.fs_read {
                         sandbox_cmd = sandbox_cmd.read_path(path);
@@ -797,9 +910,13 @@ impl PackageInstaller {
                         sandbox_cmd = sandbox_cmd.write_path(path);
                     }
                 }
-                sandbox_cmd.run()?
+                if let Some(ref token) = self.cancel {
+                    sandbox_cmd = sandbox_cmd.cancel(token.clone());
+                }
+                sandbox_cmd.run_captured()?
             } else {
-                Command::new("sh")
+                let mut command = Command::new("sh");
+                command
                     .arg("-c")
                     .arg(cmd)
                     .env("INSTALL_DIR", &self.install_dir)
@@ -808,12 +925,48 @@ impl PackageInstaller {
                     .env("PKG_ID", self.package.pkg_id.as_deref().unwrap_or_default())
                     .env("PKG_VERSION", &self.package.version)
                     .env("NPROC", &nproc)
-                    .current_dir(&self.install_dir)
-                    .status()
-                    .with_context(|| format!("executing build command {}", i + 1))?
+                    .current_dir(&self.install_dir);
+                let captured = audit::capture(command, self.cancel.as_ref())
+                    .with_context(|| format!("executing build command {}", i + 1))?;
+                (captured, None)
             };
 
+            audit::log(
+                &audit::AuditEntry {
+                    pkg_name: &self.package.pkg_name,
+                    pkg_id: self.package.pkg_id.as_deref().unwrap_or_default(),
+                    pkg_version: &self.package.version,
+                    kind: AuditKind::Build,
+                    name: format!("build {}/{}", i + 1, total_commands),
+                    command: cmd,
+                    working_dir: &self.install_dir,
+                }
+                .record(rules, &captured),
+            );
+
+            self.check_cancelled()?;
+
+            let status = captured.status;
+
             if !status.success() {
+                let violation = use_sandbox
+                    .then(|| sandbox::Violation::of(&status))
+                    .flatten();
+                self.events.emit(SoarEvent::Building {
+                    op_id: self.op_id,
+                    pkg_name: self.package.pkg_name.clone(),
+                    stage: BuildStage::Failed {
+                        command_index: i,
+                        exit_code: status.code(),
+                        violation: violation.map(|v| v.to_string()),
+                    },
+                });
+                if let Some(violation) = violation {
+                    return Err(SoarError::SandboxViolation {
+                        command: format!("Build command {}", i + 1),
+                        reason: violation.to_string(),
+                    });
+                }
                 return Err(SoarError::Custom(format!(
                     "Build command {} failed with exit code: {}",
                     i + 1,
@@ -913,8 +1066,8 @@ impl PackageInstaller {
 
         // Extraction is offered for every install, so what the file actually is
         // decides: a local AppImage or bare binary is not an archive and is
-        // left alone rather than failing.
-        if extract && compak::detect_from_file(dest).is_ok() {
+        // left alone rather than failing, and neither is a jar or zipapp.
+        if extract && !entrypoint::is_zip_program(dest) && compak::detect_from_file(dest).is_ok() {
             debug!(archive = %dest.display(), dest = %extract_dir.display(), "extracting local archive");
             compak::extract_archive(dest, extract_dir).map_err(|e| {
                 SoarError::Custom(format!(
@@ -934,6 +1087,7 @@ impl PackageInstaller {
             pkg_id = self.package.pkg_id,
             "starting package download"
         );
+        self.check_cancelled()?;
         self.write_marker()?;
 
         let package = &self.package;
@@ -962,6 +1116,10 @@ impl PackageInstaller {
                 });
             }
 
+            if let Some(ref token) = self.cancel {
+                dl = dl.cancel(token.clone());
+            }
+
             if !self.globs.is_empty() {
                 dl = dl.filter(Filter {
                     globs: self.globs.clone(),
@@ -1003,6 +1161,7 @@ impl PackageInstaller {
                                 "download failed, retrying after delay: {err}"
                             );
                             sleep(Duration::from_secs(5));
+                            self.check_cancelled()?;
                             retries += 1;
                             if retries > 1 {
                                 if let Some(ref callback) = self.progress_callback {
@@ -1044,6 +1203,7 @@ impl PackageInstaller {
                     &seed.seed,
                     output_path,
                     callback.map(|cb| move |p| cb(p)),
+                    self.cancel.as_ref(),
                 )?;
                 output_path.to_path_buf()
             } else if let Some(local_src) = local_path_from_url(url) {
@@ -1068,9 +1228,14 @@ impl PackageInstaller {
                     });
                 }
 
+                if let Some(ref token) = self.cancel {
+                    dl = dl.cancel(token.clone());
+                }
+
                 dl.execute()?
             };
 
+            self.check_cancelled()?;
             self.run_post_download_hook()?;
 
             let checksum = if PathBuf::from(&file_path).exists() {
@@ -1079,6 +1244,38 @@ impl PackageInstaller {
                 None
             };
 
+            // Distribution packages are unpacked here rather than by the
+            // downloader, so their payload can be relocated out of usr/.
+            let native = native::unpack(Path::new(&file_path), Path::new(&extract_dir))?;
+            if let Some(native) = native.as_ref().filter(|n| !n.scripts.is_empty()) {
+                warn!(
+                    "{} ships maintainer scripts that were not run: {}",
+                    native.format,
+                    native.scripts.join(", ")
+                );
+            }
+
+            // A Flatpak bundle only means something to flatpak; it leaves a
+            // launcher and the app's exports behind in place of the bundle.
+            if native.is_none() {
+                if let Some(bundle) = flatpak::read_bundle_file(Path::new(&file_path)) {
+                    debug!(reference = bundle.reference, "installing flatpak bundle");
+                    flatpak::install_bundle(
+                        &bundle,
+                        Path::new(&file_path),
+                        &self.install_dir,
+                        output_path,
+                        self.config.is_system(),
+                    )
+                    .map_err(|err| {
+                        SoarError::Custom(format!(
+                            "Refusing to install {}: {err}",
+                            package.pkg_name
+                        ))
+                    })?;
+                }
+            }
+
             let extract_path = PathBuf::from(&extract_dir);
             let extracted = extract_path.exists();
             if extracted {
@@ -1126,7 +1323,7 @@ impl PackageInstaller {
             // directory (foo-1.2.3-x86_64/). Nothing downstream can guess that
             // name, so when extraction leaves exactly one directory behind and
             // no extract_root was given, treat it as the root.
-            let auto_root = if self.extract_root.is_none() && extracted {
+            let auto_root = if self.extract_root.is_none() && extracted && native.is_none() {
                 let mut dirs = Vec::new();
                 let mut files = 0usize;
                 if let Ok(rd) = fs::read_dir(&self.install_dir) {
@@ -1207,18 +1404,25 @@ impl PackageInstaller {
                 }
             }
 
+            // Only an extracted payload is walked; otherwise the download is
+            // the one file to mark, before a layout moves it.
+            if !extracted {
+                mark_executable(output_path);
+            }
+
             if let Some(files) = self.package.files.as_deref().filter(|f| !f.is_empty()) {
                 apply_file_layout(files, &self.install_dir, output_path)?;
             }
 
             if extracted {
-                mark_elfs_executable(&self.install_dir);
+                mark_executables(&self.install_dir);
             }
 
-            install_extras(&self.package, &self.install_dir).await?;
+            install_extras(&self.package, &self.install_dir, self.cancel.as_ref()).await?;
 
             // Handle nested_extract: extract an archive within the package
             if let Some(ref nested_archive) = self.nested_extract {
+                self.check_cancelled()?;
                 let nested_archive = substitute_placeholders(
                     nested_archive,
                     Some(&self.package.version),
@@ -1289,6 +1493,11 @@ impl PackageInstaller {
         }
     }
 
+    /// Record the install in the database.
+    ///
+    /// # Returns
+    ///
+    /// The id of the installed row.
     pub async fn record(
         &self,
         unlinked: bool,
@@ -1297,7 +1506,7 @@ impl PackageInstaller {
         portable_config: Option<&str>,
         portable_share: Option<&str>,
         portable_cache: Option<&str>,
-    ) -> SoarResult<()> {
+    ) -> SoarResult<i32> {
         debug!(
             pkg_name = self.package.pkg_name,
             pkg_id = self.package.pkg_id,
@@ -1326,6 +1535,7 @@ impl PackageInstaller {
                 size,
                 provides,
                 checksum,
+                package.build_id.as_deref(),
                 &installed_date,
                 &installed_path,
             )
@@ -1426,7 +1636,7 @@ impl PackageInstaller {
 
                 let mut remove_action = |path: &Path| -> FileSystemResult<()> {
                     if let Ok(real_path) = fs::read_link(path) {
-                        if real_path.parent() == Some(&installed_path) {
+                        if real_path.starts_with(&installed_path) {
                             safe_remove(path)?;
                         }
                     }
@@ -1441,7 +1651,7 @@ impl PackageInstaller {
 
                 let mut remove_action = |path: &Path| -> FileSystemResult<()> {
                     if let Ok(real_path) = fs::read_link(path) {
-                        if real_path.parent() == Some(&installed_path) {
+                        if real_path.starts_with(&installed_path) {
                             safe_remove(path)?;
                         }
                     }
@@ -1450,6 +1660,18 @@ impl PackageInstaller {
                 let icons_path = self.config.get_icons_path();
                 if icons_path.is_dir() {
                     walk_dir(&icons_path, &mut remove_action)?;
+                    refresh_icon_cache(self.config.is_system());
+                }
+
+                // Integrating this package registers its own in their place.
+                let system_mode = self.config.is_system();
+                for dir in [
+                    mime_dir(system_mode).join("packages"),
+                    metainfo_dir(system_mode),
+                ] {
+                    if dir.is_dir() {
+                        walk_dir(&dir, &mut remove_action)?;
+                    }
                 }
 
                 if let Some(ref provides) = alt_pkg.provides {
@@ -1461,7 +1683,7 @@ impl PackageInstaller {
 
         self.remove_marker()?;
 
-        Ok(())
+        Ok(record_id)
     }
 }
 
@@ -0,0 +1,118 @@
+//! Per-package launch settings: variables, leading arguments, and a command
+//! run before the package's own.
+//!
+//! A package gets them from its repository's metadata and from its
+//! `packages.toml` entry, the latter winning. The settings a package's
+//! launchers were written with are recorded in its install directory, so a
+//! later `soar apply` can tell when they need writing again.
+
+use std::{fs, path::Path};
+
+use soar_config::packages::LaunchConfig;
+use soar_db::models::types::PackageLaunch;
+
+use crate::{
+    constants::LAUNCH_FILE,
+    error::{ErrorContext, SoarError},
+    SoarResult,
+};
+
+/// The settings a package starts with: `declared` in `packages.toml` on top
+/// of those `published` in its repository's metadata.
+pub fn effective(
+    declared: Option<&LaunchConfig>,
+    published: Option<&PackageLaunch>,
+) -> LaunchConfig {
+    let published = published
+        .map(|launch| {
+            LaunchConfig {
+                env: launch.env.clone(),
+                args: launch.args.clone(),
+                pre_launch: launch.pre_launch.clone(),
+            }
+        })
+        .unwrap_or_default();
+    match declared {
+        Some(declared) => declared.over(&published),
+        None => published,
+    }
+}
+
+/// Record `launch` in `install_dir`, or remove the record when it is empty.
+pub fn record(install_dir: &Path, launch: &LaunchConfig) -> SoarResult<()> {
+    let path = install_dir.join(LAUNCH_FILE);
+    if launch.is_empty() {
+        if path.exists() {
+            fs::remove_file(&path).with_context(|| format!("removing {}", path.display()))?;
+        }
+        return Ok(());
+    }
+    fs::write(&path, toml::to_string(launch)?)
+        .with_context(|| format!("writing {}", path.display()))
+}
+
+/// The settings recorded in `install_dir`, which are empty when there is no
+/// record.
+pub fn recorded(install_dir: &Path) -> SoarResult<LaunchConfig> {
+    let path = install_dir.join(LAUNCH_FILE);
+    if !path.is_file() {
+        return Ok(LaunchConfig::default());
+    }
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("reading {}", path.display()))?;
+    toml::from_str(&content).map_err(|err| {
+        SoarError::Custom(format!(
+            "{} is not a record of launch settings: {err}",
+            path.display()
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_declared_settings_win_over_published_ones() {
+        let published = PackageLaunch {
+            env: BTreeMap::from([
+                ("A".to_string(), "repo".to_string()),
+                ("B".to_string(), "repo".to_string()),
+            ]),
+            args: vec!["--repo".into()],
+            pre_launch: Some("true".into()),
+        };
+        let declared = LaunchConfig {
+            env: BTreeMap::from([("A".to_string(), "mine".to_string())]),
+            ..Default::default()
+        };
+
+        let launch = effective(Some(&declared), Some(&published));
+
+        assert_eq!(launch.env["A"], "mine");
+        assert_eq!(launch.env["B"], "repo");
+        assert_eq!(launch.args, ["--repo"]);
+        assert_eq!(launch.pre_launch.as_deref(), Some("true"));
+        assert!(effective(None, None).is_empty());
+    }
+
+    #[test]
+    fn test_record_round_trips_and_empty_removes_it() {
+        let dir = tempdir().unwrap();
+        let launch = LaunchConfig {
+            args: vec!["--flag".into()],
+            ..Default::default()
+        };
+
+        record(dir.path(), &launch).unwrap();
+        assert_eq!(recorded(dir.path()).unwrap(), launch);
+
+        record(dir.path(), &LaunchConfig::default()).unwrap();
+        assert!(!dir.path().join(LAUNCH_FILE).exists());
+        assert!(recorded(dir.path()).unwrap().is_empty());
+    }
+}
@@ -6,12 +6,18 @@
 
 use std::path::{Path, PathBuf};
 
-use soar_utils::path::resolve_path;
+use soar_utils::{
+    entrypoint::{self, Entrypoint},
+    path::resolve_path,
+};
 
 use crate::{
     database::models::Package,
     error::SoarError,
-    package::url::{detect_pkg_type, parse_filename},
+    package::{
+        native,
+        url::{detect_pkg_type, parse_filename},
+    },
     SoarResult,
 };
 
@@ -104,8 +110,21 @@ impl LocalPackage {
         // renamed or extensionless. When the extension tells us nothing, sniff
         // the contents so archives still get extracted. compak only matches
         // genuine archive signatures, so ELF binaries/AppImages stay as-is.
-        if pkg_type.is_none() && compak::detect_from_file(&path).is_ok() {
-            pkg_type = Some("archive".to_string());
+        if pkg_type.is_none() {
+            if let Some(format) = native::detect(&path) {
+                pkg_type = Some(format.to_string());
+            } else if let Some(kind) = entrypoint::classify(&path).and_then(|e| {
+                match e {
+                    Entrypoint::Zipapp => Some("zipapp"),
+                    Entrypoint::Jar => Some("jar"),
+                    _ => None,
+                }
+            }) {
+                // Zips too, but ones that are run rather than extracted.
+                pkg_type = Some(kind.to_string());
+            } else if compak::detect_from_file(&path).is_ok() {
+                pkg_type = Some("archive".to_string());
+            }
         }
 
         let (extracted_name, extracted_version) = parse_filename(&filename);
@@ -1,6 +1,10 @@
+pub mod compat;
+pub mod completions;
 pub mod hooks;
 pub mod install;
+pub mod launch;
 pub mod local;
+pub mod native;
 pub mod query;
 pub mod release_source;
 pub mod remote_update;
@@ -8,3 +12,4 @@ pub mod remove;
 pub mod update;
 pub mod update_info;
 pub mod url;
+pub mod versions;
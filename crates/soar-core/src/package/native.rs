@@ -0,0 +1,991 @@
+//! Distribution packages: `.deb`, `.rpm` and Arch Linux `.pkg.tar.*`.
+//!
+//! The payload is unpacked without root or a system package manager and
+//! relocated so it reads like any other soar install: `usr/bin` and `usr/sbin`
+//! become `bin/`, `usr/share` becomes `share/`, and everything else keeps its
+//! place. Maintainer scripts are reported, never run.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    fs::{self, File},
+    io::{self, BufReader, Read, Seek, SeekFrom},
+    os::unix::fs::{symlink, PermissionsExt},
+    path::{Component, Path, PathBuf},
+};
+
+use soar_package::contents::prepare_dest;
+use soar_utils::path::{is_safe_component, is_safe_relative};
+use tracing::debug;
+
+use crate::{
+    error::{ErrorContext, SoarError},
+    SoarResult,
+};
+
+const AR_MAGIC: &[u8; 8] = b"!<arch>\n";
+const RPM_LEAD_MAGIC: [u8; 4] = [0xed, 0xab, 0xee, 0xdb];
+const RPM_HEADER_MAGIC: [u8; 4] = [0x8e, 0xad, 0xe8, 0x01];
+
+/// Arch package metadata, kept at the root of the archive.
+const ARCH_PKGINFO: &str = ".PKGINFO";
+
+/// Debian maintainer scripts, as named inside `control.tar.*`.
+const DEB_SCRIPTS: &[&str] = &[
+    "preinst", "postinst", "prerm", "postrm", "config", "triggers",
+];
+
+/// RPM header tags carrying a scriptlet, with the name `rpm -q --scripts` uses.
+const RPM_SCRIPTS: &[(u32, &str)] = &[
+    (1151, "pretrans"),
+    (1023, "preinstall"),
+    (1024, "postinstall"),
+    (1025, "preuninstall"),
+    (1026, "postuninstall"),
+    (1152, "posttrans"),
+    (1065, "triggers"),
+    (1079, "verify"),
+];
+const RPM_TAG_PAYLOAD_COMPRESSOR: u32 = 1125;
+const RPM_TYPE_STRING: u32 = 6;
+
+/// Upper bound on an RPM header's data store; real ones are a few hundred KiB.
+const RPM_MAX_STORE: usize = 64 << 20;
+
+/// The distribution package formats soar can unpack.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NativeFormat {
+    Deb,
+    Rpm,
+    Arch,
+}
+
+impl fmt::Display for NativeFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NativeFormat::Deb => write!(f, "deb"),
+            NativeFormat::Rpm => write!(f, "rpm"),
+            NativeFormat::Arch => write!(f, "Arch package"),
+        }
+    }
+}
+
+/// A distribution package unpacked into a soar layout.
+#[derive(Debug)]
+pub struct NativePackage {
+    pub format: NativeFormat,
+    /// Maintainer scripts the package ships, none of which were run.
+    pub scripts: Vec<String>,
+}
+
+/// Identify a `.deb` or `.rpm` by its leading bytes.
+///
+/// Arch packages are plain compressed tarballs, so they are only recognised
+/// once extracted, by the `.PKGINFO` at their root.
+pub fn detect(path: &Path) -> Option<NativeFormat> {
+    let mut head = [0u8; 21];
+    let mut file = File::open(path).ok()?;
+    let read = file.read(&mut head).ok()?;
+    let head = &head[..read];
+
+    if head.starts_with(AR_MAGIC) && head[8..].starts_with(b"debian-binary") {
+        Some(NativeFormat::Deb)
+    } else if head.starts_with(&RPM_LEAD_MAGIC) {
+        Some(NativeFormat::Rpm)
+    } else {
+        None
+    }
+}
+
+/// Unpack a distribution package into `extract_dir`, relocated to a soar layout.
+///
+/// `file` is the downloaded artifact and `extract_dir` wherever the downloader
+/// extracted it, if it could. Returns `None` when the artifact is not a
+/// distribution package, leaving both untouched.
+pub fn unpack(file: &Path, extract_dir: &Path) -> SoarResult<Option<NativePackage>> {
+    let format = if extract_dir.join(ARCH_PKGINFO).is_file() {
+        NativeFormat::Arch
+    } else {
+        match detect(file) {
+            Some(format) => format,
+            None => return Ok(None),
+        }
+    };
+
+    let staging = extract_dir.with_file_name(".soar-native");
+    if staging.exists() {
+        fs::remove_dir_all(&staging)
+            .with_context(|| format!("removing stale {}", staging.display()))?;
+    }
+
+    debug!(%format, file = %file.display(), "unpacking distribution package");
+    let result = unpack_into(format, file, extract_dir, &staging);
+    fs::remove_dir_all(&staging).ok();
+
+    Ok(Some(NativePackage {
+        format,
+        scripts: result?,
+    }))
+}
+
+fn unpack_into(
+    format: NativeFormat,
+    file: &Path,
+    extract_dir: &Path,
+    staging: &Path,
+) -> SoarResult<Vec<String>> {
+    let (root, scripts) = match format {
+        NativeFormat::Arch => {
+            fs::rename(extract_dir, staging)
+                .with_context(|| format!("staging {}", extract_dir.display()))?;
+            let scripts = if staging.join(".INSTALL").is_file() {
+                vec![".INSTALL".to_string()]
+            } else {
+                Vec::new()
+            };
+            (staging.to_path_buf(), scripts)
+        }
+        NativeFormat::Deb => {
+            fs::create_dir_all(staging)
+                .with_context(|| format!("creating {}", staging.display()))?;
+            let scripts = unpack_deb(file, staging)?;
+            (staging.join("data"), scripts)
+        }
+        NativeFormat::Rpm => {
+            fs::create_dir_all(staging)
+                .with_context(|| format!("creating {}", staging.display()))?;
+            let scripts = unpack_rpm(file, staging)?;
+            (staging.join("data"), scripts)
+        }
+    };
+
+    if extract_dir.exists() {
+        fs::remove_dir_all(extract_dir)
+            .with_context(|| format!("removing {}", extract_dir.display()))?;
+    }
+    fs::create_dir_all(extract_dir)
+        .with_context(|| format!("creating {}", extract_dir.display()))?;
+    if root.is_dir() {
+        relocate(&root, extract_dir)?;
+    }
+
+    Ok(scripts)
+}
+
+/// Unpack a `.deb`: an `ar` archive holding `control.tar.*` and `data.tar.*`.
+///
+/// The payload lands in `staging/data`; the names of the maintainer scripts
+/// found in the control archive are returned.
+fn unpack_deb(file: &Path, staging: &Path) -> SoarResult<Vec<String>> {
+    let members = read_ar_members(file, staging, |name| {
+        name.starts_with("control.tar") || name.starts_with("data.tar")
+    })?;
+
+    let data = members
+        .iter()
+        .find(|m| m.starts_with("data.tar"))
+        .ok_or_else(|| SoarError::Custom(format!("{} has no data archive", file.display())))?;
+    extract_to(&staging.join(data), &staging.join("data"))?;
+
+    let mut scripts = Vec::new();
+    if let Some(control) = members.iter().find(|m| m.starts_with("control.tar")) {
+        let control_dir = staging.join("control");
+        extract_to(&staging.join(control), &control_dir)?;
+        for name in DEB_SCRIPTS {
+            if control_dir.join(name).is_file() {
+                scripts.push(name.to_string());
+            }
+        }
+    }
+
+    Ok(scripts)
+}
+
+/// Copy the members of an `ar` archive accepted by `wanted` into `dest`.
+///
+/// Returns the names of the members written.
+fn read_ar_members<F>(file: &Path, dest: &Path, wanted: F) -> SoarResult<Vec<String>>
+where
+    F: Fn(&str) -> bool,
+{
+    let mut reader =
+        BufReader::new(File::open(file).with_context(|| format!("opening {}", file.display()))?);
+    let mut magic = [0u8; 8];
+    reader
+        .read_exact(&mut magic)
+        .with_context(|| format!("reading {}", file.display()))?;
+    if &magic != AR_MAGIC {
+        return Err(SoarError::Custom(format!(
+            "{} is not an ar archive",
+            file.display()
+        )));
+    }
+
+    let mut written = Vec::new();
+    loop {
+        let mut header = [0u8; 60];
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
+            Err(err) => {
+                return Err(SoarError::IoError {
+                    action: format!("reading {}", file.display()),
+                    source: err,
+                })
+            }
+        }
+        if &header[58..] != b"`\n" {
+            return Err(SoarError::Custom(format!(
+                "{} has a corrupt ar member header",
+                file.display()
+            )));
+        }
+
+        // GNU ar terminates names with '/'; BSD ar pads them with spaces.
+        let name = String::from_utf8_lossy(&header[..16])
+            .trim_end()
+            .trim_end_matches('/')
+            .to_string();
+        let size: u64 = String::from_utf8_lossy(&header[48..58])
+            .trim()
+            .parse()
+            .map_err(|_| {
+                SoarError::Custom(format!("{} has a corrupt ar member size", file.display()))
+            })?;
+
+        if is_safe_component(&name) && wanted(&name) {
+            let path = dest.join(&name);
+            let mut out =
+                File::create(&path).with_context(|| format!("creating {}", path.display()))?;
+            let copied = io::copy(&mut (&mut reader).take(size), &mut out)
+                .with_context(|| format!("writing {}", path.display()))?;
+            if copied != size {
+                return Err(SoarError::Custom(format!(
+                    "{} is truncated",
+                    file.display()
+                )));
+            }
+            written.push(name);
+        } else {
+            reader
+                .seek(SeekFrom::Current(size as i64))
+                .with_context(|| format!("reading {}", file.display()))?;
+        }
+
+        // Member data is padded to an even offset.
+        if size % 2 == 1 {
+            reader
+                .seek(SeekFrom::Current(1))
+                .with_context(|| format!("reading {}", file.display()))?;
+        }
+    }
+
+    Ok(written)
+}
+
+/// Unpack an `.rpm`: a lead, a signature header, the main header and a
+/// compressed cpio payload.
+///
+/// The payload lands in `staging/data`; the scriptlets the header declares
+/// are returned.
+fn unpack_rpm(file: &Path, staging: &Path) -> SoarResult<Vec<String>> {
+    let mut reader =
+        BufReader::new(File::open(file).with_context(|| format!("opening {}", file.display()))?);
+    let mut lead = [0u8; 96];
+    reader
+        .read_exact(&mut lead)
+        .with_context(|| format!("reading {}", file.display()))?;
+    if lead[..4] != RPM_LEAD_MAGIC {
+        return Err(SoarError::Custom(format!(
+            "{} is not an rpm package",
+            file.display()
+        )));
+    }
+
+    let signature = RpmHeader::read(&mut reader)
+        .map_err(|e| SoarError::Custom(format!("{}: signature {e}", file.display())))?;
+    // The signature header is padded to an eight-byte boundary.
+    let padding = (8 - signature.len() % 8) % 8;
+    reader
+        .seek(SeekFrom::Current(padding as i64))
+        .with_context(|| format!("reading {}", file.display()))?;
+    let header = RpmHeader::read(&mut reader)
+        .map_err(|e| SoarError::Custom(format!("{}: header {e}", file.display())))?;
+
+    let scripts = RPM_SCRIPTS
+        .iter()
+        .filter(|(tag, _)| header.has(*tag))
+        .map(|(_, name)| name.to_string())
+        .collect();
+
+    let compressor = header
+        .string(RPM_TAG_PAYLOAD_COMPRESSOR)
+        .unwrap_or_else(|| "gzip".to_string());
+    let suffix = match compressor.as_str() {
+        "gzip" => Some("gz"),
+        "xz" => Some("xz"),
+        "zstd" => Some("zst"),
+        "bzip2" => Some("bz2"),
+        "identity" => None,
+        other => {
+            return Err(SoarError::Custom(format!(
+                "{} uses an unsupported payload compressor: {other}",
+                file.display()
+            )))
+        }
+    };
+
+    let cpio = match suffix {
+        Some(suffix) => {
+            let compressed = staging.join(format!("payload.cpio.{suffix}"));
+            write_rest(&mut reader, &compressed)?;
+            let out = staging.join("payload");
+            extract_to(&compressed, &out)?;
+            out.join("payload.cpio")
+        }
+        None => {
+            let raw = staging.join("payload.cpio");
+            write_rest(&mut reader, &raw)?;
+            raw
+        }
+    };
+
+    unpack_cpio(&cpio, &staging.join("data"))?;
+    Ok(scripts)
+}
+
+fn write_rest<R: Read>(reader: &mut R, path: &Path) -> SoarResult<()> {
+    let mut out = File::create(path).with_context(|| format!("creating {}", path.display()))?;
+    io::copy(reader, &mut out).with_context(|| format!("writing {}", path.display()))?;
+    Ok(())
+}
+
+fn extract_to(archive: &Path, dest: &Path) -> SoarResult<()> {
+    fs::create_dir_all(dest).with_context(|| format!("creating {}", dest.display()))?;
+    compak::extract_archive(archive, dest)
+        .map_err(|e| SoarError::Custom(format!("failed to extract {}: {e}", archive.display())))
+}
+
+/// An RPM header: an index of tagged entries over a data store.
+struct RpmHeader {
+    /// `(tag, type, offset)` for each index entry.
+    index: Vec<(u32, u32, usize)>,
+    store: Vec<u8>,
+}
+
+impl RpmHeader {
+    fn read<R: Read>(reader: &mut R) -> Result<Self, String> {
+        let mut intro = [0u8; 16];
+        reader
+            .read_exact(&mut intro)
+            .map_err(|e| format!("is truncated: {e}"))?;
+        if intro[..4] != RPM_HEADER_MAGIC {
+            return Err("has a bad magic number".into());
+        }
+        let nindex = be_u32(&intro[8..12]) as usize;
+        let hsize = be_u32(&intro[12..16]) as usize;
+        if hsize > RPM_MAX_STORE || nindex > RPM_MAX_STORE / 16 {
+            return Err("is implausibly large".into());
+        }
+
+        let mut entries = vec![0u8; nindex * 16];
+        reader
+            .read_exact(&mut entries)
+            .map_err(|e| format!("is truncated: {e}"))?;
+        let index = entries
+            .chunks_exact(16)
+            .map(|e| {
+                (
+                    be_u32(&e[..4]),
+                    be_u32(&e[4..8]),
+                    be_u32(&e[8..12]) as usize,
+                )
+            })
+            .collect();
+
+        let mut store = vec![0u8; hsize];
+        reader
+            .read_exact(&mut store)
+            .map_err(|e| format!("is truncated: {e}"))?;
+
+        Ok(Self {
+            index,
+            store,
+        })
+    }
+
+    /// Size of the header as stored, excluding any trailing padding.
+    fn len(&self) -> usize {
+        16 + self.index.len() * 16 + self.store.len()
+    }
+
+    fn has(&self, tag: u32) -> bool {
+        self.index.iter().any(|(t, ..)| *t == tag)
+    }
+
+    fn string(&self, tag: u32) -> Option<String> {
+        let (_, kind, offset) = self.index.iter().find(|(t, ..)| *t == tag)?;
+        if *kind != RPM_TYPE_STRING {
+            return None;
+        }
+        let bytes = self.store.get(*offset..)?;
+        let end = bytes.iter().position(|b| *b == 0)?;
+        Some(String::from_utf8_lossy(&bytes[..end]).into_owned())
+    }
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Unpack a cpio archive in the "newc" format RPM payloads use.
+fn unpack_cpio(archive: &Path, dest: &Path) -> SoarResult<()> {
+    let corrupt =
+        || SoarError::Custom(format!("{} is not a valid cpio archive", archive.display()));
+    let mut reader = BufReader::new(
+        File::open(archive).with_context(|| format!("opening {}", archive.display()))?,
+    );
+    fs::create_dir_all(dest).with_context(|| format!("creating {}", dest.display()))?;
+
+    // Hardlinked files carry their data on the last link only; earlier links
+    // wait here until it arrives.
+    let mut pending_links: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+
+    loop {
+        let mut header = [0u8; 110];
+        reader
+            .read_exact(&mut header)
+            .with_context(|| format!("reading {}", archive.display()))?;
+        if &header[..6] != b"070701" && &header[..6] != b"070702" {
+            return Err(corrupt());
+        }
+        let field = |i: usize| {
+            std::str::from_utf8(&header[6 + i * 8..14 + i * 8])
+                .ok()
+                .and_then(|s| u64::from_str_radix(s, 16).ok())
+                .ok_or_else(corrupt)
+        };
+        let ino = field(0)?;
+        let mode = field(1)? as u32;
+        let nlink = field(4)?;
+        let size = field(6)?;
+        let namesize = field(11)? as usize;
+
+        let mut name = vec![0u8; namesize];
+        reader
+            .read_exact(&mut name)
+            .with_context(|| format!("reading {}", archive.display()))?;
+        skip(&mut reader, pad4(110 + namesize), archive)?;
+        let name = String::from_utf8_lossy(&name)
+            .trim_end_matches('\0')
+            .to_string();
+        if name == "TRAILER!!!" {
+            break;
+        }
+
+        let relative = name.trim_start_matches("./").trim_start_matches('/');
+        let target = if relative.is_empty() || relative == "." {
+            None
+        } else if is_safe_relative(relative) {
+            contained(dest, Path::new(relative))
+        } else {
+            return Err(SoarError::Custom(format!(
+                "{} contains an unsafe path: {name}",
+                archive.display()
+            )));
+        };
+
+        let Some(target) = target else {
+            skip(&mut reader, size as usize + pad4(size as usize), archive)?;
+            continue;
+        };
+        // An earlier entry of the same name may have been a symlink; writing
+        // or chmodding through it would land outside `dest`.
+        prepare_dest(&target)?;
+
+        match mode & 0o170000 {
+            0o040000 => {
+                fs::create_dir_all(&target)
+                    .with_context(|| format!("creating {}", target.display()))?;
+                set_mode(&target, mode | 0o700);
+            }
+            0o100000 if size == 0 && nlink > 1 => {
+                pending_links.entry(ino).or_default().push(target);
+            }
+            0o100000 => {
+                let mut out = File::create(&target)
+                    .with_context(|| format!("creating {}", target.display()))?;
+                let copied = io::copy(&mut (&mut reader).take(size), &mut out)
+                    .with_context(|| format!("writing {}", target.display()))?;
+                if copied != size {
+                    return Err(corrupt());
+                }
+                set_mode(&target, mode);
+                for link in pending_links.remove(&ino).unwrap_or_default() {
+                    prepare_dest(&link)?;
+                    fs::hard_link(&target, &link)
+                        .or_else(|_| fs::copy(&target, &link).map(|_| ()))
+                        .with_context(|| format!("linking {}", link.display()))?;
+                }
+            }
+            0o120000 => {
+                let mut link = vec![0u8; size as usize];
+                reader
+                    .read_exact(&mut link)
+                    .with_context(|| format!("reading {}", archive.display()))?;
+                let link = String::from_utf8_lossy(&link).into_owned();
+                fs::remove_file(&target).ok();
+                symlink(&link, &target)
+                    .with_context(|| format!("creating symlink {}", target.display()))?;
+            }
+            // Device nodes, FIFOs and sockets have no place in a user install.
+            _ => skip(&mut reader, size as usize, archive)?,
+        }
+        skip(&mut reader, pad4(size as usize), archive)?;
+    }
+
+    // Links whose data never arrived are empty files.
+    for links in pending_links.into_values() {
+        for link in links {
+            prepare_dest(&link)?;
+            File::create(&link).with_context(|| format!("creating {}", link.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn pad4(len: usize) -> usize {
+    (4 - len % 4) % 4
+}
+
+fn skip<R: Read>(reader: &mut R, len: usize, archive: &Path) -> SoarResult<()> {
+    let skipped = io::copy(&mut reader.take(len as u64), &mut io::sink())
+        .with_context(|| format!("reading {}", archive.display()))?;
+    if skipped != len as u64 {
+        return Err(SoarError::Custom(format!(
+            "{} is truncated",
+            archive.display()
+        )));
+    }
+    Ok(())
+}
+
+fn set_mode(path: &Path, mode: u32) {
+    fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o777)).ok();
+}
+
+/// `root.join(relative)`, unless an existing parent inside `root` is a symlink
+/// that could carry the write elsewhere.
+fn contained(root: &Path, relative: &Path) -> Option<PathBuf> {
+    let mut current = root.to_path_buf();
+    let mut components = relative.components().peekable();
+    while let Some(component) = components.next() {
+        current.push(component);
+        if components.peek().is_some()
+            && fs::symlink_metadata(&current).is_ok_and(|m| m.file_type().is_symlink())
+        {
+            return None;
+        }
+    }
+    Some(current)
+}
+
+/// Where a path inside a distribution package lives in a soar install.
+///
+/// `usr/` is dropped and `sbin` folds into `bin`, so `usr/bin/foo`,
+/// `usr/sbin/foo` and `sbin/foo` all become `bin/foo`.
+pub fn relocated(path: &Path) -> PathBuf {
+    let rest = path.strip_prefix("usr").unwrap_or(path);
+    match rest.strip_prefix("sbin") {
+        Ok(tail) => Path::new("bin").join(tail),
+        Err(_) => rest.to_path_buf(),
+    }
+}
+
+/// Move the payload tree at `from` into `to`, relocating every entry.
+///
+/// When two entries land on the same path the first one wins. Symlinks that
+/// point inside the package are rewritten to stay correct after relocation;
+/// ones pointing elsewhere on the system are kept as they are.
+pub fn relocate(from: &Path, to: &Path) -> SoarResult<()> {
+    let mut entries = Vec::new();
+    collect_entries(from, Path::new(""), &mut entries)?;
+    // Every path in the payload, directories included, recorded before the
+    // moves below empty the tree out.
+    let known: HashSet<PathBuf> = entries
+        .iter()
+        .flat_map(|(path, _)| path.ancestors().map(Path::to_path_buf))
+        .collect();
+
+    for (relative, is_link) in entries {
+        // Package manager metadata at the root (.PKGINFO, .MTREE, ...).
+        if relative.components().count() == 1 && relative.to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        let new_relative = relocated(&relative);
+        let Some(dest) = contained(to, &new_relative) else {
+            continue;
+        };
+        if fs::symlink_metadata(&dest).is_ok() {
+            debug!(path = %relative.display(), "skipping entry shadowed by relocation");
+            continue;
+        }
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).with_context(|| format!("creating {}", parent.display()))?;
+        }
+
+        let source = from.join(&relative);
+        if is_link {
+            let target = fs::read_link(&source)
+                .with_context(|| format!("reading symlink {}", source.display()))?;
+            let target = rewrite_link(&relative, &new_relative, &target, &known);
+            symlink(&target, &dest)
+                .with_context(|| format!("creating symlink {}", dest.display()))?;
+        } else {
+            fs::rename(&source, &dest)
+                .with_context(|| format!("moving {} to {}", source.display(), dest.display()))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Collect every file and symlink below `root/relative`; empty directories are
+/// dropped.
+fn collect_entries(
+    root: &Path,
+    relative: &Path,
+    entries: &mut Vec<(PathBuf, bool)>,
+) -> SoarResult<()> {
+    let dir = root.join(relative);
+    let mut children = fs::read_dir(&dir)
+        .with_context(|| format!("reading {}", dir.display()))?
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("reading {}", dir.display()))?;
+    children.sort_by_key(|e| e.file_name());
+
+    for child in children {
+        let path = relative.join(child.file_name());
+        let file_type = child
+            .file_type()
+            .with_context(|| format!("reading {}", child.path().display()))?;
+        if file_type.is_dir() {
+            // Directories shipped without the write bit could not be drained.
+            let mode = child
+                .metadata()
+                .map(|m| m.permissions().mode())
+                .unwrap_or(0);
+            set_mode(&child.path(), mode | 0o700);
+            collect_entries(root, &path, entries)?;
+        } else {
+            entries.push((path, file_type.is_symlink()));
+        }
+    }
+    Ok(())
+}
+
+/// Re-aim a symlink at its target's relocated path when the target is part of
+/// the package.
+fn rewrite_link(
+    original: &Path,
+    relocated_link: &Path,
+    target: &Path,
+    known: &HashSet<PathBuf>,
+) -> PathBuf {
+    let in_package = if target.is_absolute() {
+        normalize(target.strip_prefix("/").unwrap_or(target))
+    } else {
+        normalize(&original.parent().unwrap_or(Path::new("")).join(target))
+    };
+    let Some(in_package) = in_package else {
+        return target.to_path_buf();
+    };
+    if in_package.as_os_str().is_empty() || !known.contains(&in_package) {
+        return target.to_path_buf();
+    }
+
+    let from_dir = relocated_link.parent().unwrap_or(Path::new(""));
+    relative_path(from_dir, &relocated(&in_package))
+}
+
+/// Resolve `.` and `..` lexically; `None` if the path climbs out of the root.
+fn normalize(path: &Path) -> Option<PathBuf> {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => out.push(name),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !out.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(out)
+}
+
+/// The relative path from directory `from` to `to`, both below the same root.
+fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from: Vec<_> = from.components().collect();
+    let to: Vec<_> = to.components().collect();
+    let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+
+    let mut out = PathBuf::new();
+    for _ in common..from.len() {
+        out.push("..");
+    }
+    for component in &to[common..] {
+        out.push(component);
+    }
+    if out.as_os_str().is_empty() {
+        out.push(".");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn newc_entry(out: &mut Vec<u8>, ino: u64, mode: u32, nlink: u64, name: &str, data: &[u8]) {
+        let name = format!("{name}\0");
+        let fields = [
+            ino,
+            mode as u64,
+            0,
+            0,
+            nlink,
+            0,
+            data.len() as u64,
+            0,
+            0,
+            0,
+            0,
+            name.len() as u64,
+            0,
+        ];
+        out.extend_from_slice(b"070701");
+        for field in fields {
+            out.extend_from_slice(format!("{field:08x}").as_bytes());
+        }
+        out.extend_from_slice(name.as_bytes());
+        out.resize(out.len() + pad4(110 + name.len()), 0);
+        out.extend_from_slice(data);
+        out.resize(out.len() + pad4(data.len()), 0);
+    }
+
+    #[test]
+    fn test_relocated_flattens_usr_and_sbin() {
+        assert_eq!(relocated(Path::new("usr/bin/foo")), Path::new("bin/foo"));
+        assert_eq!(relocated(Path::new("usr/sbin/foo")), Path::new("bin/foo"));
+        assert_eq!(relocated(Path::new("sbin/foo")), Path::new("bin/foo"));
+        assert_eq!(
+            relocated(Path::new("usr/share/man/man1/foo.1.gz")),
+            Path::new("share/man/man1/foo.1.gz")
+        );
+        assert_eq!(
+            relocated(Path::new("opt/foo/foo")),
+            Path::new("opt/foo/foo")
+        );
+        assert_eq!(relocated(Path::new("usr/sbinx")), Path::new("sbinx"));
+    }
+
+    #[test]
+    fn test_relocate_rewrites_links_into_package() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("payload");
+        let to = dir.path().join("out");
+        fs::create_dir_all(from.join("opt/foo")).unwrap();
+        fs::create_dir_all(from.join("usr/bin")).unwrap();
+        fs::create_dir_all(from.join("usr/share/applications")).unwrap();
+        fs::create_dir_all(&to).unwrap();
+        fs::write(from.join("opt/foo/foo"), "bin").unwrap();
+        fs::write(from.join("usr/share/applications/foo.desktop"), "x").unwrap();
+        fs::write(from.join(".PKGINFO"), "pkgname = foo").unwrap();
+        symlink("/opt/foo/foo", from.join("usr/bin/foo")).unwrap();
+        symlink("../../opt/foo/foo", from.join("usr/bin/foo-rel")).unwrap();
+        symlink("/etc/alternatives/x", from.join("usr/bin/x")).unwrap();
+
+        relocate(&from, &to).unwrap();
+
+        assert_eq!(
+            fs::read_link(to.join("bin/foo")).unwrap(),
+            Path::new("../opt/foo/foo")
+        );
+        assert_eq!(fs::read_to_string(to.join("bin/foo")).unwrap(), "bin");
+        assert_eq!(fs::read_to_string(to.join("bin/foo-rel")).unwrap(), "bin");
+        assert_eq!(
+            fs::read_link(to.join("bin/x")).unwrap(),
+            Path::new("/etc/alternatives/x")
+        );
+        assert!(to.join("share/applications/foo.desktop").is_file());
+        assert!(!to.join(".PKGINFO").exists());
+    }
+
+    #[test]
+    fn test_relative_path() {
+        assert_eq!(
+            relative_path(Path::new("bin"), Path::new("opt/foo/foo")),
+            Path::new("../opt/foo/foo")
+        );
+        assert_eq!(
+            relative_path(Path::new("bin"), Path::new("bin/foo")),
+            Path::new("foo")
+        );
+        assert_eq!(
+            relative_path(Path::new("a/b"), Path::new("a")),
+            Path::new("..")
+        );
+    }
+
+    #[test]
+    fn test_unpack_cpio_newc() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut archive = Vec::new();
+        newc_entry(&mut archive, 1, 0o040755, 2, "./usr/bin", b"");
+        newc_entry(
+            &mut archive,
+            2,
+            0o104755,
+            1,
+            "./usr/bin/foo",
+            b"#!/bin/sh\n",
+        );
+        newc_entry(&mut archive, 3, 0o100644, 2, "./usr/bin/bar", b"");
+        newc_entry(&mut archive, 3, 0o100644, 2, "./usr/bin/baz", b"shared");
+        newc_entry(&mut archive, 4, 0o120777, 1, "./usr/bin/qux", b"foo");
+        newc_entry(&mut archive, 0, 0, 1, "TRAILER!!!", b"");
+        let path = dir.path().join("payload.cpio");
+        fs::write(&path, archive).unwrap();
+
+        let dest = dir.path().join("data");
+        unpack_cpio(&path, &dest).unwrap();
+
+        let foo = dest.join("usr/bin/foo");
+        assert_eq!(fs::read_to_string(&foo).unwrap(), "#!/bin/sh\n");
+        assert_eq!(
+            fs::metadata(&foo).unwrap().permissions().mode() & 0o7777,
+            0o755
+        );
+        assert_eq!(
+            fs::read_to_string(dest.join("usr/bin/bar")).unwrap(),
+            "shared"
+        );
+        assert_eq!(
+            fs::read_link(dest.join("usr/bin/qux")).unwrap(),
+            Path::new("foo")
+        );
+    }
+
+    #[test]
+    fn test_unpack_cpio_rejects_escaping_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut archive = Vec::new();
+        newc_entry(&mut archive, 1, 0o100644, 1, "../evil", b"x");
+        newc_entry(&mut archive, 0, 0, 1, "TRAILER!!!", b"");
+        let path = dir.path().join("payload.cpio");
+        fs::write(&path, archive).unwrap();
+
+        assert!(unpack_cpio(&path, &dir.path().join("data")).is_err());
+        assert!(!dir.path().join("evil").exists());
+    }
+
+    #[test]
+    fn test_unpack_cpio_does_not_write_through_planted_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let outside = dir.path().join("outside");
+        fs::create_dir(&outside).unwrap();
+        fs::write(outside.join("victim"), "original").unwrap();
+
+        let victim = outside.join("victim");
+        let mut archive = Vec::new();
+        newc_entry(&mut archive, 1, 0o120777, 1, "./file", victim.to_str().unwrap().as_bytes());
+        newc_entry(&mut archive, 2, 0o100755, 1, "./file", b"payload");
+        newc_entry(&mut archive, 3, 0o120777, 1, "./dir", outside.to_str().unwrap().as_bytes());
+        newc_entry(&mut archive, 4, 0o040777, 2, "./dir", b"");
+        newc_entry(&mut archive, 0, 0, 1, "TRAILER!!!", b"");
+        let path = dir.path().join("payload.cpio");
+        fs::write(&path, archive).unwrap();
+
+        let dest = dir.path().join("data");
+        unpack_cpio(&path, &dest).unwrap();
+
+        assert_eq!(fs::read_to_string(&victim).unwrap(), "original");
+        assert_eq!(fs::read_to_string(dest.join("file")).unwrap(), "payload");
+        assert!(!dest.join("dir").is_symlink());
+        assert_ne!(
+            fs::metadata(&outside).unwrap().permissions().mode() & 0o777,
+            0o777
+        );
+    }
+
+    #[test]
+    fn test_read_ar_members() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut archive = AR_MAGIC.to_vec();
+        for (name, data) in [
+            ("debian-binary", &b"2.0\n"[..]),
+            ("control.tar.gz", &b"ctl"[..]),
+            ("data.tar.xz", &b"payload"[..]),
+        ] {
+            archive.extend_from_slice(
+                format!(
+                    "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+                    format!("{name}/"),
+                    0,
+                    0,
+                    0,
+                    100644,
+                    data.len()
+                )
+                .as_bytes(),
+            );
+            archive.extend_from_slice(data);
+            if data.len() % 2 == 1 {
+                archive.push(b'\n');
+            }
+        }
+        let deb = dir.path().join("foo.deb");
+        fs::write(&deb, archive).unwrap();
+        assert_eq!(detect(&deb), Some(NativeFormat::Deb));
+
+        let out = dir.path().join("out");
+        fs::create_dir_all(&out).unwrap();
+        let members = read_ar_members(&deb, &out, |n| n.contains(".tar")).unwrap();
+
+        assert_eq!(members, ["control.tar.gz", "data.tar.xz"]);
+        assert_eq!(fs::read(out.join("control.tar.gz")).unwrap(), b"ctl");
+        assert_eq!(fs::read(out.join("data.tar.xz")).unwrap(), b"payload");
+        assert!(!out.join("debian-binary").exists());
+    }
+
+    #[test]
+    fn test_rpm_header_tags() {
+        let mut bytes = RPM_HEADER_MAGIC.to_vec();
+        bytes.extend_from_slice(&[0; 4]);
+        bytes.extend_from_slice(&2u32.to_be_bytes());
+        let store = b"\0\0\0\0xz\0";
+        bytes.extend_from_slice(&(store.len() as u32).to_be_bytes());
+        for (tag, kind, offset) in [(1024u32, RPM_TYPE_STRING, 0u32), (1125, RPM_TYPE_STRING, 4)] {
+            bytes.extend_from_slice(&tag.to_be_bytes());
+            bytes.extend_from_slice(&kind.to_be_bytes());
+            bytes.extend_from_slice(&offset.to_be_bytes());
+            bytes.extend_from_slice(&1u32.to_be_bytes());
+        }
+        bytes.extend_from_slice(store);
+
+        let header = RpmHeader::read(&mut bytes.as_slice()).unwrap();
+
+        assert_eq!(header.len(), bytes.len());
+        assert!(header.has(1024));
+        assert!(!header.has(1023));
+        assert_eq!(
+            header.string(RPM_TAG_PAYLOAD_COMPRESSOR).as_deref(),
+            Some("xz")
+        );
+    }
+}
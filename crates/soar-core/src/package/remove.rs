@@ -9,8 +9,20 @@ use soar_config::{
     config::Config,
     packages::{PackageHooks, SandboxConfig},
 };
-use soar_db::{models::types::PackageProvide, repository::core::CoreRepository};
-use soar_utils::{error::FileSystemResult, fs::walk_dir};
+use soar_db::{
+    models::types::PackageProvide,
+    repository::{alternatives::AlternativeRepository, core::CoreRepository},
+};
+use soar_package::{
+    formats::{common::refresh_icon_cache, flatpak},
+    mime::{forget_defaults, refresh_desktop_database, refresh_mime_database},
+};
+use soar_utils::{
+    cancel::CancellationToken,
+    error::FileSystemResult,
+    fs::walk_dir,
+    path::{metainfo_dir, mime_dir, mimeapps_list},
+};
 use tracing::{debug, trace, warn};
 
 use super::hooks::{run_hook, HookEnv};
@@ -105,8 +117,10 @@ fn format_size(bytes: u64) -> String {
 }
 
 use crate::{
+    alternatives,
     database::{connection::DieselDatabase, models::InstalledPackage},
     error::ErrorContext,
+    package::versions,
     SoarResult,
 };
 
@@ -116,6 +130,7 @@ pub struct PackageRemover {
     config: Config,
     hooks: Option<PackageHooks>,
     sandbox: Option<SandboxConfig>,
+    cancel: Option<CancellationToken>,
 }
 
 /// Give every directory under `path` the owner write bit.
@@ -153,6 +168,7 @@ impl PackageRemover {
             config,
             hooks: None,
             sandbox: None,
+            cancel: None,
         }
     }
 
@@ -168,6 +184,13 @@ impl PackageRemover {
         self
     }
 
+    /// Stop at the pre_remove hook once `cancel` is cancelled. Nothing has
+    /// been deleted by then, and once deleting starts it is finished.
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
     /// Run a hook command with environment variables set.
     fn run_hook(&self, hook_name: &str, command: &str) -> SoarResult<()> {
         let install_dir = PathBuf::from(&self.package.installed_path);
@@ -178,7 +201,13 @@ impl PackageRemover {
             pkg_version: &self.package.version,
         };
 
-        run_hook(hook_name, command, &env, self.sandbox.as_ref())
+        run_hook(
+            hook_name,
+            command,
+            &env,
+            self.sandbox.as_ref(),
+            self.cancel.as_ref(),
+        )
     }
 
     /// Run pre_remove hook if configured.
@@ -233,12 +262,17 @@ impl PackageRemover {
                 remove_links_into(&dir, &installed_path, &mut removed_symlinks);
             }
 
+            let mut desktop_ids = Vec::new();
             let mut remove_action = |path: &Path| -> FileSystemResult<()> {
                 if path.extension() == Some(&OsString::from("desktop")) {
                     if let Ok(real_path) = fs::read_link(path) {
-                        if real_path.parent() == Some(&installed_path) {
+                        if real_path.starts_with(&installed_path) {
                             trace!("removing desktop file: {}", path.display());
-                            let _ = fs::remove_file(path);
+                            if fs::remove_file(path).is_ok() {
+                                if let Some(name) = path.file_name() {
+                                    desktop_ids.push(name.to_string_lossy().into_owned());
+                                }
+                            }
                         }
                     }
                 }
@@ -250,10 +284,38 @@ impl PackageRemover {
             if desktop_path.is_dir() {
                 walk_dir(&desktop_path, &mut remove_action)?;
             }
+            if !desktop_ids.is_empty() {
+                let list = mimeapps_list(self.config.is_system());
+                if let Err(err) = forget_defaults(&list, &desktop_ids) {
+                    warn!(
+                        "failed to drop default applications for {}: {err}",
+                        self.package.pkg_name
+                    );
+                }
+                refresh_desktop_database(&desktop_path);
+            }
+
+            let system_mode = self.config.is_system();
+            remove_links_into(
+                &metainfo_dir(system_mode),
+                &installed_path,
+                &mut removed_symlinks,
+            );
+            let before = removed_symlinks.len();
+            remove_links_into(
+                &mime_dir(system_mode).join("packages"),
+                &installed_path,
+                &mut removed_symlinks,
+            );
+            if removed_symlinks.len() != before {
+                refresh_mime_database(system_mode);
+            }
 
             let mut remove_action = |path: &Path| -> FileSystemResult<()> {
+                // Icons of every size sit in the package's own theme tree, so
+                // anything pointing under it is ours.
                 if let Ok(real_path) = fs::read_link(path) {
-                    if real_path.parent() == Some(&installed_path) {
+                    if real_path.starts_with(&installed_path) {
                         trace!("removing icon symlink: {}", path.display());
                         let _ = fs::remove_file(path);
                     }
@@ -263,9 +325,19 @@ impl PackageRemover {
             let icons_path = self.config.get_icons_path();
             if icons_path.is_dir() {
                 walk_dir(icons_path, &mut remove_action)?;
+                refresh_icon_cache(self.config.is_system());
             }
         }
 
+        // An app installed from a Flatpak bundle lives in flatpak's own
+        // store; the package directory only holds its launcher.
+        if let Err(err) = flatpak::uninstall_recorded(
+            Path::new(&self.package.installed_path),
+            self.config.is_system(),
+        ) {
+            warn!("{err}");
+        }
+
         // Calculate directory size before removal for logging
         let dir_size = fs::read_dir(&self.package.installed_path)
             .ok()
@@ -306,11 +378,26 @@ impl PackageRemover {
 
         trace!("removing package from database");
         let package_id = self.package.id as i32;
+        let provided: Vec<String> = self
+            .db
+            .with_conn(|conn| AlternativeRepository::for_package(conn, package_id))?
+            .into_iter()
+            .map(|alternative| alternative.name)
+            .collect();
         self.db.transaction(|conn| {
+            AlternativeRepository::delete_for_package(conn, package_id)?;
             CoreRepository::delete_portable(conn, package_id)?;
             CoreRepository::delete(conn, package_id)
         })?;
 
+        // The commands it held go to the packages that also provide them, and
+        // the versions left behind are selected without it.
+        let bin_path = self.config.get_bin_path()?;
+        if !provided.is_empty() {
+            alternatives::restore(&self.db, &bin_path, &provided)?;
+        }
+        versions::refresh(&self.db, &bin_path, &self.package.pkg_name)?;
+
         // Log removed symlinks at debug level
         for symlink in &removed_symlinks {
             debug!("removed symlink: {}", symlink.display());
@@ -1,10 +1,12 @@
 use std::{fs, path::Path};
 
-use soar_db::repository::core::CoreRepository;
+use soar_config::config::get_config;
+use soar_db::repository::{alternatives::AlternativeRepository, core::CoreRepository};
 
 use crate::{
     database::{connection::DieselDatabase, models::Package},
     error::ErrorContext,
+    package::versions,
     SoarResult,
 };
 
@@ -13,6 +15,7 @@ use crate::{
 /// This function finds all installed versions of the package (by pkg_id, pkg_name, repo_name)
 /// that are older than the current version and removes them from disk and database.
 /// If `force` is true, removes pinned packages too. Otherwise only unpinned packages.
+/// The version shims of the versions left are written again.
 pub fn remove_old_versions(package: &Package, db: &DieselDatabase, force: bool) -> SoarResult<()> {
     let Package {
         pkg_id,
@@ -54,6 +57,16 @@ pub fn remove_old_versions(package: &Package, db: &DieselDatabase, force: bool)
             force,
         )
     })?;
+    // The new version registered its own commands when it was installed.
+    db.with_conn(|conn| {
+        for (id, _) in &old_packages {
+            AlternativeRepository::delete_for_package(conn, *id)?;
+        }
+        Ok(())
+    })?;
+    if !old_packages.is_empty() {
+        versions::refresh(db, &get_config().get_bin_path()?, pkg_name)?;
+    }
 
     Ok(())
 }
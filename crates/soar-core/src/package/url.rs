@@ -334,6 +334,18 @@ pub(crate) fn detect_pkg_type(filename: &str) -> Option<String> {
         Some("runimage".to_string())
     } else if lower.ends_with(".nixappimage") {
         Some("nixappimage".to_string())
+    } else if lower.ends_with(".deb") {
+        Some("deb".to_string())
+    } else if lower.ends_with(".rpm") {
+        Some("rpm".to_string())
+    } else if lower.ends_with(".flatpak") {
+        Some("flatpak".to_string())
+    } else if lower.ends_with(".jar") {
+        Some("jar".to_string())
+    } else if lower.ends_with(".pyz") {
+        Some("zipapp".to_string())
+    } else if lower.ends_with(".pkg.tar.zst") || lower.ends_with(".pkg.tar.xz") {
+        Some("pacman".to_string())
     } else if lower.ends_with(".tar.gz")
         || lower.ends_with(".tgz")
         || lower.ends_with(".tar.xz")
@@ -471,6 +483,9 @@ fn remove_extensions(filename: &str) -> String {
     let lower = filename.to_lowercase();
 
     let extensions = [
+        ".pkg.tar.zst",
+        ".pkg.tar.xz",
+        ".tar.zst",
         ".tar.gz",
         ".tar.xz",
         ".tar.bz2",
@@ -480,6 +495,11 @@ fn remove_extensions(filename: &str) -> String {
         ".nixappimage",
         ".tgz",
         ".zip",
+        ".deb",
+        ".rpm",
+        ".flatpak",
+        ".jar",
+        ".pyz",
         ".exe",
         ".bin",
     ];
@@ -574,6 +594,24 @@ mod tests {
         );
         assert_eq!(detect_pkg_type("app.tar.gz"), Some("archive".to_string()));
         assert_eq!(detect_pkg_type("app"), None);
+        assert_eq!(
+            detect_pkg_type("app_1.0_amd64.deb"),
+            Some("deb".to_string())
+        );
+        assert_eq!(
+            detect_pkg_type("app-1.0-1.x86_64.rpm"),
+            Some("rpm".to_string())
+        );
+        assert_eq!(
+            detect_pkg_type("app-1.0-1-x86_64.pkg.tar.zst"),
+            Some("pacman".to_string())
+        );
+        assert_eq!(
+            detect_pkg_type("org.example.Foo.flatpak"),
+            Some("flatpak".to_string())
+        );
+        assert_eq!(detect_pkg_type("tool-2.1.jar"), Some("jar".to_string()));
+        assert_eq!(detect_pkg_type("tool.pyz"), Some("zipapp".to_string()));
     }
 
     #[test]
@@ -597,6 +635,17 @@ mod tests {
         assert_eq!(ver, "1.0");
     }
 
+    #[test]
+    fn distribution_package_suffixes_are_not_part_of_the_name() {
+        let (name, ver) = parse_filename("ripgrep_14.1.0-1_amd64.deb");
+        assert_eq!(name, "ripgrep");
+        assert_eq!(ver, "14.1.0");
+
+        let (name, ver) = parse_filename("ripgrep-14.1.0-1-x86_64.pkg.tar.zst");
+        assert_eq!(name, "ripgrep");
+        assert_eq!(ver, "14.1.0");
+    }
+
     #[test]
     fn test_parse_various_filenames() {
         // Standard pattern
@@ -0,0 +1,224 @@
+//! Versions of a package installed side by side.
+//!
+//! Only one version of a package is linked, the default `soar use` chooses.
+//! When others are installed too, each command the default links goes
+//! through a shim in its `.versions/` directory, which runs the version the
+//! nearest `.soar-version` or `.tool-versions` selects instead. The shims are
+//! written from what every version registered as an alternative, so they are
+//! rebuilt whenever a version comes or goes.
+
+use std::{
+    collections::HashSet,
+    fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+use soar_db::repository::{alternatives::AlternativeRepository, core::CoreRepository};
+use soar_utils::entrypoint;
+use tracing::{debug, info};
+
+use crate::{
+    alternatives::{self, Providers},
+    database::{connection::DieselDatabase, models::InstalledPackage},
+    error::ErrorContext,
+    SoarResult,
+};
+
+/// Write the version shims of `pkg_name` again for the versions installed
+/// now, and relink its commands to match.
+pub fn refresh(db: &DieselDatabase, bin_dir: &Path, pkg_name: &str) -> SoarResult<()> {
+    let installs: Vec<InstalledPackage> = db
+        .with_conn(|conn| {
+            CoreRepository::list_filtered(
+                conn,
+                None,
+                Some(pkg_name),
+                None,
+                None,
+                Some(true),
+                None,
+                None,
+                None,
+            )
+        })?
+        .into_iter()
+        .map(Into::into)
+        .collect();
+
+    // Every version starts from what it runs without a shim.
+    let mut commands = Vec::with_capacity(installs.len());
+    let mut names = HashSet::new();
+    for install in &installs {
+        let install_dir = Path::new(&install.installed_path);
+        let shims = install_dir.join(".versions");
+        let mut targets = Vec::new();
+        for alternative in
+            db.with_conn(|conn| AlternativeRepository::for_package(conn, install.id as i32))?
+        {
+            let target = unshimmed(&shims, PathBuf::from(&alternative.target));
+            if target != Path::new(&alternative.target) {
+                let target = target.to_string_lossy();
+                db.with_conn(|conn| {
+                    AlternativeRepository::set_target(
+                        conn,
+                        &alternative.name,
+                        install.id as i32,
+                        &target,
+                    )
+                })?;
+            }
+            names.insert(alternative.name.clone());
+            targets.push((alternative.name, target));
+        }
+        if shims.exists() {
+            fs::remove_dir_all(&shims)
+                .with_context(|| format!("removing directory {}", shims.display()))?;
+        }
+        commands.push(targets);
+    }
+
+    let default = installs.iter().position(|install| !install.unlinked);
+    if let Some(default) = default {
+        let install = &installs[default];
+        let shims = Path::new(&install.installed_path).join(".versions");
+        for (name, target) in &commands[default] {
+            let mut selectable: Vec<(String, PathBuf)> = Vec::new();
+            // Another family sharing the name is another project, whose
+            // versions mean nothing here.
+            for (other, targets) in installs.iter().zip(&commands) {
+                if other.pkg_family != install.pkg_family
+                    || selectable
+                        .iter()
+                        .any(|(version, _)| *version == other.version)
+                {
+                    continue;
+                }
+                if let Some((_, target)) = targets.iter().find(|(n, _)| n == name) {
+                    selectable.push((other.version.clone(), target.clone()));
+                }
+            }
+            if selectable.len() < 2 {
+                continue;
+            }
+
+            fs::create_dir_all(&shims)
+                .with_context(|| format!("creating directory {}", shims.display()))?;
+            let shim = shims.join(name);
+            fs::write(
+                &shim,
+                entrypoint::version_shim(pkg_name, target, &selectable),
+            )
+            .with_context(|| format!("writing shim {}", shim.display()))?;
+            fs::set_permissions(&shim, fs::Permissions::from_mode(0o755))
+                .with_context(|| format!("setting permissions on {}", shim.display()))?;
+            let shim = shim.to_string_lossy();
+            db.with_conn(|conn| {
+                AlternativeRepository::set_target(conn, name, install.id as i32, &shim)
+            })?;
+            debug!(
+                name = name,
+                versions = selectable.len(),
+                "wrote version shim"
+            );
+        }
+    } else if let Some(latest) = installs.last() {
+        info!(
+            "no version of {pkg_name} is linked; run `soar use {pkg_name}@{}` to choose one",
+            latest.version
+        );
+    }
+
+    for name in names {
+        alternatives::activate(bin_dir, &Providers::load(db, &name)?)?;
+    }
+    Ok(())
+}
+
+/// What `target` runs when it is a shim in `shims`.
+fn unshimmed(shims: &Path, target: PathBuf) -> PathBuf {
+    if target.parent() != Some(shims) {
+        return target;
+    }
+    fs::read_to_string(&target)
+        .ok()
+        .and_then(|script| entrypoint::launched_file(&script))
+        .unwrap_or(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use soar_db::models::core::{NewAlternative, NewPackage};
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_refresh_shims_the_default_while_other_versions_remain() {
+        let dir = tempdir().unwrap();
+        let bin_dir = dir.path().join("bin");
+        fs::create_dir_all(&bin_dir).unwrap();
+        let db = DieselDatabase::open_core(":memory:").unwrap();
+
+        let install = |version: &str| {
+            let install_dir = dir.path().join(format!("tool-{version}"));
+            fs::create_dir_all(&install_dir).unwrap();
+            let target = install_dir.join("tool");
+            fs::write(&target, "").unwrap();
+            let installed_path = install_dir.to_string_lossy().into_owned();
+            let target = target.to_string_lossy().into_owned();
+            db.with_conn(|conn| {
+                let id = CoreRepository::insert(
+                    conn,
+                    &NewPackage {
+                        repo_name: "r",
+                        pkg_name: "tool",
+                        version,
+                        installed_path: &installed_path,
+                        installed_date: "now",
+                        profile: "default",
+                        is_installed: true,
+                        ..Default::default()
+                    },
+                )?;
+                AlternativeRepository::register(
+                    conn,
+                    &NewAlternative {
+                        name: "tool",
+                        package_id: id,
+                        target: &target,
+                        priority: 0,
+                    },
+                )?;
+                Ok(id)
+            })
+            .unwrap()
+        };
+        let old = install("1.0");
+        db.with_conn(|conn| CoreRepository::set_unlinked(conn, old, true))
+            .unwrap();
+        install("2.0");
+
+        refresh(&db, &bin_dir, "tool").unwrap();
+        let shim = dir.path().join("tool-2.0/.versions/tool");
+        assert_eq!(fs::read_link(bin_dir.join("tool")).unwrap(), shim);
+        let script = fs::read_to_string(&shim).unwrap();
+        assert!(script.contains(&*dir.path().join("tool-1.0/tool").to_string_lossy()));
+        assert_eq!(
+            entrypoint::launched_file(&script),
+            Some(dir.path().join("tool-2.0/tool"))
+        );
+
+        db.with_conn(|conn| {
+            AlternativeRepository::delete_for_package(conn, old)?;
+            CoreRepository::delete(conn, old)
+        })
+        .unwrap();
+        refresh(&db, &bin_dir, "tool").unwrap();
+        assert_eq!(
+            fs::read_link(bin_dir.join("tool")).unwrap(),
+            dir.path().join("tool-2.0/tool")
+        );
+        assert!(!shim.exists());
+    }
+}
@@ -0,0 +1,1097 @@
+//! Sandbox module for restricting hook, build and package command execution using Landlock.
+//!
+//! Landlock is a Linux security module (available since kernel 5.13) that allows
+//! unprivileged processes to restrict their own filesystem access rights. The
+//! [`SandboxBackend::Namespace`] backend additionally runs hooks and builds in
+//! namespaces of their own; see [`namespace`]. On top of either, a seccomp
+//! filter and resource limits stop commands that misbehave; see [`seccomp`].
+
+mod namespace;
+mod seccomp;
+
+use std::{
+    fmt, fs,
+    os::unix::{
+        fs::FileTypeExt as _,
+        process::{CommandExt as _, ExitStatusExt as _},
+    },
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Output, Stdio},
+};
+
+use landlock::{
+    Access as _, AccessFs, AccessNet, BitFlags, NetPort, PathBeneath, PathFd, Ruleset,
+    RulesetAttr as _, RulesetCreatedAttr as _, ABI,
+};
+use nix::sys::{
+    resource::{getrlimit, setrlimit, Resource},
+    signal::Signal,
+};
+use serde::{Deserialize, Serialize};
+use soar_config::{
+    config::{get_config, CONFIG_PATH},
+    packages::{
+        RuntimeSandbox, SandboxBackend, SandboxConfig as PackageSandbox, PACKAGES_CONFIG_PATH,
+    },
+};
+use soar_utils::{
+    cancel::CancellationToken,
+    path::{resolve_path, xdg_cache_home, xdg_config_home, xdg_data_home},
+};
+use tracing::{debug, warn};
+
+use crate::{
+    audit::{self, Captured},
+    constants::RUNTIME_SANDBOX_FILE,
+    error::{ErrorContext, SoarError},
+    SoarResult,
+};
+
+/// Network access configuration.
+#[derive(Clone, Debug, Default)]
+pub struct NetworkConfig {
+    /// Allow all outbound network connections.
+    pub allow_all: bool,
+    /// Specific TCP ports to allow for binding.
+    pub allow_bind_tcp: Vec<u16>,
+    /// Specific TCP ports to allow for connecting.
+    pub allow_connect_tcp: Vec<u16>,
+}
+
+impl NetworkConfig {
+    /// Allow all network access.
+    pub fn allow_all() -> Self {
+        Self {
+            allow_all: true,
+            ..Default::default()
+        }
+    }
+
+    /// Allow connecting to common HTTPS/HTTP ports.
+    pub fn allow_https() -> Self {
+        Self {
+            allow_connect_tcp: vec![80, 443],
+            ..Default::default()
+        }
+    }
+}
+
+/// Resource limits for a sandboxed command. `None` leaves a limit as soar's
+/// own.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ResourceLimits {
+    /// CPU time per process, in seconds.
+    pub cpu_time: Option<u64>,
+    /// Address space per process, in bytes.
+    pub address_space: Option<u64>,
+    /// Largest file a process may write, in bytes.
+    pub file_size: Option<u64>,
+    /// Processes the user may run at once. Outside the namespace backend this
+    /// counts every process of the user, not just the command's.
+    pub processes: Option<u64>,
+}
+
+impl ResourceLimits {
+    /// Limits for hooks and builds: an hour of CPU time per process, and
+    /// 4 GiB per file written.
+    pub fn standard() -> Self {
+        Self {
+            cpu_time: Some(60 * 60),
+            file_size: Some(4 << 30),
+            ..Default::default()
+        }
+    }
+
+    /// Apply the limits to the current process.
+    ///
+    /// Meant for a `pre_exec` hook. A limit is never raised past the hard
+    /// limit soar itself runs under.
+    fn apply(&self) -> std::io::Result<()> {
+        let limit = |resource: Resource, soft: u64, hard: u64| -> std::io::Result<()> {
+            let (_, max) = getrlimit(resource)?;
+            setrlimit(resource, soft.min(max), hard.min(max))?;
+            Ok(())
+        };
+        if let Some(secs) = self.cpu_time {
+            // The gap gets the command `SIGXCPU` rather than a bare `SIGKILL`.
+            limit(Resource::RLIMIT_CPU, secs, secs.saturating_add(1))?;
+        }
+        if let Some(bytes) = self.address_space {
+            limit(Resource::RLIMIT_AS, bytes, bytes)?;
+        }
+        if let Some(bytes) = self.file_size {
+            limit(Resource::RLIMIT_FSIZE, bytes, bytes)?;
+        }
+        if let Some(count) = self.processes {
+            limit(Resource::RLIMIT_NPROC, count, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// A sandbox limit a command was stopped for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// It made a system call the seccomp filter denies.
+    Syscall,
+    /// It used up its CPU time.
+    CpuTime,
+    /// It wrote past the file size limit.
+    FileSize,
+}
+
+impl Violation {
+    /// The limit a sandboxed command that exited with `status` ran into, if
+    /// any.
+    ///
+    /// Only a command killed by the signal counts: an exit code above 128
+    /// is the command's own to choose.
+    pub fn of(status: &ExitStatus) -> Option<Self> {
+        match Signal::try_from(status.signal()?).ok()? {
+            Signal::SIGSYS => Some(Self::Syscall),
+            Signal::SIGXCPU => Some(Self::CpuTime),
+            Signal::SIGXFSZ => Some(Self::FileSize),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Syscall => "made a forbidden system call",
+            Self::CpuTime => "ran out of CPU time",
+            Self::FileSize => "wrote a file over the size limit",
+        })
+    }
+}
+
+/// The restrictions a sandboxed command actually ran under, as recorded in the
+/// audit log.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SandboxRules {
+    /// `namespace` only when the command really ran in namespaces of its own,
+    /// which also means it started from a scrubbed environment.
+    pub backend: SandboxBackend,
+    /// The Landlock ABI enforced, or `None` without Landlock.
+    pub landlock_abi: Option<u8>,
+    /// Paths the command could read.
+    pub read_paths: Vec<PathBuf>,
+    /// Paths the command could read and write.
+    pub write_paths: Vec<PathBuf>,
+    /// Whether all network access was allowed.
+    pub network: bool,
+    /// TCP ports the command could connect to, without full network access.
+    pub connect_ports: Vec<u16>,
+    /// TCP ports the command could bind, without full network access.
+    pub bind_ports: Vec<u16>,
+    /// System calls the command was killed for, or `None` without a filter.
+    pub denied_syscalls: Option<Vec<String>>,
+    /// Resource limits of the command.
+    pub limits: ResourceLimits,
+}
+
+/// Sandbox configuration for hook/build execution.
+#[derive(Clone, Debug, Default)]
+pub struct SandboxConfig {
+    /// Whether sandboxing is enabled. If false, commands run without restrictions.
+    pub enabled: bool,
+    /// Paths that can be read (in addition to defaults).
+    pub fs_read: Vec<PathBuf>,
+    /// Paths that can be written (in addition to defaults).
+    pub fs_write: Vec<PathBuf>,
+    /// Network access configuration (requires Landlock V4+).
+    pub network: NetworkConfig,
+    /// Whether to include default read paths (e.g., /usr, /lib, /etc essentials).
+    pub include_default_read_paths: bool,
+    /// Whether to include user cache/config directories in write paths.
+    pub include_user_dirs: bool,
+    /// How the command is isolated.
+    pub backend: SandboxBackend,
+    /// System calls denied on top of the default list. `None` installs no
+    /// seccomp filter at all.
+    pub denied_syscalls: Option<Vec<String>>,
+    /// Resource limits for the command.
+    pub limits: ResourceLimits,
+}
+
+impl SandboxConfig {
+    /// Create a new sandbox config with sensible defaults.
+    pub fn new() -> Self {
+        Self {
+            enabled: true,
+            include_default_read_paths: true,
+            include_user_dirs: false,
+            denied_syscalls: Some(Vec::new()),
+            limits: ResourceLimits::standard(),
+            ..Default::default()
+        }
+    }
+
+    /// The sandbox for a package's hooks and builds, as its `sandbox` block
+    /// in packages.toml configures it. Paths are left to the command.
+    pub fn for_package(package: &PackageSandbox) -> Self {
+        let mut config = Self::new()
+            .with_network(if package.allows_network() {
+                NetworkConfig::allow_all()
+            } else {
+                NetworkConfig::default()
+            })
+            .with_backend(package.backend());
+        config.denied_syscalls = Some(package.deny_syscalls.clone());
+        if let Some(limits) = &package.limits {
+            // Zero lifts a limit; sizes are configured in MiB.
+            let set = |value: Option<u64>, default: Option<u64>, scale: u64| {
+                match value {
+                    Some(0) => None,
+                    Some(value) => Some(value.saturating_mul(scale)),
+                    None => default,
+                }
+            };
+            let defaults = config.limits;
+            config.limits = ResourceLimits {
+                cpu_time: set(limits.cpu_time, defaults.cpu_time, 1),
+                address_space: set(limits.address_space, defaults.address_space, 1 << 20),
+                file_size: set(limits.file_size, defaults.file_size, 1 << 20),
+                processes: set(limits.processes, defaults.processes, 1),
+            };
+        }
+        config
+    }
+
+    /// Create a disabled sandbox config (no restrictions).
+    pub fn disabled() -> Self {
+        Self {
+            enabled: false,
+            ..Default::default()
+        }
+    }
+
+    /// Add a readable path.
+    pub fn add_read_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.fs_read.push(path.into());
+        self
+    }
+
+    /// Add a writable path.
+    pub fn add_write_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.fs_write.push(path.into());
+        self
+    }
+
+    /// Set network configuration.
+    pub fn with_network(mut self, network: NetworkConfig) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Include user directories (~/.cache, ~/.config, ~/.local).
+    pub fn with_user_dirs(mut self) -> Self {
+        self.include_user_dirs = true;
+        self
+    }
+
+    /// Set the isolation backend.
+    pub fn with_backend(mut self, backend: SandboxBackend) -> Self {
+        self.backend = backend;
+        self
+    }
+}
+
+/// Builder for running sandboxed commands.
+pub struct SandboxedCommand<'a> {
+    command: &'a str,
+    working_dir: PathBuf,
+    env_vars: Vec<(String, String)>,
+    config: SandboxConfig,
+    extra_read_paths: Vec<PathBuf>,
+    extra_write_paths: Vec<PathBuf>,
+    cancel: Option<CancellationToken>,
+}
+
+impl<'a> SandboxedCommand<'a> {
+    /// Create a new sandboxed command builder.
+    pub fn new(command: &'a str) -> Self {
+        Self {
+            command,
+            working_dir: std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/")),
+            env_vars: Vec::new(),
+            config: SandboxConfig::new(),
+            extra_read_paths: Vec::new(),
+            extra_write_paths: Vec::new(),
+            cancel: None,
+        }
+    }
+
+    /// Set the working directory.
+    pub fn working_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.working_dir = dir.into();
+        self
+    }
+
+    /// Add an environment variable.
+    pub fn env<K: Into<String>, V: Into<String>>(mut self, key: K, value: V) -> Self {
+        self.env_vars.push((key.into(), value.into()));
+        self
+    }
+
+    /// Add multiple environment variables.
+    pub fn envs<I, K, V>(mut self, vars: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.env_vars
+            .extend(vars.into_iter().map(|(k, v)| (k.into(), v.into())));
+        self
+    }
+
+    /// Set the sandbox configuration.
+    pub fn config(mut self, config: SandboxConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Add an extra readable path.
+    pub fn read_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.extra_read_paths.push(path.into());
+        self
+    }
+
+    /// Add an extra writable path (also grants read access).
+    pub fn write_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.extra_write_paths.push(path.into());
+        self
+    }
+
+    /// Stop a captured run once `token` is cancelled.
+    pub fn cancel(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    /// Disable sandboxing entirely.
+    pub fn no_sandbox(mut self) -> Self {
+        self.config.enabled = false;
+        self
+    }
+
+    /// Run the command and return the exit status.
+    pub fn run(self) -> SoarResult<std::process::ExitStatus> {
+        self.build()
+            .0
+            .status()
+            .map_err(|e| SoarError::SandboxExecution(e.to_string()))
+    }
+
+    /// Run the command with no input, capturing its output.
+    pub fn output(self) -> SoarResult<Output> {
+        self.build()
+            .0
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| SoarError::SandboxExecution(e.to_string()))
+    }
+
+    /// Run the command, passing its output through while keeping the end of
+    /// it for the audit log.
+    ///
+    /// # Returns
+    ///
+    /// What the command did, and the rules it ran under; `None` when it ran
+    /// without a sandbox.
+    pub fn run_captured(self) -> SoarResult<(Captured, Option<SandboxRules>)> {
+        let cancel = self.cancel.clone();
+        let (cmd, rules) = self.build();
+        let captured = audit::capture(cmd, cancel.as_ref())
+            .map_err(|e| SoarError::SandboxExecution(e.to_string()))?;
+        Ok((captured, rules))
+    }
+
+    fn build(self) -> (Command, Option<SandboxRules>) {
+        sandboxed_command(
+            self.command,
+            &self.working_dir,
+            &self.env_vars,
+            &self.config,
+            &self.extra_read_paths,
+            &self.extra_write_paths,
+        )
+    }
+}
+
+/// Check if Landlock is supported on this system.
+pub fn is_landlock_supported() -> bool {
+    match Ruleset::default().handle_access(AccessFs::from_all(ABI::V1)) {
+        Ok(_) => {
+            debug!("Landlock is supported on this system");
+            true
+        }
+        Err(e) => {
+            debug!("Landlock not supported: {}", e);
+            false
+        }
+    }
+}
+
+/// Check if commands can be sandboxed with `backend` on this system, itself
+/// or through the Landlock fallback.
+pub fn is_supported(backend: SandboxBackend) -> bool {
+    is_landlock_supported() || (backend == SandboxBackend::Namespace && namespace::is_supported())
+}
+
+/// The newest Landlock ABI soar writes rules for.
+const NEWEST_ABI: ABI = ABI::V5;
+
+/// The Landlock ABI the running kernel supports, as it reports it.
+fn kernel_abi() -> ABI {
+    /// `landlock_create_ruleset` flag asking for the ABI version.
+    const LANDLOCK_CREATE_RULESET_VERSION: nix::libc::c_uint = 1;
+
+    // SAFETY: asking for the version reads no ruleset and creates nothing.
+    let version = unsafe {
+        nix::libc::syscall(
+            nix::libc::SYS_landlock_create_ruleset,
+            std::ptr::null::<nix::libc::c_void>(),
+            0,
+            LANDLOCK_CREATE_RULESET_VERSION,
+        )
+    };
+    ABI::from(version as i32)
+}
+
+/// Get the best available Landlock ABI version.
+///
+/// The kernel's own when soar writes rules for it, as those are the rights
+/// actually enforced.
+fn get_best_abi() -> ABI {
+    let abi = kernel_abi().clamp(ABI::V1, NEWEST_ABI);
+    debug!("Using Landlock ABI {:?}", abi);
+    abi
+}
+
+/// Check if network restrictions are supported (V4+).
+fn is_network_supported(abi: ABI) -> bool {
+    matches!(abi, ABI::V4 | ABI::V5)
+}
+
+/// Default read-only paths that are always allowed.
+fn default_read_paths() -> Vec<PathBuf> {
+    [
+        "/usr",
+        "/lib",
+        "/lib64",
+        "/bin",
+        "/sbin",
+        "/etc/ld.so.cache",
+        "/etc/ld.so.conf",
+        "/etc/ld.so.conf.d",
+        "/etc/ssl/certs",
+        "/etc/ca-certificates",
+        "/etc/pki",
+        "/etc/resolv.conf",
+        "/etc/hosts",
+        "/etc/passwd",
+        "/etc/group",
+        "/etc/nsswitch.conf",
+        "/etc/localtime",
+        "/proc",
+        "/sys",
+        "/dev/null",
+        "/dev/zero",
+        "/dev/urandom",
+        "/dev/random",
+        "/dev/fd",
+        "/dev/stdin",
+        "/dev/stdout",
+        "/dev/stderr",
+        "/dev/tty",
+    ]
+    .iter()
+    .map(PathBuf::from)
+    .collect()
+}
+
+/// Default writable paths (device nodes that need write access).
+fn default_write_paths() -> Vec<PathBuf> {
+    [
+        "/dev/null",
+        "/dev/zero",
+        "/dev/tty",
+        "/dev/stdin",
+        "/dev/stdout",
+        "/dev/stderr",
+        "/dev/fd",
+        "/dev/pts",
+        "/dev/ptmx",
+        "/tmp",
+    ]
+    .iter()
+    .map(PathBuf::from)
+    .collect()
+}
+
+/// Get user-specific directories that might need access.
+///
+/// soar keeps its packages, launchers and configuration in these, and a
+/// command able to rewrite them could run unconfined on the next launch, so
+/// those are left out; see [`soar_paths`].
+fn user_dirs() -> Vec<PathBuf> {
+    let protected = soar_paths();
+    let mut dirs = Vec::new();
+    for dir in [xdg_cache_home(), xdg_config_home(), xdg_data_home()] {
+        writable_beneath(&dir, &protected, &mut dirs);
+    }
+    dirs
+}
+
+/// What decides how packages run: the installed packages and their
+/// launchers and sandbox profiles, the bin directory, the databases,
+/// repository metadata, cached binaries, the audit log and the
+/// configuration.
+fn soar_paths() -> Vec<PathBuf> {
+    let config = get_config();
+    let mut paths: Vec<PathBuf> = [
+        config.get_packages_path(None).ok(),
+        config.get_bin_path().ok(),
+        config.get_db_path().ok(),
+        config.get_repositories_path().ok(),
+        config.get_cache_path().ok(),
+        config.get_audit_log_path().ok(),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    paths.extend(
+        config
+            .get_audit_log_path()
+            .ok()
+            .map(|log| audit::rotated(&log)),
+    );
+    for file in [
+        CONFIG_PATH.read().unwrap().clone(),
+        PACKAGES_CONFIG_PATH.read().unwrap().clone(),
+    ] {
+        paths.extend(file.parent().map(Path::to_path_buf));
+    }
+    paths
+        .iter()
+        .map(|path| fs::canonicalize(path).unwrap_or_else(|_| path.clone()))
+        .collect()
+}
+
+/// Add `path` to `out` as writable, leaving out everything in `protected`.
+///
+/// Landlock cannot take back what a rule on a parent grants, so a directory
+/// holding a protected path is granted entry by entry instead, down to the
+/// protected path itself. An entry created in such a directory later is not
+/// writable.
+fn writable_beneath(path: &Path, protected: &[PathBuf], out: &mut Vec<PathBuf>) {
+    // A link is granted as what it leads to.
+    let Ok(real) = fs::canonicalize(path) else {
+        return;
+    };
+    if protected.iter().any(|p| real.starts_with(p)) {
+        return;
+    }
+    if !protected.iter().any(|p| p.starts_with(&real)) {
+        out.push(real);
+        return;
+    }
+    let Ok(entries) = fs::read_dir(&real) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        writable_beneath(&entry.path(), protected, out);
+    }
+}
+
+/// Add filesystem path rules to a Landlock ruleset.
+fn add_path_rules(
+    ruleset: landlock::RulesetCreated,
+    paths: &[PathBuf],
+    access: BitFlags<AccessFs>,
+) -> Result<landlock::RulesetCreated, SoarError> {
+    let mut current_ruleset = ruleset;
+
+    for path in paths {
+        if !path.exists() {
+            debug!("Skipping non-existent path: {}", path.display());
+            continue;
+        }
+        // /dev/stdout and the like lead to a pipe or socket when output is
+        // redirected, which Landlock holds no rule for. The descriptors are
+        // open already, and Landlock never restricts those.
+        if fs::metadata(path).is_ok_and(|m| m.file_type().is_fifo() || m.file_type().is_socket()) {
+            debug!("Skipping pipe or socket: {}", path.display());
+            continue;
+        }
+
+        match PathFd::new(path) {
+            Ok(fd) => {
+                current_ruleset = current_ruleset
+                    .add_rule(PathBeneath::new(fd, access))
+                    .map_err(|e| {
+                        SoarError::SandboxPathRule {
+                            path: path.display().to_string(),
+                            reason: e.to_string(),
+                        }
+                    })?;
+                debug!("Added sandbox rule for: {}", path.display());
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to open path for sandbox rule: {} ({})",
+                    path.display(),
+                    e
+                );
+            }
+        }
+    }
+
+    Ok(current_ruleset)
+}
+
+/// Add network port rules to a Landlock ruleset.
+fn add_network_rules(
+    ruleset: landlock::RulesetCreated,
+    config: &NetworkConfig,
+) -> Result<landlock::RulesetCreated, SoarError> {
+    let mut current_ruleset = ruleset;
+
+    for &port in &config.allow_bind_tcp {
+        current_ruleset = current_ruleset
+            .add_rule(NetPort::new(port, AccessNet::BindTcp))
+            .map_err(|e| {
+                SoarError::SandboxNetworkRule {
+                    port,
+                    reason: e.to_string(),
+                }
+            })?;
+        debug!("Added network bind rule for port: {}", port);
+    }
+
+    for &port in &config.allow_connect_tcp {
+        current_ruleset = current_ruleset
+            .add_rule(NetPort::new(port, AccessNet::ConnectTcp))
+            .map_err(|e| {
+                SoarError::SandboxNetworkRule {
+                    port,
+                    reason: e.to_string(),
+                }
+            })?;
+        debug!("Added network connect rule for port: {}", port);
+    }
+
+    Ok(current_ruleset)
+}
+
+/// Apply Landlock restrictions to the current process.
+///
+/// This function is designed to be called from a `pre_exec` hook, which runs
+/// in the forked child process before exec(). It sets up filesystem and
+/// optionally network restrictions using the Landlock LSM.
+///
+/// # Signal Safety
+///
+/// This function is async-signal-safe because:
+/// - Landlock operations are direct syscalls (landlock_create_ruleset,
+///   landlock_add_rule, landlock_restrict_self)
+/// - No heap allocations occur after the function starts (all data is pre-allocated)
+/// - No locks are acquired
+/// - No async-signal-unsafe functions are called
+///
+/// # Arguments
+///
+/// * `read_paths` - Paths to allow read access
+/// * `write_paths` - Paths to allow full (read/write) access
+/// * `network_config` - Network restriction configuration
+fn apply_landlock_restrictions(
+    read_paths: &[PathBuf],
+    write_paths: &[PathBuf],
+    network_config: &NetworkConfig,
+) -> std::io::Result<()> {
+    let abi = get_best_abi();
+    let read_access = AccessFs::from_read(abi);
+    let write_access = AccessFs::from_all(abi);
+
+    let ruleset_builder = Ruleset::default()
+        .handle_access(AccessFs::from_all(abi))
+        .map_err(|e| std::io::Error::other(format!("Landlock FS access setup failed: {}", e)))?;
+
+    let ruleset_builder = if is_network_supported(abi) && !network_config.allow_all {
+        ruleset_builder
+            .handle_access(AccessNet::from_all(abi))
+            .map_err(|e| {
+                std::io::Error::other(format!("Landlock network access setup failed: {}", e))
+            })?
+    } else {
+        ruleset_builder
+    };
+
+    let ruleset = ruleset_builder
+        .create()
+        .map_err(|e| std::io::Error::other(format!("Landlock ruleset creation failed: {}", e)))?;
+
+    let ruleset =
+        add_path_rules(ruleset, read_paths, read_access).map_err(std::io::Error::other)?;
+
+    let ruleset =
+        add_path_rules(ruleset, write_paths, write_access).map_err(std::io::Error::other)?;
+
+    let ruleset = if is_network_supported(abi) && !network_config.allow_all {
+        add_network_rules(ruleset, network_config).map_err(std::io::Error::other)?
+    } else {
+        ruleset
+    };
+
+    // Enforce the sandbox
+    ruleset
+        .restrict_self()
+        .map_err(|e| std::io::Error::other(format!("Failed to enforce Landlock: {}", e)))?;
+
+    debug!("Landlock sandbox activated");
+    Ok(())
+}
+
+/// Build a shell command that runs under Landlock sandbox restrictions, and
+/// in namespaces of its own with the namespace backend, along with the rules
+/// it will run under.
+fn sandboxed_command(
+    command: &str,
+    working_dir: &Path,
+    env_vars: &[(String, String)],
+    config: &SandboxConfig,
+    extra_read_paths: &[PathBuf],
+    extra_write_paths: &[PathBuf],
+) -> (Command, Option<SandboxRules>) {
+    let mut cmd = Command::new("sh");
+    cmd.arg("-c").arg(command).current_dir(working_dir);
+
+    let isolate = config.enabled
+        && config.backend == SandboxBackend::Namespace
+        && if namespace::is_supported() {
+            true
+        } else {
+            warn!("User namespaces not available, falling back to Landlock");
+            false
+        };
+    if isolate {
+        cmd.env_clear()
+            .envs(namespace::scrubbed_env(config.include_user_dirs));
+    }
+    for (key, value) in env_vars {
+        cmd.env(key, value);
+    }
+
+    // If sandbox is disabled, run directly
+    if !config.enabled {
+        debug!("Sandbox disabled, running command directly");
+        return (cmd, None);
+    }
+
+    let mut write_paths = vec![working_dir.to_path_buf()];
+    write_paths.extend(extra_write_paths.iter().cloned());
+
+    if isolate {
+        let mut writable = write_paths.clone();
+        writable.extend(config.fs_write.iter().cloned());
+        if config.include_user_dirs {
+            writable.extend(user_dirs());
+        }
+        let network = &config.network;
+        let share_network = network.allow_all
+            || !network.allow_bind_tcp.is_empty()
+            || !network.allow_connect_tcp.is_empty();
+        debug!("running command in its own namespaces");
+        namespace::isolate(&mut cmd, working_dir, &writable, share_network);
+    } else if !is_landlock_supported() {
+        warn!("Landlock not supported, running command without filesystem restrictions");
+    }
+
+    let mut rules = restrict(
+        &mut cmd,
+        config,
+        extra_read_paths.to_vec(),
+        write_paths,
+        is_landlock_supported(),
+    );
+    rules.backend = if isolate {
+        SandboxBackend::Namespace
+    } else {
+        SandboxBackend::Landlock
+    };
+    (cmd, Some(rules))
+}
+
+/// Restrict what `cmd` may touch once spawned to what `config` allows, on top
+/// of `read_paths` and `write_paths`, and put it under the configured seccomp
+/// filter and resource limits.
+///
+/// Filesystem and network rules are only enforced with `landlock`.
+///
+/// # Returns
+///
+/// The rules `cmd` will run under.
+fn restrict(
+    cmd: &mut Command,
+    config: &SandboxConfig,
+    mut read_paths: Vec<PathBuf>,
+    mut write_paths: Vec<PathBuf>,
+    landlock: bool,
+) -> SandboxRules {
+    // Pre-allocate all paths before entering the unsafe pre_exec context.
+    // This ensures no heap allocations occur in the forked child.
+    if config.include_default_read_paths {
+        read_paths.extend(default_read_paths());
+    }
+    read_paths.extend(config.fs_read.clone());
+
+    write_paths.extend(default_write_paths());
+    if config.include_user_dirs {
+        write_paths.extend(user_dirs());
+    }
+    write_paths.extend(config.fs_write.clone());
+
+    let network_config = config.network.clone();
+    let limits = config.limits;
+    let filter = config.denied_syscalls.as_deref().and_then(seccomp::compile);
+
+    let rules = SandboxRules {
+        backend: config.backend,
+        landlock_abi: (landlock && kernel_abi() != ABI::Unsupported)
+            .then(|| get_best_abi() as u8),
+        read_paths: read_paths.clone(),
+        write_paths: write_paths.clone(),
+        network: network_config.allow_all,
+        connect_ports: network_config.allow_connect_tcp.clone(),
+        bind_ports: network_config.allow_bind_tcp.clone(),
+        denied_syscalls: filter.is_some().then(|| {
+            seccomp::DEFAULT_DENIED
+                .iter()
+                .map(|name| name.to_string())
+                .chain(config.denied_syscalls.iter().flatten().cloned())
+                .collect()
+        }),
+        limits,
+    };
+
+    // SAFETY: `pre_exec` runs in the forked child process after fork() but before exec().
+    unsafe {
+        cmd.pre_exec(move || {
+            if landlock {
+                apply_landlock_restrictions(&read_paths, &write_paths, &network_config)?;
+            }
+            limits.apply()?;
+            // Last, as the filter may deny what setting up the rest needs.
+            if let Some(filter) = &filter {
+                seccomp::install(filter)?;
+            }
+            Ok(())
+        });
+    }
+    rules
+}
+
+/// Put `cmd`, which runs a command of the package installed in `package_dir`,
+/// under the package's runtime sandbox `profile`.
+///
+/// Besides what the profile lists, the package and soar's other packages are
+/// readable, so a launcher can reach an interpreter soar installed, along with
+/// the system's libraries. Without Landlock the command runs unrestricted
+/// with a warning, unless the profile requires it.
+///
+/// # Errors
+///
+/// Returns [`SoarError::Custom`] when the profile requires Landlock and it is
+/// not available.
+pub fn apply_runtime_sandbox(
+    cmd: &mut Command,
+    profile: &RuntimeSandbox,
+    package_dir: &Path,
+) -> SoarResult<()> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    if !profile.is_enabled() {
+        debug!("runtime sandbox disabled for {}", program);
+        return Ok(());
+    }
+    if !is_landlock_supported() {
+        if profile.is_required() {
+            return Err(SoarError::Custom(format!(
+                "{program} requires its runtime sandbox, but Landlock is not available on \
+                 this system. Either upgrade to Linux 5.13+ or set runtime_sandbox.require = \
+                 false."
+            )));
+        }
+        warn!(
+            "Landlock not supported, running {} without its sandbox",
+            program
+        );
+        return Ok(());
+    }
+
+    let resolve = |paths: &[String]| -> SoarResult<Vec<PathBuf>> {
+        paths.iter().map(|path| Ok(resolve_path(path)?)).collect()
+    };
+    let mut config = SandboxConfig::new().with_network(if profile.allows_network() {
+        NetworkConfig::allow_all()
+    } else {
+        NetworkConfig {
+            allow_all: false,
+            allow_bind_tcp: profile.bind_ports.clone(),
+            allow_connect_tcp: profile.connect_ports.clone(),
+        }
+    });
+    config.fs_read = resolve(&profile.fs_read)?;
+    config.fs_write = resolve(&profile.fs_write)?;
+    config.include_user_dirs = profile.allows_user_dirs();
+    // Hook and build limits would cut long-running programs short.
+    config.denied_syscalls = None;
+    config.limits = ResourceLimits::default();
+
+    let mut read_paths = vec![package_dir.to_path_buf()];
+    read_paths.extend(package_dir.parent().map(Path::to_path_buf));
+    debug!("running {} in its runtime sandbox", program);
+    restrict(cmd, &config, read_paths, Vec::new(), true);
+    Ok(())
+}
+
+/// Record `profile` in `install_dir`, where the package's launchers read it.
+///
+/// # Returns
+///
+/// The path of the profile.
+pub fn write_runtime_profile(install_dir: &Path, profile: &RuntimeSandbox) -> SoarResult<PathBuf> {
+    let path = install_dir.join(RUNTIME_SANDBOX_FILE);
+    fs::write(&path, toml::to_string(profile)?)
+        .with_context(|| format!("writing {}", path.display()))?;
+    Ok(path)
+}
+
+/// Read the runtime sandbox profile recorded at `path`.
+pub fn read_runtime_profile(path: &Path) -> SoarResult<RuntimeSandbox> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    toml::from_str(&content).map_err(|err| {
+        SoarError::Custom(format!(
+            "{} is not a runtime sandbox profile: {err}",
+            path.display()
+        ))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use soar_config::packages::SandboxLimits;
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn test_violation_of_recognises_limit_signals() {
+        let signalled = |sig: Signal| ExitStatus::from_raw(sig as i32);
+        let exited = |code: i32| ExitStatus::from_raw(code << 8);
+
+        assert_eq!(
+            Violation::of(&signalled(Signal::SIGSYS)),
+            Some(Violation::Syscall)
+        );
+        assert_eq!(
+            Violation::of(&signalled(Signal::SIGXCPU)),
+            Some(Violation::CpuTime)
+        );
+        assert_eq!(
+            Violation::of(&signalled(Signal::SIGXFSZ)),
+            Some(Violation::FileSize)
+        );
+        assert_eq!(Violation::of(&exited(128 + 25)), None);
+        assert_eq!(Violation::of(&signalled(Signal::SIGKILL)), None);
+        assert_eq!(Violation::of(&exited(1)), None);
+    }
+
+    #[test]
+    fn test_kernel_abi_agrees_with_landlock_support() {
+        assert_eq!(is_landlock_supported(), kernel_abi() != ABI::Unsupported);
+    }
+
+    #[test]
+    fn test_writable_beneath_leaves_out_protected_paths() {
+        let dir = tempdir().unwrap();
+        let data = fs::canonicalize(dir.path()).unwrap();
+        let soar = data.join("soar");
+        for sub in ["app", "soar/packages", "soar/bin", "soar/portable-dirs"] {
+            fs::create_dir_all(data.join(sub)).unwrap();
+        }
+        std::os::unix::fs::symlink(soar.join("bin"), data.join("bin-link")).unwrap();
+        let protected = [soar.join("packages"), soar.join("bin")];
+
+        let mut writable = Vec::new();
+        writable_beneath(&data, &protected, &mut writable);
+        writable.sort();
+        assert_eq!(writable, [data.join("app"), soar.join("portable-dirs")]);
+
+        let mut writable = Vec::new();
+        writable_beneath(&data.join("app"), &protected, &mut writable);
+        assert_eq!(writable, [data.join("app")]);
+    }
+
+    #[test]
+    fn test_for_package_overrides_standard_limits() {
+        let package = PackageSandbox {
+            deny_syscalls: vec!["socket".into()],
+            limits: Some(SandboxLimits {
+                cpu_time: Some(0),
+                address_space: Some(512),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        let config = SandboxConfig::for_package(&package);
+
+        assert_eq!(config.denied_syscalls, Some(vec!["socket".to_string()]));
+        assert_eq!(
+            config.limits,
+            ResourceLimits {
+                cpu_time: None,
+                address_space: Some(512 << 20),
+                file_size: ResourceLimits::standard().file_size,
+                processes: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_denied_syscall_kills_the_command() {
+        let dir = tempdir().unwrap();
+        let mut config = SandboxConfig::new();
+        config.denied_syscalls = Some(vec!["kill".into()]);
+
+        let status = SandboxedCommand::new("kill -0 $$")
+            .working_dir(dir.path())
+            .config(config)
+            .output()
+            .unwrap()
+            .status;
+
+        assert_eq!(Violation::of(&status), Some(Violation::Syscall));
+    }
+
+    #[test]
+    fn test_file_size_limit_stops_the_command() {
+        let dir = tempdir().unwrap();
+        let mut config = SandboxConfig::new();
+        config.limits.file_size = Some(1024);
+
+        let status = SandboxedCommand::new("exec head -c 4096 /dev/zero > big")
+            .working_dir(dir.path())
+            .config(config)
+            .output()
+            .unwrap()
+            .status;
+
+        assert_eq!(Violation::of(&status), Some(Violation::FileSize));
+        assert_eq!(fs::metadata(dir.path().join("big")).unwrap().len(), 1024);
+    }
+}
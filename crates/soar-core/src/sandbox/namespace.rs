@@ -0,0 +1,441 @@
+//! Namespace backend for sandboxed commands.
+//!
+//! The command runs in fresh user, mount, PID and IPC namespaces, and a network
+//! namespace of its own unless the network is allowed. Inside, the whole
+//! system is mounted read-only, `/tmp`, `/run` and `/dev/shm` are private and
+//! empty, `/proc` shows only the command's own processes, and the only
+//! writable places are the ones bound back in. No privileges are needed, only
+//! a kernel that lets unprivileged users create user namespaces (and 5.12+,
+//! for the mount API used to build the view).
+//!
+//! A process that creates a PID namespace stays outside it; its next child
+//! becomes the namespace's init. So the spawned child sets the namespaces up,
+//! forks the command, and then only waits on it to pass its exit status on.
+
+use std::{
+    env,
+    ffi::{CStr, CString, OsStr, OsString},
+    fs,
+    os::unix::{ffi::OsStrExt as _, process::CommandExt as _},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::OnceLock,
+};
+
+use nix::{
+    errno::Errno,
+    libc,
+    mount::{mount, MsFlags},
+    sched::{unshare, CloneFlags},
+    sys::{
+        signal::{kill, signal, SigHandler, Signal},
+        wait::{waitpid, WaitStatus},
+    },
+    unistd::{chdir, fork, getgid, getpid, getuid, ForkResult, Pid},
+};
+use tracing::debug;
+
+/// `MOVE_MOUNT_F_EMPTY_PATH`, which not every libc target defines.
+const MOVE_MOUNT_F_EMPTY_PATH: libc::c_uint = 0x04;
+
+/// Variables a command keeps from soar's environment; everything else is
+/// dropped.
+const PASSTHROUGH_ENV: &[&str] = &[
+    "PATH", "LANG", "LANGUAGE", "TERM", "TZ", "USER", "LOGNAME", "NO_COLOR",
+];
+
+/// Whether commands can be isolated in namespaces here.
+///
+/// User namespaces can be disabled by sysctl, refused inside containers or
+/// missing pieces of the mount API, so this runs `true` isolated once and
+/// remembers whether that worked.
+pub fn is_supported() -> bool {
+    static SUPPORTED: OnceLock<bool> = OnceLock::new();
+    *SUPPORTED.get_or_init(|| {
+        let mut cmd = Command::new("true");
+        cmd.current_dir("/")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        isolate(&mut cmd, Path::new("/"), &[], false);
+        match cmd.status() {
+            Ok(status) if status.success() => {
+                debug!("user namespaces are available");
+                true
+            }
+            Ok(status) => {
+                debug!("user namespaces not available: probe exited with {status}");
+                false
+            }
+            Err(err) => {
+                debug!("user namespaces not available: {err}");
+                false
+            }
+        }
+    })
+}
+
+/// The environment a command starts from: the locale, terminal and `PATH`
+/// of soar's, nothing else. `HOME` is the private `/tmp` unless the user's
+/// directories are shared.
+pub fn scrubbed_env(keep_home: bool) -> Vec<(OsString, OsString)> {
+    let mut vars = env::vars_os()
+        .filter(|(key, _)| {
+            let key = key.to_string_lossy();
+            PASSTHROUGH_ENV.contains(&key.as_ref()) || key.starts_with("LC_")
+        })
+        .collect::<Vec<_>>();
+    match env::var_os("HOME") {
+        Some(home) if keep_home => vars.push(("HOME".into(), home)),
+        _ => vars.push(("HOME".into(), "/tmp".into())),
+    }
+    vars
+}
+
+/// Directories covered by a private tmpfs inside the namespace.
+const PRIVATE_DIRS: [&CStr; 3] = [c"/tmp", c"/run", c"/dev/shm"];
+
+/// A path bound back into the namespace.
+struct Bind {
+    path: PathBuf,
+    c_path: CString,
+    /// Bound read-only rather than writable.
+    read_only: bool,
+    /// Directories to create, outermost first, so the mount point still
+    /// exists once a private directory covers it.
+    create_dirs: Vec<CString>,
+    /// Whether the mount point is a file to create empty.
+    create_file: bool,
+    /// The detached copy of the path, taken in the child.
+    fd: libc::c_int,
+}
+
+impl Bind {
+    fn new(path: PathBuf, is_dir: bool, read_only: bool, private: &[&Path]) -> Option<Self> {
+        let mut create_dirs = Vec::new();
+        let mut create_file = false;
+        if let Some(covered) = private.iter().find(|dir| path.starts_with(dir)) {
+            let dir = if is_dir { Some(path.as_path()) } else { path.parent() };
+            for ancestor in dir.into_iter().flat_map(Path::ancestors) {
+                if ancestor == *covered {
+                    break;
+                }
+                create_dirs.push(CString::new(ancestor.as_os_str().as_bytes()).ok()?);
+            }
+            create_dirs.reverse();
+            create_file = !is_dir;
+        }
+        Some(Self {
+            c_path: CString::new(path.as_os_str().as_bytes()).ok()?,
+            path,
+            read_only,
+            create_dirs,
+            create_file,
+            fd: -1,
+        })
+    }
+}
+
+/// Run `cmd` in namespaces of its own once spawned, starting in
+/// `working_dir`, with `writable` the only paths it may write besides its
+/// private `/tmp`.
+///
+/// `share_network` keeps the host's network; the resolver configuration
+/// stays readable then, as `/etc/resolv.conf` often points into `/run`.
+/// Restrictions registered on `cmd` afterwards apply inside the namespaces.
+pub fn isolate(cmd: &mut Command, working_dir: &Path, writable: &[PathBuf], share_network: bool) {
+    // Everything the child needs is prepared here, so it only makes syscalls:
+    // it must not allocate, as another thread may have held the allocator's
+    // lock when soar forked.
+    let private: Vec<&CStr> = PRIVATE_DIRS
+        .into_iter()
+        .filter(|dir| Path::new(OsStr::from_bytes(dir.to_bytes())).is_dir())
+        .collect();
+    let private_paths: Vec<&Path> = private
+        .iter()
+        .map(|dir| Path::new(OsStr::from_bytes(dir.to_bytes())))
+        .collect();
+
+    let mut binds = writable
+        .iter()
+        .filter_map(|path| {
+            let meta = fs::metadata(path).ok()?;
+            Bind::new(path.clone(), meta.is_dir(), false, &private_paths)
+        })
+        .collect::<Vec<_>>();
+    // Parents first, so a nested bind ends up on top of the one around it.
+    binds.sort_by_key(|bind| bind.path.components().count());
+
+    let resolver = share_network
+        .then(|| fs::canonicalize("/etc/resolv.conf").ok())
+        .flatten()
+        .and_then(|path| path.parent().map(Path::to_path_buf))
+        .filter(|dir| dir.starts_with("/run"))
+        .and_then(|dir| Bind::new(dir, true, true, &private_paths));
+    binds.extend(resolver);
+
+    let uid_map = format!("{0} {0} 1\n", getuid());
+    let gid_map = format!("{0} {0} 1\n", getgid());
+    let working_dir = working_dir.to_path_buf();
+
+    let mut flags = CloneFlags::CLONE_NEWUSER
+        | CloneFlags::CLONE_NEWNS
+        | CloneFlags::CLONE_NEWPID
+        | CloneFlags::CLONE_NEWIPC;
+    if !share_network {
+        flags |= CloneFlags::CLONE_NEWNET;
+    }
+
+    // SAFETY: `pre_exec` runs in the forked child process after fork() but before exec().
+    unsafe {
+        cmd.pre_exec(move || {
+            unshare(flags)?;
+            fs::write("/proc/self/setgroups", "deny")?;
+            fs::write("/proc/self/uid_map", &uid_map)?;
+            fs::write("/proc/self/gid_map", &gid_map)?;
+
+            match fork()? {
+                ForkResult::Parent {
+                    child,
+                } => supervise(child),
+                ForkResult::Child => {
+                    libc::prctl(libc::PR_SET_PDEATHSIG, libc::SIGKILL);
+                    build_view(&mut binds, &private)?;
+                    chdir(&working_dir)?;
+                    Ok(())
+                }
+            }
+        });
+    }
+}
+
+/// Wait for the isolated command and exit the way it did.
+fn supervise(child: Pid) -> ! {
+    // Only the command may hold the pipe `Command::spawn` waits on, or the
+    // spawn would last as long as the command.
+    unsafe { libc::syscall(libc::SYS_close_range, 3, libc::c_uint::MAX, 0) };
+    // The terminal sends these to the command as well; it decides.
+    let _ = unsafe { signal(Signal::SIGINT, SigHandler::SigIgn) };
+    let _ = unsafe { signal(Signal::SIGQUIT, SigHandler::SigIgn) };
+
+    loop {
+        match waitpid(child, None) {
+            Ok(WaitStatus::Exited(_, code)) => unsafe { libc::_exit(code) },
+            Ok(WaitStatus::Signaled(_, sig, _)) => {
+                let _ = unsafe { signal(sig, SigHandler::SigDfl) };
+                let _ = kill(getpid(), sig);
+                unsafe { libc::_exit(128 + sig as i32) }
+            }
+            Err(Errno::EINTR) | Ok(_) => continue,
+            Err(_) => unsafe { libc::_exit(127) },
+        }
+    }
+}
+
+/// Turn the mount namespace into the command's view of the system.
+///
+/// Runs in the forked child, so it only makes syscalls on what [`isolate`]
+/// prepared.
+fn build_view(binds: &mut [Bind], private: &[&CStr]) -> std::io::Result<()> {
+    mount(
+        None::<&str>,
+        "/",
+        None::<&str>,
+        MsFlags::MS_REC | MsFlags::MS_PRIVATE,
+        None::<&str>,
+    )?;
+
+    // Copies of what stays reachable are taken before anything is covered,
+    // as they may live under the private directories.
+    for bind in binds.iter_mut() {
+        bind.fd = clone_tree(&bind.c_path)?;
+        if bind.read_only {
+            set_read_only(bind.fd, c"", libc::AT_EMPTY_PATH)?;
+        }
+    }
+
+    set_read_only(libc::AT_FDCWD, c"/", 0)?;
+
+    for dir in private {
+        mount(
+            Some(c"tmpfs"),
+            *dir,
+            Some(c"tmpfs"),
+            MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+            Some(c"mode=1777"),
+        )?;
+    }
+    mount(
+        Some(c"proc"),
+        c"/proc",
+        Some(c"proc"),
+        MsFlags::MS_NOSUID | MsFlags::MS_NODEV | MsFlags::MS_NOEXEC,
+        None::<&CStr>,
+    )?;
+
+    for bind in binds.iter() {
+        // Anything under a private directory needs somewhere to go first.
+        for dir in &bind.create_dirs {
+            if unsafe { libc::mkdir(dir.as_ptr(), 0o755) } < 0 && Errno::last() != Errno::EEXIST {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+        if bind.create_file {
+            let fd = unsafe {
+                libc::open(
+                    bind.c_path.as_ptr(),
+                    libc::O_CREAT | libc::O_WRONLY | libc::O_CLOEXEC,
+                    0o644,
+                )
+            };
+            if fd < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            unsafe { libc::close(fd) };
+        }
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_move_mount,
+                bind.fd,
+                c"".as_ptr(),
+                libc::AT_FDCWD,
+                bind.c_path.as_ptr(),
+                MOVE_MOUNT_F_EMPTY_PATH,
+            )
+        };
+        if ret < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// A detached copy of the mount tree at `path`.
+fn clone_tree(path: &CString) -> std::io::Result<libc::c_int> {
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_open_tree,
+            libc::AT_FDCWD,
+            path.as_ptr(),
+            libc::OPEN_TREE_CLONE
+                | libc::O_CLOEXEC as libc::c_uint
+                | libc::AT_RECURSIVE as libc::c_uint,
+        )
+    };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(fd as libc::c_int)
+}
+
+/// Make the mount tree at `path`, relative to `dirfd`, read-only.
+fn set_read_only(
+    dirfd: libc::c_int,
+    path: &std::ffi::CStr,
+    flags: libc::c_int,
+) -> std::io::Result<()> {
+    let attr = libc::mount_attr {
+        attr_set: libc::MOUNT_ATTR_RDONLY,
+        attr_clr: 0,
+        propagation: 0,
+        userns_fd: 0,
+    };
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_mount_setattr,
+            dirfd,
+            path.as_ptr(),
+            flags | libc::AT_RECURSIVE,
+            &attr as *const libc::mount_attr,
+            size_of::<libc::mount_attr>(),
+        )
+    };
+    if ret < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Output;
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn run_isolated(script: &str, dir: &Path, share_network: bool) -> Output {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(script).current_dir(dir);
+        isolate(&mut cmd, dir, &[dir.to_path_buf()], share_network);
+        cmd.output().unwrap()
+    }
+
+    #[test]
+    fn test_isolated_command_writes_only_where_allowed() {
+        if !is_supported() {
+            return;
+        }
+        let dir = tempdir().unwrap();
+        // Somewhere outside the private /tmp, which the command can read.
+        let outside = tempfile::tempdir_in(env!("CARGO_MANIFEST_DIR")).unwrap();
+        let scratch = format!("/tmp/soar-scratch-{}", std::process::id());
+        let script = format!(
+            "echo ok > built && echo tmp > {scratch} && test -d {0} && ! touch {0}/leak",
+            outside.path().display()
+        );
+
+        let output = run_isolated(&script, dir.path(), false);
+
+        assert!(output.status.success(), "{output:?}");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("built")).unwrap(),
+            "ok\n"
+        );
+        assert!(!outside.path().join("leak").exists());
+        assert!(!Path::new(&scratch).exists());
+    }
+
+    #[test]
+    fn test_isolated_command_sees_only_its_own_processes() {
+        if !is_supported() {
+            return;
+        }
+        let dir = tempdir().unwrap();
+
+        let output = run_isolated("echo $$; ls /proc | grep -c '^[0-9]'", dir.path(), false);
+
+        assert!(output.status.success(), "{output:?}");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let mut lines = stdout.lines();
+        assert_eq!(lines.next(), Some("1"));
+        let visible: u32 = lines.next().unwrap().parse().unwrap();
+        assert!(visible < 5, "{stdout}");
+    }
+
+    #[test]
+    fn test_isolated_command_exit_status_is_passed_on() {
+        if !is_supported() {
+            return;
+        }
+        let dir = tempdir().unwrap();
+
+        let output = run_isolated("exit 7", dir.path(), true);
+
+        assert_eq!(output.status.code(), Some(7));
+    }
+
+    #[test]
+    fn test_scrubbed_env_keeps_path_but_not_secrets() {
+        let vars = scrubbed_env(false);
+        let get = |name: &str| vars.iter().find(|(k, _)| k == name).map(|(_, v)| v);
+
+        assert_eq!(get("HOME").unwrap(), "/tmp");
+        assert_eq!(get("PATH"), env::var_os("PATH").as_ref());
+        assert!(vars.iter().all(|(k, _)| {
+            k == "HOME"
+                || PASSTHROUGH_ENV.iter().any(|p| k == p)
+                || k.to_string_lossy().starts_with("LC_")
+        }));
+    }
+}
@@ -0,0 +1,368 @@
+//! Seccomp filter for sandboxed commands.
+//!
+//! The filter is a plain deny-list: a command making one of the listed system
+//! calls is killed with `SIGSYS`, which soar reports as a sandbox violation.
+//! Everything else is allowed, so the list only has to name what a hook or
+//! build has no business doing.
+//!
+//! `clone` stays allowed for spawning processes and threads, but not for
+//! creating namespaces, which the list denies through `unshare`. `clone3`
+//! passes its flags in memory the filter cannot read, so it fails with
+//! `ENOSYS` and the C library falls back to `clone`.
+
+use std::io;
+
+use nix::libc::{self, c_long, sock_filter, sock_fprog};
+use tracing::warn;
+
+/// System calls no hook or build gets to make: debugging other processes,
+/// changing mounts or namespaces, loading kernel code, and touching the
+/// clock, keyrings or swap.
+pub const DEFAULT_DENIED: &[&str] = &[
+    "ptrace",
+    "process_vm_readv",
+    "process_vm_writev",
+    "mount",
+    "umount2",
+    "pivot_root",
+    "fsopen",
+    "fsconfig",
+    "fsmount",
+    "fspick",
+    "move_mount",
+    "open_tree",
+    "mount_setattr",
+    "setns",
+    "unshare",
+    "kexec_load",
+    "kexec_file_load",
+    "init_module",
+    "finit_module",
+    "delete_module",
+    "bpf",
+    "perf_event_open",
+    "userfaultfd",
+    "io_uring_setup",
+    "open_by_handle_at",
+    "keyctl",
+    "add_key",
+    "request_key",
+    "reboot",
+    "swapon",
+    "swapoff",
+    "acct",
+    "quotactl",
+    "syslog",
+    "settimeofday",
+    "clock_settime",
+    "clock_adjtime",
+    "adjtimex",
+    "sethostname",
+    "setdomainname",
+    "vhangup",
+];
+
+/// The architecture the filter is compiled for, as seccomp reports it.
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH: Option<u32> = Some(0xC000_003E);
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH: Option<u32> = Some(0xC000_00B7);
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const AUDIT_ARCH: Option<u32> = None;
+
+/// Numbers above this belong to the x32 ABI, which would slip past the list.
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+/// `clone` flags creating a namespace.
+const CLONE_NAMESPACES: libc::c_int = libc::CLONE_NEWNS
+    | libc::CLONE_NEWUSER
+    | libc::CLONE_NEWPID
+    | libc::CLONE_NEWNET
+    | libc::CLONE_NEWIPC
+    | libc::CLONE_NEWUTS
+    | libc::CLONE_NEWCGROUP;
+
+/// Offset of the low half of the first argument in `struct seccomp_data`, on
+/// the little-endian architectures the filter is written for.
+const ARG0_LOW: u32 = 16;
+
+/// System calls that can be named in a deny-list.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+const SYSCALLS: &[(&str, c_long)] = &[
+    ("accept", libc::SYS_accept),
+    ("accept4", libc::SYS_accept4),
+    ("acct", libc::SYS_acct),
+    ("add_key", libc::SYS_add_key),
+    ("adjtimex", libc::SYS_adjtimex),
+    ("bind", libc::SYS_bind),
+    ("bpf", libc::SYS_bpf),
+    ("capset", libc::SYS_capset),
+    ("chroot", libc::SYS_chroot),
+    ("clock_adjtime", libc::SYS_clock_adjtime),
+    ("clock_settime", libc::SYS_clock_settime),
+    ("clone", libc::SYS_clone),
+    ("clone3", libc::SYS_clone3),
+    ("connect", libc::SYS_connect),
+    ("delete_module", libc::SYS_delete_module),
+    ("execve", libc::SYS_execve),
+    ("execveat", libc::SYS_execveat),
+    ("fanotify_init", libc::SYS_fanotify_init),
+    ("finit_module", libc::SYS_finit_module),
+    ("fsconfig", libc::SYS_fsconfig),
+    ("fsmount", libc::SYS_fsmount),
+    ("fsopen", libc::SYS_fsopen),
+    ("fspick", libc::SYS_fspick),
+    ("init_module", libc::SYS_init_module),
+    ("inotify_init1", libc::SYS_inotify_init1),
+    ("io_uring_enter", libc::SYS_io_uring_enter),
+    ("io_uring_register", libc::SYS_io_uring_register),
+    ("io_uring_setup", libc::SYS_io_uring_setup),
+    ("ioctl", libc::SYS_ioctl),
+    ("kexec_file_load", libc::SYS_kexec_file_load),
+    ("kexec_load", libc::SYS_kexec_load),
+    ("keyctl", libc::SYS_keyctl),
+    ("kill", libc::SYS_kill),
+    ("listen", libc::SYS_listen),
+    ("memfd_create", libc::SYS_memfd_create),
+    ("mknodat", libc::SYS_mknodat),
+    ("mount", libc::SYS_mount),
+    ("mount_setattr", libc::SYS_mount_setattr),
+    ("move_mount", libc::SYS_move_mount),
+    ("name_to_handle_at", libc::SYS_name_to_handle_at),
+    ("open_by_handle_at", libc::SYS_open_by_handle_at),
+    ("open_tree", libc::SYS_open_tree),
+    ("perf_event_open", libc::SYS_perf_event_open),
+    ("personality", libc::SYS_personality),
+    ("pivot_root", libc::SYS_pivot_root),
+    ("prctl", libc::SYS_prctl),
+    ("process_vm_readv", libc::SYS_process_vm_readv),
+    ("process_vm_writev", libc::SYS_process_vm_writev),
+    ("ptrace", libc::SYS_ptrace),
+    ("quotactl", libc::SYS_quotactl),
+    ("reboot", libc::SYS_reboot),
+    ("recvfrom", libc::SYS_recvfrom),
+    ("request_key", libc::SYS_request_key),
+    ("seccomp", libc::SYS_seccomp),
+    ("sendto", libc::SYS_sendto),
+    ("setdomainname", libc::SYS_setdomainname),
+    ("setgid", libc::SYS_setgid),
+    ("setgroups", libc::SYS_setgroups),
+    ("sethostname", libc::SYS_sethostname),
+    ("setns", libc::SYS_setns),
+    ("setregid", libc::SYS_setregid),
+    ("setresgid", libc::SYS_setresgid),
+    ("setresuid", libc::SYS_setresuid),
+    ("setreuid", libc::SYS_setreuid),
+    ("settimeofday", libc::SYS_settimeofday),
+    ("setuid", libc::SYS_setuid),
+    ("socket", libc::SYS_socket),
+    ("socketpair", libc::SYS_socketpair),
+    ("swapoff", libc::SYS_swapoff),
+    ("swapon", libc::SYS_swapon),
+    ("syslog", libc::SYS_syslog),
+    ("tgkill", libc::SYS_tgkill),
+    ("tkill", libc::SYS_tkill),
+    ("umount2", libc::SYS_umount2),
+    ("unshare", libc::SYS_unshare),
+    ("userfaultfd", libc::SYS_userfaultfd),
+    ("vhangup", libc::SYS_vhangup),
+];
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const SYSCALLS: &[(&str, c_long)] = &[];
+
+/// The number of the system call `name` on this architecture.
+pub fn syscall_number(name: &str) -> Option<c_long> {
+    SYSCALLS
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map(|&(_, nr)| nr)
+}
+
+/// Compile a filter killing a process that makes any of [`DEFAULT_DENIED`] or
+/// `extra`.
+///
+/// Names soar does not know are left out with a warning. `None` on
+/// architectures the filter is not written for.
+pub fn compile(extra: &[String]) -> Option<Vec<sock_filter>> {
+    let arch = AUDIT_ARCH?;
+
+    let mut numbers = DEFAULT_DENIED
+        .iter()
+        .copied()
+        .chain(extra.iter().map(String::as_str))
+        .filter_map(|name| {
+            let nr = syscall_number(name);
+            if nr.is_none() {
+                warn!(
+                    "Unknown system call '{}' in sandbox deny list, not filtered",
+                    name
+                );
+            }
+            nr
+        })
+        .collect::<Vec<_>>();
+    numbers.sort_unstable();
+    numbers.dedup();
+
+    let kill = stmt(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_KILL_PROCESS);
+    let mut filter = vec![
+        // A binary for another architecture numbers its calls differently.
+        stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, 4),
+        jump(libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K, arch, 1, 0),
+        kill,
+        stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, 0),
+        jump(
+            libc::BPF_JMP | libc::BPF_JGE | libc::BPF_K,
+            X32_SYSCALL_BIT,
+            0,
+            1,
+        ),
+        kill,
+    ];
+    for nr in numbers {
+        filter.push(jump(
+            libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+            nr as u32,
+            0,
+            1,
+        ));
+        filter.push(kill);
+    }
+    filter.extend([
+        // `clone` may not create namespaces.
+        jump(
+            libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+            libc::SYS_clone as u32,
+            0,
+            3,
+        ),
+        stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, ARG0_LOW),
+        jump(
+            libc::BPF_JMP | libc::BPF_JSET | libc::BPF_K,
+            CLONE_NAMESPACES as u32,
+            0,
+            1,
+        ),
+        kill,
+        stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, 0),
+        jump(
+            libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+            libc::SYS_clone3 as u32,
+            0,
+            1,
+        ),
+        stmt(
+            libc::BPF_RET | libc::BPF_K,
+            libc::SECCOMP_RET_ERRNO | libc::ENOSYS as u32,
+        ),
+        stmt(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_ALLOW),
+    ]);
+    Some(filter)
+}
+
+/// Load `filter` into the current process.
+///
+/// Meant for a `pre_exec` hook: it only makes system calls.
+pub fn install(filter: &[sock_filter]) -> io::Result<()> {
+    let prog = sock_fprog {
+        len: filter.len() as u16,
+        filter: filter.as_ptr() as *mut sock_filter,
+    };
+    // SAFETY: both calls only read their arguments; `prog` outlives them.
+    unsafe {
+        if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if libc::syscall(
+            libc::SYS_seccomp,
+            libc::SECCOMP_SET_MODE_FILTER,
+            0,
+            &prog as *const sock_fprog,
+        ) != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+fn stmt(code: u32, k: u32) -> sock_filter {
+    jump(code, k, 0, 0)
+}
+
+fn jump(code: u32, k: u32, jt: u8, jf: u8) -> sock_filter {
+    sock_filter {
+        code: code as u16,
+        jt,
+        jf,
+        k,
+    }
+}
+
+#[cfg(all(test, any(target_arch = "x86_64", target_arch = "aarch64")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_default_denied_syscall_is_known() {
+        for name in DEFAULT_DENIED {
+            assert!(syscall_number(name).is_some(), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_compile_adds_extras_once_and_skips_unknown_names() {
+        let base = compile(&[]).unwrap().len();
+
+        let filter = compile(&["socket".into(), "ptrace".into(), "frobnicate".into()]).unwrap();
+
+        // Each denied call is a comparison and a kill.
+        assert_eq!(filter.len(), base + 2);
+    }
+
+    /// Run `probe` in a child under the default filter, returning how the
+    /// child ended.
+    fn under_filter(probe: fn() -> i32) -> libc::c_int {
+        let filter = compile(&[]).unwrap();
+        // SAFETY: the child only makes system calls before exiting.
+        unsafe {
+            let pid = libc::fork();
+            if pid == 0 {
+                if install(&filter).is_err() {
+                    libc::_exit(100);
+                }
+                libc::_exit(probe());
+            }
+            let mut status = 0;
+            libc::waitpid(pid, &mut status, 0);
+            status
+        }
+    }
+
+    #[test]
+    fn test_clone_may_not_create_namespaces() {
+        let status = under_filter(|| unsafe {
+            libc::syscall(
+                libc::SYS_clone,
+                libc::CLONE_NEWUSER | libc::SIGCHLD,
+                0,
+                0,
+                0,
+                0,
+            ) as i32
+        });
+        assert!(libc::WIFSIGNALED(status));
+        assert_eq!(libc::WTERMSIG(status), libc::SIGSYS);
+
+        let status = under_filter(|| unsafe {
+            let ret = libc::syscall(libc::SYS_clone3, std::ptr::null::<u8>(), 0);
+            if ret < 0 {
+                *libc::__errno_location()
+            } else {
+                0
+            }
+        });
+        assert!(libc::WIFEXITED(status));
+        assert_eq!(libc::WEXITSTATUS(status), libc::ENOSYS);
+    }
+}
@@ -0,0 +1,13 @@
+-- Which installed packages provide each command in the bin directory. The
+-- link goes to the selected provider, or else to the one with the highest
+-- priority, the earliest registered winning a tie.
+CREATE TABLE alternatives (
+  id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+  name TEXT NOT NULL,
+  package_id INTEGER NOT NULL,
+  target TEXT NOT NULL,
+  priority INTEGER NOT NULL DEFAULT 0,
+  selected BOOLEAN NOT NULL DEFAULT false,
+  UNIQUE (name, package_id),
+  FOREIGN KEY (package_id) REFERENCES packages (id) ON DELETE CASCADE
+);
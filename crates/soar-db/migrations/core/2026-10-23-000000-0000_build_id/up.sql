@@ -0,0 +1,3 @@
+-- The build an install came from, so a snapshot named by build id can be
+-- told apart from another build of the same version.
+ALTER TABLE packages ADD COLUMN build_id TEXT;
@@ -0,0 +1,2 @@
+DROP TABLE packages_fts;
+DROP VIEW packages_search;
@@ -0,0 +1,47 @@
+-- What a search matches against, one row per package. The JSON columns are
+-- flattened to plain words here so the index never has to know their shape:
+-- `provides` is a list of objects in a database soar built and a list of
+-- strings in one an older generator published, and either may hold text JSON
+-- or JSONB. A value that is neither contributes nothing rather than failing
+-- the whole migration.
+CREATE VIEW packages_search AS
+SELECT
+  p.id AS id,
+  p.pkg_name AS pkg_name,
+  p.pkg_id AS pkg_id,
+  p.app_id AS app_id,
+  (
+    SELECT group_concat(
+      CASE WHEN type = 'object'
+        THEN concat_ws(' ', json_extract(value, '$.name'), json_extract(value, '$.target'))
+        ELSE value
+      END, ' ')
+    FROM json_each(CASE WHEN json_valid(p.provides, 9) THEN p.provides END)
+  ) AS provides,
+  (
+    SELECT group_concat(value, ' ')
+    FROM json_each(CASE WHEN json_valid(p.categories, 9) THEN p.categories END)
+  ) AS categories,
+  p.description AS description
+FROM packages p;
+
+-- Contentless, so the words are stored once, in `packages`, and the index holds
+-- only what it needs to rank. External content would read them back out of the
+-- view instead, but FTS5 qualifies every table the view names with its schema,
+-- and `main.json_each` does not exist. The index is rebuilt from the view
+-- whenever the packages change rather than kept in step by triggers, since an
+-- import replaces every row at once.
+CREATE VIRTUAL TABLE packages_fts USING fts5(
+  pkg_name,
+  pkg_id,
+  app_id,
+  provides,
+  categories,
+  description,
+  content = '',
+  tokenize = 'unicode61 remove_diacritics 2',
+  prefix = '2 3'
+);
+
+INSERT INTO packages_fts (rowid, pkg_name, pkg_id, app_id, provides, categories, description)
+SELECT id, pkg_name, pkg_id, app_id, provides, categories, description FROM packages_search;
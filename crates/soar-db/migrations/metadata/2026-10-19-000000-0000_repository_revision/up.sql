@@ -0,0 +1,3 @@
+-- The metadata revision the packages table holds, as named by the
+-- repository's delta manifest. NULL until a sync has verified one.
+ALTER TABLE repository ADD COLUMN revision TEXT;
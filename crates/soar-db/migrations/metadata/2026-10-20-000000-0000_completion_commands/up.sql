@@ -0,0 +1,3 @@
+-- Commands that print a package's shell completions, as a JSON object from
+-- shell name to command, for packages that ship none of their own.
+ALTER TABLE packages ADD COLUMN completion_commands JSONB;
@@ -0,0 +1,3 @@
+-- How a package's commands are started, as a JSON object with `env`, `args`
+-- and `pre_launch`, for programs that need variables or flags to run well.
+ALTER TABLE packages ADD COLUMN launch JSONB;
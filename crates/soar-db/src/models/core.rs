@@ -27,6 +27,8 @@ pub struct Package {
     pub download_url: Option<String>,
     /// The AppImage `.upd_info` string, which names a zsync feed.
     pub update_info: Option<String>,
+    /// The repository build it came from, when the repository names one.
+    pub build_id: Option<String>,
 }
 
 impl Queryable<packages::SqlType, Sqlite> for Package {
@@ -51,6 +53,7 @@ impl Queryable<packages::SqlType, Sqlite> for Package {
         Option<Value>,
         Option<String>,
         Option<String>,
+        Option<String>,
     );
 
     fn build(row: Self::Row) -> diesel::deserialize::Result<Self> {
@@ -75,6 +78,7 @@ impl Queryable<packages::SqlType, Sqlite> for Package {
             install_patterns: json_vec!(row.17),
             download_url: row.18,
             update_info: row.19,
+            build_id: row.20,
         })
     }
 }
@@ -113,6 +117,7 @@ pub struct NewPackage<'a> {
     pub install_patterns: Option<Value>,
     pub download_url: Option<&'a str>,
     pub update_info: Option<&'a str>,
+    pub build_id: Option<&'a str>,
 }
 
 #[derive(Default, Insertable)]
@@ -125,3 +130,28 @@ pub struct NewPortablePackage<'a> {
     pub portable_share: Option<&'a str>,
     pub portable_cache: Option<&'a str>,
 }
+
+/// One installed package providing a command in the bin directory.
+#[derive(Debug, Clone, Queryable, Selectable)]
+#[diesel(table_name = alternatives)]
+#[diesel(check_for_backend(diesel::sqlite::Sqlite))]
+pub struct Alternative {
+    pub id: i32,
+    /// The command's name in the bin directory.
+    pub name: String,
+    pub package_id: i32,
+    /// What the package links the command to.
+    pub target: String,
+    pub priority: i32,
+    /// Whether the user chose this provider over the priorities.
+    pub selected: bool,
+}
+
+#[derive(Default, Insertable)]
+#[diesel(table_name = alternatives)]
+pub struct NewAlternative<'a> {
+    pub name: &'a str,
+    pub package_id: i32,
+    pub target: &'a str,
+    pub priority: i32,
+}
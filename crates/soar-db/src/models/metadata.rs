@@ -1,9 +1,11 @@
+use std::collections::BTreeMap;
+
 use diesel::{prelude::*, sqlite::Sqlite};
 use serde_json::Value;
 
 use crate::{
     json_vec,
-    models::types::{PackageExtra, PackageFile, PackageProvide},
+    models::types::{PackageExtra, PackageFile, PackageLaunch, PackageProvide, PackageSnapshot},
     schema::metadata::*,
 };
 
@@ -38,7 +40,7 @@ pub struct Package {
     pub build_script: Option<String>,
     pub build_log: Option<String>,
     pub provides: Option<Vec<PackageProvide>>,
-    pub snapshots: Option<Vec<String>>,
+    pub snapshots: Option<Vec<PackageSnapshot>>,
     pub replaces: Option<Vec<String>>,
     pub soar_syms: bool,
     pub desktop_integration: Option<bool>,
@@ -48,6 +50,10 @@ pub struct Package {
     pub extra: Option<Vec<PackageExtra>>,
     /// What the package takes out of its artifact.
     pub files: Option<Vec<PackageFile>>,
+    /// Commands printing the package's completions, by shell or `man`.
+    pub completion_commands: Option<BTreeMap<String, String>>,
+    /// How the package's commands are started.
+    pub launch: Option<PackageLaunch>,
 }
 
 impl Queryable<packages::SqlType, Sqlite> for Package {
@@ -88,6 +94,8 @@ impl Queryable<packages::SqlType, Sqlite> for Package {
         Option<bool>,
         Option<Value>,
         Option<Value>,
+        Option<Value>,
+        Option<Value>,
     );
 
     fn build(row: Self::Row) -> diesel::deserialize::Result<Self> {
@@ -128,6 +136,8 @@ impl Queryable<packages::SqlType, Sqlite> for Package {
             portable: row.33,
             extra: json_vec!(row.34),
             files: json_vec!(row.35),
+            completion_commands: json_vec!(row.36),
+            launch: json_vec!(row.37),
         })
     }
 }
@@ -229,6 +239,8 @@ pub struct NewPackage<'a> {
     pub portable: Option<bool>,
     pub extra: Option<Value>,
     pub files: Option<Value>,
+    pub completion_commands: Option<Value>,
+    pub launch: Option<Value>,
 }
 
 #[derive(Default, Insertable)]
@@ -252,6 +264,7 @@ pub struct Repository {
     pub rowid: i32,
     pub name: String,
     pub etag: String,
+    pub revision: Option<String>,
 }
 
 #[derive(Default, Insertable)]
@@ -1,3 +1,5 @@
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 use soar_utils::path::is_safe_component;
 
@@ -151,6 +153,18 @@ pub struct PackageFile {
     pub alias: Vec<String>,
 }
 
+/// How a package's commands are started: variables set for them, arguments
+/// put before the user's, and a shell command run first.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PackageLaunch {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_launch: Option<String>,
+}
+
 /// A side file installed alongside the artifact, pinned by hash.
 ///
 /// Exists because some artifacts are a bare binary with no room for a licence,
@@ -164,3 +178,55 @@ pub struct PackageExtra {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub sha256: Option<String>,
 }
+
+/// A past build of a package that its repository still serves.
+///
+/// Read through the registry's form, so a snapshot stored as a bare version
+/// string reads as one with only a version.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(from = "soar_registry::RemoteSnapshot")]
+pub struct PackageSnapshot {
+    pub version: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ghcr_tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub download_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bsum: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+impl PackageSnapshot {
+    /// Whether `wanted` names this build, by its version or its id.
+    pub fn matches(&self, wanted: &str) -> bool {
+        self.version == wanted || self.id.as_deref() == Some(wanted)
+    }
+}
+
+impl std::fmt::Display for PackageSnapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.id {
+            Some(ref id) => write!(f, "{} ({id})", self.version),
+            None => write!(f, "{}", self.version),
+        }
+    }
+}
+
+impl From<soar_registry::RemoteSnapshot> for PackageSnapshot {
+    fn from(snapshot: soar_registry::RemoteSnapshot) -> Self {
+        Self {
+            version: snapshot.version,
+            id: snapshot.id,
+            date: snapshot.date,
+            ghcr_tag: snapshot.ghcr_tag,
+            download_url: snapshot.download_url,
+            bsum: snapshot.bsum,
+            size: snapshot.size,
+        }
+    }
+}
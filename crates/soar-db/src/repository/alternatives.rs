@@ -0,0 +1,232 @@
+//! Which installed package provides each command in the bin directory.
+//!
+//! Several packages can ship a command of the same name. Each registers it
+//! here with a priority, and the link goes to the provider the user selected,
+//! or else to the linked install with the highest priority. A tie goes to the
+//! provider registered first, so installing another package never takes a
+//! command from the one holding it without saying so.
+
+use diesel::prelude::*;
+
+use crate::{
+    models::core::{Alternative, NewAlternative, Package},
+    schema::core::{alternatives, packages},
+};
+
+/// Repository for the command alternatives of installed packages.
+pub struct AlternativeRepository;
+
+impl AlternativeRepository {
+    /// Registers a package as a provider of a command, or updates its target
+    /// and priority when it already is one. A selection is kept.
+    pub fn register(conn: &mut SqliteConnection, alternative: &NewAlternative) -> QueryResult<()> {
+        let updated = diesel::update(
+            alternatives::table
+                .filter(alternatives::name.eq(alternative.name))
+                .filter(alternatives::package_id.eq(alternative.package_id)),
+        )
+        .set((
+            alternatives::target.eq(alternative.target),
+            alternatives::priority.eq(alternative.priority),
+        ))
+        .execute(conn)?;
+
+        if updated == 0 {
+            diesel::insert_into(alternatives::table)
+                .values(alternative)
+                .execute(conn)?;
+        }
+        Ok(())
+    }
+
+    /// Changes what a package links a command to.
+    ///
+    /// # Returns
+    ///
+    /// The number of rows updated, which is 0 when the package never
+    /// registered the command.
+    pub fn set_target(
+        conn: &mut SqliteConnection,
+        name: &str,
+        package_id: i32,
+        target: &str,
+    ) -> QueryResult<usize> {
+        diesel::update(
+            alternatives::table
+                .filter(alternatives::name.eq(name))
+                .filter(alternatives::package_id.eq(package_id)),
+        )
+        .set(alternatives::target.eq(target))
+        .execute(conn)
+    }
+
+    /// The providers of a command that can hold it, best first: the selected
+    /// one, then by priority, then the earliest registered.
+    ///
+    /// A package that is not installed, or not linked because another variant
+    /// of it is, does not provide anything.
+    pub fn providers(
+        conn: &mut SqliteConnection,
+        name: &str,
+    ) -> QueryResult<Vec<(Alternative, Package)>> {
+        alternatives::table
+            .inner_join(packages::table)
+            .filter(alternatives::name.eq(name))
+            .filter(packages::is_installed.eq(true))
+            .filter(packages::unlinked.eq(false))
+            .order((
+                alternatives::selected.desc(),
+                alternatives::priority.desc(),
+                alternatives::id.asc(),
+            ))
+            .select((Alternative::as_select(), Package::as_select()))
+            .load(conn)
+    }
+
+    /// The commands with more than one provider that can hold them, sorted.
+    pub fn contested(conn: &mut SqliteConnection) -> QueryResult<Vec<String>> {
+        alternatives::table
+            .inner_join(packages::table)
+            .filter(packages::is_installed.eq(true))
+            .filter(packages::unlinked.eq(false))
+            .group_by(alternatives::name)
+            .having(diesel::dsl::count_star().gt(1))
+            .order(alternatives::name.asc())
+            .select(alternatives::name)
+            .load(conn)
+    }
+
+    /// The commands a package registered, with what it links each to.
+    pub fn for_package(
+        conn: &mut SqliteConnection,
+        package_id: i32,
+    ) -> QueryResult<Vec<Alternative>> {
+        alternatives::table
+            .filter(alternatives::package_id.eq(package_id))
+            .order(alternatives::name.asc())
+            .select(Alternative::as_select())
+            .load(conn)
+    }
+
+    /// Selects one package's provider of a command over the priorities.
+    pub fn select(conn: &mut SqliteConnection, name: &str, package_id: i32) -> QueryResult<usize> {
+        Self::clear_selection(conn, name)?;
+        diesel::update(
+            alternatives::table
+                .filter(alternatives::name.eq(name))
+                .filter(alternatives::package_id.eq(package_id)),
+        )
+        .set(alternatives::selected.eq(true))
+        .execute(conn)
+    }
+
+    /// Leaves a command to the priorities again.
+    pub fn clear_selection(conn: &mut SqliteConnection, name: &str) -> QueryResult<usize> {
+        diesel::update(alternatives::table.filter(alternatives::name.eq(name)))
+            .set(alternatives::selected.eq(false))
+            .execute(conn)
+    }
+
+    /// Hands what one install registered to the install replacing it, which
+    /// keeps the place and selection an update would otherwise lose.
+    pub fn transfer(conn: &mut SqliteConnection, from: i32, to: i32) -> QueryResult<usize> {
+        diesel::update(alternatives::table.filter(alternatives::package_id.eq(from)))
+            .set(alternatives::package_id.eq(to))
+            .execute(conn)
+    }
+
+    /// Deletes what a package registered.
+    pub fn delete_for_package(conn: &mut SqliteConnection, package_id: i32) -> QueryResult<usize> {
+        diesel::delete(alternatives::table.filter(alternatives::package_id.eq(package_id)))
+            .execute(conn)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        connection::DbConnection, migration::DbType, models::core::NewPackage,
+        repository::core::CoreRepository,
+    };
+
+    fn install(conn: &mut SqliteConnection, pkg_name: &str) -> i32 {
+        let path = format!("/p/{pkg_name}");
+        CoreRepository::insert(
+            conn,
+            &NewPackage {
+                repo_name: "r",
+                pkg_name,
+                version: "1",
+                installed_path: &path,
+                installed_date: "now",
+                profile: "default",
+                is_installed: true,
+                ..Default::default()
+            },
+        )
+        .unwrap()
+    }
+
+    fn register(conn: &mut SqliteConnection, package_id: i32, priority: i32) {
+        let target = format!("/p/{package_id}/vi");
+        AlternativeRepository::register(
+            conn,
+            &NewAlternative {
+                name: "vi",
+                package_id,
+                target: &target,
+                priority,
+            },
+        )
+        .unwrap();
+    }
+
+    fn best(conn: &mut SqliteConnection) -> Option<String> {
+        AlternativeRepository::providers(conn, "vi")
+            .unwrap()
+            .first()
+            .map(|(_, package)| package.pkg_name.clone())
+    }
+
+    #[test]
+    fn the_selection_then_priority_then_the_first_registered_wins() {
+        let mut db = DbConnection::open(":memory:", DbType::Core).unwrap();
+        let conn = db.conn();
+        let (vim, nvim, busybox) = (
+            install(conn, "vim"),
+            install(conn, "neovim"),
+            install(conn, "busybox"),
+        );
+
+        register(conn, vim, 0);
+        register(conn, nvim, 0);
+        assert_eq!(best(conn).as_deref(), Some("vim"));
+        assert_eq!(AlternativeRepository::contested(conn).unwrap(), ["vi"]);
+
+        register(conn, busybox, 10);
+        assert_eq!(best(conn).as_deref(), Some("busybox"));
+
+        AlternativeRepository::select(conn, "vi", nvim).unwrap();
+        assert_eq!(best(conn).as_deref(), Some("neovim"));
+        // Registering again, as an update does, keeps the selection.
+        register(conn, nvim, 0);
+        assert_eq!(best(conn).as_deref(), Some("neovim"));
+
+        AlternativeRepository::clear_selection(conn, "vi").unwrap();
+        CoreRepository::set_unlinked(conn, busybox, true).unwrap();
+        assert_eq!(best(conn).as_deref(), Some("vim"));
+
+        // An update keeps the place of the version it replaces.
+        let vim2 = install(conn, "vim");
+        AlternativeRepository::transfer(conn, vim, vim2).unwrap();
+        register(conn, vim2, 0);
+        CoreRepository::set_unlinked(conn, vim, true).unwrap();
+        assert_eq!(best(conn).as_deref(), Some("vim"));
+        let vim = vim2;
+
+        AlternativeRepository::delete_for_package(conn, vim).unwrap();
+        assert_eq!(best(conn).as_deref(), Some("neovim"));
+        assert!(AlternativeRepository::contested(conn).unwrap().is_empty());
+    }
+}
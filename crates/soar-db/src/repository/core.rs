@@ -83,6 +83,7 @@ pub struct InstalledPackageWithPortable {
     pub portable_cache: Option<String>,
     pub download_url: Option<String>,
     pub update_info: Option<String>,
+    pub build_id: Option<String>,
 }
 
 impl From<(Package, Option<PortablePackage>)> for InstalledPackageWithPortable {
@@ -113,6 +114,7 @@ impl From<(Package, Option<PortablePackage>)> for InstalledPackageWithPortable {
             portable_cache: portable.as_ref().and_then(|p| p.portable_cache.clone()),
             download_url: pkg.download_url,
             update_info: pkg.update_info,
+            build_id: pkg.build_id,
         }
     }
 }
@@ -463,6 +465,7 @@ impl CoreRepository {
         size: i64,
         provides: Option<Vec<PackageProvide>>,
         checksum: Option<&str>,
+        build_id: Option<&str>,
         installed_date: &str,
         installed_path: &str,
     ) -> QueryResult<Option<i32>> {
@@ -481,6 +484,7 @@ impl CoreRepository {
             packages::is_installed.eq(true),
             packages::provides.eq(provides),
             packages::checksum.eq(checksum),
+            packages::build_id.eq(build_id),
             packages::installed_path.eq(installed_path),
         ))
         .returning(packages::id)
@@ -519,8 +523,8 @@ impl CoreRepository {
         // `pkg_id IS NULL` matches every id-less row, which now includes the
         // package being installed, so it would unlink itself.
         //
-        // Switching between versions of one package passes no version, since
-        // there every other version is exactly what has to be unlinked.
+        // Without a version every version of the kept package stays linked,
+        // so switching to one of several installed side by side passes it.
         let stale = Self::rows_other_than(
             conn,
             pkg_name,
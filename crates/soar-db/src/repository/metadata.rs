@@ -4,12 +4,18 @@
 // reports against the field it was generated from.
 #![allow(clippy::redundant_field_names)]
 
-use std::sync::OnceLock;
-
-use diesel::{dsl::sql, prelude::*, sql_types::Text};
+use std::{collections::HashMap, sync::OnceLock};
+
+use diesel::{
+    connection::{DefaultLoadingMode, SimpleConnection},
+    dsl::sql,
+    prelude::*,
+    sql_types::{BigInt, Bool, Double, Integer, Text},
+    sqlite::Sqlite,
+};
 use regex::Regex;
 use serde_json::json;
-use soar_registry::RemotePackage;
+use soar_registry::{MetadataDelta, PackageKey, RemotePackage};
 use soar_utils::{
     path::is_safe_component,
     version::{compare_versions, is_newer, is_ordered},
@@ -38,6 +44,143 @@ struct PkgIdOnly {
     pkg_id: String,
 }
 
+/// Helper struct for the first pass of a ranked search.
+#[derive(Debug, QueryableByName)]
+struct RankedId {
+    #[diesel(sql_type = Integer)]
+    id: i32,
+    #[diesel(sql_type = Bool)]
+    exact: bool,
+    #[diesel(sql_type = Double)]
+    score: f64,
+}
+
+#[derive(Debug, QueryableByName)]
+struct TableCount {
+    #[diesel(sql_type = BigInt)]
+    n: i64,
+}
+
+/// Helper struct for one package's line of the revision hash.
+#[derive(Debug, QueryableByName)]
+struct RevisionLine {
+    #[diesel(sql_type = Text)]
+    line: String,
+}
+
+/// Every column a publisher sets, one JSON array per package, in an order
+/// that depends on nothing but the packages themselves.
+///
+/// JSON columns go through `json()` so a database holding them as text hashes
+/// the same as one holding JSONB, and row ids are left out because a delta
+/// and a full import number rows differently.
+const REVISION_LINES: &str = "SELECT json_array(pkg_id, pkg_name, pkg_family, pkg_type, app_id, \
+     description, version, json(licenses), download_url, size, ghcr_pkg, ghcr_size, ghcr_blob, \
+     ghcr_url, bsum, icon, desktop, appstream, json(homepages), json(notes), json(source_urls), \
+     json(categories), build_id, build_date, build_action, build_script, build_log, \
+     json(provides), json(snapshots), json(replaces), soar_syms, desktop_integration, portable, \
+     json(extra), json(files), json(completion_commands), json(launch)) AS line \
+     FROM packages ORDER BY line";
+
+/// Per-column BM25 weights, in the order `packages_fts` declares its columns:
+/// name, id, app id, provides, categories, description.
+///
+/// A word in the name says far more about what a package is than the same
+/// word somewhere in a description, which would otherwise rank a long
+/// description mentioning "editor" three times above the editor itself.
+const BM25_WEIGHTS: &str = "10.0, 6.0, 4.0, 6.0, 2.0, 1.0";
+
+/// Narrows a search by what a package is, rather than by what it says.
+///
+/// Values given for one field are alternatives, while separate fields must
+/// all hold, so `category:audio category:video type:appimage` asks for an
+/// AppImage in either category. Values compare case-insensitively.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SearchFilters {
+    pub categories: Vec<String>,
+    pub pkg_types: Vec<String>,
+    pub licenses: Vec<String>,
+}
+
+impl SearchFilters {
+    /// Whether no filter is set.
+    pub fn is_empty(&self) -> bool {
+        self.categories.is_empty() && self.pkg_types.is_empty() && self.licenses.is_empty()
+    }
+}
+
+/// A package found by [`MetadataRepository::search_ranked`].
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub package: Package,
+    /// Whether the query named the package outright.
+    pub exact: bool,
+    /// BM25 relevance as SQLite reports it, where lower is better. Zero where
+    /// nothing was ranked: a search by filters alone, or one against a
+    /// database that predates the index.
+    pub score: f64,
+}
+
+/// Turn free text into an FTS5 match expression, or `None` if it holds
+/// nothing to match.
+///
+/// Every word is quoted, so one that happens to read as FTS5 syntax (`AND`,
+/// `name:x`, a stray quote) is searched for rather than interpreted, and
+/// matched as a prefix, so `fire` finds `firefox`. A word made only of
+/// punctuation tokenizes to nothing and is dropped, since an empty phrase is
+/// an error rather than a match.
+fn fts_match_expression(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split_whitespace()
+        .filter(|word| word.chars().any(char::is_alphanumeric))
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect();
+
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+/// `?, ?, ?` for `n` bound values.
+fn placeholders(n: usize) -> String {
+    vec!["?"; n].join(", ")
+}
+
+/// Append the SQL for `filters` to a query over `packages p`.
+fn push_filters<'f, Q>(
+    mut query: diesel::query_builder::BoxedSqlQuery<'f, Sqlite, Q>,
+    filters: &SearchFilters,
+) -> diesel::query_builder::BoxedSqlQuery<'f, Sqlite, Q> {
+    // The JSON columns go through `json_valid` first, for the same reason the
+    // index's view does: one malformed value must not fail every search.
+    for (values, column) in [
+        (&filters.categories, "p.categories"),
+        (&filters.licenses, "p.licenses"),
+    ] {
+        if values.is_empty() {
+            continue;
+        }
+        query = query.sql(format!(
+            " AND EXISTS (SELECT 1 FROM json_each(CASE WHEN json_valid({column}, 9) THEN \
+             {column} END) WHERE LOWER(value) IN ({}))",
+            placeholders(values.len())
+        ));
+        for value in values {
+            query = query.bind::<Text, _>(value.to_lowercase());
+        }
+    }
+
+    if !filters.pkg_types.is_empty() {
+        query = query.sql(format!(
+            " AND LOWER(p.pkg_type) IN ({})",
+            placeholders(filters.pkg_types.len())
+        ));
+        for value in &filters.pkg_types {
+            query = query.bind::<Text, _>(value.to_lowercase());
+        }
+    }
+
+    query
+}
+
 /// Narrow candidates to those carrying `pkg_id`, unless none of them do.
 ///
 /// An id recorded at install time may have disappeared from the metadata,
@@ -195,17 +338,125 @@ impl MetadataRepository {
             .optional()
     }
 
-    /// Updates the repository metadata (name and etag).
+    /// Gets the metadata revision the database was last verified at.
+    pub fn get_repo_revision(conn: &mut SqliteConnection) -> QueryResult<Option<String>> {
+        repository::table
+            .select(repository::revision)
+            .first(conn)
+            .optional()
+            .map(Option::flatten)
+    }
+
+    /// Updates the repository metadata (name, etag and revision).
     pub fn update_repo_metadata(
         conn: &mut SqliteConnection,
         name: &str,
         etag: &str,
+        revision: Option<&str>,
     ) -> QueryResult<usize> {
         diesel::update(repository::table)
-            .set((repository::name.eq(name), repository::etag.eq(etag)))
+            .set((
+                repository::name.eq(name),
+                repository::etag.eq(etag),
+                repository::revision.eq(revision),
+            ))
             .execute(conn)
     }
 
+    /// Computes the hash identifying the packages the database holds.
+    ///
+    /// Publishers put it in their delta manifest, and a client compares it
+    /// after applying deltas, so it depends only on what the packages say and
+    /// not on how they came to be stored.
+    pub fn revision_hash(conn: &mut SqliteConnection) -> QueryResult<String> {
+        let mut hasher = blake3::Hasher::new();
+        for row in
+            diesel::sql_query(REVISION_LINES).load_iter::<RevisionLine, DefaultLoadingMode>(conn)?
+        {
+            hasher.update(row?.line.as_bytes());
+            hasher.update(b"\n");
+        }
+        Ok(hasher.finalize().to_hex().to_string())
+    }
+
+    /// Applies one delta: removes the packages it names, then inserts the
+    /// ones it adds.
+    pub fn apply_delta(conn: &mut SqliteConnection, delta: &MetadataDelta) -> QueryResult<()> {
+        trace!(
+            from = delta.from,
+            to = delta.to,
+            removed = delta.removed.len(),
+            added = delta.added.len(),
+            "applying metadata delta"
+        );
+        for key in &delta.removed {
+            let ids = Self::find_ids_by_key(conn, key)?;
+            diesel::delete(
+                package_maintainers::table.filter(package_maintainers::package_id.eq_any(&ids)),
+            )
+            .execute(conn)?;
+            diesel::delete(packages::table.filter(packages::id.eq_any(&ids))).execute(conn)?;
+        }
+        for package in &delta.added {
+            Self::insert_remote_package(conn, package)?;
+        }
+        Ok(())
+    }
+
+    /// Applies deltas in order, keeping the result only if it hashes to
+    /// `expected_hash`.
+    ///
+    /// Returns whether the deltas were kept. On a mismatch the database is
+    /// left exactly as it was.
+    pub fn apply_deltas(
+        conn: &mut SqliteConnection,
+        deltas: &[MetadataDelta],
+        expected_hash: &str,
+    ) -> QueryResult<bool> {
+        let result = conn.transaction(|conn| {
+            for delta in deltas {
+                Self::apply_delta(conn, delta)?;
+            }
+            let hash = Self::revision_hash(conn)?;
+            if hash != expected_hash {
+                debug!(
+                    expected = expected_hash,
+                    found = hash,
+                    "metadata after deltas does not match its revision"
+                );
+                return Err(diesel::result::Error::RollbackTransaction);
+            }
+            Self::rebuild_search_index(conn)
+        });
+
+        match result {
+            Ok(()) => Ok(true),
+            Err(diesel::result::Error::RollbackTransaction) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Finds the ids of the packages a delta key names.
+    fn find_ids_by_key(conn: &mut SqliteConnection, key: &PackageKey) -> QueryResult<Vec<i32>> {
+        let mut query = packages::table
+            .select(packages::id)
+            .filter(packages::pkg_name.eq(&key.pkg_name))
+            .filter(packages::version.eq(&key.version))
+            .into_boxed();
+
+        // Mirrors the import, which stores an empty pkg_id as absent.
+        query = match key.pkg_id.as_deref().filter(|s| !s.is_empty()) {
+            Some(pkg_id) => query.filter(packages::pkg_id.eq(pkg_id)),
+            None => query.filter(packages::pkg_id.is_null()),
+        };
+        query = match key.pkg_family.as_deref() {
+            Some(pkg_family) => query.filter(packages::pkg_family.eq(pkg_family)),
+            None => query.filter(packages::pkg_family.is_null()),
+        };
+
+        query.load(conn)
+    }
+
     /// Finds a package by ID using Diesel DSL.
     pub fn find_by_id(conn: &mut SqliteConnection, id: i32) -> QueryResult<Option<Package>> {
         trace!(id = id, "finding package by id");
@@ -321,6 +572,142 @@ impl MetadataRepository {
         result
     }
 
+    /// Whether this database carries the full-text search index.
+    ///
+    /// A database opened read-only was never migrated here, so one published
+    /// before the index existed can still turn up without it.
+    pub fn has_search_index(conn: &mut SqliteConnection) -> QueryResult<bool> {
+        let found = diesel::sql_query(
+            "SELECT count(*) AS n FROM sqlite_master WHERE type = 'table' AND name = 'packages_fts'",
+        )
+        .get_result::<TableCount>(conn)?;
+
+        Ok(found.n > 0)
+    }
+
+    /// Rebuilds the full-text search index from the packages table.
+    ///
+    /// The index is not kept in step with `packages` as rows change, so
+    /// anything that rewrites the packages has to rebuild it, or searches go
+    /// on matching rows that are no longer there. What it indexes is the
+    /// `packages_search` view, which flattens the JSON columns to words.
+    pub fn rebuild_search_index(conn: &mut SqliteConnection) -> QueryResult<()> {
+        trace!("rebuilding search index");
+        diesel::sql_query("INSERT INTO packages_fts (packages_fts) VALUES ('delete-all')")
+            .execute(conn)?;
+        diesel::sql_query(
+            "INSERT INTO packages_fts \
+             (rowid, pkg_name, pkg_id, app_id, provides, categories, description) \
+             SELECT id, pkg_name, pkg_id, app_id, provides, categories, description \
+             FROM packages_search",
+        )
+        .execute(conn)?;
+        Ok(())
+    }
+
+    /// Gives a database someone else built a search index over its packages.
+    ///
+    /// A repository that publishes its own database fills `packages` however
+    /// its generator likes, so the index it ships may be missing, or present
+    /// and stale. Either way it is built afresh here, creating the table and
+    /// its view if the database lacks them.
+    pub fn ensure_search_index(conn: &mut SqliteConnection) -> QueryResult<()> {
+        conn.transaction(|conn| {
+            if Self::has_search_index(conn)? {
+                return Self::rebuild_search_index(conn);
+            }
+            trace!("creating search index");
+            conn.batch_execute("DROP VIEW IF EXISTS packages_search")?;
+            conn.batch_execute(include_str!(
+                "../../migrations/metadata/2026-10-18-000000-0000_search_index/up.sql"
+            ))
+        })
+    }
+
+    /// Searches packages through the full-text index, best match first.
+    ///
+    /// `text` matches against the name, id, app id, provides, categories and
+    /// description, each word as a prefix, ranked by BM25 with a package
+    /// named outright ahead of everything else. Empty `text` lists whatever
+    /// `filters` allow, by name. A database without the index falls back to
+    /// matching the name and id with `LIKE`, which finds the same packages a
+    /// search always has, unranked.
+    pub fn search_ranked(
+        conn: &mut SqliteConnection,
+        text: &str,
+        filters: &SearchFilters,
+        limit: Option<i64>,
+    ) -> QueryResult<Vec<SearchHit>> {
+        debug!(text = text, filters = ?filters, limit = ?limit, "ranked search");
+        let name = text.trim().to_lowercase();
+        // SQLite reads a negative limit as none at all.
+        let limit = limit.unwrap_or(-1);
+
+        let query = match fts_match_expression(text) {
+            Some(expression) if Self::has_search_index(conn)? => {
+                let query = diesel::sql_query(format!(
+                    "SELECT p.id AS id, LOWER(p.pkg_name) = ? AS exact, \
+                     bm25(packages_fts, {BM25_WEIGHTS}) AS score \
+                     FROM packages_fts JOIN packages p ON p.id = packages_fts.rowid \
+                     WHERE packages_fts MATCH ?"
+                ))
+                .into_boxed()
+                .bind::<Text, _>(name)
+                .bind::<Text, _>(expression);
+                push_filters(query, filters)
+                    .sql(" ORDER BY exact DESC, score, p.pkg_name LIMIT ?")
+                    .bind::<BigInt, _>(limit)
+            }
+            Some(_) => {
+                trace!("no search index; falling back to LIKE");
+                let like_pattern = format!("%{name}%");
+                let query = diesel::sql_query(
+                    "SELECT p.id AS id, LOWER(p.pkg_name) = ? AS exact, 0.0 AS score \
+                     FROM packages p \
+                     WHERE (LOWER(p.pkg_name) LIKE ? OR LOWER(p.pkg_id) LIKE ?)",
+                )
+                .into_boxed()
+                .bind::<Text, _>(name)
+                .bind::<Text, _>(like_pattern.clone())
+                .bind::<Text, _>(like_pattern);
+                push_filters(query, filters)
+                    .sql(" ORDER BY exact DESC, p.pkg_name LIMIT ?")
+                    .bind::<BigInt, _>(limit)
+            }
+            None => {
+                let query = diesel::sql_query(
+                    "SELECT p.id AS id, 0 AS exact, 0.0 AS score FROM packages p WHERE 1",
+                )
+                .into_boxed();
+                push_filters(query, filters)
+                    .sql(" ORDER BY p.pkg_name LIMIT ?")
+                    .bind::<BigInt, _>(limit)
+            }
+        };
+
+        let ranked = query.load::<RankedId>(conn)?;
+        let ids: Vec<i32> = ranked.iter().map(|r| r.id).collect();
+        let mut packages: HashMap<i32, Package> = Self::find_by_ids(conn, &ids)?
+            .into_iter()
+            .map(|p| (p.id, p))
+            .collect();
+
+        let hits: Vec<SearchHit> = ranked
+            .into_iter()
+            .filter_map(|r| {
+                packages.remove(&r.id).map(|package| {
+                    SearchHit {
+                        package,
+                        exact: r.exact,
+                        score: r.score,
+                    }
+                })
+            })
+            .collect();
+        debug!(count = hits.len(), "ranked search completed");
+        Ok(hits)
+    }
+
     /// Searches packages (case-sensitive LIKE query) using Diesel DSL.
     pub fn search_case_sensitive(
         conn: &mut SqliteConnection,
@@ -602,6 +989,7 @@ impl MetadataRepository {
                     imported += 1;
                 }
             }
+            Self::rebuild_search_index(conn)?;
             debug!(
                 repo_name = repo_name,
                 offered = metadata.len(),
@@ -696,6 +1084,8 @@ impl MetadataRepository {
             portable: package.portable,
             extra: package.extra.as_ref().map(|e| json!(e)),
             files: package.files.as_ref().map(|f| json!(f)),
+            completion_commands: package.completion_commands.as_ref().map(|c| json!(c)),
+            launch: package.launch.as_ref().map(|l| json!(l)),
         };
 
         let inserted = diesel::insert_into(packages::table)
@@ -738,7 +1128,243 @@ impl MetadataRepository {
 
 #[cfg(test)]
 mod tests {
-    use super::supersedes_unordered;
+    use diesel::RunQueryDsl;
+    use soar_registry::{MetadataDelta, PackageKey, RemotePackage};
+
+    use super::{fts_match_expression, supersedes_unordered, MetadataRepository, SearchFilters};
+    use crate::{connection::DbConnection, migration::DbType};
+
+    fn remote(name: &str, description: &str) -> RemotePackage {
+        RemotePackage {
+            pkg_name: name.to_string(),
+            description: description.to_string(),
+            version: "1.0.0".to_string(),
+            download_url: format!("https://example.com/{name}"),
+            ..Default::default()
+        }
+    }
+
+    fn catalog() -> DbConnection {
+        let mut conn = DbConnection::open(":memory:", DbType::Metadata).unwrap();
+        let packages = vec![
+            RemotePackage {
+                pkg_type: Some("appimage".to_string()),
+                categories: Some(vec!["Audio".to_string(), "AudioVideo".to_string()]),
+                licenses: Some(vec!["GPL-3.0".to_string()]),
+                ..remote("audacity", "Cross-platform audio editor")
+            },
+            RemotePackage {
+                pkg_type: Some("static".to_string()),
+                categories: Some(vec!["Utility".to_string()]),
+                licenses: Some(vec!["MIT".to_string()]),
+                provides: Some(vec!["hx".to_string()]),
+                ..remote("helix", "A post-modern modal text editor")
+            },
+            RemotePackage {
+                pkg_type: Some("appimage".to_string()),
+                categories: Some(vec!["Utility".to_string()]),
+                licenses: Some(vec!["MIT".to_string()]),
+                ..remote("editor", "Edits things")
+            },
+        ];
+        MetadataRepository::import_packages(conn.conn(), &packages, "test").unwrap();
+        conn
+    }
+
+    fn names(conn: &mut DbConnection, text: &str, filters: &SearchFilters) -> Vec<String> {
+        MetadataRepository::search_ranked(conn.conn(), text, filters, None)
+            .unwrap()
+            .into_iter()
+            .map(|hit| hit.package.pkg_name)
+            .collect()
+    }
+
+    #[test]
+    fn every_word_is_quoted_and_matched_as_a_prefix() {
+        assert_eq!(
+            fts_match_expression("yt-dlp OR name:x").as_deref(),
+            Some(r#""yt-dlp"* "OR"* "name:x"*"#)
+        );
+        assert_eq!(
+            fts_match_expression(r#"a"b"#).as_deref(),
+            Some(r#""a""b"*"#)
+        );
+    }
+
+    #[test]
+    fn punctuation_alone_matches_nothing() {
+        assert_eq!(fts_match_expression("  - ++ "), None);
+    }
+
+    #[test]
+    fn a_package_named_outright_ranks_first() {
+        let mut conn = catalog();
+        let found = names(&mut conn, "editor", &SearchFilters::default());
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[0], "editor");
+    }
+
+    #[test]
+    fn words_match_as_prefixes_across_columns() {
+        let mut conn = catalog();
+        assert_eq!(
+            names(&mut conn, "auda", &SearchFilters::default()),
+            ["audacity"]
+        );
+        assert_eq!(names(&mut conn, "hx", &SearchFilters::default()), ["helix"]);
+        assert_eq!(
+            names(&mut conn, "modal editor", &SearchFilters::default()),
+            ["helix"]
+        );
+    }
+
+    #[test]
+    fn filters_narrow_the_matches() {
+        let mut conn = catalog();
+        let appimages = SearchFilters {
+            pkg_types: vec!["AppImage".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            names(&mut conn, "editor", &appimages),
+            ["editor", "audacity"]
+        );
+
+        let mit_audio = SearchFilters {
+            categories: vec!["audio".to_string()],
+            licenses: vec!["mit".to_string()],
+            ..Default::default()
+        };
+        assert!(names(&mut conn, "editor", &mit_audio).is_empty());
+    }
+
+    #[test]
+    fn values_for_one_field_are_alternatives() {
+        let mut conn = catalog();
+        let filters = SearchFilters {
+            categories: vec!["audio".to_string(), "utility".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            names(&mut conn, "", &filters),
+            ["audacity", "editor", "helix"]
+        );
+    }
+
+    #[test]
+    fn a_database_without_the_index_still_searches_names() {
+        let mut conn = catalog();
+        diesel::sql_query("DROP TABLE packages_fts")
+            .execute(conn.conn())
+            .unwrap();
+
+        assert!(!MetadataRepository::has_search_index(conn.conn()).unwrap());
+        assert_eq!(
+            names(&mut conn, "hel", &SearchFilters::default()),
+            ["helix"]
+        );
+    }
+
+    #[test]
+    fn a_reimport_is_what_gets_searched() {
+        let mut conn = catalog();
+        MetadataRepository::delete_all(conn.conn()).unwrap();
+        MetadataRepository::import_packages(
+            conn.conn(),
+            &[remote("ardour", "Digital audio workstation")],
+            "test",
+        )
+        .unwrap();
+
+        assert_eq!(
+            names(&mut conn, "audio", &SearchFilters::default()),
+            ["ardour"]
+        );
+    }
+
+    fn imported(packages: &[RemotePackage]) -> DbConnection {
+        let mut conn = DbConnection::open(":memory:", DbType::Metadata).unwrap();
+        MetadataRepository::import_packages(conn.conn(), packages, "test").unwrap();
+        conn
+    }
+
+    fn key(package: &RemotePackage) -> PackageKey {
+        PackageKey {
+            pkg_name: package.pkg_name.clone(),
+            pkg_id: package.pkg_id.clone(),
+            pkg_family: package.pkg_family.clone(),
+            version: package.version.clone(),
+        }
+    }
+
+    #[test]
+    fn deltas_reproduce_the_published_revision() {
+        let kept = remote("helix", "A post-modern modal text editor");
+        let old = remote("audacity", "Cross-platform audio editor");
+        let new = RemotePackage {
+            version: "2.0.0".to_string(),
+            ..remote("audacity", "Cross-platform audio editor")
+        };
+        let added = remote("ardour", "Digital audio workstation");
+
+        let mut conn = imported(&[kept.clone(), old.clone()]);
+        let published =
+            MetadataRepository::revision_hash(imported(&[added.clone(), new.clone(), kept]).conn())
+                .unwrap();
+
+        let delta = MetadataDelta {
+            from: "r1".to_string(),
+            to: "r2".to_string(),
+            removed: vec![key(&old)],
+            added: vec![new, added],
+        };
+        assert!(MetadataRepository::apply_deltas(conn.conn(), &[delta], &published).unwrap());
+        assert_eq!(
+            MetadataRepository::revision_hash(conn.conn()).unwrap(),
+            published
+        );
+        assert_eq!(
+            names(&mut conn, "workstation", &SearchFilters::default()),
+            ["ardour"]
+        );
+    }
+
+    #[test]
+    fn a_delta_that_misses_the_revision_is_rolled_back() {
+        let mut conn = catalog();
+        let before = MetadataRepository::revision_hash(conn.conn()).unwrap();
+        let delta = MetadataDelta {
+            from: "r1".to_string(),
+            to: "r2".to_string(),
+            added: vec![remote("ardour", "Digital audio workstation")],
+            ..Default::default()
+        };
+
+        assert!(!MetadataRepository::apply_deltas(conn.conn(), &[delta], "not-it").unwrap());
+        assert_eq!(
+            MetadataRepository::revision_hash(conn.conn()).unwrap(),
+            before
+        );
+    }
+
+    #[test]
+    fn the_revision_is_stored_with_the_etag() {
+        let mut conn = catalog();
+        assert_eq!(
+            MetadataRepository::get_repo_revision(conn.conn()).unwrap(),
+            None
+        );
+
+        MetadataRepository::update_repo_metadata(conn.conn(), "test", "etag", Some("r2")).unwrap();
+        assert_eq!(
+            MetadataRepository::get_repo_revision(conn.conn()).unwrap(),
+            Some("r2".to_string())
+        );
+        assert_eq!(
+            MetadataRepository::get_repo_etag(conn.conn()).unwrap(),
+            Some("etag".to_string())
+        );
+    }
 
     const HELD: &str = "89c99d2a9";
     const OFFERED: &str = "0f3a21b";
@@ -3,8 +3,10 @@
 //! This module provides type-safe database operations using the repository pattern.
 //! Each repository handles CRUD operations for a specific domain:
 //!
+//! - [`AlternativeRepository`] - Which package provides each command
 //! - [`CoreRepository`] - Installed package operations
 //! - [`MetadataRepository`] - Package metadata queries
 
+pub mod alternatives;
 pub mod core;
 pub mod metadata;
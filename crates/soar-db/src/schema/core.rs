@@ -20,6 +20,7 @@ diesel::table! {
         install_patterns -> Nullable<Jsonb>,
         download_url -> Nullable<Text>,
         update_info -> Nullable<Text>,
+        build_id -> Nullable<Text>,
     }
 
 }
@@ -36,6 +37,18 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    alternatives (id) {
+        id -> Integer,
+        name -> Text,
+        package_id -> Integer,
+        target -> Text,
+        priority -> Integer,
+        selected -> Bool,
+    }
+}
+
 diesel::joinable!(portable_package -> packages (package_id));
+diesel::joinable!(alternatives -> packages (package_id));
 
-diesel::allow_tables_to_appear_in_same_query!(packages, portable_package,);
+diesel::allow_tables_to_appear_in_same_query!(packages, portable_package, alternatives,);
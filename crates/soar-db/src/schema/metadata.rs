@@ -52,6 +52,8 @@ diesel::table! {
         portable -> Nullable<Bool>,
         extra -> Nullable<Jsonb>,
         files -> Nullable<Jsonb>,
+        completion_commands -> Nullable<Jsonb>,
+        launch -> Nullable<Jsonb>,
     }
 }
 
@@ -60,6 +62,7 @@ diesel::table! {
         rowid -> Integer,
         name -> Text,
         etag -> Text,
+        revision -> Nullable<Text>,
     }
 }
 
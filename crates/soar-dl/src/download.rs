@@ -6,7 +6,7 @@ use std::{
     sync::Arc,
 };
 
-use soar_utils::fs::is_elf;
+use soar_utils::{cancel::CancellationToken, entrypoint::is_zip_program, fs::is_elf};
 use tracing::{debug, trace, warn};
 use ureq::{
     http::{
@@ -34,6 +34,7 @@ pub struct Download {
     pub on_progress: Option<Arc<dyn Fn(Progress) + Send + Sync>>,
     pub ghcr_blob: bool,
     pub expected_checksum: Option<String>,
+    pub cancel: Option<CancellationToken>,
 }
 
 impl Download {
@@ -65,6 +66,7 @@ impl Download {
             on_progress: None,
             ghcr_blob: false,
             expected_checksum: None,
+            cancel: None,
         }
     }
 
@@ -83,6 +85,25 @@ impl Download {
         self
     }
 
+    /// Stops the download once `token` is cancelled.
+    ///
+    /// The transfer is checked between chunks. A cancelled download removes
+    /// the partial file, resume checkpoint included, and fails with
+    /// [`DownloadError::Cancelled`]; nothing is extracted.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use soar_dl::download::Download;
+    /// use soar_utils::cancel::CancellationToken;
+    ///
+    /// let _ = Download::new("https://example.com/file").cancel(CancellationToken::new());
+    /// ```
+    pub fn cancel(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
     /// Turns on GHCR blob support.
     ///
     /// When enabled, the `Authorization` header is set to `Bearer QQ==`
@@ -231,6 +252,7 @@ impl Download {
     /// ```
     pub fn execute(self) -> Result<PathBuf, DownloadError> {
         debug!(url = self.url, "starting download");
+        self.check_cancelled()?;
 
         if let Some(ref cb) = self.on_progress {
             cb(Progress::Preparing);
@@ -312,7 +334,13 @@ impl Download {
             std::fs::create_dir_all(parent)?;
         }
 
-        self.download_to_file(&output_path, resume_info)?;
+        if let Err(e) = self.download_to_file(&output_path, resume_info) {
+            if matches!(e, DownloadError::Cancelled) {
+                debug!(path = %output_path.display(), "download cancelled, removing partial file");
+                fs::remove_file(&output_path).ok();
+            }
+            return Err(e);
+        }
 
         if let Err(e) = self.verify_checksum(&output_path) {
             fs::remove_file(&output_path).ok();
@@ -326,11 +354,17 @@ impl Download {
 
         remove_resume(&output_path)?;
 
+        if self.extract && self.check_cancelled().is_err() {
+            fs::remove_file(&output_path).ok();
+            return Err(DownloadError::Cancelled);
+        }
+
         // Extraction is driven by what the file actually is, not by what the
         // caller guessed it would be. compak detects by magic number, so an
         // ELF (an AppImage, say) is never mistaken for an archive even when
         // extraction was requested.
-        if self.extract {
+        // A jar or Python zipapp is a zip, but one that runs whole.
+        if self.extract && !is_zip_program(&output_path) {
             match compak::detect_from_file(&output_path) {
                 Ok(format) => {
                     let extract_dir = self.extract_to.unwrap_or_else(|| {
@@ -359,6 +393,13 @@ impl Download {
         Ok(output_path)
     }
 
+    fn check_cancelled(&self) -> Result<(), DownloadError> {
+        match self.cancel {
+            Some(ref token) if token.is_cancelled() => Err(DownloadError::Cancelled),
+            _ => Ok(()),
+        }
+    }
+
     fn verify_checksum(&self, path: &Path) -> Result<(), DownloadError> {
         let Some(ref expected) = self.expected_checksum else {
             return Ok(());
@@ -488,6 +529,8 @@ impl Download {
         let mut last_checkpoint = downloaded / (1024 * 1024);
 
         loop {
+            self.check_cancelled()?;
+
             let n = reader.read(&mut buffer)?;
             if n == 0 {
                 break;
@@ -613,4 +656,20 @@ mod tests {
             other => panic!("expected ChecksumMismatch, got {other:?}"),
         }
     }
+
+    #[test]
+    fn a_cancelled_download_fails_before_touching_the_network() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("file");
+        let token = CancellationToken::new();
+        token.cancel();
+
+        let result = Download::new("http://127.0.0.1:9/file")
+            .output(output.to_string_lossy())
+            .cancel(token)
+            .execute();
+
+        assert!(matches!(result, Err(DownloadError::Cancelled)));
+        assert!(!output.exists());
+    }
 }
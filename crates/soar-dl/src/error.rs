@@ -78,6 +78,10 @@ pub enum DownloadError {
         help("The publisher's zsync feed may be stale; a plain download still works")
     )]
     Zsync(String),
+
+    #[error("Download cancelled")]
+    #[diagnostic(code(soar_dl::cancelled))]
+    Cancelled,
 }
 
 pub type Result<T> = miette::Result<T>;
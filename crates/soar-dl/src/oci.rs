@@ -12,7 +12,7 @@ use std::{
 
 use serde::Deserialize;
 use sha2::{Digest, Sha256};
-use soar_utils::fs::is_elf;
+use soar_utils::{cancel::CancellationToken, fs::is_elf};
 use tracing::{debug, trace};
 use ureq::http::header::{ACCEPT, AUTHORIZATION, ETAG, IF_RANGE, RANGE};
 
@@ -205,6 +205,7 @@ pub struct OciDownload {
     extract_to: Option<PathBuf>,
     parallel: usize,
     on_progress: Option<Arc<dyn Fn(Progress) + Send + Sync>>,
+    cancel: Option<CancellationToken>,
 }
 
 impl OciDownload {
@@ -239,6 +240,7 @@ impl OciDownload {
             extract_to: None,
             parallel: 1,
             on_progress: None,
+            cancel: None,
         }
     }
 
@@ -387,6 +389,31 @@ impl OciDownload {
         self
     }
 
+    /// Stops the download once `token` is cancelled.
+    ///
+    /// Layers are checked between chunks. The layer in flight is removed along
+    /// with its resume checkpoint, no further layer is started or extracted,
+    /// and the download fails with [`DownloadError::Cancelled`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use soar_dl::oci::OciDownload;
+    /// use soar_utils::cancel::CancellationToken;
+    ///
+    /// let _ = OciDownload::new("owner/repo:tag").cancel(CancellationToken::new());
+    /// ```
+    pub fn cancel(mut self, token: CancellationToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(CancellationToken::is_cancelled)
+    }
+
     /// Downloads the OCI reference according to the configured options and returns the downloaded file paths.
     ///
     /// Attempts a direct blob download if the reference tag is a digest (e.g., `sha256:...`); otherwise it
@@ -514,6 +541,10 @@ impl OciDownload {
         let total_size: u64 = layers.iter().map(|l| l.size).sum();
 
         for layer in layers {
+            if self.is_cancelled() {
+                return Err(DownloadError::Cancelled);
+            }
+
             let filename = layer.title().unwrap();
             let path = safe_layer_path(output_dir, filename)?;
 
@@ -537,6 +568,10 @@ impl OciDownload {
             self.download_layer(layer, &path, &mut downloaded, total_size)?;
 
             if self.extract {
+                if self.is_cancelled() {
+                    return Err(DownloadError::Cancelled);
+                }
+
                 let extract_dir = self
                     .extract_to
                     .clone()
@@ -610,9 +645,14 @@ impl OciDownload {
                 let extract = self.extract;
                 let extract_to = self.extract_to.clone();
                 let on_progress = self.on_progress.clone();
+                let cancel = self.cancel.clone();
 
                 thread::spawn(move || {
                     for layer in chunk {
+                        if cancel.as_ref().is_some_and(CancellationToken::is_cancelled) {
+                            break;
+                        }
+
                         let filename = match layer.title() {
                             Some(f) => f,
                             None => continue,
@@ -657,11 +697,17 @@ impl OciDownload {
                             on_progress.as_ref(),
                             &downloaded,
                             total_size,
+                            cancel.as_ref(),
                         );
 
                         match result {
                             Ok(()) => {
                                 if extract {
+                                    if cancel.as_ref().is_some_and(CancellationToken::is_cancelled)
+                                    {
+                                        break;
+                                    }
+
                                     let extract_dir =
                                         extract_to.clone().unwrap_or_else(|| output_dir.clone());
                                     if let Err(e) = compak::extract_archive(&path, &extract_dir) {
@@ -689,6 +735,12 @@ impl OciDownload {
             }
         }
 
+        // The workers each stop on their own; one cancellation, not one
+        // error per worker.
+        if self.is_cancelled() {
+            return Err(DownloadError::Cancelled);
+        }
+
         let errors = errors.lock().unwrap();
         if !errors.is_empty() {
             return Err(DownloadError::Multiple {
@@ -806,6 +858,11 @@ impl OciDownload {
             dl
         };
 
+        let dl = match self.cancel {
+            Some(ref token) => dl.cancel(token.clone()),
+            None => dl,
+        };
+
         let path = dl.execute()?;
 
         verify_layer_digest(&path, &self.reference.tag)?;
@@ -861,6 +918,7 @@ impl OciDownload {
             self.on_progress.as_ref(),
             &Arc::new(AtomicU64::new(0)),
             total_size,
+            self.cancel.as_ref(),
         )
     }
 }
@@ -873,6 +931,7 @@ impl OciDownload {
 /// - Periodically persists resume metadata while downloading (every 1 MiB).
 /// - Emits `Progress::Chunk` updates via the optional `on_progress` callback using the shared downloaded counter.
 /// - Marks the file executable (0o755) if it appears to be an ELF binary and removes any resume metadata on success.
+/// - Removes the partial file and returns `DownloadError::Cancelled` once `cancel` is cancelled.
 ///
 /// Returns `Ok(())` on success or a `DownloadError` on failure.
 #[allow(clippy::too_many_arguments)]
@@ -885,6 +944,7 @@ fn download_layer_impl(
     on_progress: Option<&Arc<dyn Fn(Progress) + Send + Sync>>,
     shared_downloaded: &Arc<AtomicU64>,
     total_size: u64,
+    cancel: Option<&CancellationToken>,
 ) -> Result<(), DownloadError> {
     let url = format!(
         "{}/{}/blobs/{}",
@@ -969,6 +1029,13 @@ fn download_layer_impl(
     }
 
     loop {
+        if cancel.is_some_and(CancellationToken::is_cancelled) {
+            drop(file);
+            std::fs::remove_file(path).ok();
+            trace!(path = %path.display(), "layer download cancelled");
+            return Err(DownloadError::Cancelled);
+        }
+
         let n = reader.read(&mut buffer)?;
         if n == 0 {
             break;
@@ -8,6 +8,7 @@
 
 use std::{fs::File, path::Path};
 
+use soar_utils::cancel::CancellationToken;
 use tracing::debug;
 use zsync_rs::{checksum::calc_sha1_stream, ControlFile, HttpClient, ZsyncAssembly};
 
@@ -105,11 +106,15 @@ pub fn differs_from(target: &ZsyncTarget, installed: impl AsRef<Path>) -> bool {
 /// The result is verified against the control file's checksums before it is
 /// moved into place, so a mismatched or truncated transfer fails here rather
 /// than producing a broken package.
+///
+/// `cancel` is checked between passes over the missing blocks; a cancelled
+/// transfer discards the partly assembled file and leaves `output` untouched.
 pub fn download<F>(
     url: &str,
     seed: &Path,
     output: &Path,
     on_progress: Option<F>,
+    cancel: Option<&CancellationToken>,
 ) -> Result<(), DownloadError>
 where
     F: Fn(Progress) + Send + Sync + 'static,
@@ -145,6 +150,11 @@ where
     }
 
     while !assembly.is_complete() {
+        if cancel.is_some_and(CancellationToken::is_cancelled) {
+            assembly.abort();
+            return Err(DownloadError::Cancelled);
+        }
+
         let fetched = assembly
             .download_missing_blocks()
             .map_err(|e| DownloadError::Zsync(format!("fetching blocks: {e}")))?;
@@ -157,6 +167,11 @@ where
         }
     }
 
+    if cancel.is_some_and(CancellationToken::is_cancelled) {
+        assembly.abort();
+        return Err(DownloadError::Cancelled);
+    }
+
     assembly
         .complete()
         .map_err(|e| DownloadError::Zsync(format!("verifying zsync result: {e}")))
@@ -110,6 +110,11 @@ pub enum SoarEvent {
         pkg_name: String,
         error: String,
     },
+    /// Operation was cancelled, and what it had done so far was undone.
+    OperationCancelled {
+        op_id: OperationId,
+        pkg_name: String,
+    },
     /// Repository sync progress.
     SyncProgress { repo_name: String, stage: SyncStage },
     /// Batch operation overall progress.
@@ -197,6 +202,8 @@ pub enum SyncStage {
     Decompressing,
     /// Writing metadata to local database.
     WritingDatabase,
+    /// Applying metadata deltas to the local database.
+    ApplyingDeltas { count: usize },
     /// Validating metadata signature.
     Validating,
     /// Sync complete.
@@ -238,8 +245,12 @@ pub enum HookStage {
     Starting,
     /// Hook completed successfully.
     Complete,
-    /// Hook failed.
-    Failed { exit_code: Option<i32> },
+    /// Hook failed. `violation` says which sandbox limit stopped it, if one
+    /// did.
+    Failed {
+        exit_code: Option<i32>,
+        violation: Option<String>,
+    },
 }
 
 /// Package execution stages (run command).
@@ -269,6 +280,13 @@ pub enum BuildStage {
     CommandComplete { command_index: usize },
     /// Activating sandbox for build.
     Sandboxing,
+    /// Build command failed. `violation` says which sandbox limit stopped
+    /// it, if one did.
+    Failed {
+        command_index: usize,
+        exit_code: Option<i32>,
+        violation: Option<String>,
+    },
 }
 
 /// Log levels.
@@ -340,6 +340,7 @@ mod tests {
             hook_name: "post_install".into(),
             stage: HookStage::Failed {
                 exit_code: Some(1),
+                violation: None,
             },
         });
 
@@ -388,6 +389,15 @@ mod tests {
                 command_index: 0,
             },
         });
+        collector.emit(SoarEvent::Building {
+            op_id: 4,
+            pkg_name: "d".into(),
+            stage: BuildStage::Failed {
+                command_index: 1,
+                exit_code: None,
+                violation: Some("ran out of CPU time".into()),
+            },
+        });
 
         // Operation completion
         collector.emit(SoarEvent::OperationComplete {
@@ -417,6 +427,12 @@ mod tests {
             repo_name: "repo".into(),
             stage: SyncStage::WritingDatabase,
         });
+        collector.emit(SoarEvent::SyncProgress {
+            repo_name: "repo".into(),
+            stage: SyncStage::ApplyingDeltas {
+                count: 2,
+            },
+        });
         collector.emit(SoarEvent::SyncProgress {
             repo_name: "repo".into(),
             stage: SyncStage::Validating,
@@ -451,6 +467,6 @@ mod tests {
             message: "error".into(),
         });
 
-        assert_eq!(collector.len(), 55);
+        assert_eq!(collector.len(), 57);
     }
 }
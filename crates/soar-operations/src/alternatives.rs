@@ -0,0 +1,166 @@
+use std::path::PathBuf;
+
+use soar_core::{
+    alternatives::{self, Providers},
+    error::SoarError,
+    SoarResult,
+};
+use soar_db::repository::alternatives::AlternativeRepository;
+use tracing::debug;
+
+use crate::{AlternativeGroup, AlternativeProvider, SoarContext};
+
+/// The providers of `name`, or of every command more than one installed
+/// package provides.
+pub fn list_alternatives(
+    ctx: &SoarContext,
+    name: Option<&str>,
+) -> SoarResult<Vec<AlternativeGroup>> {
+    let diesel_db = ctx.diesel_core_db()?;
+    let names = match name {
+        Some(name) => vec![name.to_string()],
+        None => diesel_db.with_conn(AlternativeRepository::contested)?,
+    };
+
+    let mut groups = Vec::with_capacity(names.len());
+    for name in names {
+        let group = group(Providers::load(diesel_db, &name)?);
+        if group.providers.is_empty() {
+            return Err(SoarError::Custom(format!(
+                "no installed package provides {name}"
+            )));
+        }
+        groups.push(group);
+    }
+    Ok(groups)
+}
+
+/// Give `name` to `package` whatever the priorities say, until
+/// [`auto_alternative`] hands it back to them.
+///
+/// `package` is a pkg_name, a pkg_id, or `pkg_name#pkg_id` when two
+/// providers share a name.
+pub fn set_alternative(
+    ctx: &SoarContext,
+    name: &str,
+    package: &str,
+) -> SoarResult<AlternativeGroup> {
+    debug!(name = name, package = package, "selecting alternative");
+    let diesel_db = ctx.diesel_core_db()?;
+    let group = group(Providers::load(diesel_db, name)?);
+    let package_id = find_provider(&group, package)?.package.id as i32;
+    diesel_db.with_conn(|conn| AlternativeRepository::select(conn, name, package_id))?;
+    activate(ctx, name)
+}
+
+/// Hand `name` back to the priorities.
+pub fn auto_alternative(ctx: &SoarContext, name: &str) -> SoarResult<AlternativeGroup> {
+    debug!(name = name, "leaving alternative to the priorities");
+    let diesel_db = ctx.diesel_core_db()?;
+    diesel_db.with_conn(|conn| AlternativeRepository::clear_selection(conn, name))?;
+    activate(ctx, name)
+}
+
+fn activate(ctx: &SoarContext, name: &str) -> SoarResult<AlternativeGroup> {
+    let providers = Providers::load(ctx.diesel_core_db()?, name)?;
+    if providers.providers.is_empty() {
+        return Err(SoarError::Custom(format!(
+            "no installed package provides {name}"
+        )));
+    }
+    let bin_dir = ctx.config().get_bin_path()?;
+    if !alternatives::activate(&bin_dir, &providers)? {
+        return Err(SoarError::Custom(format!(
+            "{} is not a symlink soar can change",
+            bin_dir.join(name).display()
+        )));
+    }
+    Ok(group(providers))
+}
+
+fn group(providers: Providers) -> AlternativeGroup {
+    let manual = providers.is_manual();
+    AlternativeGroup {
+        name: providers.name,
+        manual,
+        providers: providers
+            .providers
+            .into_iter()
+            .map(|(alternative, package)| {
+                AlternativeProvider {
+                    package,
+                    priority: alternative.priority,
+                    target: PathBuf::from(alternative.target),
+                }
+            })
+            .collect(),
+    }
+}
+
+fn find_provider<'a>(
+    group: &'a AlternativeGroup,
+    package: &str,
+) -> SoarResult<&'a AlternativeProvider> {
+    let matches = group
+        .providers
+        .iter()
+        .filter(|p| names_package(&p.package.pkg_name, p.package.pkg_id.as_deref(), package))
+        .collect::<Vec<_>>();
+
+    match matches.as_slice() {
+        [provider] => Ok(provider),
+        [] => {
+            Err(SoarError::Custom(format!(
+                "{package} does not provide {}; it is provided by {}",
+                group.name,
+                names(group.providers.iter())
+            )))
+        }
+        several => {
+            Err(SoarError::Custom(format!(
+                "{package} matches more than one provider of {}; name one of {}",
+                group.name,
+                names(several.iter().copied())
+            )))
+        }
+    }
+}
+
+/// Whether `package`, as given to [`set_alternative`], names the package
+/// `pkg_name` with `pkg_id`.
+fn names_package(pkg_name: &str, pkg_id: Option<&str>, package: &str) -> bool {
+    match package.split_once('#') {
+        Some((name, id)) => pkg_name == name && pkg_id == Some(id),
+        None => pkg_name == package || pkg_id == Some(package),
+    }
+}
+
+/// Each provider as `pkg_name#pkg_id`, or its pkg_name when it has no pkg_id.
+fn names<'a>(providers: impl Iterator<Item = &'a AlternativeProvider>) -> String {
+    providers
+        .map(|p| {
+            match &p.package.pkg_id {
+                Some(pkg_id) => format!("{}#{pkg_id}", p.package.pkg_name),
+                None => p.package.pkg_name.clone(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_provider_is_named_by_pkg_name_pkg_id_or_both() {
+        assert!(names_package("vim", Some("vim-bin"), "vim"));
+        assert!(names_package("vim", Some("vim-bin"), "vim-bin"));
+        assert!(names_package("vi", Some("busybox"), "vi#busybox"));
+        assert!(names_package("vi", None, "vi"));
+
+        assert!(!names_package("vi", Some("nvi"), "vi#busybox"));
+        assert!(!names_package("vi", None, "vi#busybox"));
+        assert!(!names_package("vim", Some("vim-bin"), "neovim"));
+    }
+}
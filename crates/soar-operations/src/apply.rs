@@ -1,13 +1,15 @@
-use std::collections::HashSet;
+use std::{cmp::Ordering, collections::HashSet, path::Path};
 
 use soar_config::packages::{PackagesConfig, ResolvedPackage};
 use soar_core::{
     database::{
-        connection::DieselDatabase,
+        connection::{DieselDatabase, MetadataManager},
         models::{InstalledPackage, Package},
     },
+    error::SoarError,
     package::{
         install::InstallTarget,
+        launch,
         release_source::{run_version_command, ReleaseSource},
         remove::PackageRemover,
         url::UrlPackage,
@@ -15,23 +17,29 @@ use soar_core::{
     utils::substitute_placeholders,
     SoarResult,
 };
-use soar_db::repository::{
-    core::{CoreRepository, SortDirection},
-    metadata::MetadataRepository,
+use soar_db::{
+    models::types::PackageLaunch,
+    repository::{
+        core::{CoreRepository, SortDirection},
+        metadata::MetadataRepository,
+    },
 };
 use soar_events::{RemoveStage, SoarEvent};
+use soar_utils::version::compare_versions;
 use tracing::{debug, warn};
 
 use crate::{
-    install::perform_installation, progress::next_op_id, utils::get_package_hooks, ApplyDiff,
-    ApplyReport, InstallOptions, SoarContext,
+    install::perform_installation,
+    progress::next_op_id,
+    utils::{get_package_hooks, relaunch},
+    ApplyDiff, ApplyReport, InstallOptions, SoarContext,
 };
 
 /// Status of a URL package compared against installed packages.
 enum UrlPackageStatus {
     ToInstall(InstallTarget),
     ToUpdate(InstallTarget),
-    InSync(String),
+    InSync(String, Box<InstalledPackage>),
 }
 
 /// Compute the difference between declared packages (from packages.toml) and
@@ -59,7 +67,11 @@ pub async fn compute_diff(
 
         let is_github_or_gitlab = pkg.github.is_some() || pkg.gitlab.is_some();
         if is_github_or_gitlab || pkg.url.is_some() {
-            handle_local_package(pkg, is_github_or_gitlab, &diesel_db, &mut diff)?;
+            if let Some(existing) =
+                handle_local_package(pkg, is_github_or_gitlab, &diesel_db, &mut diff)?
+            {
+                queue_relaunch(&mut diff, pkg, None, existing);
+            }
             continue;
         }
 
@@ -72,7 +84,7 @@ pub async fn compute_diff(
                         Some(&pkg.name),
                         declared_pkg_id(pkg),
                         pkg.family.as_deref(),
-                        pkg.version.as_deref(),
+                        None,
                         None,
                         Some(SortDirection::Asc),
                     )
@@ -92,7 +104,7 @@ pub async fn compute_diff(
                     Some(&pkg.name),
                     declared_pkg_id(pkg),
                     pkg.family.as_deref(),
-                    pkg.version.as_deref(),
+                    None,
                     None,
                     Some(SortDirection::Asc),
                 )?;
@@ -107,12 +119,34 @@ pub async fn compute_diff(
             })?
         };
 
+        let mut found_packages = found_packages;
+        if let Some(ref channel) = pkg.channel {
+            found_packages.extend(find_in_channel(metadata_mgr, pkg, channel)?);
+        }
+
+        // Narrowed here rather than in the query: a declared version can name
+        // a snapshot, which the version column does not hold.
+        let found_packages: Vec<Package> = match pkg.version {
+            Some(ref version) => {
+                found_packages
+                    .into_iter()
+                    .filter(|p| p.has_version(version))
+                    .collect()
+            }
+            None => found_packages,
+        };
+
         if found_packages.is_empty() {
             diff.not_found.push(pkg.name.clone());
             continue;
         }
 
-        let metadata_pkg = found_packages.into_iter().next().unwrap();
+        let metadata_pkg = if pkg.channel.is_some() && pkg.version.is_none() {
+            newest_in_first_repo(found_packages)
+        } else {
+            found_packages.into_iter().next().unwrap()
+        };
+        let resolved_pkg = metadata_pkg.resolve(pkg.version.as_deref());
 
         let installed_packages: Vec<InstalledPackage> = diesel_db
             .with_conn(|conn| {
@@ -138,13 +172,19 @@ pub async fn compute_diff(
         });
 
         if let Some(ref existing) = existing_install {
-            let version_matches = pkg.version.as_ref().is_none_or(|v| existing.version == *v);
-
-            if version_matches && existing.version == metadata_pkg.version {
+            // The resolved version is the declared one, or the version of the
+            // snapshot a declared build id names, or else the latest. A build
+            // id also tells apart builds sharing that version.
+            if existing.is_build(&resolved_pkg, pkg.version.as_deref()) {
                 diff.in_sync
                     .push(format!("{}@{}", existing.pkg_name, existing.version));
+                queue_relaunch(
+                    &mut diff,
+                    pkg,
+                    metadata_pkg.launch.as_ref(),
+                    existing.clone(),
+                );
             } else if !existing.pinned || pkg.version.is_some() {
-                let resolved_pkg = metadata_pkg.resolve(pkg.version.as_deref());
                 let target = create_install_target(pkg, resolved_pkg, Some(existing.clone()));
                 diff.to_update.push((pkg.clone(), target));
             } else {
@@ -152,9 +192,14 @@ pub async fn compute_diff(
                     "{}@{} (pinned)",
                     existing.pkg_name, existing.version
                 ));
+                queue_relaunch(
+                    &mut diff,
+                    pkg,
+                    metadata_pkg.launch.as_ref(),
+                    existing.clone(),
+                );
             }
         } else {
-            let resolved_pkg = metadata_pkg.resolve(pkg.version.as_deref());
             let target = create_install_target(pkg, resolved_pkg, None);
             diff.to_install.push((pkg.clone(), target));
         }
@@ -202,6 +247,106 @@ pub async fn compute_diff(
     Ok(diff)
 }
 
+/// Queue an in-sync install whose launch settings changed since its
+/// launchers were written, so they are written again without a reinstall.
+fn queue_relaunch(
+    diff: &mut ApplyDiff,
+    pkg: &ResolvedPackage,
+    published: Option<&PackageLaunch>,
+    existing: InstalledPackage,
+) {
+    let wanted = launch::effective(pkg.launch.as_ref(), published);
+    let recorded = launch::recorded(Path::new(&existing.installed_path))
+        .inspect_err(|err| warn!("{err}"))
+        .unwrap_or_default();
+    if wanted != recorded {
+        diff.to_relaunch.push((existing, wanted));
+    }
+}
+
+/// Packages matching a declaration in the channel it follows, from its
+/// repository or, without one, from every repository publishing the channel.
+fn find_in_channel(
+    metadata_mgr: &MetadataManager,
+    pkg: &ResolvedPackage,
+    channel: &str,
+) -> SoarResult<Vec<Package>> {
+    let repo_names: Vec<String> = match pkg.repo {
+        Some(ref repo_name) => vec![repo_name.clone()],
+        None => {
+            metadata_mgr
+                .repo_names()
+                .into_iter()
+                .map(String::from)
+                .collect()
+        }
+    };
+
+    let mut found = Vec::new();
+    for repo_name in repo_names {
+        let packages = metadata_mgr.query_channel(&repo_name, channel, |conn| {
+            MetadataRepository::find_filtered(
+                conn,
+                Some(&pkg.name),
+                declared_pkg_id(pkg),
+                pkg.family.as_deref(),
+                None,
+                None,
+                Some(SortDirection::Asc),
+            )
+        })?;
+        match packages {
+            Some(packages) => {
+                found.extend(packages.into_iter().map(|p| {
+                    let mut package: Package = p.into();
+                    package.repo_name = repo_name.clone();
+                    package
+                }));
+            }
+            None if pkg.repo.is_some() => {
+                warn!(
+                    "{} follows the '{channel}' channel, which {repo_name} does not publish or has not synced",
+                    pkg.name
+                );
+            }
+            None => {}
+        }
+    }
+    Ok(found)
+}
+
+/// The newest of the packages from the repository listed first, which with
+/// the default channel's packages ahead of a channel's is the newest version
+/// the channel permits. A tie goes to the default channel.
+fn newest_in_first_repo(found: Vec<Package>) -> Package {
+    let repo_name = found[0].repo_name.clone();
+    found
+        .into_iter()
+        .filter(|p| p.repo_name == repo_name)
+        .reduce(|newest, p| {
+            if compare_versions(&p.version, &newest.version) == Ordering::Greater {
+                p
+            } else {
+                newest
+            }
+        })
+        .unwrap()
+}
+
+/// Whether packages.toml should be rewritten to the version an install landed on.
+///
+/// A declared build id names the build exactly, which its version alone may
+/// not, so it is left as written.
+fn declared_version_differs(pkg: &ResolvedPackage, target: &InstallTarget) -> bool {
+    let Some(ref declared) = pkg.version else {
+        return true;
+    };
+    if target.package.build_id.as_deref() == Some(declared.as_str()) {
+        return false;
+    }
+    declared.strip_prefix('v').unwrap_or(declared) != target.package.version
+}
+
 /// Execute an apply operation from a computed diff.
 ///
 /// Installs new packages, updates existing ones, removes pruned ones,
@@ -217,6 +362,7 @@ pub async fn execute_apply(
     let mut installed_count = 0;
     let mut updated_count = 0;
     let mut removed_count = 0;
+    let mut relaunched_count = 0;
     let mut failed_count = 0;
 
     // Apply pending version updates for in-sync packages
@@ -233,11 +379,7 @@ pub async fn execute_apply(
     if !diff.to_install.is_empty() {
         let mut version_updates: Vec<(String, String)> = Vec::new();
         for (pkg, target) in &diff.to_install {
-            let declared_version = pkg
-                .version
-                .as_ref()
-                .map(|v| v.strip_prefix('v').unwrap_or(v));
-            if declared_version != Some(target.package.version.as_str()) {
+            if declared_version_differs(pkg, target) {
                 version_updates.push((pkg.name.clone(), target.package.version.clone()));
             }
         }
@@ -279,11 +421,7 @@ pub async fn execute_apply(
     if !diff.to_update.is_empty() {
         let mut update_version_updates: Vec<(String, String)> = Vec::new();
         for (pkg, target) in &diff.to_update {
-            let declared_version = pkg
-                .version
-                .as_ref()
-                .map(|v| v.strip_prefix('v').unwrap_or(v));
-            if declared_version != Some(target.package.version.as_str()) {
+            if declared_version_differs(pkg, target) {
                 update_version_updates.push((pkg.name.clone(), target.package.version.clone()));
             }
         }
@@ -321,9 +459,33 @@ pub async fn execute_apply(
         }
     }
 
+    // Write launchers again for packages whose launch settings changed
+    if !diff.to_relaunch.is_empty() {
+        let bin_dir = ctx.config().get_bin_path()?;
+        for (pkg, settings) in &diff.to_relaunch {
+            debug!(
+                pkg_name = pkg.pkg_name,
+                "writing launchers for new launch settings"
+            );
+            match relaunch(&diesel_db, pkg, &bin_dir, settings) {
+                Ok(()) => relaunched_count += 1,
+                Err(e) => {
+                    warn!(
+                        "Failed to apply launch settings for '{}': {}",
+                        pkg.pkg_name, e
+                    );
+                    failed_count += 1;
+                }
+            }
+        }
+    }
+
     // Remove pruned packages
     if !diff.to_remove.is_empty() {
         for pkg in diff.to_remove {
+            if ctx.cancellation().is_cancelled() {
+                return Err(SoarError::Cancelled);
+            }
             let op_id = next_op_id();
             ctx.events().emit(SoarEvent::Removing {
                 op_id,
@@ -336,6 +498,7 @@ pub async fn execute_apply(
                 .await
                 .with_hooks(hooks)
                 .with_sandbox(sandbox)
+                .with_cancellation(ctx.cancellation().clone())
                 .remove()
                 .await
             {
@@ -349,6 +512,13 @@ pub async fn execute_apply(
                     });
                     removed_count += 1;
                 }
+                Err(e) if e.is_cancelled() => {
+                    ctx.events().emit(SoarEvent::OperationCancelled {
+                        op_id,
+                        pkg_name: pkg.pkg_name.clone(),
+                    });
+                    return Err(SoarError::Cancelled);
+                }
                 Err(e) => {
                     ctx.events().emit(SoarEvent::OperationFailed {
                         op_id,
@@ -372,11 +542,11 @@ pub async fn execute_apply(
         installed_count,
         updated_count,
         removed_count,
+        relaunched_count,
         failed_count,
     })
 }
 
-/// Handle local (URL/github/gitlab) packages in apply diff.
 /// What a declaration identifies: name, package id, family and repository.
 type DeclaredKeys = HashSet<(String, Option<String>, Option<String>, Option<String>)>;
 
@@ -398,12 +568,17 @@ fn declared_key(pkg: &ResolvedPackage) -> (String, Option<String>, Option<String
     )
 }
 
+/// Handle local (URL/github/gitlab) packages in apply diff.
+///
+/// # Returns
+///
+/// The package's install when it is already in sync.
 fn handle_local_package(
     pkg: &ResolvedPackage,
     is_github_or_gitlab: bool,
     diesel_db: &DieselDatabase,
     diff: &mut ApplyDiff,
-) -> SoarResult<()> {
+) -> SoarResult<Option<InstalledPackage>> {
     // Only what the user set. The family is derived from the download URL,
     // which identifies the source just as well without minting an id.
     let local_pkg_id = declared_pkg_id(pkg).map(str::to_string);
@@ -433,7 +608,7 @@ fn handle_local_package(
             if let Some(ref existing) = installed {
                 if existing.version == normalized {
                     diff.in_sync.push(format!("{} (local)", pkg.name));
-                    return Ok(());
+                    return Ok(Some(existing.clone()));
                 }
             }
         }
@@ -444,7 +619,7 @@ fn handle_local_package(
                 warn!("Failed to run version_command for {}: {}", pkg.name, e);
                 diff.not_found
                     .push(format!("{} (version_command failed: {})", pkg.name, e));
-                return Ok(());
+                return Ok(None);
             }
         };
 
@@ -465,7 +640,7 @@ fn handle_local_package(
                         .push((pkg.name.clone(), version.clone()));
                 }
                 diff.in_sync.push(format!("{} (local)", pkg.name));
-                return Ok(());
+                return Ok(Some(existing.clone()));
             }
         }
 
@@ -481,7 +656,7 @@ fn handle_local_package(
                             "{} (version_command returned no URL and no url field configured)",
                             pkg.name
                         ));
-                        return Ok(());
+                        return Ok(None);
                     }
                 }
             }
@@ -496,12 +671,8 @@ fn handle_local_package(
         )?;
         url_pkg.size = result.size;
 
-        match check_url_package_status(&url_pkg, pkg, "local", diesel_db)? {
-            UrlPackageStatus::ToInstall(target) => diff.to_install.push((pkg.clone(), target)),
-            UrlPackageStatus::ToUpdate(target) => diff.to_update.push((pkg.clone(), target)),
-            UrlPackageStatus::InSync(label) => diff.in_sync.push(label),
-        }
-        return Ok(());
+        let status = check_url_package_status(&url_pkg, pkg, "local", diesel_db)?;
+        return Ok(queue_url_package(status, pkg, diff));
     }
 
     // Handle github/gitlab packages
@@ -511,7 +682,7 @@ fn handle_local_package(
             if let Some(ref existing) = installed {
                 if existing.version == normalized {
                     diff.in_sync.push(format!("{} (local)", pkg.name));
-                    return Ok(());
+                    return Ok(Some(existing.clone()));
                 }
             }
         }
@@ -523,7 +694,7 @@ fn handle_local_package(
                     "{} (missing asset_pattern for github/gitlab source)",
                     pkg.name
                 ));
-                return Ok(());
+                return Ok(None);
             }
         };
         let release = match source.resolve_version(pkg.version.as_deref()) {
@@ -531,7 +702,7 @@ fn handle_local_package(
             Err(e) => {
                 warn!("Failed to resolve release for {}: {}", pkg.name, e);
                 diff.not_found.push(format!("{} ({})", pkg.name, e));
-                return Ok(());
+                return Ok(None);
             }
         };
         let version = release
@@ -548,12 +719,8 @@ fn handle_local_package(
             local_pkg_id.as_deref(),
         )?;
 
-        match check_url_package_status(&url_pkg, pkg, "local", diesel_db)? {
-            UrlPackageStatus::ToInstall(target) => diff.to_install.push((pkg.clone(), target)),
-            UrlPackageStatus::ToUpdate(target) => diff.to_update.push((pkg.clone(), target)),
-            UrlPackageStatus::InSync(label) => diff.in_sync.push(label),
-        }
-        return Ok(());
+        let status = check_url_package_status(&url_pkg, pkg, "local", diesel_db)?;
+        return Ok(queue_url_package(status, pkg, diff));
     }
 
     // Handle plain URL packages
@@ -563,7 +730,7 @@ fn handle_local_package(
             if let Some(ref existing) = installed {
                 if existing.version == normalized {
                     diff.in_sync.push(format!("{} (local)", pkg.name));
-                    return Ok(());
+                    return Ok(Some(existing.clone()));
                 }
             }
         }
@@ -577,14 +744,29 @@ fn handle_local_package(
             declared_pkg_id(pkg),
         )?;
 
-        match check_url_package_status(&url_pkg, pkg, "local", diesel_db)? {
-            UrlPackageStatus::ToInstall(target) => diff.to_install.push((pkg.clone(), target)),
-            UrlPackageStatus::ToUpdate(target) => diff.to_update.push((pkg.clone(), target)),
-            UrlPackageStatus::InSync(label) => diff.in_sync.push(label),
-        }
+        let status = check_url_package_status(&url_pkg, pkg, "local", diesel_db)?;
+        return Ok(queue_url_package(status, pkg, diff));
     }
 
-    Ok(())
+    Ok(None)
+}
+
+/// Put a URL package where its status says it belongs in `diff`, returning
+/// its install when that is already in sync.
+fn queue_url_package(
+    status: UrlPackageStatus,
+    pkg: &ResolvedPackage,
+    diff: &mut ApplyDiff,
+) -> Option<InstalledPackage> {
+    match status {
+        UrlPackageStatus::ToInstall(target) => diff.to_install.push((pkg.clone(), target)),
+        UrlPackageStatus::ToUpdate(target) => diff.to_update.push((pkg.clone(), target)),
+        UrlPackageStatus::InSync(label, existing) => {
+            diff.in_sync.push(label);
+            return Some(*existing);
+        }
+    }
+    None
 }
 
 fn check_url_package_status(
@@ -623,10 +805,10 @@ fn check_url_package_status(
             let target = create_url_install_target(url_pkg, pkg, installed);
             Ok(UrlPackageStatus::ToUpdate(target))
         } else {
-            Ok(UrlPackageStatus::InSync(format!(
-                "{} ({})",
-                pkg.name, display_label
-            )))
+            Ok(UrlPackageStatus::InSync(
+                format!("{} ({})", pkg.name, display_label),
+                Box::new(existing.clone()),
+            ))
         }
     } else {
         let existing_install = installed_packages.into_iter().next();
@@ -644,6 +826,7 @@ fn create_install_target(
         package,
         existing_install: existing,
         pinned: resolved.pinned,
+        keep_versions: false,
         profile: resolved.profile.clone(),
         portable: resolved.portable.as_ref().and_then(|p| p.path.clone()),
         portable_home: resolved.portable.as_ref().and_then(|p| p.home.clone()),
@@ -659,6 +842,11 @@ fn create_install_target(
         build: resolved.build.clone(),
         sandbox: resolved.sandbox.clone(),
         arch_map: resolved.arch_map.clone(),
+        appimage_mode: resolved.appimage_mode,
+        completion_commands: resolved.completion_commands.clone(),
+        launch: resolved.launch.clone(),
+        priority: resolved.priority,
+        runtime_sandbox: resolved.runtime_sandbox.clone(),
     }
 }
 
@@ -673,6 +861,7 @@ fn create_url_install_target(
         package,
         existing_install: existing,
         pinned: resolved.pinned,
+        keep_versions: false,
         profile: resolved.profile.clone(),
         portable: resolved.portable.as_ref().and_then(|p| p.path.clone()),
         portable_home: resolved.portable.as_ref().and_then(|p| p.home.clone()),
@@ -688,5 +877,48 @@ fn create_url_install_target(
         build: resolved.build.clone(),
         sandbox: resolved.sandbox.clone(),
         arch_map: resolved.arch_map.clone(),
+        appimage_mode: resolved.appimage_mode,
+        completion_commands: resolved.completion_commands.clone(),
+        launch: resolved.launch.clone(),
+        priority: resolved.priority,
+        runtime_sandbox: resolved.runtime_sandbox.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(repo_name: &str, version: &str) -> Package {
+        Package {
+            repo_name: repo_name.to_string(),
+            pkg_name: "neovim".to_string(),
+            version: version.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn a_channel_takes_the_newest_version_in_the_first_repo() {
+        let found = vec![
+            package("soarpkgs", "0.10.0"),
+            package("other", "0.12.0"),
+            package("soarpkgs", "0.11.0-rc1"),
+            package("soarpkgs", "0.11.0"),
+        ];
+        let newest = newest_in_first_repo(found);
+        assert_eq!(newest.repo_name, "soarpkgs");
+        assert_eq!(newest.version, "0.11.0");
+    }
+
+    #[test]
+    fn a_tie_goes_to_the_default_channel() {
+        let mut stable = package("soarpkgs", "0.10.0");
+        stable.build_id = Some("stable".to_string());
+        let mut testing = package("soarpkgs", "0.10.0");
+        testing.build_id = Some("testing".to_string());
+
+        let newest = newest_in_first_repo(vec![stable, testing]);
+        assert_eq!(newest.build_id.as_deref(), Some("stable"));
     }
 }
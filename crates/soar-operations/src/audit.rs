@@ -0,0 +1,88 @@
+use soar_core::{
+    audit::{self, AuditRecord},
+    SoarResult,
+};
+use tracing::debug;
+
+use crate::{AuditQuery, SoarContext};
+
+/// Read the audit log, oldest first, keeping the records `query` selects.
+pub fn query_audit(ctx: &SoarContext, query: &AuditQuery) -> SoarResult<Vec<AuditRecord>> {
+    let path = ctx.config().get_audit_log_path()?;
+    debug!(path = %path.display(), "reading audit log");
+    Ok(select(audit::read(&path)?, query))
+}
+
+fn select(records: Vec<AuditRecord>, query: &AuditQuery) -> Vec<AuditRecord> {
+    let mut records = records
+        .into_iter()
+        .filter(|r| {
+            query
+                .package
+                .as_deref()
+                .is_none_or(|p| r.pkg_name == p || r.pkg_id == p)
+        })
+        .filter(|r| query.kind.is_none_or(|kind| r.kind == kind))
+        .filter(|r| !query.failed || r.failed())
+        .collect::<Vec<_>>();
+    if let Some(limit) = query.limit {
+        records.drain(..records.len().saturating_sub(limit));
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use soar_core::audit::AuditKind;
+
+    use super::*;
+
+    fn record(pkg_name: &str, kind: AuditKind, exit_code: i32) -> AuditRecord {
+        AuditRecord {
+            timestamp: String::new(),
+            pkg_name: pkg_name.into(),
+            pkg_id: format!("{pkg_name}-bin"),
+            pkg_version: "1.0".into(),
+            kind,
+            name: "post_install".into(),
+            command: "true".into(),
+            working_dir: "/tmp".into(),
+            env: BTreeMap::new(),
+            sandbox: None,
+            exit_code: Some(exit_code),
+            signal: None,
+            violation: None,
+            duration_ms: 0,
+            stdout: String::new(),
+            stderr: String::new(),
+            truncated: false,
+        }
+    }
+
+    #[test]
+    fn filters_combine_and_limit_keeps_the_latest() {
+        let records = vec![
+            record("tool", AuditKind::Hook, 1),
+            record("other", AuditKind::Hook, 1),
+            record("tool", AuditKind::Build, 1),
+            record("tool", AuditKind::Hook, 0),
+            record("tool", AuditKind::Hook, 2),
+        ];
+
+        let selected = select(
+            records.clone(),
+            &AuditQuery {
+                package: Some("tool-bin".into()),
+                kind: Some(AuditKind::Hook),
+                failed: true,
+                limit: Some(1),
+            },
+        );
+        assert_eq!(selected, [records[4].clone()]);
+
+        let selected = select(records.clone(), &AuditQuery::default());
+        assert_eq!(selected, records);
+    }
+}
@@ -17,14 +17,15 @@ use soar_db::{
     repository::{core::CoreRepository, metadata::MetadataRepository},
 };
 use soar_events::{EventSinkHandle, LogLevel, SoarEvent, SyncStage};
-use soar_registry::{fetch_metadata, write_metadata_db, MetadataContent, RemotePackage};
+use soar_registry::{
+    fetch_metadata, write_metadata_db, FetchedMetadata, MetadataContent, MetadataDelta,
+    RemotePackage, Revision,
+};
+use soar_utils::cancel::CancellationToken;
 use tokio::sync::OnceCell as AsyncOnceCell;
 use tracing::{debug, trace};
 
-type SyncTaskResult = (
-    soar_registry::Result<Option<(String, MetadataContent)>>,
-    String,
-);
+type SyncTaskResult = (soar_registry::Result<Option<FetchedMetadata>>, String);
 
 /// Ensures the core database file exists and is writable by this process.
 fn ensure_core_db_file(path: &Path) -> SoarResult<()> {
@@ -65,21 +66,73 @@ fn handle_json_metadata<P: AsRef<Path>>(
     Ok(())
 }
 
-/// Bring a published metadata database up to the schema soar reads.
+/// Bring a published metadata database up to the schema soar reads, with a
+/// search index over the packages it actually holds.
 fn migrate_metadata(path: &Path) -> SoarResult<()> {
-    DbConnection::open(path, DbType::Metadata)
+    let mut conn = DbConnection::open(path, DbType::Metadata)
         .map_err(|e| SoarError::Custom(format!("migrating repository metadata: {}", e)))?;
-    Ok(())
+    MetadataRepository::ensure_search_index(conn.conn())
+        .map_err(|e| SoarError::Custom(format!("building search index: {}", e)))
+}
+
+/// Apply deltas to a metadata database, keeping them only if the result is
+/// the revision they lead to.
+fn apply_metadata_deltas(
+    path: &Path,
+    deltas: &[MetadataDelta],
+    revision: Option<&Revision>,
+) -> SoarResult<bool> {
+    let Some(revision) = revision else {
+        return Ok(false);
+    };
+    let mut conn = DbConnection::open(path, DbType::Metadata)
+        .map_err(|e| SoarError::Custom(format!("opening metadata database: {}", e)))?;
+    MetadataRepository::apply_deltas(conn.conn(), deltas, &revision.hash)
+        .map_err(|e| SoarError::Custom(format!("applying metadata deltas: {}", e)))
+}
+
+/// The revision to record for freshly stored metadata: the one the manifest
+/// names, if the stored packages hash to it.
+///
+/// A mismatch is not an error. The manifest and the full metadata are
+/// published separately, so one can briefly run ahead of the other; the next
+/// sync then downloads in full again rather than building on a revision the
+/// database does not hold.
+fn verified_revision(path: &Path, revision: Option<Revision>) -> Option<String> {
+    let revision = revision?;
+    let mut conn = DbConnection::open(path, DbType::Metadata).ok()?;
+    let hash = MetadataRepository::revision_hash(conn.conn()).ok()?;
+    if hash == revision.hash {
+        Some(revision.id)
+    } else {
+        debug!(
+            revision = revision.id,
+            expected = revision.hash,
+            found = hash,
+            "metadata does not match the revision its manifest names"
+        );
+        None
+    }
 }
 
 #[derive(Clone)]
 pub struct SoarContext {
     inner: Arc<SoarContextInner>,
+    events: EventSinkHandle,
+    cancel: CancellationToken,
+}
+
+/// The name a sync reports a repository under, naming the channel when it is
+/// not the default one.
+fn sync_name(repo: &Repository) -> String {
+    match &repo.channel {
+        Some(channel) => format!("{} ({channel})", repo.name),
+        None => repo.name.clone(),
+    }
 }
 
 struct SoarContextInner {
     config: Config,
-    events: EventSinkHandle,
     diesel_core_db: OnceCell<DieselDatabase>,
     metadata_manager: AsyncOnceCell<MetadataManager>,
 }
@@ -89,10 +142,35 @@ impl SoarContext {
         Self {
             inner: Arc::new(SoarContextInner {
                 config,
-                events,
                 diesel_core_db: OnceCell::new(),
                 metadata_manager: AsyncOnceCell::new(),
             }),
+            events,
+            cancel: CancellationToken::new(),
+        }
+    }
+
+    /// The same context reporting to another sink.
+    ///
+    /// The databases it has opened are shared rather than opened again, so a
+    /// long-lived caller can give each operation its own event stream.
+    pub fn with_events(&self, events: EventSinkHandle) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            events,
+            cancel: self.cancel.clone(),
+        }
+    }
+
+    /// The same context, stopped by `cancel` instead.
+    ///
+    /// Operations run through it check the token between steps, and roll
+    /// back what they had started once it is cancelled.
+    pub fn with_cancellation(&self, cancel: CancellationToken) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            events: self.events.clone(),
+            cancel,
         }
     }
 
@@ -103,7 +181,12 @@ impl SoarContext {
 
     #[inline]
     pub fn events(&self) -> &EventSinkHandle {
-        &self.inner.events
+        &self.events
+    }
+
+    #[inline]
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancel
     }
 
     pub fn diesel_core_db(&self) -> SoarResult<&DieselDatabase> {
@@ -138,22 +221,26 @@ impl SoarContext {
         );
         let mut tasks = Vec::new();
 
+        // Every channel a repository publishes is synced, each into its own
+        // database, so a package can switch channels without waiting on one.
         for repo in self
             .inner
             .config
             .repositories
             .iter()
             .filter(|r| r.is_enabled())
+            .flat_map(|r| std::iter::once(r.clone()).chain(r.channel_views()))
         {
             trace!(
                 repo_name = repo.name,
+                channel = repo.channel,
                 url = repo.url,
                 "scheduling repository sync"
             );
             let repo_clone = repo.clone();
-            let etag = self.read_repo_etag(&repo_clone);
-            let events = self.inner.events.clone();
-            let repo_name = repo.name.clone();
+            let (etag, revision) = self.read_repo_sync_state(&repo_clone);
+            let events = self.events.clone();
+            let repo_name = sync_name(&repo);
 
             let task: tokio::task::JoinHandle<SyncTaskResult> = tokio::task::spawn(async move {
                 if force {
@@ -162,31 +249,92 @@ impl SoarContext {
                         stage: SyncStage::Fetching,
                     });
                 }
-                let result = fetch_metadata(&repo_clone, force, etag).await;
+                let result = fetch_metadata(&repo_clone, force, etag, revision).await;
                 (result, repo_name)
             });
             tasks.push((task, repo));
         }
 
-        for (task, repo) in tasks {
+        // Each repository is written whole or not at all, so a cancelled sync
+        // stops between them; the fetches still running only read.
+        let mut tasks = tasks.into_iter();
+        while let Some((task, repo)) = tasks.next() {
+            if self.cancel.is_cancelled() {
+                task.abort();
+                tasks.for_each(|(task, _)| task.abort());
+                return Err(SoarError::Cancelled);
+            }
+
             let (result, repo_name) = task
                 .await
                 .map_err(|err| SoarError::Custom(format!("Join handle error: {err}")))?;
 
             match result {
-                Ok(Some((etag, content))) => {
-                    let repo_path = repo.get_path()?;
-                    let metadata_db_path = repo_path.join("metadata.db");
+                Ok(Some(mut fetched)) => {
+                    let metadata_db_path = repo.metadata_db_path()?;
 
-                    self.inner.events.emit(SoarEvent::SyncProgress {
-                        repo_name: repo_name.clone(),
-                        stage: SyncStage::Decompressing,
-                    });
+                    let mut applied = false;
+                    if let MetadataContent::Delta(deltas) = &fetched.content {
+                        self.events.emit(SoarEvent::SyncProgress {
+                            repo_name: repo_name.clone(),
+                            stage: SyncStage::ApplyingDeltas {
+                                count: deltas.len(),
+                            },
+                        });
 
-                    self.inner.events.emit(SoarEvent::SyncProgress {
-                        repo_name: repo_name.clone(),
-                        stage: SyncStage::WritingDatabase,
-                    });
+                        // A chain that does not land on the signed revision, or
+                        // fails partway, is rolled back, leaving the database
+                        // as it was, and the full metadata replaces it instead.
+                        let outcome = apply_metadata_deltas(
+                            &metadata_db_path,
+                            deltas,
+                            fetched.revision.as_ref(),
+                        );
+                        applied = matches!(outcome, Ok(true));
+                        if !applied {
+                            let reason = match outcome {
+                                Err(err) => format!("could not be applied ({err})"),
+                                Ok(_) => "did not reproduce the published metadata".to_string(),
+                            };
+                            self.events.emit(SoarEvent::Log {
+                                level: LogLevel::Warning,
+                                message: format!(
+                                    "Deltas for {repo_name} {reason}; fetching it in full"
+                                ),
+                            });
+                            fetched = match fetch_metadata(&repo, true, None, None).await {
+                                Ok(Some(full)) => full,
+                                Ok(None) => continue,
+                                Err(err) => {
+                                    self.events.emit(SoarEvent::Log {
+                                        level: LogLevel::Error,
+                                        message: format!(
+                                            "Failed to sync repository {repo_name}: {err}"
+                                        ),
+                                    });
+                                    continue;
+                                }
+                            };
+                        }
+                    }
+
+                    let FetchedMetadata {
+                        etag,
+                        content,
+                        revision,
+                    } = fetched;
+
+                    if !matches!(content, MetadataContent::Delta(_)) {
+                        self.events.emit(SoarEvent::SyncProgress {
+                            repo_name: repo_name.clone(),
+                            stage: SyncStage::Decompressing,
+                        });
+
+                        self.events.emit(SoarEvent::SyncProgress {
+                            repo_name: repo_name.clone(),
+                            stage: SyncStage::WritingDatabase,
+                        });
+                    }
 
                     match content {
                         MetadataContent::SqliteDb(db_bytes) => {
@@ -195,7 +343,9 @@ impl SoarContext {
                             // A published database was built by whatever soar
                             // the repository runs, so it can predate the
                             // columns these queries name. Opening it through
-                            // the migration runner brings it up to them. The
+                            // the migration runner brings it up to them, and
+                            // the search index is built afresh over whatever
+                            // rows its generator wrote. The
                             // rebuild runs outside a transaction, so a failure
                             // leaves a half-migrated file: it is discarded
                             // rather than queried.
@@ -207,16 +357,25 @@ impl SoarContext {
                         MetadataContent::Json(packages) => {
                             handle_json_metadata(&packages, &metadata_db_path, &repo.name)?;
                         }
+                        // Applied above; reaching here means they matched.
+                        MetadataContent::Delta(_) => {}
                     }
 
-                    self.inner.events.emit(SoarEvent::SyncProgress {
+                    let revision = if applied {
+                        revision.map(|revision| revision.id)
+                    } else {
+                        verified_revision(&metadata_db_path, revision)
+                    };
+
+                    self.events.emit(SoarEvent::SyncProgress {
                         repo_name: repo_name.clone(),
                         stage: SyncStage::Validating,
                     });
 
-                    self.validate_packages(repo, &etag).await?;
+                    self.validate_packages(&repo, &etag, revision.as_deref())
+                        .await?;
 
-                    self.inner.events.emit(SoarEvent::SyncProgress {
+                    self.events.emit(SoarEvent::SyncProgress {
                         repo_name: repo_name.clone(),
                         stage: SyncStage::Complete {
                             package_count: None,
@@ -225,16 +384,16 @@ impl SoarContext {
                 }
                 Ok(None) => {
                     if force {
-                        self.inner.events.emit(SoarEvent::SyncProgress {
+                        self.events.emit(SoarEvent::SyncProgress {
                             repo_name: repo_name.clone(),
                             stage: SyncStage::UpToDate,
                         });
                     }
                 }
                 Err(err) => {
-                    self.inner.events.emit(SoarEvent::Log {
+                    self.events.emit(SoarEvent::Log {
                         level: LogLevel::Error,
-                        message: format!("Failed to sync repository {}: {err}", repo.name),
+                        message: format!("Failed to sync repository {repo_name}: {err}"),
                     });
                 }
             };
@@ -243,7 +402,12 @@ impl SoarContext {
         Ok(())
     }
 
-    async fn validate_packages(&self, repo: &Repository, etag: &str) -> SoarResult<()> {
+    async fn validate_packages(
+        &self,
+        repo: &Repository,
+        etag: &str,
+        revision: Option<&str>,
+    ) -> SoarResult<()> {
         trace!(
             repo_name = repo.name,
             "validating installed packages against repository"
@@ -251,10 +415,16 @@ impl SoarContext {
         let diesel_core_db = self.diesel_core_db()?;
         let repo_name = repo.name.clone();
 
-        let repo_path = repo.get_path()?;
-        let metadata_db_path = repo_path.join("metadata.db");
+        let metadata_db = DieselDatabase::open_metadata(repo.metadata_db_path()?)?;
 
-        let metadata_db = DieselDatabase::open_metadata(&metadata_db_path)?;
+        // Installed packages are recorded against the repository, not a
+        // channel, and a package missing from a channel has not been replaced.
+        if repo.channel.is_some() {
+            metadata_db.with_conn(|conn| {
+                MetadataRepository::update_repo_metadata(conn, &repo.name, etag, revision)
+            })?;
+            return Ok(());
+        }
 
         let installed_packages = diesel_core_db.with_conn(|conn| {
             CoreRepository::list_filtered(
@@ -285,7 +455,7 @@ impl SoarContext {
                     .with_conn(|conn| MetadataRepository::find_replacement_pkg_id(conn, pkg_id))?;
 
                 if let Some(new_pkg_id) = replacement {
-                    self.inner.events.emit(SoarEvent::Log {
+                    self.events.emit(SoarEvent::Log {
                         level: LogLevel::Info,
                         message: format!(
                             "{} is replaced by {} in {}",
@@ -300,8 +470,9 @@ impl SoarContext {
             }
         }
 
-        metadata_db
-            .with_conn(|conn| MetadataRepository::update_repo_metadata(conn, &repo.name, etag))?;
+        metadata_db.with_conn(|conn| {
+            MetadataRepository::update_repo_metadata(conn, &repo.name, etag, revision)
+        })?;
 
         Ok(())
     }
@@ -331,8 +502,7 @@ impl SoarContext {
             .iter()
             .filter(|r| r.is_enabled())
         {
-            if let Ok(repo_path) = repo.get_path() {
-                let metadata_db = repo_path.join("metadata.db");
+            if let Ok(metadata_db) = repo.metadata_db_path() {
                 if metadata_db.is_file() {
                     trace!(
                         repo_name = repo.name,
@@ -345,29 +515,127 @@ impl SoarContext {
                     }
                 }
             }
+
+            for view in repo.channel_views() {
+                let (Some(channel), Ok(metadata_db)) = (&view.channel, view.metadata_db_path())
+                else {
+                    continue;
+                };
+                if metadata_db.is_file() {
+                    if readonly {
+                        manager.add_channel_readonly(&repo.name, channel, metadata_db)?;
+                    } else {
+                        manager.add_channel(&repo.name, channel, metadata_db)?;
+                    }
+                }
+            }
         }
 
         debug!(repos = manager.repo_count(), "metadata manager created");
         Ok(manager)
     }
 
-    fn read_repo_etag(&self, repo: &Repository) -> Option<String> {
-        let repo_path = repo.get_path().ok()?;
-        let metadata_db = repo_path.join("metadata.db");
-
-        if !metadata_db.exists() {
-            return None;
-        }
+    /// Reads the etag and revision stored by the last sync.
+    fn read_repo_sync_state(&self, repo: &Repository) -> (Option<String>, Option<String>) {
+        let Some(metadata_db) = repo.metadata_db_path().ok().filter(|path| path.exists()) else {
+            return (None, None);
+        };
 
         trace!(
             repo_name = repo.name,
             url = repo.url,
             path = %metadata_db.display(),
-            "reading stored etag"
+            "reading stored etag and revision"
         );
-        let mut conn = DbConnection::open(&metadata_db, DbType::Metadata).ok()?;
-        MetadataRepository::get_repo_etag(conn.conn())
+        let Ok(mut conn) = DbConnection::open(&metadata_db, DbType::Metadata) else {
+            return (None, None);
+        };
+        let etag = MetadataRepository::get_repo_etag(conn.conn())
             .ok()
-            .flatten()
+            .flatten();
+        let revision = MetadataRepository::get_repo_revision(conn.conn())
+            .ok()
+            .flatten();
+        (etag, revision)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use diesel::{connection::SimpleConnection, RunQueryDsl};
+    use soar_db::repository::metadata::SearchFilters;
+
+    use super::*;
+
+    fn remote(name: &str, description: &str) -> RemotePackage {
+        RemotePackage {
+            pkg_name: name.to_string(),
+            description: description.to_string(),
+            version: "1.0.0".to_string(),
+            download_url: format!("https://example.com/{name}"),
+            ..Default::default()
+        }
+    }
+
+    /// A database as a repository's own generator might publish it: the
+    /// packages are there, but the index over them is not, or is left over
+    /// from rows it no longer holds.
+    fn published(dir: &Path, index: &str) -> Vec<u8> {
+        let path = dir.join("published.db");
+        let mut conn = DbConnection::open(&path, DbType::Metadata).unwrap();
+        let packages = vec![
+            remote("helix", "A post-modern modal text editor"),
+            remote("editor", "Edits things"),
+        ];
+        MetadataRepository::import_packages(conn.conn(), &packages, "test").unwrap();
+        conn.conn().batch_execute(index).unwrap();
+        diesel::sql_query(
+            "INSERT INTO packages (pkg_id, pkg_name, description, version, download_url) \
+             VALUES ('vim', 'vim', 'The ubiquitous text editor', '9.1', 'https://example.com/vim')",
+        )
+        .execute(conn.conn())
+        .unwrap();
+        drop(conn);
+        fs::read(path).unwrap()
+    }
+
+    fn synced(index: &str) -> Vec<String> {
+        let dir = tempfile::tempdir().unwrap();
+        let bytes = published(dir.path(), index);
+        let path = dir.path().join("metadata.db");
+        write_metadata_db(&bytes, &path).unwrap();
+        migrate_metadata(&path).unwrap();
+
+        let mut conn = DbConnection::open(&path, DbType::Metadata).unwrap();
+        assert!(MetadataRepository::has_search_index(conn.conn()).unwrap());
+        MetadataRepository::search_ranked(
+            conn.conn(),
+            "editor",
+            &SearchFilters::default(),
+            None,
+        )
+        .unwrap()
+        .into_iter()
+        .map(|hit| {
+            assert!(hit.exact || hit.score < 0.0);
+            hit.package.pkg_name
+        })
+        .collect()
+    }
+
+    #[test]
+    fn a_prebuilt_database_without_an_index_is_given_one() {
+        let found = synced("DROP TABLE packages_fts; DROP VIEW packages_search;");
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[0], "editor");
+        assert!(found.contains(&"vim".to_string()));
+    }
+
+    #[test]
+    fn a_prebuilt_database_with_a_stale_index_is_reindexed() {
+        let found = synced("INSERT INTO packages_fts (packages_fts) VALUES ('delete-all');");
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[0], "editor");
+        assert!(found.contains(&"vim".to_string()));
     }
 }
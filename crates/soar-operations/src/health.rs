@@ -1,17 +1,30 @@
-use std::path::PathBuf;
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+};
 
-use soar_core::{package::remove::PackageRemover, SoarResult};
+use soar_core::{
+    package::{
+        compat::{self, Incompatibility},
+        remove::PackageRemover,
+    },
+    SoarResult,
+};
 use soar_db::repository::core::CoreRepository;
 use soar_events::{RemoveStage, SoarEvent};
+use soar_package::{formats::appimage::fuse_available, get_file_type, PackageFormat};
 use soar_utils::{error::FileSystemResult, fs::walk_dir, path::resolve_path};
 use tracing::debug;
 
 use crate::{
     progress::next_op_id, utils::get_package_hooks, BrokenPackage, FailedInfo, HealthReport,
-    RemoveReport, RemovedInfo, SoarContext,
+    MissingRuntimes, RemoveReport, RemovedInfo, SoarContext, UnresolvedLibraries,
 };
 
-/// Check system health: PATH configuration, broken packages, and broken symlinks.
+/// Check system health: PATH configuration, broken packages, broken symlinks,
+/// AppImages that need a FUSE this system lacks, binaries missing shared
+/// libraries, and scripts missing their interpreter.
 pub fn check_health(ctx: &SoarContext) -> SoarResult<HealthReport> {
     debug!("checking system health");
     let config = ctx.config();
@@ -48,6 +61,12 @@ pub fn check_health(ctx: &SoarContext) -> SoarResult<HealthReport> {
 
     let broken_packages = get_broken_packages(ctx)?;
     let broken_symlinks = get_broken_symlinks(ctx)?;
+    let unmountable_appimages = if fuse_available() {
+        Vec::new()
+    } else {
+        get_unmountable_appimages(ctx)?
+    };
+    let (unresolved_libraries, missing_runtimes) = get_missing_dependencies(ctx)?;
 
     Ok(HealthReport {
         path_configured,
@@ -56,6 +75,9 @@ pub fn check_health(ctx: &SoarContext) -> SoarResult<HealthReport> {
         man_path_configured,
         broken_packages,
         broken_symlinks,
+        unmountable_appimages,
+        unresolved_libraries,
+        missing_runtimes,
     })
 }
 
@@ -152,6 +174,110 @@ fn get_broken_packages(ctx: &SoarContext) -> SoarResult<Vec<BrokenPackage>> {
         .collect())
 }
 
+/// Installed packages whose binary is an AppImage still needing FUSE.
+///
+/// One installed with `appimage_mode = "extract"` has a launcher in its place
+/// instead, so it is not listed.
+fn get_unmountable_appimages(ctx: &SoarContext) -> SoarResult<Vec<BrokenPackage>> {
+    let diesel_db = ctx.diesel_core_db()?;
+    let installed = diesel_db.with_conn(CoreRepository::list_all)?;
+
+    Ok(installed
+        .into_iter()
+        .filter(|p| p.is_installed)
+        .filter(|p| {
+            let bin_name = p
+                .provides
+                .as_ref()
+                .and_then(|provides| provides.iter().find(|p| !p.symlink_to_bin))
+                .map(|p| p.name.as_str())
+                .unwrap_or(&p.pkg_name);
+            File::open(Path::new(&p.installed_path).join(bin_name))
+                .ok()
+                .and_then(|file| get_file_type(&mut BufReader::new(file)).ok())
+                == Some(PackageFormat::AppImage)
+        })
+        .map(|p| {
+            BrokenPackage {
+                pkg_name: p.pkg_name,
+                installed_path: p.installed_path,
+            }
+        })
+        .collect())
+}
+
+/// Installed packages with a binary whose shared libraries do not resolve, and
+/// those with a script, zipapp or jar whose interpreter is gone.
+///
+/// The binaries checked are the commands linked into the bin directory, or the
+/// package's main binary where it links none.
+fn get_missing_dependencies(
+    ctx: &SoarContext,
+) -> SoarResult<(Vec<UnresolvedLibraries>, Vec<MissingRuntimes>)> {
+    let diesel_db = ctx.diesel_core_db()?;
+    let installed = diesel_db.with_conn(CoreRepository::list_all)?;
+
+    let bin_path = ctx.config().get_bin_path()?;
+    let commands: Vec<PathBuf> = fs::read_dir(&bin_path)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| fs::canonicalize(entry.path()).ok())
+        .collect();
+
+    let mut unresolved_libraries = Vec::new();
+    let mut missing_runtimes = Vec::new();
+    for p in installed.into_iter().filter(|p| p.is_installed) {
+        let Ok(install_dir) = fs::canonicalize(&p.installed_path) else {
+            continue;
+        };
+        let mut binaries: Vec<PathBuf> = commands
+            .iter()
+            .filter(|c| c.starts_with(&install_dir))
+            .cloned()
+            .collect();
+        if binaries.is_empty() {
+            let bin_name = p
+                .provides
+                .as_ref()
+                .and_then(|provides| provides.iter().find(|p| !p.symlink_to_bin))
+                .map(|p| p.name.as_str())
+                .unwrap_or(&p.pkg_name);
+            binaries.push(install_dir.join(bin_name));
+        }
+
+        let mut libraries = Vec::new();
+        let mut runtimes = Vec::new();
+        for issue in binaries.iter().flat_map(|bin| compat::check(bin)) {
+            match issue {
+                Incompatibility::UnresolvedLibraries(missing) => libraries.extend(missing),
+                Incompatibility::MissingRuntime(runtime) => runtimes.push(runtime),
+                _ => {}
+            }
+        }
+        libraries.sort();
+        libraries.dedup();
+        runtimes.sort();
+        runtimes.dedup();
+
+        if !libraries.is_empty() {
+            unresolved_libraries.push(UnresolvedLibraries {
+                pkg_name: p.pkg_name.clone(),
+                installed_path: p.installed_path.clone(),
+                libraries,
+            });
+        }
+        if !runtimes.is_empty() {
+            missing_runtimes.push(MissingRuntimes {
+                pkg_name: p.pkg_name,
+                installed_path: p.installed_path,
+                runtimes,
+            });
+        }
+    }
+    Ok((unresolved_libraries, missing_runtimes))
+}
+
 fn get_broken_symlinks(ctx: &SoarContext) -> SoarResult<Vec<PathBuf>> {
     let config = ctx.config();
     let mut broken = Vec::new();
@@ -0,0 +1,113 @@
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+};
+
+use soar_core::{error::SoarError, SoarResult};
+use soar_db::repository::core::{CoreRepository, SortDirection};
+use soar_package::{
+    extract_contents,
+    formats::appimage::{appdir_path, is_extracted_appimage},
+    get_file_type, list_contents, PackageFormat,
+};
+use tracing::debug;
+
+use crate::{BundleContents, ExtractedContents, SoarContext};
+
+/// Resolve what `target` names to the file holding a bundle.
+///
+/// A path to an existing file is taken as is. Anything else names an
+/// installed package, whose main binary is used.
+fn resolve_bundle(ctx: &SoarContext, target: &str) -> SoarResult<PathBuf> {
+    let path = Path::new(target);
+    if target.contains('/') || path.is_file() {
+        if !path.is_file() {
+            return Err(SoarError::Custom(format!("{target} is not a file")));
+        }
+        return Ok(path.to_path_buf());
+    }
+
+    let diesel_db = ctx.diesel_core_db()?;
+    let packages = diesel_db.with_conn(|conn| {
+        CoreRepository::list_filtered(
+            conn,
+            None,
+            Some(target),
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(SortDirection::Asc),
+        )
+    })?;
+    let package = packages
+        .into_iter()
+        .find(|p| p.is_installed && !p.unlinked)
+        .ok_or_else(|| SoarError::Custom(format!("{target} is not installed")))?;
+
+    let bin_name = package
+        .provides
+        .as_ref()
+        .and_then(|provides| provides.iter().find(|p| !p.symlink_to_bin))
+        .map(|p| p.name.as_str())
+        .unwrap_or(&package.pkg_name);
+    let bin_path = Path::new(&package.installed_path).join(bin_name);
+
+    // An AppImage installed with `appimage_mode = "extract"` is a launcher now,
+    // and its files are already on disk.
+    if is_extracted_appimage(&bin_path) {
+        return Err(SoarError::Custom(format!(
+            "{target} was extracted when it was installed; its files are in {}",
+            appdir_path(&bin_path).display()
+        )));
+    }
+    Ok(bin_path)
+}
+
+/// List the files bundled in a package, or in a package file at a path.
+///
+/// With `verify`, every file is checked against the checksum its format
+/// records for it.
+pub fn inspect_contents(
+    ctx: &SoarContext,
+    target: &str,
+    verify: bool,
+) -> SoarResult<BundleContents> {
+    debug!(target = target, verify = verify, "listing bundle contents");
+    let path = resolve_bundle(ctx, target)?;
+    let format = File::open(&path)
+        .ok()
+        .and_then(|file| get_file_type(&mut BufReader::new(file)).ok())
+        .unwrap_or(PackageFormat::Unknown);
+    let entries = list_contents(&path, verify)?;
+
+    Ok(BundleContents {
+        path,
+        format,
+        entries,
+    })
+}
+
+/// Extract files bundled in a package, or in a package file at a path, into
+/// `dest`.
+///
+/// Only the entries at or below one of `only` are extracted, or everything
+/// when it is empty.
+pub fn extract_bundle(
+    ctx: &SoarContext,
+    target: &str,
+    dest: &Path,
+    only: &[String],
+) -> SoarResult<ExtractedContents> {
+    debug!(target = target, dest = %dest.display(), "extracting bundle contents");
+    let path = resolve_bundle(ctx, target)?;
+    let count = extract_contents(&path, dest, only)?;
+
+    Ok(ExtractedContents {
+        path,
+        dest: dest.to_path_buf(),
+        count,
+    })
+}
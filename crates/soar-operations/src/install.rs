@@ -1,4 +1,5 @@
 use std::{
+    collections::HashSet,
     fs::{self, File},
     io::{BufReader, Read},
     path::{Path, PathBuf},
@@ -10,20 +11,28 @@ use std::{
 };
 
 use minisign_verify::{PublicKey, Signature};
-use soar_config::utils::default_install_patterns;
+use soar_config::{
+    config::{AppImageMode, Config},
+    utils::default_install_patterns,
+};
 use soar_core::{
+    alternatives,
     database::{
         connection::{DieselDatabase, MetadataManager},
         models::{InstalledPackage, Package},
     },
     error::{ErrorContext, SoarError},
     package::{
+        compat, completions,
+        hooks::HookEnv,
         install::{InstallMarker, InstallTarget, PackageInstaller},
+        launch,
         local::LocalPackage,
         query::PackageQuery,
         remove::make_tree_writable,
         update::remove_old_versions,
         url::UrlPackage,
+        versions,
     },
     SoarResult,
 };
@@ -31,8 +40,11 @@ use soar_db::repository::{
     core::{CoreRepository, SortDirection},
     metadata::{narrow_by_pkg_id, MetadataRepository},
 };
-use soar_events::{InstallStage, SoarEvent, VerifyStage};
-use soar_package::integrate_package;
+use soar_events::{EventSinkHandle, InstallStage, OperationId, SoarEvent, VerifyStage};
+use soar_package::{
+    formats::appimage::{extract_appimage, is_extracted_appimage},
+    get_file_type, integrate_package, PackageFormat,
+};
 use soar_utils::{
     hash::{calculate_checksum, hash_string},
     lock::FileLock,
@@ -45,7 +57,11 @@ use tracing::{debug, trace, warn};
 
 use crate::{
     progress::{create_progress_bridge, next_op_id},
-    utils::{has_desktop_integration, link_shared_files, mangle_package_symlinks},
+    utils::{
+        get_launch_config, get_priority, has_desktop_integration, link_package_symlinks,
+        link_shared_files, package_symlinks, recorded_runtime_sandbox, write_launch_wrappers,
+        write_launchers, write_sandbox_launchers,
+    },
     FailedInfo, InstallOptions, InstallReport, InstalledInfo, ResolveResult, SoarContext,
 };
 
@@ -490,11 +506,18 @@ fn resolve_by_pkg_id(
 
         let existing_install = installed_packages
             .iter()
-            .find(|ip| ip.pkg_name == pkg.pkg_name)
+            .find(|ip| {
+                ip.pkg_name == pkg.pkg_name
+                    && (query.version.is_none() || ip.version == pkg.version)
+            })
             .cloned();
 
         if let Some(ref existing) = existing_install {
-            if existing.is_installed && !options.force {
+            // Another build of the same version replaces this one.
+            if existing.is_installed
+                && !options.force
+                && (query.version.is_none() || existing.is_build(&pkg, query.version.as_deref()))
+            {
                 continue;
             }
         }
@@ -503,6 +526,7 @@ fn resolve_by_pkg_id(
             package: pkg,
             existing_install,
             pinned: query.version.is_some(),
+            keep_versions: query.version.is_some(),
             profile: None,
             ..Default::default()
         });
@@ -552,12 +576,19 @@ fn resolve_normal(
     match packages.len() {
         0 => Ok(ResolveResult::NotFound(package_name.to_string())),
         1 => {
-            let pkg = packages.into_iter().next().unwrap();
+            let pkg = packages
+                .into_iter()
+                .next()
+                .unwrap()
+                .resolve(query.version.as_deref());
             // Same name from another repository is a different package, so it
-            // must not be mistaken for this one already being installed.
-            let installed_pkg = installed_packages
-                .iter()
-                .find(|ip| ip.is_installed && ip.repo_name == pkg.repo_name);
+            // must not be mistaken for this one already being installed. A
+            // version named on its own goes next to the others.
+            let installed_pkg = installed_packages.iter().find(|ip| {
+                ip.is_installed
+                    && ip.repo_name == pkg.repo_name
+                    && (query.version.is_none() || ip.is_build(&pkg, query.version.as_deref()))
+            });
 
             if let Some(installed) = installed_pkg {
                 if !options.force {
@@ -574,12 +605,11 @@ fn resolve_normal(
                 .find(|ip| ip.version == pkg.version)
                 .cloned();
 
-            let pkg = pkg.resolve(query.version.as_deref());
-
             Ok(ResolveResult::Resolved(vec![InstallTarget {
                 package: pkg,
                 existing_install,
                 pinned: query.version.is_some(),
+                keep_versions: query.version.is_some(),
                 profile: None,
                 ..Default::default()
             }]))
@@ -603,9 +633,13 @@ fn resolve_normal(
                     .into_iter()
                     .max_by(|a, b| compare_versions(&a.version, &b.version))
                     .unwrap();
-                let installed_pkg = installed_packages
-                    .iter()
-                    .find(|ip| ip.is_installed && ip.repo_name == newest.repo_name);
+                let newest = newest.resolve(query.version.as_deref());
+                let installed_pkg = installed_packages.iter().find(|ip| {
+                    ip.is_installed
+                        && ip.repo_name == newest.repo_name
+                        && (query.version.is_none()
+                            || ip.is_build(&newest, query.version.as_deref()))
+                });
                 if let Some(installed) = installed_pkg {
                     if !options.force {
                         return Ok(ResolveResult::AlreadyInstalled {
@@ -623,11 +657,11 @@ fn resolve_normal(
                             && ip.pkg_family.as_deref() == newest.pkg_family.as_deref()
                     })
                     .cloned();
-                let newest = newest.resolve(query.version.as_deref());
                 return Ok(ResolveResult::Resolved(vec![InstallTarget {
                     package: newest,
                     existing_install,
                     pinned: query.version.is_some(),
+                    keep_versions: query.version.is_some(),
                     profile: None,
                     ..Default::default()
                 }]));
@@ -730,6 +764,10 @@ fn find_packages(
 ///
 /// Handles concurrency control, download, verification, symlink creation,
 /// desktop integration, and database recording.
+///
+/// Once the context's token is cancelled no further package is started, and
+/// those still downloading are rolled back. The packages that had already
+/// been linked finish, and the whole fails with [`SoarError::Cancelled`].
 pub async fn perform_installation(
     ctx: &SoarContext,
     targets: Vec<InstallTarget>,
@@ -761,6 +799,9 @@ pub async fn perform_installation(
 
     for target in targets {
         let permit = semaphore.clone().acquire_owned().await.unwrap();
+        if ctx.cancellation().is_cancelled() {
+            break;
+        }
         let ctx = ctx.clone();
         let db = diesel_db.clone();
         let installed = installed.clone();
@@ -805,16 +846,22 @@ pub async fn perform_installation(
                             notes: target.package.notes.clone(),
                         });
                     }
-                    if let Err(err) = remove_old_versions(&target.package, &db, false) {
-                        warn!(error = %err, "could not remove the superseded version");
+                    if !target.keep_versions {
+                        if let Err(err) = remove_old_versions(&target.package, &db, false) {
+                            warn!(error = %err, "could not remove the superseded version");
+                        }
                     }
                 }
                 Err(err) => {
                     match err {
+                        // Reported as such where it was rolled back.
+                        err if err.is_cancelled() => {}
                         SoarError::Warning(msg) => {
                             warnings.lock().unwrap().push(msg);
-                            if let Err(err) = remove_old_versions(&target.package, &db, false) {
-                                warn!(error = %err, "could not remove the superseded version");
+                            if !target.keep_versions {
+                                if let Err(err) = remove_old_versions(&target.package, &db, false) {
+                                    warn!(error = %err, "could not remove the superseded version");
+                                }
                             }
                         }
                         _ => {
@@ -852,6 +899,10 @@ pub async fn perform_installation(
             .map_err(|err| SoarError::Custom(format!("Join handle error: {err}")))?;
     }
 
+    if ctx.cancellation().is_cancelled() {
+        return Err(SoarError::Cancelled);
+    }
+
     let installed = Arc::try_unwrap(installed).unwrap().into_inner().unwrap();
     let failed = Arc::try_unwrap(failed).unwrap().into_inner().unwrap();
     let warnings = Arc::try_unwrap(warnings).unwrap().into_inner().unwrap();
@@ -867,8 +918,11 @@ pub async fn perform_installation(
 /// inapplicable to this package's source.
 /// Exemption only skips the gate; an explicit `bsum` (e.g. a user-provided pin) is still
 /// enforced by checksum verification.
+///
+/// A snapshot relocated to a build of its own with no checksum is never exempt: nothing
+/// in the metadata pins which build its tag or URL serves.
 fn source_skips_integrity_gate(pkg: &Package) -> bool {
-    pkg.repo_name == "local" || pkg.ghcr_pkg.is_some()
+    !pkg.unverified_snapshot && (pkg.repo_name == "local" || pkg.ghcr_pkg.is_some())
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -902,7 +956,8 @@ async fn install_single_package(
     let mut lock_attempts = 0u32;
     let _package_lock = loop {
         match FileLock::try_acquire(&pkg.pkg_name) {
-            Ok(Some(lock)) => break Ok(lock),
+            Ok(Some(lock)) => break Ok(Some(lock)),
+            Ok(None) if ctx.cancellation().is_cancelled() => break Ok(None),
             Ok(None) => {
                 lock_attempts += 1;
                 if lock_attempts == 1 {
@@ -920,7 +975,8 @@ async fn install_single_package(
             Err(err) => break Err(err),
         }
     }
-    .map_err(|e| SoarError::Custom(format!("Failed to acquire package lock: {}", e)))?;
+    .map_err(|e| SoarError::Custom(format!("Failed to acquire package lock: {}", e)))?
+    .ok_or(SoarError::Cancelled)?;
 
     // Re-check if package is already installed after acquiring lock
     let freshly_installed = core_db
@@ -1106,10 +1162,16 @@ async fn install_single_package(
         events.clone(),
         op_id,
     )
-    .await?;
+    .await?
+    .with_cancellation(ctx.cancellation().clone());
 
     // Download
-    let downloaded_checksum = installer.download_package().await?;
+    let downloaded_checksum = match installer.download_package().await {
+        Err(err) if err.is_cancelled() => {
+            return Err(roll_back_cancelled(&installer, &events, op_id, pkg));
+        }
+        result => result?,
+    };
 
     // Signature verification
     let mut verified_sig_count = 0usize;
@@ -1196,6 +1258,13 @@ async fn install_single_package(
         }
     }
 
+    // Nothing outside the install directory has been touched yet, so this is
+    // the last point a cancelled install is undone whole. Past it, it is
+    // finished instead.
+    if ctx.cancellation().is_cancelled() {
+        return Err(roll_back_cancelled(&installer, &events, op_id, pkg));
+    }
+
     // Create symlinks
     events.emit(SoarEvent::Installing {
         op_id,
@@ -1207,7 +1276,7 @@ async fn install_single_package(
     // through `files`, not through a binary mapping.
     let binaries = target.binaries.clone().filter(|bins| !bins.is_empty());
 
-    let symlinks = mangle_package_symlinks(
+    let mut symlinks = package_symlinks(
         &install_dir,
         &bin_dir,
         pkg.provides.as_deref(),
@@ -1217,12 +1286,59 @@ async fn install_single_package(
         binaries.as_deref(),
         target.arch_map.as_ref(),
         pkg.files.as_deref(),
-    )
-    .await?;
+    )?;
+
+    // A binary that cannot start here would only fail later, as a bare "No
+    // such file or directory", so it is refused while that can be explained.
+    // That is before linking, so whatever version is installed keeps its
+    // commands.
+    let binaries: Vec<&Path> = if symlinks.is_empty() {
+        vec![real_bin.as_path()]
+    } else {
+        symlinks.iter().map(|(src, _)| src.as_path()).collect()
+    };
+    if let Err(err) = check_compatibility(&pkg.pkg_name, &install_dir, &binaries) {
+        if let Err(err) = installer.roll_back() {
+            warn!(pkg_name = pkg.pkg_name, error = %err, "could not roll back refused install");
+        }
+        return Err(err);
+    }
+    link_package_symlinks(&symlinks)?;
+    write_launchers(&install_dir, &mut symlinks)?;
+
+    // A tool that prints its own completions gets them written into the
+    // package first, so they are linked like ones it shipped.
+    let shells = ctx.config().completion_shells();
+    let completion_commands = completions::completion_commands(
+        pkg.completion_commands.as_ref(),
+        target.completion_commands.as_ref(),
+        target.sandbox.as_ref(),
+        &pkg.pkg_name,
+    );
+    if !completion_commands.is_empty() {
+        let env = HookEnv {
+            install_dir: &install_dir,
+            pkg_name: &pkg.pkg_name,
+            pkg_id: pkg.pkg_id.as_deref().unwrap_or_default(),
+            pkg_version: &pkg.version,
+        };
+        let name = completions::completion_name(
+            symlinks.first().map(|(_, link)| link.as_path()),
+            &pkg.pkg_name,
+        );
+        let generated = completions::generate_completions(
+            &completion_commands,
+            name,
+            &shells,
+            &env,
+            target.sandbox.as_ref(),
+        )?;
+        debug!(count = generated.len(), "generated completions");
+    }
 
     // Man pages and completions only mean anything where the system looks for
     // them, so they are linked out of the package the same way binaries are.
-    let shared = link_shared_files(&install_dir, &bin_dir, &ctx.config().completion_shells())?;
+    let shared = link_shared_files(&install_dir, &bin_dir, &shells)?;
 
     // Desktop integration
     if !unlinked || has_desktop_integration(pkg, ctx.config()) {
@@ -1247,6 +1363,64 @@ async fn install_single_package(
         .await?;
     }
 
+    // Extracting replaces each AppImage with its launcher, so it waits until
+    // desktop integration has taken the icon and desktop file out of it.
+    if appimage_mode(target, &symlinks, config) == AppImageMode::Extract {
+        let mut extracted = HashSet::new();
+        for bin in symlinks
+            .iter()
+            .map(|(src, _)| src)
+            .chain(std::iter::once(&real_bin))
+        {
+            if !extracted.insert(bin.clone()) || !is_appimage(bin) {
+                continue;
+            }
+            events.emit(SoarEvent::Installing {
+                op_id,
+                pkg_name: pkg.pkg_name.clone(),
+                stage: InstallStage::Extracting,
+            });
+            debug!(bin = %bin.display(), "extracting AppImage");
+            extract_appimage(bin)?;
+        }
+    }
+
+    // Launch settings go in front of what the link runs; the sandbox then goes
+    // in front of them, so a pre_launch command runs sandboxed too.
+    let runs_appimage = symlinks.iter().any(|(src, _)| is_appimage(src));
+    let declared_launch = target
+        .launch
+        .clone()
+        .or_else(|| get_launch_config(&pkg.pkg_name));
+    write_launch_wrappers(
+        &install_dir,
+        &mut symlinks,
+        &launch::effective(declared_launch.as_ref(), pkg.launch.as_ref()),
+    )?;
+
+    // The runtime sandbox goes in front of everything else, so it holds for
+    // whatever the link ran before. An update keeps the profile the previous
+    // install recorded unless packages.toml gives another one.
+    let runtime_sandbox = target
+        .runtime_sandbox
+        .clone()
+        .or_else(|| {
+            let existing = target.existing_install.as_ref()?;
+            recorded_runtime_sandbox(Path::new(&existing.installed_path))
+        })
+        .filter(|profile| profile.is_enabled());
+    if let Some(profile) = runtime_sandbox {
+        // fusermount is setuid, which Landlock's no_new_privs disarms.
+        if runs_appimage {
+            warn!(
+                "{}: an AppImage cannot mount itself inside its sandbox; set appimage_mode = \
+                 \"extract\" to run it sandboxed",
+                pkg.pkg_name
+            );
+        }
+        write_sandbox_launchers(&install_dir, &mut symlinks, &profile)?;
+    }
+
     // Record to database
     events.emit(SoarEvent::Installing {
         op_id,
@@ -1254,7 +1428,7 @@ async fn install_single_package(
         stage: InstallStage::RecordingDatabase,
     });
 
-    installer
+    let record_id = installer
         .record(
             unlinked,
             eff_portable,
@@ -1265,6 +1439,22 @@ async fn install_single_package(
         )
         .await?;
 
+    // A command another package also provides goes to whichever ranks first,
+    // not to whichever was installed last.
+    let priority = target
+        .priority
+        .or_else(|| get_priority(&pkg.pkg_name))
+        .unwrap_or(0);
+    alternatives::claim(
+        &core_db,
+        &bin_dir,
+        record_id,
+        target.existing_install.as_ref().map(|e| e.id as i32),
+        &symlinks,
+        priority,
+    )?;
+    versions::refresh(&core_db, &bin_dir, &pkg.pkg_name)?;
+
     installer.run_post_install_hook()?;
 
     events.emit(SoarEvent::OperationComplete {
@@ -1281,6 +1471,23 @@ async fn install_single_package(
     Ok((install_dir, symlinks, shared))
 }
 
+/// Undo an install that was cancelled before it was linked, and say so.
+fn roll_back_cancelled(
+    installer: &PackageInstaller,
+    events: &EventSinkHandle,
+    op_id: OperationId,
+    pkg: &Package,
+) -> SoarError {
+    if let Err(err) = installer.roll_back() {
+        warn!(pkg_name = pkg.pkg_name, error = %err, "could not roll back cancelled install");
+    }
+    events.emit(SoarEvent::OperationCancelled {
+        op_id,
+        pkg_name: pkg.pkg_name.clone(),
+    });
+    SoarError::Cancelled
+}
+
 fn verify_signatures(pubkey_str: &str, install_dir: &Path) -> SoarResult<usize> {
     let pubkey = PublicKey::from_base64(pubkey_str.trim())
         .map_err(|err| SoarError::Custom(format!("Failed to parse public key: {}", err)))?;
@@ -1343,6 +1550,61 @@ fn verify_signatures(pubkey_str: &str, install_dir: &Path) -> SoarResult<usize>
     Ok(verified)
 }
 
+/// How the AppImages of this install are run.
+///
+/// packages.toml decides first, then the global setting. With neither, an
+/// update keeps extracting a package that was extracted before.
+fn appimage_mode(
+    target: &InstallTarget,
+    symlinks: &[(PathBuf, PathBuf)],
+    config: &Config,
+) -> AppImageMode {
+    if let Some(mode) = target.appimage_mode.or(config.appimage_mode) {
+        return mode;
+    }
+    let was_extracted = target.existing_install.as_ref().is_some_and(|existing| {
+        let old_dir = Path::new(&existing.installed_path);
+        symlinks
+            .iter()
+            .filter_map(|(src, _)| src.file_name())
+            .any(|name| is_extracted_appimage(old_dir.join(name)))
+    });
+    if was_extracted {
+        AppImageMode::Extract
+    } else {
+        AppImageMode::Mount
+    }
+}
+
+fn is_appimage(path: &Path) -> bool {
+    File::open(path)
+        .ok()
+        .and_then(|file| get_file_type(&mut BufReader::new(file)).ok())
+        == Some(PackageFormat::AppImage)
+}
+
+/// Refuse binaries that cannot start on this host, and warn about ones that
+/// may fail once running.
+fn check_compatibility(pkg_name: &str, install_dir: &Path, binaries: &[&Path]) -> SoarResult<()> {
+    let mut checked = HashSet::new();
+    for bin in binaries {
+        if !checked.insert(*bin) {
+            continue;
+        }
+        let name = bin.strip_prefix(install_dir).unwrap_or(bin).display();
+        let issues = compat::check(bin);
+        if let Some(fatal) = issues.iter().find(|issue| issue.is_fatal()) {
+            return Err(SoarError::Custom(format!(
+                "Refusing to install {pkg_name}: {name} {fatal}"
+            )));
+        }
+        for issue in issues {
+            warn!("{pkg_name}: {name} {issue}");
+        }
+    }
+    Ok(())
+}
+
 fn cleanup_sig_files(install_dir: &Path) {
     if let Ok(entries) = fs::read_dir(install_dir) {
         for entry in entries.filter_map(|e| e.ok()) {
@@ -1386,6 +1648,15 @@ mod tests {
         )));
     }
 
+    #[test]
+    fn unverified_snapshot_is_subject_to_integrity_gate() {
+        let snapshot = Package {
+            unverified_snapshot: true,
+            ..pkg("some-repo", Some("ghcr.io/org/repo:older"), None)
+        };
+        assert!(!source_skips_integrity_gate(&snapshot));
+    }
+
     #[test]
     fn registry_source_is_subject_to_integrity_gate() {
         assert!(!source_skips_integrity_gate(&pkg("soarpkgs", None, None)));
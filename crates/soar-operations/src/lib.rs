@@ -3,14 +3,19 @@ pub mod progress;
 pub mod types;
 pub mod utils;
 
+pub mod alternatives;
 pub mod apply;
+pub mod audit;
 pub mod health;
+pub mod inspect;
 pub mod install;
 pub mod list;
+pub mod mime;
 pub mod remove;
 pub mod repo;
 pub mod run;
 pub mod search;
+pub mod shell;
 pub mod switch;
 pub mod sync;
 pub mod update;
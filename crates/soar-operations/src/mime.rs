@@ -0,0 +1,101 @@
+use std::{fs, path::PathBuf};
+
+use soar_core::{error::SoarError, SoarResult};
+use soar_db::repository::core::{CoreRepository, SortDirection};
+use soar_package::mime::{desktop_mime_types, is_valid_mime_type, set_default};
+use soar_utils::path::mimeapps_list;
+use tracing::debug;
+
+use crate::{MimeDefault, SoarContext};
+
+/// Make an installed package the default application for a MIME type or
+/// `x-scheme-handler/<scheme>` URL scheme.
+///
+/// Only a type the package's desktop entry declares in `MimeType=` is
+/// accepted, since the desktop would not hand anything else to it.
+pub fn set_default_application(
+    ctx: &SoarContext,
+    pkg_name: &str,
+    mime: &str,
+) -> SoarResult<MimeDefault> {
+    debug!(
+        pkg_name = pkg_name,
+        mime = mime,
+        "setting default application"
+    );
+    if !is_valid_mime_type(mime) {
+        return Err(SoarError::Custom(format!(
+            "`{mime}` is not a MIME type, such as text/plain or x-scheme-handler/https"
+        )));
+    }
+
+    let diesel_db = ctx.diesel_core_db()?;
+    let packages = diesel_db.with_conn(|conn| {
+        CoreRepository::list_filtered(
+            conn,
+            None,
+            Some(pkg_name),
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(SortDirection::Asc),
+        )
+    })?;
+    let package = packages
+        .into_iter()
+        .find(|p| p.is_installed && !p.unlinked)
+        .ok_or_else(|| SoarError::Custom(format!("{pkg_name} is not installed")))?;
+
+    let config = ctx.config();
+    let desktop_path = config.get_desktop_path()?;
+    let installed_path = PathBuf::from(&package.installed_path);
+
+    let mut declared = Vec::new();
+    let mut entries: Vec<PathBuf> = fs::read_dir(&desktop_path)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|e| e.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "desktop"))
+        .filter(|path| fs::read_link(path).is_ok_and(|target| target.starts_with(&installed_path)))
+        .collect();
+    entries.sort();
+
+    if entries.is_empty() {
+        return Err(SoarError::Custom(format!(
+            "{pkg_name} has no desktop entry to open files with"
+        )));
+    }
+
+    for entry in entries {
+        let Ok(content) = fs::read_to_string(&entry) else {
+            continue;
+        };
+        let types = desktop_mime_types(&content);
+        if types.iter().any(|t| t.eq_ignore_ascii_case(mime)) {
+            let desktop_id = entry
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned();
+            let mimeapps = mimeapps_list(config.is_system());
+            set_default(&mimeapps, mime, &desktop_id)?;
+            return Ok(MimeDefault {
+                desktop_id,
+                mimeapps,
+            });
+        }
+        declared.extend(types);
+    }
+
+    Err(SoarError::Custom(if declared.is_empty() {
+        format!("{pkg_name} does not declare any MIME types it opens")
+    } else {
+        format!(
+            "{pkg_name} does not open {mime}; it declares {}",
+            declared.join(", ")
+        )
+    }))
+}
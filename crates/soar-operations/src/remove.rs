@@ -118,6 +118,10 @@ pub fn resolve_removals(
 }
 
 /// Remove installed packages. Emits events through the context's event sink.
+///
+/// Once the context's token is cancelled no further package is removed. One
+/// whose pre_remove hook is stopped is left installed, and the whole fails
+/// with [`SoarError::Cancelled`].
 pub async fn perform_removal(
     ctx: &SoarContext,
     packages: Vec<InstalledPackage>,
@@ -129,6 +133,9 @@ pub async fn perform_removal(
     let mut failed = Vec::new();
 
     for pkg in packages {
+        if ctx.cancellation().is_cancelled() {
+            return Err(SoarError::Cancelled);
+        }
         let op_id = next_op_id();
 
         ctx.events().emit(SoarEvent::Removing {
@@ -147,7 +154,8 @@ pub async fn perform_removal(
         let remover = PackageRemover::new(pkg.clone(), diesel_db.clone(), ctx.config().clone())
             .await
             .with_hooks(hooks)
-            .with_sandbox(sandbox);
+            .with_sandbox(sandbox)
+            .with_cancellation(ctx.cancellation().clone());
 
         match remover.remove().await {
             Ok(()) => {
@@ -169,6 +177,13 @@ pub async fn perform_removal(
                     version: pkg.version,
                 });
             }
+            Err(err) if err.is_cancelled() => {
+                ctx.events().emit(SoarEvent::OperationCancelled {
+                    op_id,
+                    pkg_name: pkg.pkg_name.clone(),
+                });
+                return Err(SoarError::Cancelled);
+            }
             Err(err) => {
                 ctx.events().emit(SoarEvent::OperationFailed {
                     op_id,
@@ -11,6 +11,7 @@ use crate::SoarContext;
 /// Fields that can be updated on an existing repository.
 pub struct RepoUpdate {
     pub url: Option<String>,
+    pub delta_url: Option<String>,
     pub enabled: Option<bool>,
     pub pubkey: Option<String>,
     pub desktop_integration: Option<bool>,
@@ -60,6 +61,9 @@ impl SoarContext {
             if let Some(url) = update.url {
                 repo.url = url;
             }
+            if let Some(delta_url) = update.delta_url {
+                repo.delta_url = Some(delta_url);
+            }
             if let Some(enabled) = update.enabled {
                 repo.enabled = Some(enabled);
             }
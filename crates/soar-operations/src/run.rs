@@ -1,22 +1,29 @@
-use std::{fs, path::Path, process::Command, sync::Arc};
+use std::{
+    convert::Infallible, ffi::OsString, fs, os::unix::process::CommandExt as _, path::Path,
+    process::Command, sync::Arc,
+};
 
+use soar_config::packages::RuntimeSandbox;
 use soar_core::{
     database::models::Package,
     error::{ErrorContext, SoarError},
-    package::{install::apply_file_layout, query::PackageQuery},
+    package::{compat, install::apply_file_layout, query::PackageQuery},
+    sandbox,
     utils::get_extract_dir,
     SoarResult,
 };
 use soar_db::repository::metadata::MetadataRepository;
 use soar_dl::{download::Download, oci::OciDownload, types::OverwriteMode};
 use soar_utils::{
+    entrypoint,
     hash::{calculate_checksum, hash_string},
     version::compare_versions,
 };
-use tracing::debug;
+use tracing::{debug, warn};
 
 use crate::{
     progress::{create_progress_bridge, next_op_id},
+    utils::get_runtime_sandbox,
     AmbiguousPackage, PrepareRunResult, RunResult, SoarContext,
 };
 
@@ -270,11 +277,53 @@ pub async fn prepare_run(
     })
 }
 
-/// Execute a binary with the given arguments.
-pub fn execute_binary(path: &Path, args: &[String]) -> SoarResult<RunResult> {
+/// The runtime sandbox profile a one-off run of `package` uses: the one
+/// packages.toml gives the package, or the default profile, which allows
+/// nothing beyond the package and the system's libraries.
+pub fn runtime_profile(package: &str) -> SoarResult<RuntimeSandbox> {
+    let query = PackageQuery::try_from(package)?;
+    let name = query.name.as_deref().unwrap_or(package);
+    Ok(get_runtime_sandbox(name)
+        .filter(RuntimeSandbox::is_enabled)
+        .unwrap_or_default())
+}
+
+/// Execute a binary with the given arguments, inside `sandbox` when given.
+pub fn execute_binary(
+    path: &Path,
+    args: &[String],
+    sandbox: Option<&RuntimeSandbox>,
+) -> SoarResult<RunResult> {
     debug!(path = %path.display(), args = ?args, "executing binary");
 
-    let status = Command::new(path)
+    let name = path
+        .file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy();
+    let issues = compat::check(path);
+    if let Some(fatal) = issues.iter().find(|issue| issue.is_fatal()) {
+        return Err(SoarError::Custom(format!(
+            "Refusing to run {name}: it {fatal}"
+        )));
+    }
+    for issue in issues {
+        warn!("{name} {issue}");
+    }
+
+    // A jar or zipapp is run through its interpreter, as its launcher would.
+    let mut command = match entrypoint::classify(path).and_then(|e| e.invocation(path)) {
+        Some(argv) => {
+            let mut command = Command::new(&argv[0]);
+            command.args(&argv[1..]);
+            command
+        }
+        None => Command::new(path),
+    };
+    if let Some(profile) = sandbox {
+        let package_dir = path.parent().unwrap_or(path);
+        sandbox::apply_runtime_sandbox(&mut command, profile, package_dir)?;
+    }
+    let status = command
         .args(args)
         .status()
         .with_context(|| format!("executing command {}", path.display()))?;
@@ -284,6 +333,21 @@ pub fn execute_binary(path: &Path, args: &[String]) -> SoarResult<RunResult> {
     })
 }
 
+/// Replace this process with `program` run under the runtime sandbox profile
+/// recorded at `profile`, which is what an installed package's sandbox
+/// launcher asks for.
+///
+/// Only returns when the program could not be started.
+pub fn exec_sandboxed(profile: &Path, program: &Path, args: &[OsString]) -> SoarResult<Infallible> {
+    let package_dir = profile.parent().unwrap_or(profile);
+    let profile = sandbox::read_runtime_profile(profile)?;
+
+    let mut command = Command::new(program);
+    command.args(args);
+    sandbox::apply_runtime_sandbox(&mut command, &profile, package_dir)?;
+    Err(command.exec()).with_context(|| format!("executing command {}", program.display()))
+}
+
 fn download_to_cache(
     package: &Package,
     output_path: &Path,
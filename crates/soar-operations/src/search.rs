@@ -11,7 +11,7 @@ use soar_db::{
     models::metadata::FuzzyCandidate,
     repository::{
         core::{CoreRepository, SortDirection},
-        metadata::MetadataRepository,
+        metadata::{MetadataRepository, SearchFilters, SearchHit},
     },
 };
 use soar_utils::version::compare_versions;
@@ -22,10 +22,61 @@ use crate::{
     SearchEntry, SearchResult, SoarContext,
 };
 
+/// A search as typed: the words to rank by, and the `field:value` filters
+/// mixed in among them.
+///
+/// `category:`, `type:` and `license:` narrow by what a package is, and
+/// `repo:` by where it comes from. Any other word is text, including one
+/// with a colon in it that names no field, so nothing typed is ever dropped.
+#[derive(Debug, Default, PartialEq)]
+pub struct SearchQuery {
+    pub text: String,
+    pub filters: SearchFilters,
+    pub repos: Vec<String>,
+}
+
+impl SearchQuery {
+    pub fn parse(query: &str) -> Self {
+        let mut parsed = Self::default();
+        let mut words = Vec::new();
+
+        for word in query.split_whitespace() {
+            let field = word
+                .split_once(':')
+                .filter(|(_, value)| !value.is_empty())
+                .and_then(|(key, value)| {
+                    let target = match key.to_ascii_lowercase().as_str() {
+                        "category" => &mut parsed.filters.categories,
+                        "type" => &mut parsed.filters.pkg_types,
+                        "license" => &mut parsed.filters.licenses,
+                        "repo" => &mut parsed.repos,
+                        _ => return None,
+                    };
+                    Some((target, value))
+                });
+
+            match field {
+                Some((target, value)) => target.push(value.to_string()),
+                None => words.push(word),
+            }
+        }
+
+        parsed.text = words.join(" ");
+        parsed
+    }
+
+    /// Whether a repository is one this search covers.
+    fn covers(&self, repo_name: &str) -> bool {
+        self.repos.is_empty() || self.repos.iter().any(|r| r == repo_name)
+    }
+}
+
 /// Search for packages across all repositories.
 ///
-/// Uses fuzzy matching by default. Falls back to SQL LIKE for case-sensitive
-/// searches.
+/// Ranks through each repository's full-text index by default, and falls back
+/// to fuzzy matching when plain text finds nothing, so a misspelt name still
+/// turns something up. A case-sensitive search matches the query literally
+/// with SQL LIKE.
 pub async fn search_packages(
     ctx: &SoarContext,
     query: &str,
@@ -57,7 +108,15 @@ pub async fn search_packages(
                 .collect())
         })?
     } else {
-        fuzzy_search(ctx, query, search_limit).await?
+        let parsed = SearchQuery::parse(query);
+        let found = ranked_search(ctx, &parsed, search_limit).await?;
+        let plain_text = parsed.filters.is_empty() && parsed.repos.is_empty();
+        if found.is_empty() && plain_text && !parsed.text.is_empty() {
+            debug!("no ranked matches; falling back to fuzzy search");
+            fuzzy_search(ctx, &parsed.text, search_limit).await?
+        } else {
+            found
+        }
     };
 
     // One row per package: a result repeated once per published version says
@@ -176,6 +235,45 @@ pub async fn search_packages(
     })
 }
 
+/// Returns the best ranked matches across the repositories a query covers.
+///
+/// Each repository ranks against its own index, and the hits are merged on
+/// those scores: a package named outright first, then by BM25. Scores from
+/// different catalogs are not strictly comparable, since each weighs a word by
+/// how rare it is there, but they are close enough to interleave results
+/// rather than list one repository after another.
+async fn ranked_search(
+    ctx: &SoarContext,
+    query: &SearchQuery,
+    limit: usize,
+) -> SoarResult<Vec<Package>> {
+    let metadata_mgr = ctx.metadata_manager().await?;
+    let sql_limit = limit as i64;
+
+    let mut hits: Vec<(String, SearchHit)> = metadata_mgr.query_all_flat(|repo_name, conn| {
+        if !query.covers(repo_name) {
+            return Ok(Vec::new());
+        }
+        let hits =
+            MetadataRepository::search_ranked(conn, &query.text, &query.filters, Some(sql_limit))?;
+        Ok(hits
+            .into_iter()
+            .map(|hit| (repo_name.to_string(), hit))
+            .collect())
+    })?;
+
+    hits.sort_by(|(_, a), (_, b)| b.exact.cmp(&a.exact).then(a.score.total_cmp(&b.score)));
+
+    Ok(hits
+        .into_iter()
+        .map(|(repo_name, hit)| {
+            let mut pkg: Package = hit.package.into();
+            pkg.repo_name = repo_name;
+            pkg
+        })
+        .collect())
+}
+
 /// Returns top fuzzy-matched packages across all repositories.
 async fn fuzzy_search(ctx: &SoarContext, query: &str, limit: usize) -> SoarResult<Vec<Package>> {
     let metadata_mgr = ctx.metadata_manager().await?;
@@ -394,3 +492,40 @@ pub async fn query_package(ctx: &SoarContext, query_str: &str) -> SoarResult<Vec
 
     Ok(packages)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_are_lifted_out_of_the_text() {
+        let query = SearchQuery::parse("audio category:Audio editor type:appimage repo:bincache");
+        assert_eq!(query.text, "audio editor");
+        assert_eq!(query.filters.categories, ["Audio"]);
+        assert_eq!(query.filters.pkg_types, ["appimage"]);
+        assert_eq!(query.repos, ["bincache"]);
+    }
+
+    #[test]
+    fn a_field_may_be_given_more_than_once() {
+        let query = SearchQuery::parse("license:MIT License:Apache-2.0");
+        assert!(query.text.is_empty());
+        assert_eq!(query.filters.licenses, ["MIT", "Apache-2.0"]);
+    }
+
+    #[test]
+    fn a_colon_naming_no_field_stays_text() {
+        let query = SearchQuery::parse("bat:official category: std::fs");
+        assert_eq!(query.text, "bat:official category: std::fs");
+        assert!(query.filters.is_empty());
+        assert!(query.repos.is_empty());
+    }
+
+    #[test]
+    fn no_repo_filter_covers_every_repository() {
+        assert!(SearchQuery::parse("bat").covers("bincache"));
+        let scoped = SearchQuery::parse("bat repo:soarpkgs");
+        assert!(scoped.covers("soarpkgs"));
+        assert!(!scoped.covers("bincache"));
+    }
+}
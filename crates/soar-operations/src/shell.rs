@@ -0,0 +1,329 @@
+use std::{
+    env, fs,
+    os::unix::{self, fs::PermissionsExt as _},
+    path::{Path, PathBuf},
+    process::{self, Command},
+};
+
+use nix::{
+    errno::Errno,
+    sys::signal::{kill, signal, SigHandler, Signal},
+    unistd::Pid,
+};
+use soar_core::{
+    error::{ErrorContext, SoarError},
+    package::compat,
+    SoarResult,
+};
+use soar_utils::entrypoint;
+use tracing::{debug, warn};
+
+use crate::{RunResult, SoarContext};
+
+/// A temporary directory of commands from cached packages, removed when
+/// dropped.
+pub struct ShellBin {
+    dir: PathBuf,
+    /// Names of the commands it holds, sorted.
+    pub commands: Vec<String>,
+}
+
+impl ShellBin {
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for ShellBin {
+    fn drop(&mut self) {
+        if let Err(err) = fs::remove_dir_all(&self.dir) {
+            warn!("Failed to remove {}: {}", self.dir.display(), err);
+        }
+    }
+}
+
+/// Cache entries a shell downloaded, removed when dropped unless kept.
+pub struct Downloads {
+    cache_bin: PathBuf,
+    binaries: Vec<PathBuf>,
+    keep: bool,
+}
+
+impl Downloads {
+    /// Track downloads for a shell, which keeps them in the cache with `keep`.
+    pub fn new(ctx: &SoarContext, keep: bool) -> SoarResult<Self> {
+        Ok(Self {
+            cache_bin: ctx.config().get_cache_path()?.join("bin"),
+            binaries: Vec::new(),
+            keep,
+        })
+    }
+
+    /// Track `binary`, which `prepare_run` just downloaded.
+    pub fn push(&mut self, binary: PathBuf) {
+        self.binaries.push(binary);
+    }
+}
+
+impl Drop for Downloads {
+    fn drop(&mut self) {
+        if self.keep {
+            return;
+        }
+        for binary in &self.binaries {
+            let Some(entry) = binary
+                .ancestors()
+                .find(|dir| dir.parent() == Some(self.cache_bin.as_path()))
+            else {
+                continue;
+            };
+            debug!(path = %entry.display(), "removing cached package");
+            if let Err(err) = fs::remove_dir_all(entry) {
+                warn!("Failed to remove {}: {}", entry.display(), err);
+            }
+        }
+    }
+}
+
+/// Link the commands of packages `prepare_run` put in the cache into a fresh
+/// directory under the cache.
+///
+/// Directories left behind by shells that are no longer running are removed
+/// first.
+///
+/// Each binary is linked under its own name. A package laid out with a `bin/`
+/// directory has every executable in it linked, so a package shipping several
+/// tools provides all of them. A jar, zipapp, or script whose interpreter is
+/// elsewhere here gets a launcher instead, and a name already taken by an
+/// earlier package is skipped with a warning.
+pub fn make_shell_bin(ctx: &SoarContext, binaries: &[PathBuf]) -> SoarResult<ShellBin> {
+    let cache = ctx.config().get_cache_path()?;
+    sweep_stale(&cache);
+    let dir = cache.join(format!("shell-{}", process::id()));
+    if dir.exists() {
+        fs::remove_dir_all(&dir)
+            .with_context(|| format!("removing directory {}", dir.display()))?;
+    }
+    fs::create_dir_all(&dir).with_context(|| format!("creating directory {}", dir.display()))?;
+    let mut shell_bin = ShellBin {
+        dir,
+        commands: Vec::new(),
+    };
+
+    for binary in binaries {
+        for command in package_commands(binary)? {
+            let Some(name) = command
+                .file_name()
+                .map(|n| n.to_string_lossy().into_owned())
+            else {
+                continue;
+            };
+            if shell_bin.commands.contains(&name) {
+                warn!(
+                    "{} is provided by more than one package, keeping the first",
+                    name
+                );
+                continue;
+            }
+
+            let issues = compat::check(&command);
+            if let Some(fatal) = issues.iter().find(|issue| issue.is_fatal()) {
+                return Err(SoarError::Custom(format!(
+                    "Refusing to run {name}: it {fatal}"
+                )));
+            }
+            for issue in issues {
+                warn!("{name} {issue}");
+            }
+
+            let link = shell_bin.dir.join(&name);
+            match entrypoint::classify(&command).and_then(|e| e.launcher(&command)) {
+                Some(script) => {
+                    fs::write(&link, script)
+                        .with_context(|| format!("writing launcher {}", link.display()))?;
+                    fs::set_permissions(&link, fs::Permissions::from_mode(0o755))
+                        .with_context(|| format!("setting permissions on {}", link.display()))?;
+                }
+                None => {
+                    unix::fs::symlink(&command, &link)
+                        .with_context(|| format!("creating symlink {}", link.display()))?;
+                }
+            }
+            debug!(command = %command.display(), link = %link.display(), "linked into shell");
+            shell_bin.commands.push(name);
+        }
+    }
+    shell_bin.commands.sort();
+    Ok(shell_bin)
+}
+
+/// Remove the `shell-<pid>` directories in `cache` whose shell is gone, as a
+/// soar that was killed never removed its own.
+fn sweep_stale(cache: &Path) {
+    let Ok(entries) = fs::read_dir(cache) else {
+        return;
+    };
+    for entry in entries.filter_map(Result::ok) {
+        let name = entry.file_name();
+        let Some(pid) = name
+            .to_str()
+            .and_then(|name| name.strip_prefix("shell-"))
+            .and_then(|pid| pid.parse::<i32>().ok())
+            .filter(|pid| *pid > 0)
+        else {
+            continue;
+        };
+        // A process that exists but is not ours still counts as running.
+        if pid == process::id() as i32 || kill(Pid::from_raw(pid), None) != Err(Errno::ESRCH) {
+            continue;
+        }
+        let path = entry.path();
+        debug!(path = %path.display(), "removing stale shell directory");
+        if let Err(err) = fs::remove_dir_all(&path) {
+            warn!("Failed to remove {}: {}", path.display(), err);
+        }
+    }
+}
+
+/// The commands a cached package provides: its binary, and the other
+/// executables beside it when it was laid out into a `bin/` directory.
+fn package_commands(binary: &Path) -> SoarResult<Vec<PathBuf>> {
+    let mut commands = vec![binary.to_path_buf()];
+    let Some(bin_dir) = binary
+        .parent()
+        .filter(|dir| dir.file_name().is_some_and(|name| name == "bin"))
+    else {
+        return Ok(commands);
+    };
+
+    let mut others = fs::read_dir(bin_dir)
+        .with_context(|| format!("reading {} directory", bin_dir.display()))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path != binary
+                && fs::metadata(path)
+                    .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        })
+        .collect::<Vec<_>>();
+    others.sort();
+    commands.extend(others);
+    Ok(commands)
+}
+
+/// Run `command`, or `$SHELL` when it is empty, with `shell_bin` first on
+/// `PATH`.
+///
+/// `SOAR_SHELL` is set to `packages`, space-separated, so a prompt can show
+/// it is inside one.
+pub fn run_shell(
+    shell_bin: &ShellBin,
+    packages: &[String],
+    command: &[String],
+) -> SoarResult<RunResult> {
+    let path = match env::var_os("PATH") {
+        Some(path) => {
+            let mut dirs = vec![shell_bin.path().to_path_buf()];
+            dirs.extend(env::split_paths(&path));
+            env::join_paths(dirs).map_err(|err| SoarError::Custom(err.to_string()))?
+        }
+        None => shell_bin.path().as_os_str().to_owned(),
+    };
+
+    let mut cmd = match command.split_first() {
+        Some((program, args)) => {
+            let mut cmd = Command::new(program);
+            cmd.args(args);
+            cmd
+        }
+        None => Command::new(env::var_os("SHELL").unwrap_or_else(|| "/bin/sh".into())),
+    };
+    debug!(command = ?cmd, "starting shell");
+
+    let mut child = cmd
+        .env("PATH", path)
+        .env("SOAR_SHELL", packages.join(" "))
+        .spawn()
+        .with_context(|| format!("executing command {:?}", cmd.get_program()))?;
+
+    // Ctrl-C in the shell reaches soar too. Outliving it is what lets the
+    // directory be removed once the shell exits.
+    // SAFETY: only the dispositions are changed; no handler runs.
+    let previous = unsafe {
+        [Signal::SIGINT, Signal::SIGQUIT].map(|sig| (sig, signal(sig, SigHandler::SigIgn)))
+    };
+    let status = child.wait();
+    for (sig, handler) in previous {
+        if let Ok(handler) = handler {
+            // SAFETY: restores what was there before.
+            unsafe {
+                let _ = signal(sig, handler);
+            }
+        }
+    }
+    let status = status.with_context(|| format!("waiting for {:?}", cmd.get_program()))?;
+
+    Ok(RunResult {
+        exit_code: status.code().unwrap_or(-1),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    fn executable(path: &Path) {
+        File::create(path).unwrap();
+        fs::set_permissions(path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn laid_out_packages_provide_every_executable_in_bin() {
+        let dir = tempdir().unwrap();
+        let bin = dir.path().join("bin");
+        fs::create_dir(&bin).unwrap();
+        for name in ["tool", "tool-extra", "helper"] {
+            executable(&bin.join(name));
+        }
+        File::create(bin.join("README")).unwrap();
+
+        let commands = package_commands(&bin.join("tool")).unwrap();
+
+        assert_eq!(
+            commands,
+            [bin.join("tool"), bin.join("helper"), bin.join("tool-extra")]
+        );
+    }
+
+    #[test]
+    fn stale_shell_directories_are_swept() {
+        let dir = tempdir().unwrap();
+        // Past the kernel's limit on pids, so never running.
+        let stale = dir.path().join("shell-2147483646");
+        let own = dir.path().join(format!("shell-{}", process::id()));
+        let other = dir.path().join("shell-notes");
+        for path in [&stale, &own, &other] {
+            fs::create_dir(path).unwrap();
+        }
+
+        sweep_stale(dir.path());
+
+        assert!(!stale.exists());
+        assert!(own.exists());
+        assert!(other.exists());
+    }
+
+    #[test]
+    fn plain_downloads_provide_only_themselves() {
+        let dir = tempdir().unwrap();
+        executable(&dir.path().join("tool"));
+        executable(&dir.path().join("other"));
+
+        let commands = package_commands(&dir.path().join("tool")).unwrap();
+
+        assert_eq!(commands, [dir.path().join("tool")]);
+    }
+}
@@ -1,8 +1,10 @@
 use std::path::PathBuf;
 
 use soar_core::{
+    alternatives,
     database::models::{InstalledPackage, Package},
     error::SoarError,
+    package::{launch, versions},
     SoarResult,
 };
 use soar_db::repository::{
@@ -13,7 +15,10 @@ use soar_package::{formats::common::setup_portable_dir, integrate_package};
 use tracing::debug;
 
 use crate::{
-    utils::{has_desktop_integration, mangle_package_symlinks},
+    utils::{
+        get_priority, has_desktop_integration, mangle_package_symlinks, recorded_runtime_sandbox,
+        write_launch_wrappers, write_launchers, write_sandbox_launchers,
+    },
     SoarContext, VariantInfo,
 };
 
@@ -49,6 +54,47 @@ pub fn list_variants(ctx: &SoarContext, name: &str) -> SoarResult<Vec<VariantInf
         .collect())
 }
 
+/// Make the installed `version` of a package its default.
+///
+/// # Returns
+///
+/// The variant switched to.
+pub async fn switch_version(
+    ctx: &SoarContext,
+    name: &str,
+    version: &str,
+) -> SoarResult<InstalledPackage> {
+    let variants = list_variants(ctx, name)?;
+    let matching = variants
+        .iter()
+        .enumerate()
+        .filter(|(_, variant)| variant.package.version == version)
+        .map(|(idx, _)| idx)
+        .collect::<Vec<_>>();
+    match matching.as_slice() {
+        [idx] => {
+            switch_variant(ctx, name, *idx).await?;
+            Ok(variants[*idx].package.clone())
+        }
+        [] if variants.is_empty() => Err(SoarError::Custom(format!("{name} is not installed"))),
+        [] => {
+            let installed = variants
+                .iter()
+                .map(|variant| variant.package.version.as_str())
+                .collect::<Vec<_>>();
+            Err(SoarError::Custom(format!(
+                "{name}@{version} is not installed; installed versions: {}",
+                installed.join(", ")
+            )))
+        }
+        _ => {
+            Err(SoarError::Custom(format!(
+                "several variants of {name} are at {version}; run `soar use {name}` to choose one"
+            )))
+        }
+    }
+}
+
 /// Switch the active variant for a package name.
 ///
 /// `selected_index` is the 0-based index into the list returned by [`list_variants`].
@@ -93,7 +139,7 @@ pub async fn switch_variant(
             &selected_package.repo_name,
             pkg_id,
             selected_package.pkg_family.as_deref(),
-            None,
+            Some(&selected_package.version),
         )?;
         CoreRepository::link_by_row_id(conn, selected_package.id)
     })?;
@@ -103,7 +149,7 @@ pub async fn switch_variant(
     let install_dir = PathBuf::from(&selected_package.installed_path);
 
     // Re-create symlinks
-    let symlinks = mangle_package_symlinks(
+    let mut symlinks = mangle_package_symlinks(
         &install_dir,
         &bin_dir,
         selected_package.provides.as_deref(),
@@ -115,6 +161,30 @@ pub async fn switch_variant(
         None,
     )
     .await?;
+    write_launchers(&install_dir, &mut symlinks)?;
+    // The variant switched to keeps the launch settings and sandbox its
+    // install recorded.
+    write_launch_wrappers(
+        &install_dir,
+        &mut symlinks,
+        &launch::recorded(&install_dir)?,
+    )?;
+    if let Some(profile) =
+        recorded_runtime_sandbox(&install_dir).filter(|profile| profile.is_enabled())
+    {
+        write_sandbox_launchers(&install_dir, &mut symlinks, &profile)?;
+    }
+    // Commands another package provides go to whichever should hold them.
+    alternatives::claim(
+        diesel_db,
+        &bin_dir,
+        selected_package.id,
+        None,
+        &symlinks,
+        get_priority(pkg_name).unwrap_or(0),
+    )?;
+    // The other versions stay selectable through the new default.
+    versions::refresh(diesel_db, &bin_dir, pkg_name)?;
 
     let actual_bin = symlinks.first().map(|(src, _)| src.as_path());
 
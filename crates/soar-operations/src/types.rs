@@ -1,9 +1,11 @@
 use std::path::PathBuf;
 
 use soar_core::{
+    audit::AuditKind,
     database::models::{InstalledPackage, Package},
     package::install::InstallTarget,
 };
+use soar_package::{ContentEntry, PackageFormat};
 
 // ---- Install ----
 
@@ -173,6 +175,15 @@ pub struct HealthReport {
     pub man_path_configured: bool,
     pub broken_packages: Vec<BrokenPackage>,
     pub broken_symlinks: Vec<PathBuf>,
+    /// AppImages that cannot start because this system has no FUSE. Empty
+    /// wherever FUSE is available.
+    pub unmountable_appimages: Vec<BrokenPackage>,
+    /// Installed packages whose binaries need shared libraries this system
+    /// does not provide.
+    pub unresolved_libraries: Vec<UnresolvedLibraries>,
+    /// Installed packages with a script, zipapp or jar whose interpreter is no
+    /// longer installed.
+    pub missing_runtimes: Vec<MissingRuntimes>,
 }
 
 pub struct BrokenPackage {
@@ -180,6 +191,18 @@ pub struct BrokenPackage {
     pub installed_path: String,
 }
 
+pub struct UnresolvedLibraries {
+    pub pkg_name: String,
+    pub installed_path: String,
+    pub libraries: Vec<String>,
+}
+
+pub struct MissingRuntimes {
+    pub pkg_name: String,
+    pub installed_path: String,
+    pub runtimes: Vec<String>,
+}
+
 // ---- Apply ----
 
 /// Result of comparing declared packages vs installed packages.
@@ -193,6 +216,9 @@ pub struct ApplyDiff {
     pub to_remove: Vec<InstalledPackage>,
     /// Packages already in sync.
     pub in_sync: Vec<String>,
+    /// In-sync packages whose launch settings changed, with the settings to
+    /// write their launchers for.
+    pub to_relaunch: Vec<(InstalledPackage, soar_config::packages::LaunchConfig)>,
     /// Packages not found in metadata.
     pub not_found: Vec<String>,
     /// Pending version updates for packages.toml (package_name, version).
@@ -201,7 +227,10 @@ pub struct ApplyDiff {
 
 impl ApplyDiff {
     pub fn has_changes(&self) -> bool {
-        !self.to_install.is_empty() || !self.to_update.is_empty() || !self.to_remove.is_empty()
+        !self.to_install.is_empty()
+            || !self.to_update.is_empty()
+            || !self.to_remove.is_empty()
+            || !self.to_relaunch.is_empty()
     }
 
     pub fn has_toml_updates(&self) -> bool {
@@ -213,9 +242,27 @@ pub struct ApplyReport {
     pub installed_count: usize,
     pub updated_count: usize,
     pub removed_count: usize,
+    pub relaunched_count: usize,
     pub failed_count: usize,
 }
 
+// ---- Inspect ----
+
+pub struct BundleContents {
+    /// The file the entries were read from.
+    pub path: PathBuf,
+    pub format: PackageFormat,
+    pub entries: Vec<ContentEntry>,
+}
+
+pub struct ExtractedContents {
+    /// The file the entries were extracted from.
+    pub path: PathBuf,
+    pub dest: PathBuf,
+    /// How many entries were written.
+    pub count: usize,
+}
+
 // ---- Run ----
 
 pub enum PrepareRunResult {
@@ -227,9 +274,51 @@ pub struct RunResult {
     pub exit_code: i32,
 }
 
+// ---- MIME ----
+
+pub struct MimeDefault {
+    /// The desktop entry now opening the type, such as `foo-soar.desktop`.
+    pub desktop_id: String,
+    /// The `mimeapps.list` it was recorded in.
+    pub mimeapps: PathBuf,
+}
+
 // ---- Switch (use) ----
 
 pub struct VariantInfo {
     pub package: InstalledPackage,
     pub is_active: bool,
 }
+
+// ---- Audit ----
+
+/// Which audit records to return.
+#[derive(Debug, Default)]
+pub struct AuditQuery {
+    /// Only records for this package name or pkg_id.
+    pub package: Option<String>,
+    pub kind: Option<AuditKind>,
+    /// Only records of commands that failed.
+    pub failed: bool,
+    /// At most this many records, the most recent.
+    pub limit: Option<usize>,
+}
+
+// ---- Alternatives ----
+
+/// A command and the installed packages providing it.
+pub struct AlternativeGroup {
+    pub name: String,
+    /// Whether the user chose the provider holding it, rather than the
+    /// priorities.
+    pub manual: bool,
+    /// Best first; the first holds the command.
+    pub providers: Vec<AlternativeProvider>,
+}
+
+pub struct AlternativeProvider {
+    pub package: InstalledPackage,
+    pub priority: i32,
+    /// What the package links the command to.
+    pub target: PathBuf,
+}
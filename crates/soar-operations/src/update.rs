@@ -1,4 +1,4 @@
-use std::{collections::HashSet, path::Path};
+use std::{cmp::Ordering, collections::HashSet, path::Path};
 
 use soar_config::packages::{PackagesConfig, ResolvedPackage};
 use soar_core::{
@@ -24,6 +24,7 @@ use soar_db::repository::{
 };
 use soar_dl::zsync;
 use soar_events::{SoarEvent, UpdateCheckStatus, UpdateCleanupStage};
+use soar_utils::version::compare_versions;
 use tracing::{debug, warn};
 
 use crate::{
@@ -92,7 +93,10 @@ pub async fn check_updates(
                     continue;
                 }
 
-                if let Some(update_info) = check_repo_update(&pkg, metadata_mgr, &diesel_db, ctx)? {
+                let channel = declared_channel(&resolved_packages, &pkg);
+                if let Some(update_info) =
+                    check_repo_update(&pkg, channel, metadata_mgr, &diesel_db, ctx)?
+                {
                     updates.push(update_info);
                 }
             }
@@ -134,7 +138,10 @@ pub async fn check_updates(
                 continue;
             }
 
-            if let Some(update_info) = check_repo_update(&pkg, metadata_mgr, &diesel_db, ctx)? {
+            let channel = declared_channel(&resolved_packages, &pkg);
+            if let Some(update_info) =
+                check_repo_update(&pkg, channel, metadata_mgr, &diesel_db, ctx)?
+            {
                 updates.push(update_info);
             }
         }
@@ -143,30 +150,76 @@ pub async fn check_updates(
     Ok(updates)
 }
 
+/// The channel packages.toml has an installed package follow, when it names
+/// one besides the default.
+fn declared_channel<'a>(
+    resolved_packages: &'a [ResolvedPackage],
+    pkg: &InstalledPackage,
+) -> Option<&'a str> {
+    resolved_packages
+        .iter()
+        .find(|r| {
+            r.name == pkg.pkg_name
+                && r.family
+                    .as_deref()
+                    .is_none_or(|f| Some(f) == pkg.pkg_family.as_deref())
+                && r.repo.as_deref().is_none_or(|repo| repo == pkg.repo_name)
+        })
+        .and_then(|r| r.channel.as_deref())
+}
+
 fn check_repo_update(
     pkg: &InstalledPackage,
+    channel: Option<&str>,
     metadata_mgr: &soar_core::database::connection::MetadataManager,
     diesel_db: &DieselDatabase,
     ctx: &SoarContext,
 ) -> SoarResult<Option<UpdateInfo>> {
-    let new_pkg: Option<Package> = metadata_mgr
-        .query_repo(&pkg.repo_name, |conn| {
-            MetadataRepository::find_newer_version(
-                conn,
-                &pkg.pkg_name,
-                pkg.pkg_id.as_deref(),
-                pkg.pkg_family.as_deref(),
-                &pkg.version,
-                pkg.checksum.as_deref(),
-            )
-        })?
-        .flatten()
-        .map(|p| {
-            let package: Package = p.into();
-            let mut package = package.resolve(None);
-            package.repo_name = pkg.repo_name.clone();
-            package
-        });
+    let find_newer = |conn: &mut _| {
+        MetadataRepository::find_newer_version(
+            conn,
+            &pkg.pkg_name,
+            pkg.pkg_id.as_deref(),
+            pkg.pkg_family.as_deref(),
+            &pkg.version,
+            pkg.checksum.as_deref(),
+        )
+    };
+
+    let mut newest = metadata_mgr
+        .query_repo(&pkg.repo_name, find_newer)?
+        .flatten();
+
+    // A package following a channel still takes a newer stable release: the
+    // channel only widens what it may update to.
+    if let Some(channel) = channel {
+        match metadata_mgr.query_channel(&pkg.repo_name, channel, find_newer)? {
+            Some(candidate) => {
+                newest = match (newest, candidate) {
+                    (Some(stable), Some(candidate))
+                        if compare_versions(&candidate.version, &stable.version)
+                            == Ordering::Greater =>
+                    {
+                        Some(candidate)
+                    }
+                    (stable, candidate) => stable.or(candidate),
+                };
+            }
+            None => {
+                warn!(
+                    "{} follows the '{channel}' channel, which {} does not publish or has not synced",
+                    pkg.pkg_name, pkg.repo_name
+                );
+            }
+        }
+    }
+
+    let new_pkg: Option<Package> = newest.map(|p| {
+        let package: Package = p.into();
+        let mut package = package.resolve(None);
+        package.repo_name = pkg.repo_name.clone();
+        package
+    });
 
     let Some(package) = new_pkg else {
         ctx.events().emit(SoarEvent::UpdateCheck {
@@ -373,6 +426,7 @@ fn check_local_update(
         package: updated_url_pkg.to_package(),
         existing_install: Some(pkg.clone()),
         pinned: resolved.pinned,
+        keep_versions: false,
         profile: resolved.profile.clone(),
         portable: resolved.portable.as_ref().and_then(|p| p.path.clone()),
         portable_home: resolved.portable.as_ref().and_then(|p| p.home.clone()),
@@ -388,6 +442,11 @@ fn check_local_update(
         build: resolved.build.clone(),
         sandbox: resolved.sandbox.clone(),
         arch_map: resolved.arch_map.clone(),
+        appimage_mode: resolved.appimage_mode,
+        completion_commands: resolved.completion_commands.clone(),
+        launch: resolved.launch.clone(),
+        priority: resolved.priority,
+        runtime_sandbox: resolved.runtime_sandbox.clone(),
     };
 
     Ok(Some(UpdateInfo {
@@ -1,29 +1,37 @@
 use std::{
     collections::{HashMap, HashSet},
-    fs,
+    env, fs,
     os::{unix, unix::fs::PermissionsExt},
     path::{Path, PathBuf},
 };
 
 use soar_config::{
     config::Config,
-    packages::{BinaryMapping, PackageHooks, PackagesConfig, SandboxConfig},
+    packages::{
+        BinaryMapping, LaunchConfig, PackageHooks, PackagesConfig, RuntimeSandbox, SandboxConfig,
+    },
 };
 use soar_core::{
+    alternatives,
+    constants::RUNTIME_SANDBOX_FILE,
     database::{
         connection::DieselDatabase,
         models::{InstalledPackage, Package},
     },
     error::{ErrorContext, SoarError},
-    package::{local::LocalPackage, url::UrlPackage},
+    package::{launch, local::LocalPackage, url::UrlPackage, versions},
+    sandbox,
     utils::{shared_link_targets, substitute_placeholders},
     SoarResult,
 };
 use soar_db::{
     models::types::{PackageFile, PackageProvide},
-    repository::core::{CoreRepository, SortDirection},
+    repository::{
+        alternatives::AlternativeRepository,
+        core::{CoreRepository, SortDirection},
+    },
 };
-use soar_utils::fs::is_elf;
+use soar_utils::{entrypoint, fs::is_elf};
 use tracing::{debug, warn};
 
 /// Check if a package should have desktop integration (desktop files, icons).
@@ -49,17 +57,59 @@ pub fn get_package_hooks(pkg_name: &str) -> (Option<PackageHooks>, Option<Sandbo
         .unwrap_or((None, None))
 }
 
-/// Creates the bin-directory symlinks declared by a package's `provides`.
+/// Look up the launch settings packages.toml gives a package.
+pub fn get_launch_config(pkg_name: &str) -> Option<LaunchConfig> {
+    PackagesConfig::load(None)
+        .ok()?
+        .resolved_packages()
+        .into_iter()
+        .find(|p| p.name == pkg_name)
+        .and_then(|p| p.launch)
+}
+
+/// Look up the priority packages.toml gives a package's commands.
+pub fn get_priority(pkg_name: &str) -> Option<i32> {
+    PackagesConfig::load(None)
+        .ok()?
+        .resolved_packages()
+        .into_iter()
+        .find(|p| p.name == pkg_name)
+        .and_then(|p| p.priority)
+}
+
+/// Look up the runtime sandbox profile packages.toml gives a package.
+pub fn get_runtime_sandbox(pkg_name: &str) -> Option<RuntimeSandbox> {
+    PackagesConfig::load(None)
+        .ok()?
+        .resolved_packages()
+        .into_iter()
+        .find(|p| p.name == pkg_name)
+        .and_then(|p| p.runtime_sandbox)
+}
+
+/// The runtime sandbox profile recorded in a package's install directory, if
+/// it has one that can be read.
+pub fn recorded_runtime_sandbox(install_dir: &Path) -> Option<RuntimeSandbox> {
+    let recorded = install_dir.join(RUNTIME_SANDBOX_FILE);
+    if !recorded.is_file() {
+        return None;
+    }
+    sandbox::read_runtime_profile(&recorded)
+        .inspect_err(|err| warn!("{err}"))
+        .ok()
+}
+
+/// The bin-directory symlinks declared by a package's `provides`, as
+/// `(source, link)` pairs; nothing is created.
 ///
 /// Provides whose name or target is not a safe single path component are skipped
 /// (see [`PackageProvide::is_safe`]) so untrusted metadata cannot escape
-/// `install_dir`/`bin_dir` when building symlink paths. Returns the created
-/// `(source, link)` pairs.
-fn create_provide_symlinks(
+/// `install_dir`/`bin_dir` when building symlink paths.
+fn provide_symlinks(
     install_dir: &Path,
     bin_dir: &Path,
     provides: &[PackageProvide],
-) -> SoarResult<Vec<(PathBuf, PathBuf)>> {
+) -> Vec<(PathBuf, PathBuf)> {
     let mut symlinks = Vec::new();
     let mut processed_paths = HashSet::new();
     for provide in provides {
@@ -77,21 +127,10 @@ fn create_provide_symlinks(
             if !processed_paths.insert(target_path.clone()) {
                 continue;
             }
-            if target_path.is_symlink() || target_path.is_file() {
-                std::fs::remove_file(&target_path)
-                    .with_context(|| format!("removing provide {}", target_path.display()))?;
-            }
-            unix::fs::symlink(&real_path, &target_path).with_context(|| {
-                format!(
-                    "creating symlink {} -> {}",
-                    real_path.display(),
-                    target_path.display()
-                )
-            })?;
             symlinks.push((real_path.clone(), target_path));
         }
     }
-    Ok(symlinks)
+    symlinks
 }
 
 /// Link a package's man pages and completions where the system looks for them.
@@ -178,6 +217,37 @@ pub async fn mangle_package_symlinks(
     binaries: Option<&[BinaryMapping]>,
     arch_map: Option<&HashMap<String, String>>,
     files: Option<&[PackageFile]>,
+) -> SoarResult<Vec<(PathBuf, PathBuf)>> {
+    let symlinks = package_symlinks(
+        install_dir,
+        bin_dir,
+        provides,
+        pkg_name,
+        version,
+        entrypoint,
+        binaries,
+        arch_map,
+        files,
+    )?;
+    link_package_symlinks(&symlinks)?;
+    Ok(symlinks)
+}
+
+/// The `(source, link)` pairs [`mangle_package_symlinks`] would create, worked
+/// out without touching the bin directory.
+///
+/// Each source is made executable, which stays inside the package.
+#[allow(clippy::too_many_arguments)]
+pub fn package_symlinks(
+    install_dir: &Path,
+    bin_dir: &Path,
+    provides: Option<&[PackageProvide]>,
+    pkg_name: &str,
+    version: &str,
+    entrypoint: Option<&str>,
+    binaries: Option<&[BinaryMapping]>,
+    arch_map: Option<&HashMap<String, String>>,
+    files: Option<&[PackageFile]>,
 ) -> SoarResult<Vec<(PathBuf, PathBuf)>> {
     let mut symlinks = Vec::new();
 
@@ -220,16 +290,8 @@ pub async fn mangle_package_symlinks(
                 if !source_path.exists() {
                     continue;
                 }
-                let link_path = bin_dir.join(name);
                 set_executable(&source_path)?;
-                if link_path.is_symlink() || link_path.is_file() {
-                    std::fs::remove_file(&link_path).with_context(|| {
-                        format!("removing existing file/symlink at {}", link_path.display())
-                    })?;
-                }
-                unix::fs::symlink(&source_path, &link_path)
-                    .with_context(|| format!("creating symlink {}", link_path.display()))?;
-                symlinks.push((source_path, link_path));
+                symlinks.push((source_path, bin_dir.join(name)));
             }
         }
         return Ok(symlinks);
@@ -324,20 +386,6 @@ pub async fn mangle_package_symlinks(
                     }
 
                     set_executable(&source_path)?;
-
-                    if link_path.is_symlink() || link_path.is_file() {
-                        std::fs::remove_file(&link_path).with_context(|| {
-                            format!("removing existing file/symlink at {}", link_path.display())
-                        })?;
-                    }
-
-                    unix::fs::symlink(&source_path, &link_path).with_context(|| {
-                        format!(
-                            "creating symlink {} -> {}",
-                            source_path.display(),
-                            link_path.display()
-                        )
-                    })?;
                     symlinks.push((source_path, link_path));
                 }
             }
@@ -346,7 +394,7 @@ pub async fn mangle_package_symlinks(
     }
 
     let provides = provides.unwrap_or_default();
-    symlinks.extend(create_provide_symlinks(install_dir, bin_dir, provides)?);
+    symlinks.extend(provide_symlinks(install_dir, bin_dir, provides));
 
     if provides.is_empty() {
         let soar_syms = install_dir.join("SOAR_SYMS");
@@ -360,29 +408,274 @@ pub async fn mangle_package_symlinks(
             find_executable(install_dir, binaries_dir, is_syms, pkg_name, entrypoint)?
         {
             set_executable(&executable)?;
+            symlinks.push((executable, bin_dir.join(pkg_name)));
+        }
+    }
+    Ok(symlinks)
+}
 
-            let symlink_name = bin_dir.join(pkg_name);
-            if symlink_name.is_symlink() || symlink_name.is_file() {
-                std::fs::remove_file(&symlink_name).with_context(|| {
-                    format!(
-                        "removing existing file/symlink at {}",
-                        symlink_name.display()
-                    )
-                })?;
-            }
-            unix::fs::symlink(&executable, &symlink_name).with_context(|| {
-                format!(
-                    "creating symlink {} -> {}",
-                    executable.display(),
-                    symlink_name.display()
-                )
-            })?;
-            symlinks.push((executable, symlink_name));
+/// Create `(source, link)` pairs, replacing whatever each link path holds.
+pub fn link_package_symlinks(symlinks: &[(PathBuf, PathBuf)]) -> SoarResult<()> {
+    for (source, link) in symlinks {
+        if link.is_symlink() || link.is_file() {
+            fs::remove_file(link)
+                .with_context(|| format!("removing existing file/symlink at {}", link.display()))?;
+        }
+        unix::fs::symlink(source, link).with_context(|| {
+            format!(
+                "creating symlink {} -> {}",
+                source.display(),
+                link.display()
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// Put a launcher in front of each linked command that cannot run by itself.
+///
+/// A jar or zipapp needs its interpreter named on the command line, and a
+/// script whose `#!` names an interpreter at a path this host lacks needs it
+/// found elsewhere. The launcher is written inside the package, under
+/// `.launchers/`, so the bin link still points into the package and is
+/// cleaned up with it; its entry in `symlinks` is updated to match.
+pub fn write_launchers(install_dir: &Path, symlinks: &mut [(PathBuf, PathBuf)]) -> SoarResult<()> {
+    let launchers = install_dir.join(".launchers");
+    for (source, link) in symlinks.iter_mut() {
+        let Some(script) = entrypoint::classify(&*source).and_then(|e| e.launcher(source)) else {
+            continue;
+        };
+        let Some(name) = link.file_name() else {
+            continue;
+        };
+        fs::create_dir_all(&launchers)
+            .with_context(|| format!("creating directory {}", launchers.display()))?;
+        let launcher = launchers.join(name);
+        fs::write(&launcher, script)
+            .with_context(|| format!("writing launcher {}", launcher.display()))?;
+        fs::set_permissions(&launcher, fs::Permissions::from_mode(0o755))
+            .with_context(|| format!("setting permissions on {}", launcher.display()))?;
+
+        debug!(source = %source.display(), launcher = %launcher.display(), "linking through a launcher");
+        fs::remove_file(&*link).with_context(|| format!("removing symlink {}", link.display()))?;
+        unix::fs::symlink(&launcher, &*link)
+            .with_context(|| format!("creating symlink {}", link.display()))?;
+        *source = launcher;
+    }
+    Ok(())
+}
+
+/// Put a launcher applying `launch` in front of each linked command, and
+/// record the settings in the package.
+///
+/// The launchers are written under `.launch/`, in front of any interpreter
+/// launcher; entries in `symlinks` are updated to match. Empty settings leave
+/// the links as they are and remove what an earlier install wrote.
+pub fn write_launch_wrappers(
+    install_dir: &Path,
+    symlinks: &mut [(PathBuf, PathBuf)],
+    launch: &LaunchConfig,
+) -> SoarResult<()> {
+    if let Some(name) = launch
+        .env
+        .keys()
+        .find(|name| !entrypoint::is_variable_name(name))
+    {
+        return Err(SoarError::Custom(format!(
+            "launch setting env: `{name}` is not a variable name a shell can set"
+        )));
+    }
+    let launchers = install_dir.join(".launch");
+    if launchers.exists() {
+        fs::remove_dir_all(&launchers)
+            .with_context(|| format!("removing directory {}", launchers.display()))?;
+    }
+    launch::record(install_dir, launch)?;
+    if launch.is_empty() {
+        return Ok(());
+    }
+
+    let env = launch
+        .env
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect::<Vec<_>>();
+    for (source, link) in symlinks.iter_mut() {
+        let Some(name) = link.file_name() else {
+            continue;
+        };
+        fs::create_dir_all(&launchers)
+            .with_context(|| format!("creating directory {}", launchers.display()))?;
+        let launcher = launchers.join(name);
+        fs::write(
+            &launcher,
+            entrypoint::settings_launcher(
+                source,
+                install_dir,
+                env.iter().copied(),
+                &launch.args,
+                launch.pre_launch.as_deref(),
+            ),
+        )
+        .with_context(|| format!("writing launcher {}", launcher.display()))?;
+        fs::set_permissions(&launcher, fs::Permissions::from_mode(0o755))
+            .with_context(|| format!("setting permissions on {}", launcher.display()))?;
+
+        debug!(source = %source.display(), launcher = %launcher.display(), "linking through a settings launcher");
+        relink_held(link, source, &launcher)?;
+        *source = launcher;
+    }
+    Ok(())
+}
+
+/// Point `link` at `launcher` when it runs `source`.
+///
+/// A link that goes elsewhere belongs to another package providing the same
+/// command, which keeps it; the launcher is still written for when the
+/// command comes back.
+fn relink_held(link: &Path, source: &Path, launcher: &Path) -> SoarResult<()> {
+    if fs::read_link(link).is_ok_and(|current| current != source) {
+        return Ok(());
+    }
+    if fs::symlink_metadata(link).is_ok() {
+        fs::remove_file(link).with_context(|| format!("removing symlink {}", link.display()))?;
+    }
+    unix::fs::symlink(launcher, link)
+        .with_context(|| format!("creating symlink {}", link.display()))?;
+    Ok(())
+}
+
+/// The bin links into `install_dir`, each paired with what it runs once the
+/// version shims, settings and sandbox launchers in front of it are looked
+/// through.
+///
+/// This is where [`write_launch_wrappers`] and [`write_sandbox_launchers`]
+/// start from when a package's settings change without a reinstall.
+pub fn launch_links(install_dir: &Path, bin_dir: &Path) -> SoarResult<Vec<(PathBuf, PathBuf)>> {
+    let mut symlinks = Vec::new();
+    let entries = match fs::read_dir(bin_dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(symlinks),
+        Err(err) => {
+            return Err(err).with_context(|| format!("reading {} directory", bin_dir.display()))
         }
+    };
+    for entry in entries.filter_map(Result::ok) {
+        let link = entry.path();
+        let Ok(source) = fs::read_link(&link) else {
+            continue;
+        };
+        if !source.starts_with(install_dir) {
+            continue;
+        }
+        symlinks.push((launched_source(install_dir, source), link));
     }
+    symlinks.sort();
     Ok(symlinks)
 }
 
+/// What `launcher` runs once the version shims, settings and sandbox
+/// launchers of `install_dir` are looked through.
+fn launched_source(install_dir: &Path, mut launcher: PathBuf) -> PathBuf {
+    let layers = [
+        install_dir.join(".versions"),
+        install_dir.join(".launch"),
+        install_dir.join(".sandbox"),
+    ];
+    while launcher
+        .parent()
+        .is_some_and(|dir| layers.iter().any(|l| l == dir))
+    {
+        let Some(next) = fs::read_to_string(&launcher)
+            .ok()
+            .and_then(|script| entrypoint::launched_file(&script))
+        else {
+            break;
+        };
+        launcher = next;
+    }
+    launcher
+}
+
+/// Write a package's launchers again for `launch` and whatever runtime
+/// sandbox it recorded, relinking its bin links to match.
+///
+/// Commands another package holds as alternatives get their launchers
+/// written too, and keep their links.
+pub fn relaunch(
+    db: &DieselDatabase,
+    package: &InstalledPackage,
+    bin_dir: &Path,
+    launch: &LaunchConfig,
+) -> SoarResult<()> {
+    let package_id = package.id as i32;
+    let install_dir = Path::new(&package.installed_path);
+    let mut symlinks = launch_links(install_dir, bin_dir)?;
+    for (source, link) in &symlinks {
+        fs::remove_file(link).with_context(|| format!("removing symlink {}", link.display()))?;
+        unix::fs::symlink(source, link)
+            .with_context(|| format!("creating symlink {}", link.display()))?;
+    }
+    let held = symlinks
+        .iter()
+        .map(|(_, link)| link.clone())
+        .collect::<HashSet<_>>();
+    for alternative in db.with_conn(|conn| AlternativeRepository::for_package(conn, package_id))? {
+        let link = bin_dir.join(&alternative.name);
+        if !held.contains(&link) {
+            symlinks.push((
+                launched_source(install_dir, PathBuf::from(alternative.target)),
+                link,
+            ));
+        }
+    }
+
+    write_launch_wrappers(install_dir, &mut symlinks, launch)?;
+    if let Some(profile) = recorded_runtime_sandbox(install_dir) {
+        write_sandbox_launchers(install_dir, &mut symlinks, &profile)?;
+    }
+    alternatives::retarget(db, bin_dir, package_id, &symlinks)?;
+    versions::refresh(db, bin_dir, &package.pkg_name)
+}
+
+/// Put a sandbox launcher in front of each linked command.
+///
+/// `profile` is recorded in the package, and each launcher runs its command
+/// through soar, which applies the profile before it execs the command. The
+/// launcher finds soar on `PATH` when it runs, falling back to the soar
+/// writing it. The launchers are written under `.sandbox/`, so one can stand
+/// in front of an interpreter launcher; entries in `symlinks` are updated to
+/// match.
+pub fn write_sandbox_launchers(
+    install_dir: &Path,
+    symlinks: &mut [(PathBuf, PathBuf)],
+    profile: &RuntimeSandbox,
+) -> SoarResult<()> {
+    let soar = env::current_exe().with_context(|| "locating the soar executable".into())?;
+    let profile_path = sandbox::write_runtime_profile(install_dir, profile)?;
+    let launchers = install_dir.join(".sandbox");
+    for (source, link) in symlinks.iter_mut() {
+        let Some(name) = link.file_name() else {
+            continue;
+        };
+        fs::create_dir_all(&launchers)
+            .with_context(|| format!("creating directory {}", launchers.display()))?;
+        let launcher = launchers.join(name);
+        fs::write(
+            &launcher,
+            entrypoint::sandbox_launcher(&soar, &profile_path, source),
+        )
+        .with_context(|| format!("writing launcher {}", launcher.display()))?;
+        fs::set_permissions(&launcher, fs::Permissions::from_mode(0o755))
+            .with_context(|| format!("setting permissions on {}", launcher.display()))?;
+
+        debug!(source = %source.display(), launcher = %launcher.display(), "linking through a sandbox launcher");
+        relink_held(link, source, &launcher)?;
+        *source = launcher;
+    }
+    Ok(())
+}
+
 fn set_executable(path: &Path) -> SoarResult<()> {
     let metadata =
         fs::metadata(path).with_context(|| format!("reading metadata for {}", path.display()))?;
@@ -425,7 +718,7 @@ fn find_executable(
         })?
         .filter_map(|e| e.ok())
         .map(|e| e.path())
-        .filter(|p| p.is_file() && (is_syms || is_elf(p)))
+        .filter(|p| p.is_file() && (is_syms || is_program(p)))
         .collect();
 
     let pkg_name_lower = pkg_name.to_lowercase();
@@ -439,14 +732,14 @@ fn find_executable(
         let fallback_path = install_dir.join(fallback);
         if fallback_path.is_dir() {
             let exact_path = fallback_path.join(pkg_name);
-            if exact_path.is_file() && is_elf(&exact_path) {
+            if exact_path.is_file() && is_program(&exact_path) {
                 return Ok(Some(exact_path));
             }
             if let Ok(entries) = fs::read_dir(&fallback_path) {
                 let fallback_files: Vec<PathBuf> = entries
                     .filter_map(|e| e.ok())
                     .map(|e| e.path())
-                    .filter(|p| p.is_file() && is_elf(p))
+                    .filter(|p| p.is_file() && is_program(p))
                     .collect();
                 if let Some(found) =
                     find_matching_executable(&fallback_files, pkg_name, &pkg_name_lower)
@@ -464,7 +757,19 @@ fn find_executable(
         return Ok(Some(found));
     }
 
-    Ok(all_files.into_iter().next())
+    // Nothing is named after the package, so any native binary is a better
+    // guess than a helper script that happens to sort first.
+    Ok(all_files
+        .iter()
+        .find(|p| is_elf(p))
+        .or(all_files.first())
+        .cloned())
+}
+
+/// Whether `path` is something a command can be linked to: an ELF, a `#!`
+/// script, a Python zipapp or a jar.
+fn is_program(path: &Path) -> bool {
+    entrypoint::classify(path).is_some()
 }
 
 fn collect_executables_recursive(dir: &Path, files: &mut Vec<PathBuf>) {
@@ -475,7 +780,7 @@ fn collect_executables_recursive(dir: &Path, files: &mut Vec<PathBuf>) {
         let path = entry.path();
         if path.is_dir() {
             collect_executables_recursive(&path, files);
-        } else if path.is_file() && is_elf(&path) {
+        } else if path.is_file() && is_program(&path) {
             files.push(path);
         }
     }
@@ -621,10 +926,15 @@ mod tests {
         path::PathBuf,
     };
 
+    use soar_config::packages::RuntimeSandbox;
     use soar_db::models::types::PackageProvide;
+    use soar_utils::entrypoint;
     use tempfile::{tempdir, TempDir};
 
-    use super::{create_provide_symlinks, is_installed, InstalledIndex, NameCounts};
+    use super::{
+        is_installed, link_package_symlinks, provide_symlinks, recorded_runtime_sandbox,
+        write_sandbox_launchers, InstalledIndex, NameCounts,
+    };
 
     /// One installed package of `name`, recorded under `family`.
     fn installed_as(name: &str, family: Option<&str>, installed: bool) -> InstalledIndex {
@@ -760,6 +1070,16 @@ mod tests {
         (root, install, bin)
     }
 
+    fn create_provide_symlinks(
+        install: &std::path::Path,
+        bin: &std::path::Path,
+        provides: &[PackageProvide],
+    ) -> Vec<(PathBuf, PathBuf)> {
+        let symlinks = provide_symlinks(install, bin, provides);
+        link_package_symlinks(&symlinks).unwrap();
+        symlinks
+    }
+
     fn is_symlink(path: &std::path::Path) -> bool {
         path.symlink_metadata()
             .map(|m| m.file_type().is_symlink())
@@ -772,7 +1092,7 @@ mod tests {
         File::create(install.join("clipcat")).unwrap();
 
         let provides = vec![PackageProvide::from_string("clipcat")];
-        let created = create_provide_symlinks(&install, &bin, &provides).unwrap();
+        let created = create_provide_symlinks(&install, &bin, &provides);
 
         let link = bin.join("clipcat");
         assert_eq!(created, vec![(install.join("clipcat"), link.clone())]);
@@ -785,7 +1105,7 @@ mod tests {
         File::create(install.join("clipcatd")).unwrap();
 
         let provides = vec![PackageProvide::from_string("clipcatd==clipcat")];
-        let created = create_provide_symlinks(&install, &bin, &provides).unwrap();
+        let created = create_provide_symlinks(&install, &bin, &provides);
 
         assert_eq!(created.len(), 2);
         assert!(is_symlink(&bin.join("clipcatd")));
@@ -800,7 +1120,7 @@ mod tests {
         File::create(&victim).unwrap();
 
         let provides = vec![PackageProvide::from_string("clipcat=>../victim")];
-        let created = create_provide_symlinks(&install, &bin, &provides).unwrap();
+        let created = create_provide_symlinks(&install, &bin, &provides);
 
         assert!(created.is_empty());
         assert!(
@@ -816,9 +1136,32 @@ mod tests {
         File::create(&victim).unwrap();
 
         let provides = vec![PackageProvide::from_string("@../evil")];
-        let created = create_provide_symlinks(&install, &bin, &provides).unwrap();
+        let created = create_provide_symlinks(&install, &bin, &provides);
 
         assert!(created.is_empty());
         assert!(victim.symlink_metadata().unwrap().file_type().is_file());
     }
+
+    #[test]
+    fn sandbox_launchers_stand_in_front_of_each_link() {
+        let (_root, install, bin) = setup();
+        let app = install.join("app");
+        fs::write(&app, "#!/bin/sh\n").unwrap();
+        let link = bin.join("app");
+        std::os::unix::fs::symlink(&app, &link).unwrap();
+        let mut symlinks = vec![(app.clone(), link.clone())];
+
+        let profile = RuntimeSandbox {
+            connect_ports: vec![443],
+            ..Default::default()
+        };
+        write_sandbox_launchers(&install, &mut symlinks, &profile).unwrap();
+
+        let launcher = install.join(".sandbox/app");
+        assert_eq!(symlinks, [(launcher.clone(), link.clone())]);
+        assert_eq!(fs::read_link(&link).unwrap(), launcher);
+        let script = fs::read_to_string(&launcher).unwrap();
+        assert_eq!(entrypoint::launched_file(&script), Some(app));
+        assert_eq!(recorded_runtime_sandbox(&install), Some(profile));
+    }
 }
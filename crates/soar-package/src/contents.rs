@@ -0,0 +1,224 @@
+//! Listing, verifying and extracting the files bundled inside a package.
+//!
+//! onelf packages, the AppImage family (AppImage, FlatImage and RunImage) and
+//! Wrappe packages carry a whole directory tree behind their runtime. These helpers read that
+//! tree without running the package, which helps when a bundled binary
+//! misbehaves.
+
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::{Component, Path, PathBuf},
+};
+
+use soar_utils::path::is_safe_relative;
+
+use crate::{
+    error::{ErrorContext, PackageError, Result},
+    formats::{appimage, get_file_type, onelf, wrappe, PackageFormat},
+};
+
+/// What kind of filesystem object an entry is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ContentKind {
+    File,
+    Directory,
+    /// A symbolic link, with the target it points to.
+    Symlink(PathBuf),
+    /// A device, socket or anything else the format can hold.
+    Other,
+}
+
+/// A single entry of a bundle.
+#[derive(Debug, Clone)]
+pub struct ContentEntry {
+    /// Path of the entry, relative to the root of the bundle.
+    pub path: PathBuf,
+    pub kind: ContentKind,
+    /// Size of the file contents in bytes, zero for anything but files.
+    pub size: u64,
+    /// Permission bits, when the format records them.
+    pub mode: Option<u32>,
+    /// Whether the contents match the checksum the format records for them.
+    ///
+    /// `None` when the entry was not verified, or the format carries no
+    /// checksum for it.
+    pub verified: Option<bool>,
+}
+
+/// Lists the entries bundled in the package at `path`.
+///
+/// With `verify`, the contents of every file are read back and checked
+/// against the checksum the format records, which only onelf does.
+///
+/// # Errors
+///
+/// Returns [`PackageError`] if the file cannot be read, or is not a format
+/// whose contents can be listed.
+pub fn list_contents<P: AsRef<Path>>(path: P, verify: bool) -> Result<Vec<ContentEntry>> {
+    let path = path.as_ref();
+    match bundle_format(path)? {
+        PackageFormat::Onelf => onelf::list_onelf(path, verify),
+        PackageFormat::AppImage | PackageFormat::FlatImage | PackageFormat::RunImage => {
+            appimage::list_appimage(path)
+        }
+        PackageFormat::Wrappe => wrappe::list_wrappe(path),
+        format => Err(unsupported(path, format)),
+    }
+}
+
+/// Extracts entries of the package at `path` into `dest`.
+///
+/// Only the entries at or below one of `only` are extracted, or the whole
+/// tree when it is empty. onelf files are verified as they are written, and a
+/// file whose checksum does not match fails the extraction.
+///
+/// # Returns
+///
+/// The number of entries extracted.
+///
+/// # Errors
+///
+/// Returns [`PackageError`] if the package cannot be read, is not a format
+/// whose contents can be listed, or an entry cannot be written.
+pub fn extract_contents<P: AsRef<Path>, Q: AsRef<Path>>(
+    path: P,
+    dest: Q,
+    only: &[String],
+) -> Result<usize> {
+    let path = path.as_ref();
+    let dest = dest.as_ref();
+    let only: Vec<PathBuf> = only
+        .iter()
+        .map(|p| PathBuf::from(p.trim_start_matches('/')))
+        .collect();
+    let wanted = |entry: &Path| only.is_empty() || only.iter().any(|p| entry.starts_with(p));
+
+    let format = bundle_format(path)?;
+    if !matches!(
+        format,
+        PackageFormat::Onelf
+            | PackageFormat::AppImage
+            | PackageFormat::FlatImage
+            | PackageFormat::RunImage
+            | PackageFormat::Wrappe
+    ) {
+        return Err(unsupported(path, format));
+    }
+
+    fs::create_dir_all(dest).with_context(|| format!("creating {}", dest.display()))?;
+    let extracted = match format {
+        PackageFormat::Onelf => onelf::extract_onelf(path, dest, &wanted)?,
+        PackageFormat::Wrappe => wrappe::extract_wrappe(path, dest, &wanted)?,
+        _ => appimage::unpack(path, dest, &wanted)?,
+    };
+
+    if extracted == 0 && !only.is_empty() {
+        return Err(PackageError::Custom(format!(
+            "{} has no entry matching {}",
+            path.display(),
+            only.iter()
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )));
+    }
+    Ok(extracted)
+}
+
+fn bundle_format(path: &Path) -> Result<PackageFormat> {
+    let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    get_file_type(&mut BufReader::new(file))
+}
+
+fn unsupported(path: &Path, format: PackageFormat) -> PackageError {
+    let reason = match format {
+        PackageFormat::ELF => "is a plain ELF binary with nothing bundled",
+        _ => "is not a onelf, AppImage, FlatImage, RunImage or Wrappe package",
+    };
+    PackageError::Custom(format!(
+        "Cannot list the contents of {}: it {reason}",
+        path.display()
+    ))
+}
+
+/// Maps a path inside a bundle to where it is extracted below `root`.
+///
+/// The bundle root maps to nothing, and a path that would leave `root`, or
+/// pass through a link already extracted, is refused.
+pub(crate) fn entry_dest(root: &Path, path: &Path) -> Result<Option<PathBuf>> {
+    let relative = path.strip_prefix("/").unwrap_or(path);
+    if relative.as_os_str().is_empty() {
+        return Ok(None);
+    }
+    if !relative.to_str().is_some_and(is_safe_relative) {
+        return Err(PackageError::Custom(format!(
+            "Bundled entry {} escapes {}",
+            path.display(),
+            root.display()
+        )));
+    }
+
+    let mut dest = root.to_path_buf();
+    let mut components = relative.components().peekable();
+    while let Some(Component::Normal(part)) = components.next() {
+        dest.push(part);
+        if components.peek().is_some() && dest.is_symlink() {
+            return Err(PackageError::Custom(format!(
+                "Bundled entry {} passes through a link",
+                path.display()
+            )));
+        }
+    }
+    Ok(Some(dest))
+}
+
+/// Readies `dest` for an extracted entry.
+///
+/// The directories leading to it are created, and a link already at `dest`
+/// is removed so that writing the entry never follows it.
+pub fn prepare_dest(dest: &Path) -> Result<()> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("creating {}", parent.display()))?;
+    }
+    if dest.is_symlink() {
+        fs::remove_file(dest).with_context(|| format!("removing {}", dest.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    #[test]
+    fn entries_stay_inside_the_extraction_root() {
+        let dir = tempdir().unwrap();
+        let root = dir.path();
+        symlink("/etc", root.join("lib")).unwrap();
+
+        assert_eq!(entry_dest(root, Path::new("/")).unwrap(), None);
+        assert_eq!(
+            entry_dest(root, Path::new("/usr/bin/app")).unwrap(),
+            Some(root.join("usr/bin/app"))
+        );
+        assert!(entry_dest(root, Path::new("/../outside")).is_err());
+        assert!(entry_dest(root, Path::new("/lib/passwd")).is_err());
+        assert!(entry_dest(root, Path::new("/lib")).is_ok());
+    }
+
+    #[test]
+    fn plain_files_have_no_contents() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("plain");
+        fs::write(&path, b"#!/bin/sh\necho hello\n").unwrap();
+
+        let err = list_contents(&path, false).unwrap_err().to_string();
+        assert!(err.contains("Cannot list the contents"), "{err}");
+        assert!(extract_contents(&path, dir.path().join("out"), &[]).is_err());
+    }
+}
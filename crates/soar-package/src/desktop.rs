@@ -0,0 +1,741 @@
+//! Desktop entries, as the freedesktop.org Desktop Entry Specification has
+//! them.
+//!
+//! [`DesktopEntry`] keeps every line it read, comments and unknown keys
+//! included, so rewriting a few keys leaves the rest of a package's file as
+//! it shipped.
+
+use std::fmt;
+
+use crate::error::{PackageError, Result};
+
+/// The group every desktop entry starts with.
+pub const MAIN_GROUP: &str = "Desktop Entry";
+
+const ACTION_PREFIX: &str = "Desktop Action ";
+
+/// Keys the specification defines for the main group.
+const MAIN_KEYS: &[&str] = &[
+    "Type",
+    "Version",
+    "Name",
+    "GenericName",
+    "NoDisplay",
+    "Comment",
+    "Icon",
+    "Hidden",
+    "OnlyShowIn",
+    "NotShowIn",
+    "DBusActivatable",
+    "TryExec",
+    "Exec",
+    "Path",
+    "Terminal",
+    "Actions",
+    "MimeType",
+    "Categories",
+    "Implements",
+    "Keywords",
+    "StartupNotify",
+    "StartupWMClass",
+    "URL",
+    "PrefersNonDefaultGPU",
+    "SingleMainWindow",
+];
+
+/// Keys the specification defines for an action group.
+const ACTION_KEYS: &[&str] = &["Name", "Icon", "Exec"];
+
+/// Keys that may carry a `[locale]` suffix.
+const LOCALIZED_KEYS: &[&str] = &["Name", "GenericName", "Comment", "Icon", "Keywords"];
+
+const BOOLEAN_KEYS: &[&str] = &[
+    "NoDisplay",
+    "Hidden",
+    "DBusActivatable",
+    "Terminal",
+    "StartupNotify",
+    "PrefersNonDefaultGPU",
+    "SingleMainWindow",
+];
+
+/// Characters that force an `Exec` argument into quotes.
+const RESERVED: &str = " \t\n\"'\\><~|&;$*?#()`";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Line {
+    /// A comment or blank line, kept verbatim.
+    Other(String),
+    Group(String),
+    Entry {
+        key: String,
+        locale: Option<String>,
+        value: String,
+    },
+}
+
+/// Something about an entry the specification does not allow.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Problem {
+    pub group: String,
+    pub message: String,
+    /// Whether desktops ignore the entry for it, rather than only the key.
+    pub fatal: bool,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.group, self.message)
+    }
+}
+
+/// A parsed desktop entry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DesktopEntry {
+    lines: Vec<Line>,
+}
+
+impl DesktopEntry {
+    /// Parses an entry, failing only on lines that are neither a group, a
+    /// key, a comment nor blank.
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut lines = Vec::new();
+        for (number, line) in content.lines().enumerate() {
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                lines.push(Line::Other(line.to_string()));
+            } else if let Some(group) = trimmed.strip_prefix('[').and_then(|g| g.strip_suffix(']'))
+            {
+                lines.push(Line::Group(group.to_string()));
+            } else if let Some((key, value)) = line.split_once('=') {
+                let key = key.trim();
+                let (key, locale) = match key.split_once('[') {
+                    Some((key, locale)) => {
+                        let locale = locale.strip_suffix(']').ok_or_else(|| {
+                            malformed(number, &format!("unclosed locale in `{key}`"))
+                        })?;
+                        (key, Some(locale.to_string()))
+                    }
+                    None => (key, None),
+                };
+                lines.push(Line::Entry {
+                    key: key.to_string(),
+                    locale,
+                    value: value.trim_start().to_string(),
+                });
+            } else {
+                return Err(malformed(number, &format!("`{trimmed}` is not a key")));
+            }
+        }
+        Ok(Self {
+            lines,
+        })
+    }
+
+    /// The names of the groups, in order.
+    pub fn groups(&self) -> impl Iterator<Item = &str> {
+        self.lines.iter().filter_map(|line| {
+            match line {
+                Line::Group(name) => Some(name.as_str()),
+                _ => None,
+            }
+        })
+    }
+
+    /// The raw value of an unlocalized key.
+    pub fn get(&self, group: &str, key: &str) -> Option<&str> {
+        self.entries(group)
+            .find_map(|(k, locale, value)| (k == key && locale.is_none()).then_some(value))
+    }
+
+    /// The items of an unlocalized list key such as `MimeType`, split on `;`
+    /// with `\;` kept as a literal semicolon. Empty items are dropped.
+    pub fn get_list(&self, group: &str, key: &str) -> Vec<String> {
+        let Some(value) = self.get(group, key) else {
+            return Vec::new();
+        };
+        let mut items = Vec::new();
+        let mut item = String::new();
+        let mut chars = value.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => {
+                    match chars.next() {
+                        Some(';') => item.push(';'),
+                        Some(next) => {
+                            item.push('\\');
+                            item.push(next);
+                        }
+                        None => item.push('\\'),
+                    }
+                }
+                ';' => items.push(std::mem::take(&mut item)),
+                c => item.push(c),
+            }
+        }
+        items.push(item);
+        items
+            .into_iter()
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect()
+    }
+
+    /// The value of `key` for `locale`, such as `de_DE.UTF-8`, falling back
+    /// the way the specification orders it: `lang_COUNTRY@MODIFIER`,
+    /// `lang_COUNTRY`, `lang@MODIFIER`, `lang`, then the unlocalized value.
+    pub fn localized(&self, group: &str, key: &str, locale: &str) -> Option<&str> {
+        let locale = match locale.split_once('.') {
+            Some((head, tail)) => {
+                let modifier = tail.split_once('@').map(|(_, m)| m);
+                match modifier {
+                    Some(m) => format!("{head}@{m}"),
+                    None => head.to_string(),
+                }
+            }
+            None => locale.to_string(),
+        };
+        let (base, modifier) = match locale.split_once('@') {
+            Some((base, modifier)) => (base, Some(modifier)),
+            None => (locale.as_str(), None),
+        };
+        let (lang, country) = match base.split_once('_') {
+            Some((lang, country)) => (lang, Some(country)),
+            None => (base, None),
+        };
+
+        let mut candidates = Vec::new();
+        if let (Some(country), Some(modifier)) = (country, modifier) {
+            candidates.push(format!("{lang}_{country}@{modifier}"));
+        }
+        if let Some(country) = country {
+            candidates.push(format!("{lang}_{country}"));
+        }
+        if let Some(modifier) = modifier {
+            candidates.push(format!("{lang}@{modifier}"));
+        }
+        candidates.push(lang.to_string());
+
+        candidates
+            .iter()
+            .find_map(|candidate| {
+                self.entries(group).find_map(|(k, l, value)| {
+                    (k == key && l == Some(candidate.as_str())).then_some(value)
+                })
+            })
+            .or_else(|| self.get(group, key))
+    }
+
+    /// Sets an unlocalized key, adding it at the end of the group when it is
+    /// not there yet. Does nothing for a group the entry lacks.
+    pub fn set(&mut self, group: &str, key: &str, value: impl Into<String>) {
+        let value = value.into();
+        let Some(range) = self.group_range(group) else {
+            return;
+        };
+        for line in &mut self.lines[range.clone()] {
+            if let Line::Entry {
+                key: k,
+                locale: None,
+                value: v,
+            } = line
+            {
+                if k == key {
+                    *v = value;
+                    return;
+                }
+            }
+        }
+
+        let at = range
+            .clone()
+            .rev()
+            .find(|&i| matches!(self.lines[i], Line::Entry { .. }))
+            .map_or(range.start, |i| i + 1);
+        self.lines.insert(
+            at,
+            Line::Entry {
+                key: key.to_string(),
+                locale: None,
+                value,
+            },
+        );
+    }
+
+    /// The groups whose `Exec` launches something: the main group and every
+    /// action group.
+    pub fn launch_groups(&self) -> Vec<String> {
+        self.groups()
+            .filter(|g| *g == MAIN_GROUP || g.starts_with(ACTION_PREFIX))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Points `Exec` and `TryExec` in every launching group at `command`,
+    /// keeping the arguments each line carried.
+    pub fn point_exec_at(&mut self, command: &str) {
+        for group in self.launch_groups() {
+            for key in ["Exec", "TryExec"] {
+                if let Some(old) = self.get(&group, key) {
+                    let new = exec_line(old, command);
+                    self.set(&group, key, new);
+                }
+            }
+        }
+    }
+
+    /// Everything about the entry the specification does not allow.
+    pub fn validate(&self) -> Vec<Problem> {
+        let mut problems = Vec::new();
+        let mut problem = |group: &str, message: String, fatal: bool| {
+            problems.push(Problem {
+                group: group.to_string(),
+                message,
+                fatal,
+            });
+        };
+
+        if self.lines.iter().any(|l| matches!(l, Line::Entry { .. }))
+            && !matches!(
+                self.lines.iter().find(|l| !matches!(l, Line::Other(_))),
+                Some(Line::Group(_))
+            )
+        {
+            problem(MAIN_GROUP, "keys come before the first group".into(), true);
+        }
+        match self.groups().next() {
+            Some(MAIN_GROUP) => {}
+            Some(other) => {
+                problem(
+                    other,
+                    format!("the first group is not [{MAIN_GROUP}]"),
+                    true,
+                );
+            }
+            None => problem(MAIN_GROUP, "the group is missing".into(), true),
+        }
+
+        let mut seen_groups = Vec::new();
+        for group in self.groups() {
+            if group.contains(['[', ']']) || group.chars().any(char::is_control) {
+                problem(group, "the group name has invalid characters".into(), false);
+            }
+            if seen_groups.contains(&group) {
+                problem(group, "the group appears twice".into(), true);
+            }
+            seen_groups.push(group);
+
+            let mut seen_keys: Vec<(&str, Option<&str>)> = Vec::new();
+            for (key, locale, value) in self.entries(group) {
+                if !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                    problem(group, format!("`{key}` is not a valid key name"), false);
+                }
+                if seen_keys.contains(&(key, locale)) {
+                    problem(group, format!("`{key}` is set twice"), false);
+                }
+                seen_keys.push((key, locale));
+
+                let known = if group == MAIN_GROUP {
+                    MAIN_KEYS
+                } else if group.starts_with(ACTION_PREFIX) {
+                    ACTION_KEYS
+                } else {
+                    continue;
+                };
+                if !known.contains(&key) && !key.starts_with("X-") {
+                    problem(group, format!("`{key}` is not a known key"), false);
+                }
+                if locale.is_some() && !LOCALIZED_KEYS.contains(&key) {
+                    problem(group, format!("`{key}` cannot be localized"), false);
+                }
+                if BOOLEAN_KEYS.contains(&key) && !matches!(value, "true" | "false") {
+                    problem(
+                        group,
+                        format!("`{key}` must be true or false, not `{value}`"),
+                        false,
+                    );
+                }
+            }
+        }
+
+        if !seen_groups.contains(&MAIN_GROUP) {
+            return problems;
+        }
+
+        let dbus = self.get(MAIN_GROUP, "DBusActivatable") == Some("true");
+        match self.get(MAIN_GROUP, "Type") {
+            None => problem(MAIN_GROUP, "`Type` is missing".into(), true),
+            Some("Application") if !dbus && self.get(MAIN_GROUP, "Exec").is_none() => {
+                problem(MAIN_GROUP, "`Exec` is missing".into(), true)
+            }
+            Some("Link") if self.get(MAIN_GROUP, "URL").is_none() => {
+                problem(MAIN_GROUP, "`URL` is missing".into(), true)
+            }
+            _ => {}
+        }
+        if self.get(MAIN_GROUP, "Name").is_none() {
+            problem(MAIN_GROUP, "`Name` is missing".into(), true);
+        }
+
+        let actions: Vec<&str> = self
+            .get(MAIN_GROUP, "Actions")
+            .unwrap_or_default()
+            .split(';')
+            .filter(|a| !a.is_empty())
+            .collect();
+        for action in &actions {
+            let group = format!("{ACTION_PREFIX}{action}");
+            if !seen_groups.contains(&group.as_str()) {
+                problem(
+                    MAIN_GROUP,
+                    format!("action `{action}` has no [{group}] group"),
+                    false,
+                );
+                continue;
+            }
+            if self.get(&group, "Name").is_none() {
+                problem(&group, "`Name` is missing".into(), false);
+            }
+            if !dbus && self.get(&group, "Exec").is_none() {
+                problem(&group, "`Exec` is missing".into(), false);
+            }
+        }
+        for group in &seen_groups {
+            if let Some(action) = group.strip_prefix(ACTION_PREFIX) {
+                if !actions.contains(&action) {
+                    problem(group, "the action is not listed in `Actions`".into(), false);
+                }
+            }
+        }
+
+        for group in self.launch_groups() {
+            if let Some(exec) = self.get(&group, "Exec") {
+                if let Err(message) = check_exec(exec) {
+                    problem(&group, format!("`Exec` {message}"), group == MAIN_GROUP);
+                }
+            }
+        }
+
+        problems
+    }
+
+    fn group_range(&self, group: &str) -> Option<std::ops::Range<usize>> {
+        let start = self
+            .lines
+            .iter()
+            .position(|l| matches!(l, Line::Group(g) if g == group))?
+            + 1;
+        let end = self.lines[start..]
+            .iter()
+            .position(|l| matches!(l, Line::Group(_)))
+            .map_or(self.lines.len(), |i| start + i);
+        Some(start..end)
+    }
+
+    fn entries<'a>(
+        &'a self,
+        group: &str,
+    ) -> impl Iterator<Item = (&'a str, Option<&'a str>, &'a str)> {
+        let range = self.group_range(group).unwrap_or(0..0);
+        self.lines[range].iter().filter_map(|line| {
+            match line {
+                Line::Entry {
+                    key,
+                    locale,
+                    value,
+                } => Some((key.as_str(), locale.as_deref(), value.as_str())),
+                _ => None,
+            }
+        })
+    }
+}
+
+impl fmt::Display for DesktopEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for line in &self.lines {
+            match line {
+                Line::Other(text) => writeln!(f, "{text}")?,
+                Line::Group(name) => writeln!(f, "[{name}]")?,
+                Line::Entry {
+                    key,
+                    locale: Some(locale),
+                    value,
+                } => writeln!(f, "{key}[{locale}]={value}")?,
+                Line::Entry {
+                    key,
+                    locale: None,
+                    value,
+                } => writeln!(f, "{key}={value}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn malformed(number: usize, reason: &str) -> PackageError {
+    PackageError::Custom(format!(
+        "Malformed desktop entry at line {}: {reason}",
+        number + 1
+    ))
+}
+
+/// An `Exec` value pointed at the command as installed.
+///
+/// The arguments the entry carried are kept after it. With none to keep,
+/// nothing follows the command: a launcher reading the line whole would take
+/// a trailing space as part of the command and fail to find it.
+fn exec_line(old: &str, command: &str) -> String {
+    if old.contains("{{pkg_path}}") {
+        return old.replace("{{pkg_path}}", command);
+    }
+
+    let args: Vec<&str> = old.split_whitespace().skip(1).collect();
+    if args.is_empty() {
+        command.to_string()
+    } else {
+        format!("{command} {}", args.join(" "))
+    }
+}
+
+/// Encodes one argument for an `Exec` value.
+///
+/// Two layers apply: `%` starts a field code, so a literal one doubles, and
+/// inside quotes a backslash guards `"`, a backtick, `$` and itself, which the
+/// desktop-entry string layer then escapes a second time.
+pub fn quote_exec_arg(arg: &str) -> String {
+    let arg = arg.replace('%', "%%");
+    // Quoted only when it has to be: xdg-open's generic fallback resolves the
+    // Exec path with a plain `command -v` and finds nothing behind quotes.
+    if !arg.is_empty() && !arg.contains(|c| RESERVED.contains(c)) {
+        return arg;
+    }
+
+    let mut out = String::with_capacity(arg.len() + 2);
+    out.push('"');
+    for c in arg.chars() {
+        match c {
+            '\\' => out.push_str(r"\\\\"),
+            '"' | '`' | '$' => {
+                out.push_str(r"\\");
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Checks an `Exec` value's quoting and field codes, returning what is wrong.
+fn check_exec(value: &str) -> std::result::Result<(), String> {
+    // The string layer first: `\\` is one backslash to the quoting below.
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('s') => unescaped.push(' '),
+                Some('n') => unescaped.push('\n'),
+                Some('t') => unescaped.push('\t'),
+                Some('r') => unescaped.push('\r'),
+                Some(other) => {
+                    unescaped.push('\\');
+                    unescaped.push(other);
+                }
+                None => unescaped.push('\\'),
+            }
+        } else {
+            unescaped.push(c);
+        }
+    }
+    if unescaped.trim().is_empty() {
+        return Err("is empty".into());
+    }
+
+    let mut quoted = false;
+    let mut file_codes = 0;
+    let mut chars = unescaped.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => quoted = !quoted,
+            '\\' if quoted => {
+                chars.next();
+            }
+            '%' => {
+                match chars.next() {
+                    Some('%') => {}
+                    Some(code) if quoted => {
+                        return Err(format!("has the field code %{code} inside quotes"));
+                    }
+                    Some('f' | 'F' | 'u' | 'U') => file_codes += 1,
+                    Some('i' | 'c' | 'k' | 'd' | 'D' | 'n' | 'N' | 'v' | 'm') => {}
+                    Some(code) => return Err(format!("has the unknown field code %{code}")),
+                    None => return Err("ends in a lone %".into()),
+                }
+            }
+            _ => {}
+        }
+    }
+    if quoted {
+        return Err("has an unterminated quote".into());
+    }
+    if file_codes > 1 {
+        return Err("has more than one of %f, %F, %u and %U".into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENTRY: &str = "\
+# Shipped by upstream
+[Desktop Entry]
+Type=Application
+Name=Foo
+Name[de]=Fuu
+Name[sr@latin]=Fu
+Exec=/opt/foo/foo --new %U
+TryExec=/opt/foo/foo
+Icon=foo
+Actions=window;
+
+[Desktop Action window]
+Name=New Window
+Exec=/opt/foo/foo --window
+";
+
+    #[test]
+    fn list_values_split_on_unescaped_semicolons() {
+        let entry = DesktopEntry::parse(
+            "[Desktop Entry]\nKeywords=a\\;b;c;;\nMimeType=text/plain; image/png\n",
+        )
+        .unwrap();
+        assert_eq!(entry.get_list(MAIN_GROUP, "Keywords"), ["a;b", "c"]);
+        assert_eq!(
+            entry.get_list(MAIN_GROUP, "MimeType"),
+            ["text/plain", "image/png"]
+        );
+        assert!(entry.get_list(MAIN_GROUP, "Categories").is_empty());
+    }
+
+    #[test]
+    fn an_entry_written_back_is_unchanged() {
+        let entry = DesktopEntry::parse(ENTRY).unwrap();
+        assert_eq!(entry.to_string(), ENTRY);
+        assert!(entry.validate().is_empty(), "{:?}", entry.validate());
+    }
+
+    #[test]
+    fn a_line_that_is_not_a_key_is_rejected() {
+        assert!(DesktopEntry::parse("[Desktop Entry]\nName Foo\n").is_err());
+        assert!(DesktopEntry::parse("[Desktop Entry]\nName[de=Foo\n").is_err());
+    }
+
+    #[test]
+    fn localized_keys_fall_back_in_the_specified_order() {
+        let entry = DesktopEntry::parse(ENTRY).unwrap();
+        assert_eq!(
+            entry.localized(MAIN_GROUP, "Name", "de_DE.UTF-8"),
+            Some("Fuu")
+        );
+        assert_eq!(
+            entry.localized(MAIN_GROUP, "Name", "sr_RS.UTF-8@latin"),
+            Some("Fu")
+        );
+        assert_eq!(entry.localized(MAIN_GROUP, "Name", "fr_FR"), Some("Foo"));
+        assert_eq!(entry.get(MAIN_GROUP, "Name"), Some("Foo"));
+    }
+
+    #[test]
+    fn exec_is_pointed_at_the_command_in_every_launching_group() {
+        let mut entry = DesktopEntry::parse(ENTRY).unwrap();
+        entry.point_exec_at("/bin/foo");
+        assert_eq!(entry.get(MAIN_GROUP, "Exec"), Some("/bin/foo --new %U"));
+        assert_eq!(entry.get(MAIN_GROUP, "TryExec"), Some("/bin/foo"));
+        assert_eq!(
+            entry.get("Desktop Action window", "Exec"),
+            Some("/bin/foo --window")
+        );
+    }
+
+    #[test]
+    fn setting_a_missing_key_adds_it_to_its_group() {
+        let mut entry = DesktopEntry::parse(ENTRY).unwrap();
+        entry.set(MAIN_GROUP, "Icon", "foo-soar");
+        entry.set(MAIN_GROUP, "StartupWMClass", "foo");
+        let text = entry.to_string();
+        assert!(text.contains("Icon=foo-soar\nActions=window;\nStartupWMClass=foo\n\n[Desktop"));
+    }
+
+    #[test]
+    fn validation_reports_what_the_specification_forbids() {
+        let entry = DesktopEntry::parse(
+            "[Desktop Entry]\nType=Application\nExec=foo %f %U\nTerminal=yes\nExec[de]=bar\nFrobnicate=1\nX-Vendor=1\nActions=gone;\n\n[Desktop Action stray]\nName=Stray\n",
+        )
+        .unwrap();
+        let messages: Vec<String> = entry.validate().iter().map(|p| p.to_string()).collect();
+        assert_eq!(
+            messages,
+            [
+                "[Desktop Entry] `Terminal` must be true or false, not `yes`",
+                "[Desktop Entry] `Exec` cannot be localized",
+                "[Desktop Entry] `Frobnicate` is not a known key",
+                "[Desktop Entry] `Name` is missing",
+                "[Desktop Entry] action `gone` has no [Desktop Action gone] group",
+                "[Desktop Action stray] the action is not listed in `Actions`",
+                "[Desktop Entry] `Exec` has more than one of %f, %F, %u and %U",
+            ]
+        );
+    }
+
+    #[test]
+    fn an_entry_without_the_main_group_is_fatal() {
+        let entry = DesktopEntry::parse("[Something]\nName=Foo\n").unwrap();
+        assert!(entry.validate().iter().any(|p| p.fatal));
+        let entry = DesktopEntry::parse("Name=Foo\n").unwrap();
+        assert!(entry.validate().iter().any(|p| p.fatal));
+    }
+
+    #[test]
+    fn exec_quoting_and_field_codes_are_checked() {
+        assert!(check_exec("foo %F").is_ok());
+        assert!(check_exec("\"/opt/my apps/foo\" --title \"100%%\"").is_ok());
+        assert!(check_exec("sh -c \"echo \\\\$HOME\"").is_ok());
+        assert_eq!(
+            check_exec("foo \"%f\"").unwrap_err(),
+            "has the field code %f inside quotes"
+        );
+        assert_eq!(
+            check_exec("foo %z").unwrap_err(),
+            "has the unknown field code %z"
+        );
+        assert_eq!(
+            check_exec("foo \"bar").unwrap_err(),
+            "has an unterminated quote"
+        );
+        assert_eq!(check_exec("  ").unwrap_err(), "is empty");
+    }
+
+    #[test]
+    fn a_command_with_no_arguments_ends_the_line() {
+        assert_eq!(exec_line("/old/bin", "/new/bin"), "/new/bin");
+    }
+
+    #[test]
+    fn the_arguments_an_entry_carried_are_kept() {
+        assert_eq!(
+            exec_line("/old/bin --flag %U", "/new/bin"),
+            "/new/bin --flag %U"
+        );
+    }
+
+    #[test]
+    fn a_placeholder_is_filled_where_it_stands() {
+        assert_eq!(
+            exec_line("env FOO=1 {{pkg_path}} %F", "/new/bin"),
+            "env FOO=1 /new/bin %F"
+        );
+    }
+}
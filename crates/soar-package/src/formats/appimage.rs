@@ -1,19 +1,364 @@
 //! AppImage format handling.
 
-use std::{fs, path::Path};
+use std::{
+    env, fs,
+    os::unix::fs::{symlink, PermissionsExt},
+    path::{Component, Path, PathBuf},
+};
 
 use soar_utils::fs::read_file_signature;
-use squishy::appimage::{AppImage, AppImageEntryKind};
+use squishy::{
+    appimage::{AppImage, AppImageEntryKind, AppImageFS},
+    dwarfs::DwarFSEntryKind,
+    EntryKind,
+};
 
 use super::{
     common::{symlink_desktop_with_config, symlink_icon_with_mode},
     PNG_MAGIC_BYTES,
 };
 use crate::{
-    error::{ErrorContext, Result},
+    contents::{entry_dest, prepare_dest, ContentEntry, ContentKind},
+    desktop::{DesktopEntry, MAIN_GROUP},
+    error::{ErrorContext, PackageError, Result},
     traits::PackageExt,
 };
 
+/// Where the AppDir of an AppImage installed at `bin_path` is extracted to.
+pub fn appdir_path<P: AsRef<Path>>(bin_path: P) -> PathBuf {
+    bin_path.as_ref().with_extension("AppDir")
+}
+
+/// Whether the file at `bin_path` is the launcher of an extracted AppImage.
+pub fn is_extracted_appimage<P: AsRef<Path>>(bin_path: P) -> bool {
+    appdir_path(bin_path).join("AppRun").is_file()
+}
+
+/// Whether this system can mount an AppImage through FUSE.
+///
+/// The AppImage runtime needs the `/dev/fuse` device and a `fusermount`
+/// helper to mount its filesystem without privileges.
+pub fn fuse_available() -> bool {
+    if !Path::new("/dev/fuse").exists() {
+        return false;
+    }
+    let path = env::var_os("PATH").unwrap_or_default();
+    env::split_paths(&path).any(|dir| {
+        ["fusermount3", "fusermount"]
+            .iter()
+            .any(|helper| dir.join(helper).is_file())
+    })
+}
+
+/// Extracts the AppImage at `bin_path` and replaces it with a launcher.
+///
+/// The AppDir is unpacked beside the file, at [`appdir_path`], and the
+/// AppImage itself is overwritten by a script that runs its `AppRun`. Links
+/// pointing at the AppImage keep working, but nothing needs FUSE anymore.
+///
+/// # Errors
+///
+/// Returns [`PackageError`] if the AppImage cannot be read, holds no
+/// `AppRun`, or the AppDir or launcher cannot be written.
+pub fn extract_appimage<P: AsRef<Path>>(bin_path: P) -> Result<PathBuf> {
+    let bin_path = bin_path.as_ref();
+    let appdir = appdir_path(bin_path);
+
+    if appdir.exists() {
+        fs::remove_dir_all(&appdir)
+            .with_context(|| format!("removing stale AppDir {}", appdir.display()))?;
+    }
+    fs::create_dir_all(&appdir).with_context(|| format!("creating AppDir {}", appdir.display()))?;
+
+    unpack(bin_path, &appdir, &|_| true)?;
+
+    let app_run = appdir.join("AppRun");
+    if fs::symlink_metadata(&app_run).is_err() {
+        return Err(PackageError::Custom(format!(
+            "{} has no AppRun to launch",
+            bin_path.display()
+        )));
+    }
+
+    fs::write(bin_path, launcher_script(bin_path, &appdir))
+        .with_context(|| format!("writing launcher {}", bin_path.display()))?;
+    set_mode(bin_path, 0o755)?;
+
+    Ok(appdir)
+}
+
+/// Lists the entries of the AppImage, FlatImage or RunImage at `path`.
+///
+/// Neither squashfs nor dwarfs images record a checksum per file, so the
+/// entries are never marked as verified.
+pub(crate) fn list_appimage(path: &Path) -> Result<Vec<ContentEntry>> {
+    let appimage = AppImage::new(None, &path, None)?;
+    let relative = |path: &Path| path.strip_prefix("/").unwrap_or(path).to_path_buf();
+
+    let entries = match &appimage.fs {
+        AppImageFS::SquashFS(squashfs) => {
+            squashfs
+                .entries()
+                .map(|entry| {
+                    ContentEntry {
+                        path: relative(&entry.path),
+                        kind: match &entry.kind {
+                            EntryKind::File(_) => ContentKind::File,
+                            EntryKind::Directory => ContentKind::Directory,
+                            EntryKind::Symlink(target) => {
+                                ContentKind::Symlink(
+                                    target.strip_prefix("/").unwrap_or(target).into(),
+                                )
+                            }
+                            EntryKind::Unknown => ContentKind::Other,
+                        },
+                        size: u64::from(entry.size),
+                        mode: Some(u32::from(entry.header.permissions)),
+                        verified: None,
+                    }
+                })
+                .collect()
+        }
+        AppImageFS::DwarFS(dwarfs) => {
+            dwarfs
+                .entries()
+                .map(|entry| {
+                    ContentEntry {
+                        path: relative(&entry.path),
+                        kind: match &entry.kind {
+                            DwarFSEntryKind::File => ContentKind::File,
+                            DwarFSEntryKind::Directory => ContentKind::Directory,
+                            DwarFSEntryKind::Symlink(target) => {
+                                ContentKind::Symlink(target.clone())
+                            }
+                            _ => ContentKind::Other,
+                        },
+                        size: entry.size,
+                        mode: Some(entry.mode & 0o7777),
+                        verified: None,
+                    }
+                })
+                .collect()
+        }
+    };
+    Ok(entries)
+}
+
+/// Unpacks the entries of the image at `path` selected by `wanted` below
+/// `root`, creating the directories leading to each of them.
+///
+/// Returns how many entries were written.
+pub(crate) fn unpack(path: &Path, root: &Path, wanted: &dyn Fn(&Path) -> bool) -> Result<usize> {
+    let selected = |path: &Path| wanted(path.strip_prefix("/").unwrap_or(path));
+    let mut count = 0;
+
+    let mut appimage = AppImage::new(None, &path, None)?;
+    match &mut appimage.fs {
+        AppImageFS::SquashFS(squashfs) => {
+            for entry in squashfs.entries() {
+                if !selected(&entry.path) {
+                    continue;
+                }
+                let Some(dest) = entry_dest(root, &entry.path)? else {
+                    continue;
+                };
+                prepare_dest(&dest)?;
+                match &entry.kind {
+                    EntryKind::File(file) => {
+                        squashfs.write_file(file, &dest)?;
+                        set_mode(&dest, u32::from(entry.header.permissions))?;
+                    }
+                    EntryKind::Directory => create_dir(&dest)?,
+                    // squishy roots every squashfs link target, relative or not.
+                    EntryKind::Symlink(target) => {
+                        let target = target.strip_prefix("/").unwrap_or(target);
+                        create_link(target, &dest)?;
+                    }
+                    EntryKind::Unknown => continue,
+                }
+                count += 1;
+            }
+        }
+        AppImageFS::DwarFS(dwarfs) => {
+            let entries: Vec<_> = dwarfs.entries().collect();
+            for entry in entries {
+                if !selected(&entry.path) {
+                    continue;
+                }
+                let Some(dest) = entry_dest(root, &entry.path)? else {
+                    continue;
+                };
+                prepare_dest(&dest)?;
+                match &entry.kind {
+                    DwarFSEntryKind::File => {
+                        dwarfs.write_file(&entry, &dest)?;
+                        set_mode(&dest, entry.mode)?;
+                    }
+                    DwarFSEntryKind::Directory => create_dir(&dest)?,
+                    DwarFSEntryKind::Symlink(target) => create_link(target, &dest)?,
+                    _ => continue,
+                }
+                count += 1;
+            }
+        }
+    }
+    Ok(count)
+}
+
+fn create_dir(dest: &Path) -> Result<()> {
+    fs::create_dir_all(dest).with_context(|| format!("creating {}", dest.display()))
+}
+
+fn create_link(target: &Path, dest: &Path) -> Result<()> {
+    symlink(target, dest).with_context(|| format!("linking {}", dest.display()))
+}
+
+/// Applies the permission bits of an extracted file, leaving out setuid,
+/// setgid and sticky bits, which an unpacked AppDir has no business keeping.
+fn set_mode(dest: &Path, mode: u32) -> Result<()> {
+    fs::set_permissions(dest, fs::Permissions::from_mode(mode & 0o777))
+        .with_context(|| format!("setting permissions on {}", dest.display()))
+}
+
+/// The launcher written in place of an extracted AppImage.
+///
+/// It sets what the AppImage runtime would have: `APPIMAGE`, `APPDIR` and
+/// `ARGV0`, and the portable home and config directories when they exist.
+fn launcher_script(bin_path: &Path, appdir: &Path) -> String {
+    let quote = |path: &Path| format!("'{}'", path.display().to_string().replace('\'', "'\\''"));
+    format!(
+        "#!/bin/sh\n\
+         # Generated by soar: runs an AppImage extracted for systems without FUSE.\n\
+         export APPIMAGE={appimage}\n\
+         export APPDIR={appdir}\n\
+         export ARGV0=\"$0\"\n\
+         [ -d {home} ] && export HOME={home}\n\
+         [ -d {config} ] && export XDG_CONFIG_HOME={config}\n\
+         exec \"$APPDIR/AppRun\" \"$@\"\n",
+        appimage = quote(bin_path),
+        appdir = quote(appdir),
+        home = quote(&bin_path.with_extension("home")),
+        config = quote(&bin_path.with_extension("config")),
+    )
+}
+
+/// Extracts every size of the AppImage's own icon from its icon theme.
+///
+/// The icon is the one its desktop entry names, found under
+/// `usr/share/icons/hicolor/<size>/apps/`. Each copy is renamed after
+/// `pkg_name`, which is what the extracted desktop file will reference.
+/// Returns whether any was found.
+fn integrate_themed_icons(
+    appimage: &mut AppImage,
+    install_dir: &Path,
+    pkg_name: &str,
+    config: &soar_config::config::Config,
+) -> Result<bool> {
+    let Some(icon) = appimage
+        .find_desktop()
+        .and_then(|entry| appimage.read_entry(&entry).ok())
+        .and_then(|bytes| desktop_icon_name(&String::from_utf8_lossy(&bytes)))
+    else {
+        return Ok(false);
+    };
+
+    let theme = Path::new("/usr/share/icons/hicolor");
+    let all: Vec<_> = appimage.entries().collect();
+    let entries: Vec<_> = all
+        .iter()
+        .filter(|entry| {
+            let stem = entry.path.file_stem().and_then(|s| s.to_str());
+            let ext = entry.path.extension().and_then(|s| s.to_str());
+            let in_apps = entry
+                .path
+                .parent()
+                .is_some_and(|p| p.file_name() == Some("apps".as_ref()));
+            entry.path.starts_with(theme)
+                && in_apps
+                && matches!(ext, Some("png" | "svg"))
+                && (stem == Some(icon.as_str())
+                    || stem == Some(format!("{icon}-symbolic").as_str()))
+        })
+        .collect();
+
+    let mut found = false;
+    for entry in entries {
+        // Sizes are often links to one another; the link's place is what says
+        // which size it stands for.
+        let source = match &entry.kind {
+            AppImageEntryKind::File => entry,
+            AppImageEntryKind::Symlink(target) => {
+                // Targets are reported with a leading `/` whether or not they
+                // were relative, so both readings are tried.
+                let relative = target.strip_prefix("/").unwrap_or(target);
+                let candidates = [
+                    lexical_join(entry.path.parent().unwrap_or(theme), relative),
+                    Some(target.clone()),
+                ];
+                match all.iter().find(|e| {
+                    e.kind == AppImageEntryKind::File && candidates.contains(&Some(e.path.clone()))
+                }) {
+                    Some(source) => source,
+                    None => continue,
+                }
+            }
+            _ => continue,
+        };
+        let Ok(relative) = entry.path.strip_prefix(theme) else {
+            continue;
+        };
+        let Some(size) = relative.components().next() else {
+            continue;
+        };
+        let symbolic = entry
+            .path
+            .file_stem()
+            .is_some_and(|s| s.to_string_lossy().ends_with("-symbolic"));
+        let name = if symbolic {
+            format!("{pkg_name}-symbolic")
+        } else {
+            pkg_name.to_string()
+        };
+        let ext = entry.path.extension().unwrap_or_default().to_string_lossy();
+        let dest = install_dir
+            .join("share/icons/hicolor")
+            .join(size)
+            .join("apps")
+            .join(format!("{name}.{ext}"));
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("creating directory {}", parent.display()))?;
+        }
+        if appimage.write_entry(source, &dest).is_err() {
+            continue;
+        }
+        symlink_icon_with_mode(&dest, config.is_system())?;
+        found = true;
+    }
+    Ok(found)
+}
+
+/// `base` joined with `relative`, resolving `..` without touching the disk.
+fn lexical_join(base: &Path, relative: &Path) -> Option<PathBuf> {
+    let mut out = base.to_path_buf();
+    for component in relative.components() {
+        match component {
+            Component::Normal(name) => out.push(name),
+            Component::ParentDir if !out.pop() => return None,
+            _ => {}
+        }
+    }
+    Some(out)
+}
+
+/// The `Icon` key of a desktop entry's `[Desktop Entry]` group.
+fn desktop_icon_name(content: &str) -> Option<String> {
+    let entry = DesktopEntry::parse(content).ok()?;
+    let value = entry.get(MAIN_GROUP, "Icon")?.trim();
+    // A path is not a theme name, and nothing can be looked up by it.
+    (!value.is_empty() && !value.contains('/')).then(|| value.to_string())
+}
+
 /// Integrates an AppImage by extracting its embedded resources.
 ///
 /// This function extracts icons, desktop files, and AppStream metadata from
@@ -51,6 +396,9 @@ pub async fn integrate_appimage<P: AsRef<Path>, T: PackageExt>(
     // their stems match.
     let mut icon_available = has_icon;
     if !has_icon {
+        icon_available = integrate_themed_icons(&mut appimage, install_dir, pkg_name, config)?;
+    }
+    if !icon_available {
         if let Some(entry) = appimage.find_icon() {
             if entry.kind == AppImageEntryKind::File {
                 let dest = format!("{}/{}.DirIcon", install_dir.display(), pkg_name);
@@ -82,6 +430,27 @@ pub async fn integrate_appimage<P: AsRef<Path>, T: PackageExt>(
         }
     }
 
+    // MIME types the app defines for itself, which its desktop entry may
+    // name in `MimeType=`.
+    let mime_packages: Vec<_> = appimage
+        .entries()
+        .filter(|e| {
+            e.kind == AppImageEntryKind::File
+                && e.path.parent() == Some(Path::new("/usr/share/mime/packages"))
+                && e.path.extension().is_some_and(|ext| ext == "xml")
+        })
+        .collect();
+    if !mime_packages.is_empty() {
+        let dir = install_dir.join("share/mime/packages");
+        fs::create_dir_all(&dir).with_context(|| format!("creating {}", dir.display()))?;
+        for entry in mime_packages {
+            let Some(name) = entry.path.file_name() else {
+                continue;
+            };
+            let _ = appimage.write_entry(&entry, dir.join(name));
+        }
+    }
+
     if let Some(entry) = appimage.find_appstream() {
         if entry.kind == AppImageEntryKind::File {
             let file_name = if entry
@@ -101,3 +470,49 @@ pub async fn integrate_appimage<P: AsRef<Path>, T: PackageExt>(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn appdir_sits_beside_the_appimage() {
+        assert_eq!(
+            appdir_path("/pkgs/krita/krita.AppImage"),
+            PathBuf::from("/pkgs/krita/krita.AppDir")
+        );
+        assert_eq!(
+            appdir_path("/pkgs/krita/krita"),
+            PathBuf::from("/pkgs/krita/krita.AppDir")
+        );
+    }
+
+    #[test]
+    fn launcher_runs_apprun_with_the_runtime_environment() {
+        let script = launcher_script(
+            Path::new("/pkgs/it's/app"),
+            Path::new("/pkgs/it's/app.AppDir"),
+        );
+        assert!(script.starts_with("#!/bin/sh\n"));
+        assert!(script.contains("export APPIMAGE='/pkgs/it'\\''s/app'\n"));
+        assert!(script.contains("export APPDIR='/pkgs/it'\\''s/app.AppDir'\n"));
+        assert!(script.contains("export HOME='/pkgs/it'\\''s/app.home'\n"));
+        assert!(script.ends_with("exec \"$APPDIR/AppRun\" \"$@\"\n"));
+    }
+
+    #[test]
+    fn icon_name_comes_from_the_main_group_only() {
+        assert_eq!(
+            desktop_icon_name("[Desktop Entry]\nName=Foo\nIcon=foo\n").as_deref(),
+            Some("foo")
+        );
+        assert_eq!(
+            desktop_icon_name("[Desktop Entry]\nIcon=/opt/foo/foo.png\n"),
+            None
+        );
+        assert_eq!(
+            desktop_icon_name("[Desktop Entry]\nName=Foo\n\n[Desktop Action new]\nIcon=bar\n"),
+            None
+        );
+    }
+}
@@ -10,23 +10,32 @@ use std::{
     fs::{self, File},
     io::{BufReader, BufWriter, Write},
     path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::SystemTime,
 };
 
 use image::{imageops::FilterType, DynamicImage, GenericImageView};
-use regex::Regex;
 use soar_config::config::get_config;
 use soar_utils::{
     fs::{create_symlink, walk_dir},
     path::{icons_dir, is_safe_component},
 };
-use tracing::{debug, trace};
+use tracing::{debug, trace, warn};
 
 use super::{
-    appimage::integrate_appimage, get_file_type, onelf::integrate_onelf,
-    wrappe::setup_wrappe_portable_dir, PackageFormat,
+    appimage::{appdir_path, integrate_appimage, is_extracted_appimage},
+    get_file_type,
+    onelf::integrate_onelf,
+    wrappe::setup_wrappe_portable_dir,
+    PackageFormat,
 };
 use crate::{
+    desktop::{DesktopEntry, MAIN_GROUP},
     error::{ErrorContext, PackageError, Result},
+    mime::{
+        is_mime_package, metainfo_id, refresh_desktop_database, refresh_mime_database,
+        register_metainfo, register_mime_package,
+    },
     traits::PackageExt,
 };
 
@@ -71,8 +80,8 @@ fn normalize_image(image: DynamicImage) -> DynamicImage {
 
 /// Creates a symlink for an icon in the appropriate icons directory.
 ///
-/// The icon is normalized to a supported dimension and symlinked to
-/// `~/.local/share/icons/{WxH}/apps/{name}-soar.{ext}`.
+/// The icon is linked to `~/.local/share/icons/hicolor/{size}/{context}/{name}-soar.{ext}`;
+/// see [`symlink_icon_with_mode`] for how the size is chosen.
 ///
 /// # Arguments
 ///
@@ -92,34 +101,54 @@ pub fn symlink_icon<P: AsRef<Path>>(real_path: P) -> Result<PathBuf> {
 /// Creates a symlink for an icon in the appropriate icons directory.
 ///
 /// Uses the provided `system_mode` flag to determine the icons directory.
+///
+/// An icon already laid out as a theme (`icons/<theme>/<size>/<context>/`)
+/// keeps its size and context. Otherwise an SVG is scalable, or symbolic when
+/// its name ends in `-symbolic`, and a PNG is normalized to the nearest
+/// supported dimension.
 pub fn symlink_icon_with_mode<P: AsRef<Path>>(real_path: P, system_mode: bool) -> Result<PathBuf> {
     let real_path = real_path.as_ref();
     trace!(path = %real_path.display(), "creating icon symlink");
-    let icon_name = real_path.file_stem().unwrap();
+    let icon_name = real_path.file_stem().unwrap().to_string_lossy();
     let ext = real_path.extension();
+    let symbolic = icon_name.strip_suffix("-symbolic");
 
-    let (w, h) = if ext == Some(OsStr::new("svg")) {
-        (128, 128)
-    } else {
-        let image = image::open(real_path)?;
-        let (orig_w, orig_h) = image.dimensions();
+    let (size, context) = match theme_location(real_path) {
+        Some(location) => location,
+        None if ext == Some(OsStr::new("svg")) => {
+            let size = if symbolic.is_some() {
+                "symbolic"
+            } else {
+                "scalable"
+            };
+            (size.to_string(), "apps".to_string())
+        }
+        None => {
+            let image = image::open(real_path)?;
+            let (orig_w, orig_h) = image.dimensions();
 
-        let normalized_image = normalize_image(image);
-        let (w, h) = normalized_image.dimensions();
+            let normalized_image = normalize_image(image);
+            let (w, h) = normalized_image.dimensions();
 
-        if (w, h) != (orig_w, orig_h) {
-            normalized_image.save(real_path)?;
-        }
+            if (w, h) != (orig_w, orig_h) {
+                normalized_image.save(real_path)?;
+            }
 
-        (w, h)
+            (format!("{w}x{h}"), "apps".to_string())
+        }
     };
 
+    // A symbolic icon is looked up by its base name with `-symbolic` appended,
+    // so the suffix has to stay last.
+    let link_name = match symbolic {
+        Some(base) => format!("{base}-soar-symbolic"),
+        None => format!("{icon_name}-soar"),
+    };
     let final_path = icons_dir(system_mode)
-        .join(format!("{w}x{h}"))
-        .join("apps")
+        .join(size)
+        .join(context)
         .join(format!(
-            "{}-soar.{}",
-            icon_name.to_string_lossy(),
+            "{link_name}.{}",
             ext.unwrap_or_default().to_string_lossy()
         ));
 
@@ -133,31 +162,76 @@ pub fn symlink_icon_with_mode<P: AsRef<Path>>(real_path: P, system_mode: bool) -
     Ok(final_path)
 }
 
-/// An `Exec` or `TryExec` line pointed at the command as installed.
+/// The size directory and context of an icon laid out as an icon theme, such
+/// as `share/icons/hicolor/48x48/apps/foo.png`.
+fn theme_location(path: &Path) -> Option<(String, String)> {
+    let context = path.parent()?;
+    let size = context.parent()?;
+    let icons = size.parent()?.parent()?;
+    if icons.file_name()? != "icons" {
+        return None;
+    }
+
+    let size = size.file_name()?.to_str()?;
+    let context = context.file_name()?.to_str()?;
+    let is_size = matches!(size, "scalable" | "symbolic")
+        || size
+            .split_once('@')
+            .map_or(size, |(dims, _)| dims)
+            .split_once('x')
+            .is_some_and(|(w, h)| {
+                !w.is_empty()
+                    && !h.is_empty()
+                    && w.chars().all(|c| c.is_ascii_digit())
+                    && h.chars().all(|c| c.is_ascii_digit())
+            });
+    (is_size && is_safe_component(context)).then(|| (size.to_string(), context.to_string()))
+}
+
+/// Tell desktop environments the icon theme changed.
 ///
-/// The arguments the entry carried are kept after it. With none to keep,
-/// nothing follows the command: a launcher reading the line whole would take
-/// a trailing space as part of the command and fail to find it.
-fn exec_line(field: &str, old: &str, command: &str) -> String {
-    if old.contains("{{pkg_path}}") {
-        return format!("{field}={}", old.replace("{{pkg_path}}", command));
+/// GTK and Qt compare the theme directory's modification time with what they
+/// cached, so touching it is enough for icons to show up, or go away, without
+/// logging out. A prebuilt `icon-theme.cache` would still hide them, so one is
+/// rebuilt, or removed where it cannot be.
+pub fn refresh_icon_cache(system_mode: bool) {
+    let dir = icons_dir(system_mode);
+    if !dir.is_dir() {
+        return;
     }
 
-    let args: Vec<&str> = old.split_whitespace().skip(1).collect();
-    if args.is_empty() {
-        format!("{field}={command}")
-    } else {
-        format!("{field}={command} {}", args.join(" "))
+    let cache = dir.join("icon-theme.cache");
+    if cache.exists() {
+        let rebuilt = Command::new("gtk-update-icon-cache")
+            .args(["-f", "-t", "-q"])
+            .arg(&dir)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        if !rebuilt {
+            debug!(cache = %cache.display(), "removing stale icon cache");
+            fs::remove_file(&cache).ok();
+        }
+    }
+
+    let now = SystemTime::now();
+    for path in [dir.join("index.theme"), dir] {
+        if let Ok(file) = File::open(&path) {
+            file.set_modified(now).ok();
+        }
     }
 }
 
 /// Creates a symlink for a desktop file with modified fields.
 ///
-/// Updates the Exec and TryExec fields in the desktop file to point to the
-/// installed package, then creates a symlink in the applications directory.
-/// The Icon field is rewritten to the soar-managed icon only when the package
-/// ships a matching icon (`has_icon`); otherwise it is left untouched so that
-/// references to generic system icons keep working.
+/// Updates the Exec and TryExec fields of the entry and its actions to point
+/// to the package's command in the bin directory, which is its launcher when
+/// it has launch settings or a sandbox, then creates a symlink in the
+/// applications directory. The Icon field is
+/// rewritten to the soar-managed icon only when the package ships a matching
+/// icon (`has_icon`); otherwise it is left untouched so that references to
+/// generic system icons keep working.
 ///
 /// # Arguments
 ///
@@ -167,7 +241,8 @@ fn exec_line(field: &str, old: &str, command: &str) -> String {
 ///
 /// # Returns
 ///
-/// The path to the created symlink.
+/// The path to the created symlink, or `None` when the file cannot be read as
+/// a desktop entry and was left unlinked.
 ///
 /// # Errors
 ///
@@ -176,7 +251,7 @@ pub fn symlink_desktop<P: AsRef<Path>, T: PackageExt>(
     real_path: P,
     package: &T,
     has_icon: bool,
-) -> Result<PathBuf> {
+) -> Result<Option<PathBuf>> {
     symlink_desktop_with_config(real_path, package, has_icon, &get_config())
 }
 
@@ -189,7 +264,7 @@ pub fn symlink_desktop_with_config<P: AsRef<Path>, T: PackageExt>(
     package: &T,
     has_icon: bool,
     config: &soar_config::config::Config,
-) -> Result<PathBuf> {
+) -> Result<Option<PathBuf>> {
     let pkg_name = package.pkg_name();
     let real_path = real_path.as_ref();
     trace!(path = %real_path.display(), pkg_name = pkg_name, "creating desktop file symlink");
@@ -197,27 +272,38 @@ pub fn symlink_desktop_with_config<P: AsRef<Path>, T: PackageExt>(
         .with_context(|| format!("reading content of desktop file: {}", real_path.display()))?;
     let file_name = real_path.file_stem().unwrap();
 
-    let bin_path = config.get_bin_path()?;
-
-    let final_content = {
-        let re = Regex::new(r"(?m)^(Icon|Exec|TryExec)=(.*)").unwrap();
-
-        re.replace_all(&content, |caps: &regex::Captures| {
-            match &caps[1] {
-                "Icon" if has_icon => format!("Icon={}-soar", file_name.to_string_lossy()),
-                "Icon" => caps[0].to_string(),
-                "Exec" | "TryExec" => {
-                    exec_line(
-                        &caps[1],
-                        &caps[2],
-                        &format!("{}/{}", bin_path.display(), pkg_name),
-                    )
-                }
-                _ => unreachable!(),
-            }
-        })
-        .to_string()
+    // One line a desktop cannot read is upstream's to fix, and not worth the
+    // rest of the install.
+    let mut entry = match DesktopEntry::parse(&content) {
+        Ok(entry) => entry,
+        Err(err) => {
+            warn!("{}: {err}; not linking it", real_path.display());
+            return Ok(None);
+        }
     };
+    // Desktops hide an entry with a fatal problem, so those are worth a
+    // warning. The rest only cost a key and are left to upstream.
+    for problem in entry.validate() {
+        if problem.fatal {
+            warn!("{}: {problem}", real_path.display());
+        } else {
+            debug!("{}: {problem}", real_path.display());
+        }
+    }
+
+    if has_icon {
+        entry.set(
+            MAIN_GROUP,
+            "Icon",
+            format!("{}-soar", file_name.to_string_lossy()),
+        );
+    }
+    let bin_path = config.get_bin_path()?;
+    // Exec goes through the bin link, which is the launcher applying the
+    // package's launch settings and sandbox when it has them. Repeating them
+    // here would pass its arguments twice.
+    entry.point_exec_at(&format!("{}/{}", bin_path.display(), pkg_name));
+    let final_content = entry.to_string();
 
     let mut writer = BufWriter::new(
         File::create(real_path)
@@ -238,7 +324,7 @@ pub fn symlink_desktop_with_config<P: AsRef<Path>, T: PackageExt>(
 
     create_symlink(real_path, &final_path)?;
     debug!(desktop = %final_path.display(), "desktop file symlink created");
-    Ok(final_path)
+    Ok(Some(final_path))
 }
 
 /// Creates a portable link for package data directories.
@@ -365,6 +451,7 @@ pub fn setup_portable_dir<P: AsRef<Path>, T: PackageExt>(
 /// - Desktop file symlinking
 /// - Icon symlinking with dimension normalization
 /// - AppImage resource extraction
+/// - MIME type and AppStream metainfo registration
 /// - Portable directory setup
 ///
 /// # Arguments
@@ -401,11 +488,14 @@ pub async fn integrate_package<P: AsRef<Path>, T: PackageExt>(
         .unwrap_or_else(|| install_dir.join(pkg_name));
 
     let system_mode = config.is_system();
+    // An extracted AppImage's own icons and desktop files belong to the app,
+    // not to the desktop, so its AppDir is left out of both walks.
+    let appdir = appdir_path(&bin_path);
 
     let mut has_icon = false;
     let mut icon_stems: HashSet<String> = HashSet::new();
     let mut symlink_action = |path: &Path| -> Result<()> {
-        if path == bin_path.as_path() {
+        if path == bin_path.as_path() || path.starts_with(&appdir) {
             return Ok(());
         }
         let ext = path.extension();
@@ -424,7 +514,7 @@ pub async fn integrate_package<P: AsRef<Path>, T: PackageExt>(
     let mut symlink_action = |path: &Path| -> Result<()> {
         // Never treat the package binary itself as a desktop file. Its name can
         // legitimately end in `.desktop`, but its contents are the executable.
-        if path == bin_path.as_path() {
+        if path == bin_path.as_path() || path.starts_with(&appdir) {
             return Ok(());
         }
         let ext = path.extension();
@@ -464,7 +554,7 @@ pub async fn integrate_package<P: AsRef<Path>, T: PackageExt>(
             }
             trace!("setting up portable directories");
             setup_portable_dir(
-                bin_path,
+                &bin_path,
                 package,
                 portable,
                 portable_home,
@@ -501,9 +591,45 @@ pub async fn integrate_package<P: AsRef<Path>, T: PackageExt>(
             trace!("setting up Wrappe portable directory");
             setup_wrappe_portable_dir(&bin_path, pkg_name, portable)?;
         }
+        PackageFormat::Unknown if is_extracted_appimage(&bin_path) => {
+            // Its resources were taken out before the AppImage was, so only
+            // the portable directories its launcher reads are left.
+            trace!("setting up extracted AppImage portable directories");
+            setup_portable_dir(
+                &bin_path,
+                package,
+                portable,
+                portable_home,
+                portable_config,
+                portable_share,
+                portable_cache,
+            )?;
+        }
         _ => {}
     }
 
+    // After the format step, which is what takes these out of an AppImage.
+    let mut has_mime = false;
+    let mut register_action = |path: &Path| -> Result<()> {
+        if path == bin_path.as_path() || path.starts_with(&appdir) {
+            return Ok(());
+        }
+        if is_mime_package(path) {
+            has_mime = true;
+            register_mime_package(path, system_mode)?;
+        } else if metainfo_id(path).is_some() {
+            register_metainfo(path, system_mode)?;
+        }
+        Ok(())
+    };
+    walk_dir(install_dir, &mut register_action)?;
+
+    if has_mime {
+        refresh_mime_database(system_mode);
+    }
+    refresh_desktop_database(&config.get_desktop_path()?);
+    refresh_icon_cache(system_mode);
+
     debug!(
         pkg_name = pkg_name,
         has_desktop = has_desktop,
@@ -515,30 +641,76 @@ pub async fn integrate_package<P: AsRef<Path>, T: PackageExt>(
 
 #[cfg(test)]
 mod tests {
-    use super::exec_line;
+    use soar_config::config::Config;
+
+    use super::{fs, symlink_desktop_with_config, theme_location, PackageExt, Path};
+
+    struct Pkg;
+
+    impl PackageExt for Pkg {
+        fn pkg_name(&self) -> &str {
+            "foo"
+        }
+
+        fn pkg_id(&self) -> Option<&str> {
+            None
+        }
+
+        fn pkg_family(&self) -> Option<&str> {
+            None
+        }
+
+        fn version(&self) -> &str {
+            "1.0"
+        }
+
+        fn repo_name(&self) -> &str {
+            "local"
+        }
+    }
 
     #[test]
-    fn a_command_with_no_arguments_ends_the_line() {
-        assert_eq!(exec_line("Exec", "/old/bin", "/new/bin"), "Exec=/new/bin");
-        assert_eq!(
-            exec_line("TryExec", "/old/bin", "/new/bin"),
-            "TryExec=/new/bin"
-        );
+    fn a_desktop_file_that_does_not_parse_is_left_unlinked() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("foo.desktop");
+        let content = "[Desktop Entry]\nName=Foo\nnot a key\n";
+        fs::write(&path, content).unwrap();
+
+        let linked =
+            symlink_desktop_with_config(&path, &Pkg, false, &Config::default_config::<&str>(&[]))
+                .unwrap();
+
+        assert_eq!(linked, None);
+        assert_eq!(fs::read_to_string(&path).unwrap(), content);
     }
 
     #[test]
-    fn the_arguments_an_entry_carried_are_kept() {
+    fn an_icon_in_a_theme_layout_keeps_its_size_and_context() {
+        assert_eq!(
+            theme_location(Path::new("/p/share/icons/hicolor/48x48/apps/foo.png")),
+            Some(("48x48".to_string(), "apps".to_string()))
+        );
+        assert_eq!(
+            theme_location(Path::new("/p/share/icons/hicolor/32x32@2/apps/foo.png")),
+            Some(("32x32@2".to_string(), "apps".to_string()))
+        );
+        assert_eq!(
+            theme_location(Path::new("/p/icons/hicolor/symbolic/apps/foo-symbolic.svg")),
+            Some(("symbolic".to_string(), "apps".to_string()))
+        );
         assert_eq!(
-            exec_line("Exec", "/old/bin --flag %U", "/new/bin"),
-            "Exec=/new/bin --flag %U"
+            theme_location(Path::new("/p/icons/hicolor/scalable/mimetypes/foo.svg")),
+            Some(("scalable".to_string(), "mimetypes".to_string()))
         );
     }
 
     #[test]
-    fn a_placeholder_is_filled_where_it_stands() {
+    fn an_icon_outside_a_theme_layout_has_no_location() {
+        assert_eq!(theme_location(Path::new("/p/foo.png")), None);
+        assert_eq!(theme_location(Path::new("/p/share/pixmaps/foo.png")), None);
         assert_eq!(
-            exec_line("Exec", "env FOO=1 {{pkg_path}} %F", "/new/bin"),
-            "Exec=env FOO=1 /new/bin %F"
+            theme_location(Path::new("/p/share/icons/hicolor/large/apps/foo.png")),
+            None
         );
     }
 }
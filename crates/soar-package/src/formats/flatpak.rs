@@ -0,0 +1,579 @@
+//! Flatpak single-file bundles.
+//!
+//! A `.flatpak` bundle is an OSTree static delta serialized as a GVariant,
+//! with the app's ref and metadata in the delta's leading `a{sv}`. Its
+//! contents only make sense to flatpak, so soar reads that metadata itself and
+//! leaves installing the app to a local `flatpak`, then links the desktop
+//! entries and icons it exports like any other package's.
+
+use std::{
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use soar_utils::fs::is_elf;
+use tracing::{debug, trace, warn};
+
+use crate::{
+    desktop::DesktopEntry,
+    error::{ErrorContext, PackageError, Result},
+};
+
+/// Where the ref of an installed bundle is recorded, inside the package
+/// directory, so removing the package can uninstall it from flatpak.
+pub const REF_FILE: &str = ".flatpak-ref";
+
+/// The most metadata read from a bundle. Real ones carry a few hundred
+/// kilobytes of AppStream data and icons.
+const MAX_METADATA: u64 = 16 * 1024 * 1024;
+
+/// Variable-size members of the superblock tuple ahead of its last, each with
+/// a framing offset at the end of the file.
+const SUPERBLOCK_OFFSETS: u64 = 6;
+
+/// What a bundle says about the app it carries.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FlatpakBundle {
+    /// The full ref, such as `app/org.example.App/x86_64/stable`.
+    pub reference: String,
+    pub app_id: String,
+    pub arch: String,
+    pub branch: String,
+    /// The runtime it needs, such as `org.freedesktop.Platform/x86_64/23.08`.
+    pub runtime: Option<String>,
+    /// Where flatpak can fetch that runtime from, when the bundle says.
+    pub runtime_repo: Option<String>,
+}
+
+/// Reads a bundle's metadata, or `None` when `reader` is not one.
+pub fn read_bundle<R: Read + Seek>(reader: &mut R) -> Option<FlatpakBundle> {
+    let size = reader.seek(SeekFrom::End(0)).ok()?;
+    let osize = offset_size(size);
+    if size < osize * SUPERBLOCK_OFFSETS + 8 {
+        return None;
+    }
+
+    // Tuple offsets are stored last to first, so the metadata's end is the
+    // very last one.
+    reader.seek(SeekFrom::End(-(osize as i64))).ok()?;
+    let mut end = vec![0u8; osize as usize];
+    reader.read_exact(&mut end).ok()?;
+    let end = read_offset(&end);
+    if end == 0 || end > MAX_METADATA || end > size {
+        return None;
+    }
+
+    reader.seek(SeekFrom::Start(0)).ok()?;
+    let mut metadata = vec![0u8; end as usize];
+    reader.read_exact(&mut metadata).ok()?;
+    reader.rewind().ok()?;
+
+    let mut reference = None;
+    let mut keyfile = None;
+    let mut runtime_repo = None;
+    for (key, signature, value) in dict_entries(&metadata)? {
+        match (key, signature) {
+            ("ref", "s") => reference = Some(c_string(value)?),
+            ("metadata", "s") => keyfile = Some(c_string(value)?),
+            ("runtime-repo", "s") => runtime_repo = Some(c_string(value)?),
+            _ => {}
+        }
+    }
+
+    let reference = reference?;
+    let mut parts = reference.split('/');
+    let (Some("app"), Some(app_id), Some(arch), Some(branch), None) = (
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+        parts.next(),
+    ) else {
+        return None;
+    };
+    let runtime = keyfile.as_deref().and_then(|k| keyfile_value(k, "runtime"));
+
+    Some(FlatpakBundle {
+        app_id: app_id.to_string(),
+        arch: arch.to_string(),
+        branch: branch.to_string(),
+        reference,
+        runtime,
+        runtime_repo: runtime_repo.filter(|r| !r.is_empty()),
+    })
+}
+
+/// Reads the bundle at `path`, or `None` when it is not one.
+pub fn read_bundle_file(path: &Path) -> Option<FlatpakBundle> {
+    // An ELF can end in anything, so one never counts.
+    if is_elf(path) {
+        return None;
+    }
+    read_bundle(&mut File::open(path).ok()?)
+}
+
+/// GVariant sizes its offsets by the container they frame.
+fn offset_size(size: u64) -> u64 {
+    match size {
+        0..=0xff => 1,
+        0x100..=0xffff => 2,
+        0x1_0000..=0xffff_ffff => 4,
+        _ => 8,
+    }
+}
+
+/// Framing offsets are little-endian whatever the data's byte order.
+fn read_offset(bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .rev()
+        .fold(0u64, |acc, &b| (acc << 8) | u64::from(b))
+}
+
+fn align8(offset: usize) -> usize {
+    (offset + 7) & !7
+}
+
+/// The entries of a serialized `a{sv}`: key, value signature and value bytes.
+fn dict_entries(data: &[u8]) -> Option<Vec<(&str, &str, &[u8])>> {
+    if data.is_empty() {
+        return Some(Vec::new());
+    }
+    let osize = offset_size(data.len() as u64) as usize;
+    let table = read_offset(data.get(data.len() - osize..)?) as usize;
+    if table > data.len() || !(data.len() - table).is_multiple_of(osize) {
+        return None;
+    }
+
+    let mut entries = Vec::new();
+    let mut start = 0;
+    for chunk in data[table..].chunks(osize) {
+        let end = read_offset(chunk) as usize;
+        let entry = data.get(start..end)?;
+        entries.push(dict_entry(entry)?);
+        start = align8(end);
+    }
+    Some(entries)
+}
+
+/// A `{sv}`: the key's end is framed at the back, and the variant's type
+/// signature follows its value after a NUL.
+fn dict_entry(entry: &[u8]) -> Option<(&str, &str, &[u8])> {
+    let osize = offset_size(entry.len() as u64) as usize;
+    let body = entry.get(..entry.len().checked_sub(osize)?)?;
+    let key_end = read_offset(&entry[body.len()..]) as usize;
+    let key = std::str::from_utf8(body.get(..key_end.checked_sub(1)?)?).ok()?;
+
+    let variant = body.get(align8(key_end)..)?;
+    let split = variant.iter().rposition(|&b| b == 0)?;
+    let signature = std::str::from_utf8(&variant[split + 1..]).ok()?;
+    Some((key, signature, &variant[..split]))
+}
+
+fn c_string(value: &[u8]) -> Option<String> {
+    let value = value.strip_suffix(&[0]).unwrap_or(value);
+    String::from_utf8(value.to_vec()).ok()
+}
+
+/// A key of the `[Application]` group of a flatpak metadata keyfile.
+fn keyfile_value(keyfile: &str, key: &str) -> Option<String> {
+    let mut in_group = false;
+    for line in keyfile.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_group = line == "[Application]";
+        } else if in_group {
+            if let Some((k, v)) = line.split_once('=') {
+                if k.trim() == key {
+                    return Some(v.trim().to_string());
+                }
+            }
+        }
+    }
+    None
+}
+
+/// The `flatpak` installation flag matching soar's own mode.
+fn scope(system_mode: bool) -> &'static str {
+    if system_mode {
+        "--system"
+    } else {
+        "--user"
+    }
+}
+
+fn flatpak_available() -> bool {
+    Command::new("flatpak")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Installs the bundle at `bundle_path` with the local `flatpak`, then lays
+/// out `install_dir` like any other package: a launcher at `bin_path` that
+/// runs the app, and copies of the desktop entries, icons and MIME types it
+/// exports for soar to integrate.
+///
+/// The bundle file itself is replaced by the launcher.
+pub fn install_bundle(
+    bundle: &FlatpakBundle,
+    bundle_path: &Path,
+    install_dir: &Path,
+    bin_path: &Path,
+    system_mode: bool,
+) -> Result<()> {
+    if !flatpak_available() {
+        let runtime = bundle
+            .runtime
+            .as_deref()
+            .map(|r| format!(" and its runtime {r}"))
+            .unwrap_or_default();
+        return Err(PackageError::Custom(format!(
+            "{} is a Flatpak bundle, which needs flatpak{runtime} to run. \
+             Install flatpak and try again",
+            bundle.app_id
+        )));
+    }
+
+    debug!(reference = bundle.reference, "installing flatpak bundle");
+    let status = Command::new("flatpak")
+        .args([
+            "install",
+            scope(system_mode),
+            "--noninteractive",
+            "--bundle",
+        ])
+        .arg(bundle_path)
+        .status()
+        .with_context(|| "running flatpak install".to_string())?;
+    if !status.success() {
+        let hint = match (&bundle.runtime, &bundle.runtime_repo) {
+            (Some(runtime), None) => {
+                format!(
+                    ". The bundle names no repository for its runtime {runtime}, \
+                     so a remote providing it has to be added first"
+                )
+            }
+            _ => String::new(),
+        };
+        return Err(PackageError::Custom(format!(
+            "flatpak could not install {}{hint}",
+            bundle.app_id
+        )));
+    }
+
+    // The app is in flatpak's store now, so anything failing past here takes
+    // it back out rather than leave it installed behind soar's back.
+    let laid_out = fs::write(install_dir.join(REF_FILE), &bundle.reference)
+        .with_context(|| format!("recording the flatpak ref in {}", install_dir.display()))
+        .and_then(|()| lay_out(bundle, bundle_path, install_dir, bin_path, system_mode));
+    if let Err(err) = laid_out {
+        if let Err(uninstall_err) = uninstall(&bundle.reference, system_mode) {
+            warn!("{uninstall_err}");
+        }
+        fs::remove_file(install_dir.join(REF_FILE)).ok();
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// Replaces the bundle with its launcher and copies the app's exports in.
+fn lay_out(
+    bundle: &FlatpakBundle,
+    bundle_path: &Path,
+    install_dir: &Path,
+    bin_path: &Path,
+    system_mode: bool,
+) -> Result<()> {
+    fs::remove_file(bundle_path).ok();
+    fs::remove_file(bin_path).ok();
+    fs::write(bin_path, launcher_script(bundle))
+        .with_context(|| format!("writing launcher {}", bin_path.display()))?;
+    fs::set_permissions(bin_path, fs::Permissions::from_mode(0o755))
+        .with_context(|| format!("setting permissions on {}", bin_path.display()))?;
+
+    if let Some(export) = export_dir(bundle, system_mode) {
+        copy_exports(&export, install_dir)?;
+    }
+    Ok(())
+}
+
+/// Uninstalls the app whose ref was recorded in `install_dir`, if any.
+pub fn uninstall_recorded(install_dir: &Path, system_mode: bool) -> Result<()> {
+    let Ok(reference) = fs::read_to_string(install_dir.join(REF_FILE)) else {
+        return Ok(());
+    };
+    uninstall(reference.trim(), system_mode)
+}
+
+fn uninstall(reference: &str, system_mode: bool) -> Result<()> {
+    debug!(reference = reference, "uninstalling flatpak app");
+    let status = Command::new("flatpak")
+        .args([
+            "uninstall",
+            scope(system_mode),
+            "--noninteractive",
+            reference,
+        ])
+        .status()
+        .with_context(|| "running flatpak uninstall".to_string())?;
+    if !status.success() {
+        return Err(PackageError::Custom(format!(
+            "flatpak could not uninstall {reference}"
+        )));
+    }
+    Ok(())
+}
+
+fn launcher_script(bundle: &FlatpakBundle) -> String {
+    format!(
+        "#!/bin/sh\nexec flatpak run --arch={} --branch={} {} \"$@\"\n",
+        bundle.arch, bundle.branch, bundle.app_id
+    )
+}
+
+/// The deployed app's export tree, as flatpak reports it.
+fn export_dir(bundle: &FlatpakBundle, system_mode: bool) -> Option<PathBuf> {
+    let output = Command::new("flatpak")
+        .args([
+            "info",
+            scope(system_mode),
+            "--show-location",
+            &bundle.reference,
+        ])
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let location = String::from_utf8(output.stdout).ok()?;
+    let export = Path::new(location.trim()).join("export");
+    (output.status.success() && export.is_dir()).then_some(export)
+}
+
+/// Copies what integration links out of the export tree into the package,
+/// pointing each desktop entry's `Exec` at the launcher.
+fn copy_exports(export: &Path, install_dir: &Path) -> Result<()> {
+    for dir in ["share/applications", "share/icons", "share/mime/packages"] {
+        let from = export.join(dir);
+        if from.is_dir() {
+            copy_tree(&from, &install_dir.join(dir))?;
+        }
+    }
+
+    let applications = install_dir.join("share/applications");
+    let Ok(entries) = fs::read_dir(&applications) else {
+        return Ok(());
+    };
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.extension().is_none_or(|ext| ext != "desktop") {
+            continue;
+        }
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("reading desktop file {}", path.display()))?;
+        // One entry flatpak exported but a desktop cannot read is not worth
+        // the rest of the install; it is left out instead.
+        let mut entry = match DesktopEntry::parse(&content) {
+            Ok(entry) => entry,
+            Err(err) => {
+                warn!("{}: {err}; not linking it", path.display());
+                fs::remove_file(&path).ok();
+                continue;
+            }
+        };
+        for group in entry.launch_groups() {
+            if let Some(exec) = entry.get(&group, "Exec") {
+                let exec = launcher_exec(exec);
+                entry.set(&group, "Exec", exec);
+            }
+        }
+        fs::write(&path, entry.to_string())
+            .with_context(|| format!("writing desktop file {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// An exported `flatpak run ... <app-id> @@ %F @@` line as the launcher takes
+/// it: the field codes it forwards, with flatpak's own arguments dropped.
+fn launcher_exec(exec: &str) -> String {
+    let args: Vec<&str> = exec
+        .split_whitespace()
+        .skip_while(|arg| *arg != "run")
+        .skip(1)
+        .skip_while(|arg| arg.starts_with("--"))
+        .skip(1)
+        .filter(|arg| !arg.starts_with("@@"))
+        .collect();
+    let mut line = String::from("{{pkg_path}}");
+    for arg in args {
+        line.push(' ');
+        line.push_str(arg);
+    }
+    line
+}
+
+fn copy_tree(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to).with_context(|| format!("creating directory {}", to.display()))?;
+    for entry in fs::read_dir(from).with_context(|| format!("reading {}", from.display()))? {
+        let entry = entry.with_context(|| format!("reading {}", from.display()))?;
+        let source = entry.path();
+        let dest = to.join(entry.file_name());
+        // Exports are links into the deployment; what they point at is what
+        // gets copied.
+        if source.is_dir() {
+            copy_tree(&source, &dest)?;
+        } else if source.is_file() {
+            trace!(from = %source.display(), to = %dest.display(), "copying export");
+            fs::copy(&source, &dest)
+                .with_context(|| format!("copying {} to {}", source.display(), dest.display()))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn offset_bytes(value: usize, osize: usize) -> Vec<u8> {
+        value.to_le_bytes()[..osize].to_vec()
+    }
+
+    /// A `{sv}` holding a string.
+    fn string_entry(key: &str, value: &str) -> Vec<u8> {
+        let mut body = key.as_bytes().to_vec();
+        body.push(0);
+        let key_end = body.len();
+        body.resize(align8(key_end), 0);
+        body.extend_from_slice(value.as_bytes());
+        body.extend_from_slice(b"\0\0s");
+        let osize = offset_size((body.len() + 1) as u64) as usize;
+        body.extend(offset_bytes(key_end, osize));
+        body
+    }
+
+    fn dict(entries: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut ends = Vec::new();
+        for entry in entries {
+            data.resize(align8(data.len()), 0);
+            data.extend_from_slice(entry);
+            ends.push(data.len());
+        }
+        let osize = offset_size((data.len() + ends.len() * 2) as u64) as usize;
+        for end in ends {
+            data.extend(offset_bytes(end, osize));
+        }
+        data
+    }
+
+    /// A superblock with `metadata` first and nothing in the rest.
+    fn bundle(metadata: &[u8]) -> Vec<u8> {
+        let mut data = metadata.to_vec();
+        data.resize(align8(data.len()), 0);
+        data.extend_from_slice(&[0; 8]);
+        data.extend_from_slice(&[0; 300]);
+        let body = data.len();
+        let osize = offset_size((body + 12) as u64) as usize;
+        for _ in 0..SUPERBLOCK_OFFSETS - 1 {
+            data.extend(offset_bytes(body, osize));
+        }
+        data.extend(offset_bytes(metadata.len(), osize));
+        data
+    }
+
+    #[test]
+    fn reads_the_ref_and_runtime_of_a_bundle() {
+        let metadata = dict(&[
+            string_entry("ref", "app/org.example.Foo/x86_64/stable"),
+            string_entry(
+                "metadata",
+                "[Application]\nname=org.example.Foo\nruntime=org.freedesktop.Platform/x86_64/23.08\n",
+            ),
+            string_entry("runtime-repo", "https://dl.flathub.org/repo/flathub.flatpakrepo"),
+        ]);
+
+        let bundle = read_bundle(&mut Cursor::new(bundle(&metadata))).unwrap();
+        assert_eq!(
+            bundle,
+            FlatpakBundle {
+                reference: "app/org.example.Foo/x86_64/stable".into(),
+                app_id: "org.example.Foo".into(),
+                arch: "x86_64".into(),
+                branch: "stable".into(),
+                runtime: Some("org.freedesktop.Platform/x86_64/23.08".into()),
+                runtime_repo: Some("https://dl.flathub.org/repo/flathub.flatpakrepo".into()),
+            }
+        );
+    }
+
+    #[test]
+    fn other_files_are_not_bundles() {
+        assert_eq!(read_bundle(&mut Cursor::new(vec![0u8; 4096])), None);
+        assert_eq!(read_bundle(&mut Cursor::new(b"#!/bin/sh\n".to_vec())), None);
+        // A runtime bundle is not an app.
+        let metadata = dict(&[string_entry("ref", "runtime/org.example.Platform/x86_64/1")]);
+        assert_eq!(read_bundle(&mut Cursor::new(bundle(&metadata))), None);
+    }
+
+    #[test]
+    fn exported_exec_lines_keep_only_their_field_codes() {
+        assert_eq!(
+            launcher_exec(
+                "/usr/bin/flatpak run --branch=stable --arch=x86_64 --command=foo --file-forwarding org.example.Foo @@u %U @@"
+            ),
+            "{{pkg_path}} %U"
+        );
+        assert_eq!(
+            launcher_exec("/usr/bin/flatpak run --branch=stable org.example.Foo --new-window"),
+            "{{pkg_path}} --new-window"
+        );
+    }
+
+    #[test]
+    fn an_unreadable_exported_entry_is_left_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let export = dir.path().join("export");
+        let applications = export.join("share/applications");
+        fs::create_dir_all(&applications).unwrap();
+        fs::write(
+            applications.join("org.example.Foo.desktop"),
+            "[Desktop Entry]\nType=Application\nName=Foo\nExec=/usr/bin/flatpak run org.example.Foo\n",
+        )
+        .unwrap();
+        fs::write(
+            applications.join("org.example.Bar.desktop"),
+            "[Desktop Entry]\nnot a key\n",
+        )
+        .unwrap();
+
+        let install_dir = dir.path().join("pkg");
+        copy_exports(&export, &install_dir).unwrap();
+
+        let copied = install_dir.join("share/applications");
+        let foo = fs::read_to_string(copied.join("org.example.Foo.desktop")).unwrap();
+        assert!(foo.contains("Exec={{pkg_path}}"));
+        assert!(!copied.join("org.example.Bar.desktop").exists());
+    }
+
+    #[test]
+    fn the_launcher_runs_the_exact_ref() {
+        let bundle = FlatpakBundle {
+            reference: "app/org.example.Foo/aarch64/beta".into(),
+            app_id: "org.example.Foo".into(),
+            arch: "aarch64".into(),
+            branch: "beta".into(),
+            runtime: None,
+            runtime_repo: None,
+        };
+        assert_eq!(
+            launcher_script(&bundle),
+            "#!/bin/sh\nexec flatpak run --arch=aarch64 --branch=beta org.example.Foo \"$@\"\n"
+        );
+    }
+}
@@ -5,6 +5,7 @@
 
 pub mod appimage;
 pub mod common;
+pub mod flatpak;
 pub mod onelf;
 pub mod wrappe;
 
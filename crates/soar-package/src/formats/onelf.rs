@@ -8,13 +8,15 @@
 use std::{
     fs::{self, File},
     io::{Read, Seek, SeekFrom},
-    path::Path,
+    os::unix::fs::{symlink, PermissionsExt},
+    path::{Path, PathBuf},
 };
 
 use onelf_format::{Entry, EntryKind, Footer, Manifest, FOOTER_SIZE};
 
 use super::common::{symlink_desktop_with_config, symlink_icon_with_mode};
 use crate::{
+    contents::{entry_dest, prepare_dest, ContentEntry, ContentKind},
     error::{ErrorContext, PackageError, Result},
     traits::PackageExt,
 };
@@ -117,6 +119,68 @@ fn decompress_entry(
     Ok(result)
 }
 
+/// Decompresses a file entry and checks it against its BLAKE3 content hash.
+fn read_verified_entry(
+    file: &mut File,
+    footer: &Footer,
+    entry: &Entry,
+    dict: Option<&[u8]>,
+) -> Result<Vec<u8>> {
+    let data = decompress_entry(file, footer, entry, dict)?;
+    if *blake3::hash(&data).as_bytes() != entry.content_hash {
+        return Err(invalid(
+            "contents do not match the BLAKE3 hash in the manifest",
+        ));
+    }
+    Ok(data)
+}
+
+/// Looks up a string in the manifest's string table.
+///
+/// Unlike [`Manifest::get_string`], an offset past the end of the table is
+/// reported instead of panicking.
+fn string_at(manifest: &Manifest, offset: u32) -> Result<&str> {
+    if offset as usize > manifest.string_table.len() {
+        return Err(invalid(format!(
+            "onelf string offset {offset} is out of range"
+        )));
+    }
+    Ok(manifest.get_string(offset))
+}
+
+/// Builds the package-relative path of every entry.
+///
+/// Unlike [`Manifest::entry_path`], a parent that does not exist or a parent
+/// chain that loops is reported instead of followed.
+fn entry_paths(manifest: &Manifest) -> Result<Vec<PathBuf>> {
+    let count = manifest.entries.len();
+    (0..count)
+        .map(|index| {
+            let mut parts = Vec::new();
+            let mut idx = index;
+            loop {
+                let entry = &manifest.entries[idx];
+                let name = string_at(manifest, entry.name)?;
+                if name.is_empty() {
+                    break;
+                }
+                parts.push(name);
+                if entry.parent == u32::MAX {
+                    break;
+                }
+                idx = entry.parent as usize;
+                if idx >= count || parts.len() > count {
+                    return Err(invalid(format!(
+                        "onelf entry {index} has an invalid parent"
+                    )));
+                }
+            }
+            parts.reverse();
+            Ok(parts.into_iter().collect())
+        })
+        .collect()
+}
+
 /// Finds the entry index of a file at the given package-relative path.
 fn find_entry_by_path(manifest: &Manifest, path: &str) -> Option<usize> {
     manifest
@@ -233,3 +297,253 @@ pub async fn integrate_onelf<P: AsRef<Path>, T: PackageExt>(
 
     Ok(())
 }
+
+/// Lists every entry of the onelf package at `path`.
+///
+/// With `verify`, each file is decompressed and compared with the BLAKE3 hash
+/// the manifest records for it.
+pub(crate) fn list_onelf(path: &Path, verify: bool) -> Result<Vec<ContentEntry>> {
+    let mut file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let (footer, manifest) = read_footer_and_manifest(&mut file)?;
+    let dict = read_dict(&mut file, &footer)?;
+    let paths = entry_paths(&manifest)?;
+
+    let mut entries = Vec::with_capacity(paths.len());
+    for (entry, path) in manifest.entries.iter().zip(paths) {
+        let (kind, size, verified) = match entry.kind {
+            EntryKind::Dir => (ContentKind::Directory, 0, None),
+            EntryKind::Symlink => {
+                let target = string_at(&manifest, entry.symlink_target)?;
+                (ContentKind::Symlink(target.into()), 0, None)
+            }
+            EntryKind::File => {
+                let size = entry.blocks.iter().map(|b| b.original_size).sum();
+                let verified = verify.then(|| {
+                    read_verified_entry(&mut file, &footer, entry, dict.as_deref()).is_ok()
+                });
+                (ContentKind::File, size, verified)
+            }
+        };
+        entries.push(ContentEntry {
+            path,
+            kind,
+            size,
+            mode: Some(entry.mode & 0o7777),
+            verified,
+        });
+    }
+    Ok(entries)
+}
+
+/// Extracts the entries of the onelf package at `path` that `wanted` selects
+/// below `root`.
+///
+/// Every file is checked against its BLAKE3 hash before it is written, so a
+/// corrupted package fails instead of leaving a damaged copy behind.
+///
+/// Returns how many entries were written.
+pub(crate) fn extract_onelf(
+    path: &Path,
+    root: &Path,
+    wanted: &dyn Fn(&Path) -> bool,
+) -> Result<usize> {
+    let mut file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let (footer, manifest) = read_footer_and_manifest(&mut file)?;
+    let dict = read_dict(&mut file, &footer)?;
+    let paths = entry_paths(&manifest)?;
+
+    let mut count = 0;
+    for (entry, relative) in manifest.entries.iter().zip(paths) {
+        if !wanted(&relative) {
+            continue;
+        }
+        let Some(dest) = entry_dest(root, &relative)? else {
+            continue;
+        };
+        prepare_dest(&dest)?;
+        match entry.kind {
+            EntryKind::Dir => {
+                fs::create_dir_all(&dest)
+                    .with_context(|| format!("creating {}", dest.display()))?;
+            }
+            EntryKind::File => {
+                let data = read_verified_entry(&mut file, &footer, entry, dict.as_deref())
+                    .map_err(|e| invalid(format!("{}: {e}", relative.display())))?;
+                fs::write(&dest, data).with_context(|| format!("writing {}", dest.display()))?;
+                fs::set_permissions(&dest, fs::Permissions::from_mode(entry.mode & 0o777))
+                    .with_context(|| format!("setting permissions on {}", dest.display()))?;
+            }
+            EntryKind::Symlink => {
+                let target = string_at(&manifest, entry.symlink_target)?;
+                symlink(target, &dest).with_context(|| format!("linking {}", dest.display()))?;
+            }
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use onelf_format::{Block, Flags, ManifestHeader, StringTableBuilder};
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::contents::{extract_contents, list_contents};
+
+    /// Packs `bin/app` and an `app` link to it into a minimal onelf file.
+    fn pack(path: &Path, content_hash: [u8; 32]) {
+        let content = b"#!/bin/sh\necho hello\n";
+        let mut strings = StringTableBuilder::new();
+        let entry = |kind, parent, name, mode| {
+            Entry {
+                kind,
+                parent,
+                name,
+                mode,
+                mtime_secs: 0,
+                mtime_nsec: 0,
+                content_hash: [0; 32],
+                num_blocks: 0,
+                blocks: Vec::new(),
+                symlink_target: 0,
+            }
+        };
+
+        let mut out = b"\x7fELF stub".to_vec();
+        let payload_offset = out.len() as u64;
+        let block = zstd::bulk::compress(content, 3).unwrap();
+        out.extend_from_slice(&block);
+
+        let mut file = entry(EntryKind::File, 0, strings.add("app"), 0o755);
+        file.content_hash = content_hash;
+        file.num_blocks = 1;
+        file.blocks.push(Block {
+            payload_offset: 0,
+            compressed_size: block.len() as u64,
+            original_size: content.len() as u64,
+        });
+        let mut link = entry(EntryKind::Symlink, u32::MAX, strings.add("app"), 0o777);
+        link.symlink_target = strings.add("bin/app");
+        let dir = entry(EntryKind::Dir, u32::MAX, strings.add("bin"), 0o755);
+
+        let string_table = strings.finish();
+        let manifest = Manifest {
+            header: ManifestHeader {
+                version: 1,
+                entry_count: 3,
+                string_table_size: string_table.len() as u32,
+                entrypoint_count: 0,
+                default_entrypoint: 0,
+                lib_dir_count: 0,
+                name_offset: 0,
+                package_id: [0; 32],
+            },
+            entrypoints: Vec::new(),
+            entries: vec![dir, file, link],
+            lib_dir_offsets: Vec::new(),
+            string_table,
+        }
+        .serialize()
+        .unwrap();
+        let manifest_offset = out.len() as u64;
+        let compressed = zstd::bulk::compress(&manifest, 3).unwrap();
+        out.extend_from_slice(&compressed);
+
+        Footer {
+            format_version: 1,
+            flags: Flags::empty(),
+            manifest_offset,
+            manifest_compressed: compressed.len() as u64,
+            manifest_original: manifest.len() as u64,
+            payload_offset,
+            payload_size: block.len() as u64,
+            dict_offset: 0,
+            dict_size: 0,
+            manifest_checksum: xxhash_rust::xxh32::xxh32(&manifest, 0).to_le_bytes(),
+        }
+        .write_to(&mut out)
+        .unwrap();
+        fs::write(path, out).unwrap();
+    }
+
+    fn app_hash() -> [u8; 32] {
+        *blake3::hash(b"#!/bin/sh\necho hello\n").as_bytes()
+    }
+
+    #[test]
+    fn lists_every_entry_and_verifies_files() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("pkg");
+        pack(&path, app_hash());
+
+        let entries = list_contents(&path, true).unwrap();
+        let paths: Vec<_> = entries.iter().map(|e| e.path.clone()).collect();
+        assert_eq!(
+            paths,
+            [
+                PathBuf::from("bin"),
+                PathBuf::from("bin/app"),
+                PathBuf::from("app")
+            ]
+        );
+        assert_eq!(entries[1].kind, ContentKind::File);
+        assert_eq!(entries[1].size, 21);
+        assert_eq!(entries[1].mode, Some(0o755));
+        assert_eq!(entries[1].verified, Some(true));
+        assert_eq!(entries[2].kind, ContentKind::Symlink("bin/app".into()));
+        assert_eq!(entries[2].verified, None);
+
+        let unverified = list_contents(&path, false).unwrap();
+        assert_eq!(unverified[1].verified, None);
+    }
+
+    #[test]
+    fn a_file_that_does_not_match_its_hash_fails_verification() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("pkg");
+        pack(&path, [7; 32]);
+
+        let entries = list_contents(&path, true).unwrap();
+        assert_eq!(entries[1].verified, Some(false));
+
+        let err = extract_contents(&path, dir.path().join("out"), &[])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("bin/app"), "{err}");
+    }
+
+    #[test]
+    fn extracts_selected_entries_or_the_whole_tree() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("pkg");
+        pack(&path, app_hash());
+
+        let one = dir.path().join("one");
+        assert_eq!(
+            extract_contents(&path, &one, &["/bin/app".into()]).unwrap(),
+            1
+        );
+        assert_eq!(
+            fs::read(one.join("bin/app")).unwrap(),
+            b"#!/bin/sh\necho hello\n"
+        );
+        assert!(!one.join("app").exists());
+        assert!(extract_contents(&path, &one, &["missing".into()]).is_err());
+
+        let all = dir.path().join("all");
+        assert_eq!(extract_contents(&path, &all, &[]).unwrap(), 3);
+        assert_eq!(
+            fs::read_link(all.join("app")).unwrap(),
+            PathBuf::from("bin/app")
+        );
+        assert_eq!(
+            fs::metadata(all.join("bin/app"))
+                .unwrap()
+                .permissions()
+                .mode()
+                & 0o777,
+            0o755
+        );
+    }
+}
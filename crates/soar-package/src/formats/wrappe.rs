@@ -1,9 +1,324 @@
 //! Wrappe format handling.
+//!
+//! Wrappe appends a compressed payload to a small runner, and ends the file
+//! with the settings the runner starts with. Working back from the end, a
+//! package holds:
+//!
+//! | Part | Size |
+//! |------|------|
+//! | file contents, each a zstd frame | `payload_size` |
+//! | zstd dictionary, if any | `dictionary_size` |
+//! | directory sections | `directory_sections` x 132 |
+//! | file sections | `file_sections` x 185 |
+//! | symlink sections | `symlink_sections` x 261 |
+//! | payload header | 48 |
+//! | starter info | 801 |
+//!
+//! The layout changed between wrappe releases, and the starter info records
+//! which one a package uses in its `wrappe_format` byte. Only
+//! [`WRAPPE_FORMAT`] is read; any other is refused rather than misread.
 
-use std::path::{Path, PathBuf};
+use std::{
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    os::unix::fs::{symlink, PermissionsExt},
+    path::{Path, PathBuf},
+};
+
+use soar_utils::path::is_safe_component;
 
 use super::common::create_portable_link;
-use crate::error::{PackageError, Result};
+use crate::{
+    contents::{entry_dest, prepare_dest, ContentEntry, ContentKind},
+    error::{ErrorContext, PackageError, Result},
+};
+
+/// The payload layout this module reads.
+pub const WRAPPE_FORMAT: u8 = 201;
+
+/// Size of the starter info ending every package.
+const STARTER_INFO_SIZE: u64 = 801;
+
+/// Where `wrappe_format` sits in the starter info, after the signature, four
+/// flags, the 16-byte uid and four more flags.
+const FORMAT_OFFSET: usize = 32;
+
+/// Size of the payload header: six little-endian `u64`s.
+const HEADER_SIZE: u64 = 48;
+
+/// Size of the NUL-padded names in every section.
+const NAME_SIZE: usize = 128;
+
+/// `name`, then the index of the parent directory as a `u32`.
+const DIRECTORY_SECTION_SIZE: usize = NAME_SIZE + 4;
+
+/// Position, compressed and decompressed size, access and modification
+/// times as seconds and nanoseconds, mode, a read-only flag, parent and name.
+const FILE_SECTION_SIZE: usize = 8 * 3 + (8 + 4) * 2 + 4 + 1 + 4 + NAME_SIZE;
+
+/// Parent, a kind byte, the link's name and its target.
+const SYMLINK_SECTION_SIZE: usize = 4 + 1 + NAME_SIZE * 2;
+
+fn invalid<S: Into<String>>(msg: S) -> PackageError {
+    PackageError::Custom(msg.into())
+}
+
+/// Where a file's contents are in the payload.
+struct FileData {
+    position: u64,
+    size: u64,
+    decompressed_size: u64,
+}
+
+/// An entry of the payload, with its path inside the bundle.
+struct WrappeEntry {
+    path: PathBuf,
+    kind: ContentKind,
+    mode: Option<u32>,
+    data: Option<FileData>,
+}
+
+/// The sections of a Wrappe payload, read from the end of the file.
+struct Payload {
+    /// Where the file contents start in the package.
+    data_offset: u64,
+    dictionary: Vec<u8>,
+    entries: Vec<WrappeEntry>,
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+/// A NUL-padded name, which has to be one path component.
+fn name_at(bytes: &[u8], offset: usize) -> Result<String> {
+    let raw = &bytes[offset..offset + NAME_SIZE];
+    let end = raw.iter().position(|&b| b == 0).unwrap_or(NAME_SIZE);
+    String::from_utf8(raw[..end].to_vec()).map_err(|_| invalid("Wrappe entry name is not UTF-8"))
+}
+
+fn component(name: String) -> Result<String> {
+    if is_safe_component(&name) {
+        Ok(name)
+    } else {
+        Err(invalid(format!("Wrappe entry name {name:?} is not a file name")))
+    }
+}
+
+/// Reads the sections of the Wrappe package in `file`.
+fn read_payload(file: &mut File) -> Result<Payload> {
+    let file_size = file
+        .metadata()
+        .with_context(|| "reading Wrappe file metadata".to_string())?
+        .len();
+    let trailer = STARTER_INFO_SIZE + HEADER_SIZE;
+    if file_size < trailer {
+        return Err(invalid("file too small for a Wrappe payload"));
+    }
+
+    file.seek(SeekFrom::Start(file_size - trailer))
+        .with_context(|| "seeking to Wrappe payload header".to_string())?;
+    let mut tail = vec![0u8; trailer as usize];
+    file.read_exact(&mut tail)
+        .with_context(|| "reading Wrappe payload header".to_string())?;
+    let (header, info) = tail.split_at(HEADER_SIZE as usize);
+
+    let format = info[FORMAT_OFFSET];
+    if format != WRAPPE_FORMAT {
+        return Err(invalid(format!(
+            "Wrappe payload format {format} is not supported, only {WRAPPE_FORMAT} is"
+        )));
+    }
+
+    let directories = u64_at(header, 0);
+    let files = u64_at(header, 8);
+    let symlinks = u64_at(header, 16);
+    let dictionary_size = u64_at(header, 24);
+    let payload_size = u64_at(header, 40);
+
+    let sections_size = directories
+        .checked_mul(DIRECTORY_SECTION_SIZE as u64)
+        .zip(files.checked_mul(FILE_SECTION_SIZE as u64))
+        .zip(symlinks.checked_mul(SYMLINK_SECTION_SIZE as u64))
+        .and_then(|((d, f), s)| d.checked_add(f)?.checked_add(s));
+    let data_offset = sections_size
+        .and_then(|size| size.checked_add(dictionary_size)?.checked_add(payload_size))
+        .and_then(|size| (file_size - trailer).checked_sub(size));
+    let (Some(sections_size), Some(data_offset)) = (sections_size, data_offset) else {
+        return Err(invalid("Wrappe payload header does not fit the file"));
+    };
+
+    file.seek(SeekFrom::Start(data_offset + payload_size))
+        .with_context(|| "seeking to Wrappe sections".to_string())?;
+    let mut dictionary = vec![0u8; dictionary_size as usize];
+    file.read_exact(&mut dictionary)
+        .with_context(|| "reading Wrappe dictionary".to_string())?;
+    let mut sections = vec![0u8; sections_size as usize];
+    file.read_exact(&mut sections)
+        .with_context(|| "reading Wrappe sections".to_string())?;
+
+    // Directory 0 is the unpack root. Every other names a parent listed
+    // before it, so a path is always built from paths already known.
+    let mut dirs: Vec<PathBuf> = Vec::with_capacity(directories as usize);
+    let mut entries = Vec::new();
+    let (dir_sections, rest) = sections.split_at(directories as usize * DIRECTORY_SECTION_SIZE);
+    for (index, section) in dir_sections.chunks(DIRECTORY_SECTION_SIZE).enumerate() {
+        if index == 0 {
+            dirs.push(PathBuf::new());
+            continue;
+        }
+        let parent = u32_at(section, NAME_SIZE) as usize;
+        let parent = dirs
+            .get(parent)
+            .filter(|_| parent < index)
+            .ok_or_else(|| invalid("Wrappe directory has no valid parent"))?;
+        let path = parent.join(component(name_at(section, 0)?)?);
+        dirs.push(path.clone());
+        entries.push(WrappeEntry {
+            path,
+            kind: ContentKind::Directory,
+            mode: None,
+            data: None,
+        });
+    }
+    let parent_of = |section: &[u8], offset: usize| {
+        dirs.get(u32_at(section, offset) as usize)
+            .ok_or_else(|| invalid("Wrappe entry has no valid parent"))
+    };
+
+    let (file_sections, link_sections) = rest.split_at(files as usize * FILE_SECTION_SIZE);
+    for section in file_sections.chunks(FILE_SECTION_SIZE) {
+        let data = FileData {
+            position: u64_at(section, 0),
+            size: u64_at(section, 8),
+            decompressed_size: u64_at(section, 16),
+        };
+        if data
+            .position
+            .checked_add(data.size)
+            .is_none_or(|end| end > payload_size)
+        {
+            return Err(invalid("Wrappe file lies outside the payload"));
+        }
+        let mode = u32_at(section, 48);
+        let parent = parent_of(section, 53)?;
+        entries.push(WrappeEntry {
+            path: parent.join(component(name_at(section, 57)?)?),
+            kind: ContentKind::File,
+            mode: Some(mode & 0o7777),
+            data: Some(data),
+        });
+    }
+
+    for section in link_sections.chunks(SYMLINK_SECTION_SIZE) {
+        let parent = parent_of(section, 0)?;
+        let target = name_at(section, 5 + NAME_SIZE)?;
+        entries.push(WrappeEntry {
+            path: parent.join(component(name_at(section, 5)?)?),
+            kind: ContentKind::Symlink(target.into()),
+            mode: None,
+            data: None,
+        });
+    }
+
+    Ok(Payload {
+        data_offset,
+        dictionary,
+        entries,
+    })
+}
+
+/// Decompresses a file's contents, capped at the size its section records.
+fn read_file(file: &mut File, payload: &Payload, data: &FileData) -> Result<Vec<u8>> {
+    file.seek(SeekFrom::Start(payload.data_offset + data.position))
+        .with_context(|| "seeking to Wrappe file".to_string())?;
+    let mut compressed = vec![0u8; data.size as usize];
+    file.read_exact(&mut compressed)
+        .with_context(|| "reading Wrappe file".to_string())?;
+
+    let cap = data.decompressed_size as usize;
+    let decompressed = if payload.dictionary.is_empty() {
+        zstd::bulk::decompress(&compressed, cap)
+    } else {
+        zstd::bulk::Decompressor::with_dictionary(&payload.dictionary)
+            .and_then(|mut dec| dec.decompress(&compressed, cap))
+    }
+    .map_err(|e| invalid(format!("Wrappe file decompression failed: {e}")))?;
+    if decompressed.len() != cap {
+        return Err(invalid("Wrappe file size mismatch"));
+    }
+    Ok(decompressed)
+}
+
+/// Lists every entry of the Wrappe package at `path`.
+///
+/// Wrappe records no checksum a listing could check, so nothing is marked as
+/// verified.
+pub(crate) fn list_wrappe(path: &Path) -> Result<Vec<ContentEntry>> {
+    let mut file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let payload = read_payload(&mut file)?;
+    Ok(payload
+        .entries
+        .into_iter()
+        .map(|entry| {
+            ContentEntry {
+                size: entry.data.map_or(0, |d| d.decompressed_size),
+                path: entry.path,
+                kind: entry.kind,
+                mode: entry.mode,
+                verified: None,
+            }
+        })
+        .collect())
+}
+
+/// Extracts the entries of the Wrappe package at `path` that `wanted` selects
+/// below `root`.
+///
+/// Returns how many entries were written.
+pub(crate) fn extract_wrappe(
+    path: &Path,
+    root: &Path,
+    wanted: &dyn Fn(&Path) -> bool,
+) -> Result<usize> {
+    let mut file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let payload = read_payload(&mut file)?;
+
+    let mut count = 0;
+    for entry in &payload.entries {
+        if !wanted(&entry.path) {
+            continue;
+        }
+        let Some(dest) = entry_dest(root, &entry.path)? else {
+            continue;
+        };
+        prepare_dest(&dest)?;
+        match (&entry.kind, &entry.data) {
+            (ContentKind::File, Some(data)) => {
+                let contents = read_file(&mut file, &payload, data)
+                    .map_err(|e| invalid(format!("{}: {e}", entry.path.display())))?;
+                fs::write(&dest, contents)
+                    .with_context(|| format!("writing {}", dest.display()))?;
+                let mode = entry.mode.unwrap_or(0o644) & 0o777;
+                fs::set_permissions(&dest, fs::Permissions::from_mode(mode))
+                    .with_context(|| format!("setting permissions on {}", dest.display()))?;
+            }
+            (ContentKind::Symlink(target), _) => {
+                symlink(target, &dest).with_context(|| format!("linking {}", dest.display()))?;
+            }
+            _ => {
+                fs::create_dir_all(&dest)
+                    .with_context(|| format!("creating {}", dest.display()))?;
+            }
+        }
+        count += 1;
+    }
+    Ok(count)
+}
 
 /// Sets up portable directory for a Wrappe package.
 ///
@@ -42,3 +357,113 @@ pub fn setup_wrappe_portable_dir<P: AsRef<Path>>(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use tempfile::tempdir;
+
+    use super::*;
+    use crate::{
+        contents::{extract_contents, list_contents},
+        formats::WRAPPE_MAGIC_BYTES,
+    };
+
+    fn name(value: &str) -> Vec<u8> {
+        let mut bytes = value.as_bytes().to_vec();
+        bytes.resize(NAME_SIZE, 0);
+        bytes
+    }
+
+    /// Packs `bin/app` and an `app` link to it behind a stand-in runner.
+    fn pack(path: &Path, format: u8) {
+        let content = b"#!/bin/sh\necho hello\n";
+        let compressed = zstd::bulk::compress(content, 3).unwrap();
+
+        let mut out = b"\x7fELF runner".to_vec();
+        out.extend_from_slice(&compressed);
+
+        // Directories: the root, then bin/.
+        out.extend(name(""));
+        out.extend(0u32.to_le_bytes());
+        out.extend(name("bin"));
+        out.extend(0u32.to_le_bytes());
+
+        out.extend(0u64.to_le_bytes());
+        out.extend((compressed.len() as u64).to_le_bytes());
+        out.extend((content.len() as u64).to_le_bytes());
+        out.extend([0u8; 24]);
+        out.extend(0o100755u32.to_le_bytes());
+        out.push(0);
+        out.extend(1u32.to_le_bytes());
+        out.extend(name("app"));
+
+        out.extend(0u32.to_le_bytes());
+        out.push(0);
+        out.extend(name("app"));
+        out.extend(name("bin/app"));
+
+        for value in [2u64, 1, 1, 0, 0, compressed.len() as u64] {
+            out.extend(value.to_le_bytes());
+        }
+
+        let mut info = vec![0u8; STARTER_INFO_SIZE as usize];
+        info[..8].copy_from_slice(&WRAPPE_MAGIC_BYTES);
+        info[FORMAT_OFFSET] = format;
+        out.extend(info);
+        fs::write(path, out).unwrap();
+    }
+
+    #[test]
+    fn lists_every_entry_with_its_size() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("app.wrappe");
+        pack(&path, WRAPPE_FORMAT);
+
+        let entries = list_contents(&path, false).unwrap();
+        let summary: Vec<_> = entries
+            .iter()
+            .map(|e| (e.path.to_str().unwrap(), e.kind.clone(), e.size))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("bin", ContentKind::Directory, 0),
+                ("bin/app", ContentKind::File, 21),
+                ("app", ContentKind::Symlink("bin/app".into()), 0),
+            ]
+        );
+        assert_eq!(entries[1].mode, Some(0o755));
+    }
+
+    #[test]
+    fn extracts_selected_entries_or_the_whole_tree() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("app.wrappe");
+        pack(&path, WRAPPE_FORMAT);
+
+        let all = dir.path().join("all");
+        assert_eq!(extract_contents(&path, &all, &[]).unwrap(), 3);
+        assert_eq!(
+            fs::read_to_string(all.join("bin/app")).unwrap(),
+            "#!/bin/sh\necho hello\n"
+        );
+        assert_eq!(fs::read_link(all.join("app")).unwrap(), Path::new("bin/app"));
+
+        let some = dir.path().join("some");
+        assert_eq!(
+            extract_contents(&path, &some, &["bin".to_string()]).unwrap(),
+            2
+        );
+        assert!(!some.join("app").is_symlink());
+    }
+
+    #[test]
+    fn another_payload_format_is_refused() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("app.wrappe");
+        pack(&path, WRAPPE_FORMAT + 1);
+
+        let err = list_contents(&path, false).unwrap_err().to_string();
+        assert!(err.contains("not supported"), "{err}");
+    }
+}
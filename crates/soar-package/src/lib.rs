@@ -28,10 +28,14 @@
 //! }
 //! ```
 
+pub mod contents;
+pub mod desktop;
 pub mod error;
 pub mod formats;
+pub mod mime;
 pub mod traits;
 
+pub use contents::{extract_contents, list_contents, ContentEntry, ContentKind};
 pub use error::{ErrorContext, PackageError, Result};
 pub use formats::{
     common::integrate_package, get_file_type, PackageFormat, APPIMAGE_MAGIC_BYTES, ELF_MAGIC_BYTES,
@@ -0,0 +1,433 @@
+//! File associations, URL schemes and AppStream metadata.
+//!
+//! Linking a desktop entry is not enough for its `MimeType=` to take effect:
+//! the desktop database has to index it, MIME types a package defines itself
+//! have to be compiled into the shared MIME database, and AppStream metainfo
+//! has to sit where software centres look. URL schemes ride along as
+//! `x-scheme-handler/<scheme>` types.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use soar_utils::{
+    fs::create_symlink,
+    path::{metainfo_dir, mime_dir},
+};
+use tracing::{debug, trace};
+
+use crate::{
+    desktop::{DesktopEntry, MAIN_GROUP},
+    error::{ErrorContext, Result},
+};
+
+const DEFAULT_APPLICATIONS: &str = "Default Applications";
+const ADDED_ASSOCIATIONS: &str = "Added Associations";
+
+/// Whether `path` is a MIME type definition, `mime/packages/<name>.xml`.
+pub fn is_mime_package(path: &Path) -> bool {
+    let packages = path.parent();
+    path.extension().is_some_and(|ext| ext == "xml")
+        && packages
+            .and_then(Path::file_name)
+            .is_some_and(|n| n == "packages")
+        && packages
+            .and_then(Path::parent)
+            .and_then(Path::file_name)
+            .is_some_and(|n| n == "mime")
+}
+
+/// The component id of an AppStream file, from a name such as
+/// `org.example.App.metainfo.xml` or the older `app.appdata.xml`.
+pub fn metainfo_id(path: &Path) -> Option<&str> {
+    let name = path.file_name()?.to_str()?;
+    name.strip_suffix(".metainfo.xml")
+        .or_else(|| name.strip_suffix(".appdata.xml"))
+        .filter(|id| !id.is_empty())
+}
+
+/// Links a MIME type definition into the shared MIME database as
+/// `mime/packages/{name}-soar.xml`.
+///
+/// The database only sees it once [`refresh_mime_database`] has run.
+pub fn register_mime_package(real_path: &Path, system_mode: bool) -> Result<PathBuf> {
+    let stem = real_path.file_stem().unwrap_or_default().to_string_lossy();
+    let final_path = mime_dir(system_mode)
+        .join("packages")
+        .join(format!("{stem}-soar.xml"));
+    replace_link(real_path, &final_path)?;
+    debug!(mime = %final_path.display(), "MIME package symlink created");
+    Ok(final_path)
+}
+
+/// Links AppStream metainfo into the metainfo directory as
+/// `{id}-soar.metainfo.xml`, whichever of the two names it shipped under.
+pub fn register_metainfo(real_path: &Path, system_mode: bool) -> Result<Option<PathBuf>> {
+    let Some(id) = metainfo_id(real_path) else {
+        return Ok(None);
+    };
+    let final_path = metainfo_dir(system_mode).join(format!("{id}-soar.metainfo.xml"));
+    replace_link(real_path, &final_path)?;
+    debug!(metainfo = %final_path.display(), "metainfo symlink created");
+    Ok(Some(final_path))
+}
+
+fn replace_link(real_path: &Path, final_path: &Path) -> Result<()> {
+    if final_path.is_symlink() {
+        fs::remove_file(final_path)
+            .with_context(|| format!("removing existing symlink at {}", final_path.display()))?;
+    }
+    create_symlink(real_path, final_path)?;
+    Ok(())
+}
+
+/// Rebuilds the shared MIME database after definitions were added or removed.
+///
+/// Without `update-mime-database` the compiled database is left as it was:
+/// it then lacks the new types, which only costs the association.
+pub fn refresh_mime_database(system_mode: bool) {
+    let dir = mime_dir(system_mode);
+    if !dir.join("packages").is_dir() {
+        return;
+    }
+    if !run_quietly(Command::new("update-mime-database").arg(&dir)) {
+        debug!(dir = %dir.display(), "update-mime-database unavailable, MIME cache not rebuilt");
+    }
+}
+
+/// Rebuilds the `mimeinfo.cache` of a desktop entry directory.
+///
+/// Where `update-desktop-database` is missing, an existing cache is written
+/// here instead, since a stale one would keep pointing at removed entries and
+/// hide new ones. Without a cache, launchers read the entries themselves.
+pub fn refresh_desktop_database(desktop_dir: &Path) {
+    if !desktop_dir.is_dir() {
+        return;
+    }
+    if run_quietly(
+        Command::new("update-desktop-database")
+            .arg("-q")
+            .arg(desktop_dir),
+    ) {
+        return;
+    }
+
+    let cache = desktop_dir.join("mimeinfo.cache");
+    if cache.exists() {
+        trace!(cache = %cache.display(), "writing mimeinfo.cache directly");
+        if let Err(err) = fs::write(&cache, mimeinfo_cache(desktop_dir)) {
+            debug!(cache = %cache.display(), "failed to write mimeinfo.cache: {err}");
+        }
+    }
+}
+
+fn run_quietly(command: &mut Command) -> bool {
+    command
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// The contents `update-desktop-database` would write for `dir`.
+fn mimeinfo_cache(dir: &Path) -> String {
+    let mut types: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    collect_mime_types(dir, "", &mut types);
+
+    let mut out = String::from("[MIME Cache]\n");
+    for (mime, ids) in types {
+        out.push_str(&format!("{mime}={};\n", ids.join(";")));
+    }
+    out
+}
+
+/// Entries in subdirectories get ids joined with `-`, as the desktop entry
+/// specification has it.
+fn collect_mime_types(dir: &Path, prefix: &str, types: &mut BTreeMap<String, Vec<String>>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+    paths.sort();
+
+    for path in paths {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if path.is_dir() {
+            collect_mime_types(&path, &format!("{prefix}{name}-"), types);
+        } else if path.extension().is_some_and(|ext| ext == "desktop") {
+            let Ok(content) = fs::read_to_string(&path) else {
+                continue;
+            };
+            for mime in desktop_mime_types(&content) {
+                types
+                    .entry(mime)
+                    .or_default()
+                    .push(format!("{prefix}{name}"));
+            }
+        }
+    }
+}
+
+/// The MIME types, URL schemes included, a desktop entry says it opens.
+pub fn desktop_mime_types(content: &str) -> Vec<String> {
+    DesktopEntry::parse(content)
+        .map(|entry| entry.get_list(MAIN_GROUP, "MimeType"))
+        .unwrap_or_default()
+}
+
+/// Whether `mime` has the `type/subtype` shape, so nothing else can be
+/// written into `mimeapps.list` under its name.
+pub fn is_valid_mime_type(mime: &str) -> bool {
+    let valid = |part: &str| {
+        !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "!#$&^_.+-".contains(c))
+    };
+    mime.split_once('/')
+        .is_some_and(|(kind, subtype)| valid(kind) && valid(subtype))
+}
+
+/// Makes `desktop_id` the default application for `mime` in the
+/// `mimeapps.list` at `list`, and lists it first among the associations.
+pub fn set_default(list: &Path, mime: &str, desktop_id: &str) -> Result<()> {
+    let content = fs::read_to_string(list).unwrap_or_default();
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+
+    set_key(
+        &mut lines,
+        DEFAULT_APPLICATIONS,
+        mime,
+        desktop_id.to_string(),
+    );
+    let associations = get_key(&lines, ADDED_ASSOCIATIONS, mime)
+        .map(|value| {
+            value
+                .split(';')
+                .filter(|id| !id.is_empty() && *id != desktop_id)
+                .fold(format!("{desktop_id};"), |acc, id| format!("{acc}{id};"))
+        })
+        .unwrap_or_else(|| format!("{desktop_id};"));
+    set_key(&mut lines, ADDED_ASSOCIATIONS, mime, associations);
+
+    write_list(list, &lines)
+}
+
+/// Drops every default and association naming one of `desktop_ids`, so a
+/// removed package does not stay the handler of anything.
+pub fn forget_defaults(list: &Path, desktop_ids: &[String]) -> Result<()> {
+    if desktop_ids.is_empty() {
+        return Ok(());
+    }
+    let Ok(content) = fs::read_to_string(list) else {
+        return Ok(());
+    };
+
+    let mut changed = false;
+    let mut group = String::new();
+    let mut lines = Vec::new();
+    for line in content.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with('[') {
+            group = trimmed.trim_matches(['[', ']']).to_string();
+        } else if group == DEFAULT_APPLICATIONS || group == ADDED_ASSOCIATIONS {
+            if let Some((key, value)) = line.split_once('=') {
+                let kept: Vec<&str> = value
+                    .split(';')
+                    .filter(|id| !id.is_empty() && !desktop_ids.iter().any(|d| d == id))
+                    .collect();
+                if kept.len() != value.split(';').filter(|id| !id.is_empty()).count() {
+                    changed = true;
+                    if !kept.is_empty() {
+                        let tail = if value.ends_with(';') { ";" } else { "" };
+                        lines.push(format!("{key}={}{tail}", kept.join(";")));
+                    }
+                    continue;
+                }
+            }
+        }
+        lines.push(line.to_string());
+    }
+
+    if changed {
+        write_list(list, &lines)?;
+    }
+    Ok(())
+}
+
+fn write_list(list: &Path, lines: &[String]) -> Result<()> {
+    if let Some(parent) = list.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("creating directory {}", parent.display()))?;
+    }
+    let mut content = lines.join("\n");
+    content.push('\n');
+    fs::write(list, content).with_context(|| format!("writing {}", list.display()))
+}
+
+/// The line range of `[group]`'s keys, header excluded.
+fn group_range(lines: &[String], group: &str) -> Option<(usize, usize)> {
+    let header = format!("[{group}]");
+    let start = lines.iter().position(|l| l.trim() == header)? + 1;
+    let end = lines[start..]
+        .iter()
+        .position(|l| l.trim().starts_with('['))
+        .map_or(lines.len(), |i| start + i);
+    Some((start, end))
+}
+
+fn get_key<'a>(lines: &'a [String], group: &str, key: &str) -> Option<&'a str> {
+    let (start, end) = group_range(lines, group)?;
+    lines[start..end].iter().find_map(|line| {
+        line.split_once('=')
+            .filter(|(k, _)| k.trim() == key)
+            .map(|(_, v)| v.trim())
+    })
+}
+
+fn set_key(lines: &mut Vec<String>, group: &str, key: &str, value: String) {
+    let entry = format!("{key}={value}");
+    let Some((start, end)) = group_range(lines, group) else {
+        if lines.last().is_some_and(|l| !l.trim().is_empty()) {
+            lines.push(String::new());
+        }
+        lines.push(format!("[{group}]"));
+        lines.push(entry);
+        return;
+    };
+
+    let existing = (start..end).find(|&i| {
+        lines[i]
+            .split_once('=')
+            .is_some_and(|(k, _)| k.trim() == key)
+    });
+    match existing {
+        Some(i) => lines[i] = entry,
+        None => {
+            // After the group's last key, ahead of any blank separator.
+            let at = (start..end)
+                .rev()
+                .find(|&i| !lines[i].trim().is_empty())
+                .map_or(start, |i| i + 1);
+            lines.insert(at, entry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognises_mime_packages_and_metainfo() {
+        assert!(is_mime_package(Path::new(
+            "/pkg/share/mime/packages/foo.xml"
+        )));
+        assert!(!is_mime_package(Path::new(
+            "/pkg/share/foo/packages/foo.xml"
+        )));
+        assert!(!is_mime_package(Path::new("/pkg/share/mime/foo.xml")));
+
+        assert_eq!(
+            metainfo_id(Path::new("/pkg/org.example.App.metainfo.xml")),
+            Some("org.example.App")
+        );
+        assert_eq!(metainfo_id(Path::new("/pkg/foo.appdata.xml")), Some("foo"));
+        assert_eq!(metainfo_id(Path::new("/pkg/foo.xml")), None);
+    }
+
+    #[test]
+    fn reads_mime_types_from_the_desktop_entry_group_only() {
+        let content = "[Desktop Entry]\nName=Foo\nMimeType=text/plain;x-scheme-handler/foo;\n\n[Desktop Action new]\nMimeType=image/png;\n";
+        assert_eq!(
+            desktop_mime_types(content),
+            ["text/plain", "x-scheme-handler/foo"]
+        );
+        assert!(desktop_mime_types("[Desktop Entry]\nName=Foo\n").is_empty());
+    }
+
+    #[test]
+    fn accepts_only_type_and_subtype() {
+        assert!(is_valid_mime_type("text/plain"));
+        assert!(is_valid_mime_type("x-scheme-handler/soar"));
+        assert!(is_valid_mime_type("application/vnd.ms-excel"));
+        assert!(!is_valid_mime_type("text"));
+        assert!(!is_valid_mime_type("text/"));
+        assert!(!is_valid_mime_type("text/plain\n[Default Applications]"));
+        assert!(!is_valid_mime_type("a/b=c"));
+    }
+
+    #[test]
+    fn setting_a_default_keeps_the_rest_of_the_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let list = dir.path().join("mimeapps.list");
+        fs::write(
+            &list,
+            "[Default Applications]\ntext/plain=vim.desktop\nimage/png=feh.desktop\n\n[Added Associations]\ntext/plain=vim.desktop;\n",
+        )
+        .unwrap();
+
+        set_default(&list, "text/plain", "foo-soar.desktop").unwrap();
+        set_default(&list, "x-scheme-handler/foo", "foo-soar.desktop").unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&list).unwrap(),
+            "[Default Applications]\ntext/plain=foo-soar.desktop\nimage/png=feh.desktop\nx-scheme-handler/foo=foo-soar.desktop\n\n[Added Associations]\ntext/plain=foo-soar.desktop;vim.desktop;\nx-scheme-handler/foo=foo-soar.desktop;\n"
+        );
+    }
+
+    #[test]
+    fn setting_a_default_creates_the_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let list = dir.path().join("config/mimeapps.list");
+
+        set_default(&list, "text/plain", "foo-soar.desktop").unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&list).unwrap(),
+            "[Default Applications]\ntext/plain=foo-soar.desktop\n\n[Added Associations]\ntext/plain=foo-soar.desktop;\n"
+        );
+    }
+
+    #[test]
+    fn forgetting_removes_only_the_named_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let list = dir.path().join("mimeapps.list");
+        fs::write(
+            &list,
+            "[Default Applications]\ntext/plain=foo-soar.desktop\nimage/png=feh.desktop\n\n[Added Associations]\ntext/plain=foo-soar.desktop;vim.desktop;\n\n[Removed Associations]\nimage/png=foo-soar.desktop;\n",
+        )
+        .unwrap();
+
+        forget_defaults(&list, &["foo-soar.desktop".to_string()]).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(&list).unwrap(),
+            "[Default Applications]\nimage/png=feh.desktop\n\n[Added Associations]\ntext/plain=vim.desktop;\n\n[Removed Associations]\nimage/png=foo-soar.desktop;\n"
+        );
+    }
+
+    #[test]
+    fn writes_mimeinfo_cache_from_the_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("foo-soar.desktop"),
+            "[Desktop Entry]\nMimeType=text/plain;x-scheme-handler/foo;\n",
+        )
+        .unwrap();
+        fs::create_dir(dir.path().join("wine")).unwrap();
+        fs::write(
+            dir.path().join("wine/notepad.desktop"),
+            "[Desktop Entry]\nMimeType=text/plain;\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            mimeinfo_cache(dir.path()),
+            "[MIME Cache]\ntext/plain=foo-soar.desktop;wine-notepad.desktop;\nx-scheme-handler/foo=foo-soar.desktop;\n"
+        );
+    }
+}
@@ -0,0 +1,348 @@
+//! Incremental metadata sync through deltas.
+//!
+//! A repository that sets `delta_url` publishes a manifest beside its full
+//! metadata. The manifest names the revision the full metadata is at, the
+//! hash of that revision's packages, and deltas that each take the packages
+//! from one revision to the next. A client holding an older revision fetches
+//! only the deltas on its way to the current one.
+//!
+//! Only the manifest is signed. The deltas need no signature of their own:
+//! the caller applies them in a transaction and keeps the result only when it
+//! hashes to the revision hash the signed manifest names.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs,
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+use soar_config::repository::Repository;
+use soar_dl::http_client::SHARED_AGENT;
+use tracing::debug;
+use ureq::http::header::{CACHE_CONTROL, PRAGMA};
+use url::Url;
+
+use crate::{
+    error::{ErrorContext, RegistryError, Result},
+    metadata::{
+        decode_zstd, ensure_remote_scheme_allowed, fetch_signature_text, local_metadata_path,
+        read_local_signature, verify_metadata_signature, MAX_METADATA_SIZE, ZST_MAGIC_BYTES,
+    },
+    package::RemotePackage,
+};
+
+/// Maximum size, in bytes, of a delta manifest.
+pub const MAX_MANIFEST_SIZE: u64 = 1024 * 1024;
+
+/// Most deltas a single sync applies.
+///
+/// A client further behind than this downloads the full metadata, which by
+/// then is likely smaller than the deltas anyway.
+pub const MAX_DELTA_CHAIN: usize = 64;
+
+/// The manifest a repository publishes at its `delta_url`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DeltaManifest {
+    /// The revision the full metadata is at.
+    pub revision: String,
+    /// The hash of the packages at `revision`.
+    pub hash: String,
+    /// The deltas on offer, in any order.
+    #[serde(default)]
+    pub deltas: Vec<DeltaLink>,
+}
+
+/// One delta a manifest offers.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DeltaLink {
+    /// The revision the delta applies to.
+    pub from: String,
+    /// The revision applying it produces.
+    pub to: String,
+    /// Where the delta is, relative to the manifest.
+    pub url: String,
+}
+
+/// Identifies a package within a repository's metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PackageKey {
+    pub pkg_name: String,
+    #[serde(default)]
+    pub pkg_id: Option<String>,
+    #[serde(default)]
+    pub pkg_family: Option<String>,
+    pub version: String,
+}
+
+/// The changes between two metadata revisions.
+///
+/// A changed package is removed and added again, so applying a delta never
+/// needs to merge fields.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct MetadataDelta {
+    pub from: String,
+    pub to: String,
+    #[serde(default)]
+    pub removed: Vec<PackageKey>,
+    #[serde(default)]
+    pub added: Vec<RemotePackage>,
+}
+
+/// A metadata revision and the hash its packages must have.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Revision {
+    pub id: String,
+    pub hash: String,
+}
+
+impl DeltaManifest {
+    /// The revision this manifest describes.
+    pub fn current(&self) -> Revision {
+        Revision {
+            id: self.revision.clone(),
+            hash: self.hash.clone(),
+        }
+    }
+
+    /// The shortest run of deltas leading from `from` to the manifest's
+    /// revision, in the order they apply.
+    ///
+    /// Returns `None` when no run of at most [`MAX_DELTA_CHAIN`] deltas gets
+    /// there, and an empty run when `from` is already current.
+    pub fn chain_from(&self, from: &str) -> Option<Vec<&DeltaLink>> {
+        let mut edges: HashMap<&str, Vec<&DeltaLink>> = HashMap::new();
+        for link in &self.deltas {
+            edges.entry(link.from.as_str()).or_default().push(link);
+        }
+
+        let mut seen = HashSet::from([from]);
+        let mut queue = VecDeque::from([(from, Vec::new())]);
+        while let Some((at, chain)) = queue.pop_front() {
+            if at == self.revision {
+                return Some(chain);
+            }
+            if chain.len() == MAX_DELTA_CHAIN {
+                continue;
+            }
+            for link in edges.get(at).into_iter().flatten() {
+                if seen.insert(link.to.as_str()) {
+                    let mut next = chain.clone();
+                    next.push(*link);
+                    queue.push_back((link.to.as_str(), next));
+                }
+            }
+        }
+        None
+    }
+}
+
+impl MetadataDelta {
+    /// Parse a delta, decompressing it first if it is zstd.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() >= 4 && bytes[..4] == ZST_MAGIC_BYTES {
+            Ok(serde_json::from_slice(&decode_zstd(bytes)?)?)
+        } else {
+            Ok(serde_json::from_slice(bytes)?)
+        }
+    }
+}
+
+/// Fetches and verifies the delta manifest a repository publishes.
+///
+/// The manifest is checked against the repository's signature the same way
+/// the full metadata is, from `<delta_url>.sig`.
+pub fn fetch_manifest(repo: &Repository, delta_url: &str) -> Result<DeltaManifest> {
+    let content = read_source(repo, delta_url, MAX_MANIFEST_SIZE)?;
+
+    match local_metadata_path(delta_url) {
+        Some(path) => verify_metadata_signature(repo, &content, || read_local_signature(&path))?,
+        None => {
+            verify_metadata_signature(repo, &content, || {
+                fetch_signature_text(&format!("{delta_url}.sig"))
+            })?
+        }
+    }
+
+    Ok(serde_json::from_slice(&content)?)
+}
+
+/// Fetches the deltas of a chain, checking each is the one its link names.
+///
+/// Gives up once the deltas together outgrow [`MAX_METADATA_SIZE`], the
+/// point past which downloading the full metadata is no worse.
+pub fn fetch_deltas(
+    repo: &Repository,
+    delta_url: &str,
+    chain: &[&DeltaLink],
+) -> Result<Vec<MetadataDelta>> {
+    let mut total = 0u64;
+    let mut deltas = Vec::with_capacity(chain.len());
+
+    for link in chain {
+        let url = resolve_link(delta_url, &link.url)?;
+        debug!(repo_name = repo.name, url, "fetching metadata delta");
+
+        let content = read_source(repo, &url, MAX_METADATA_SIZE - total)?;
+        total += content.len() as u64;
+
+        let delta = MetadataDelta::parse(&content)?;
+        if delta.from != link.from || delta.to != link.to {
+            return Err(RegistryError::InvalidDelta(format!(
+                "{url} goes from {} to {}, but the manifest says {} to {}",
+                delta.from, delta.to, link.from, link.to
+            )));
+        }
+        deltas.push(delta);
+    }
+
+    Ok(deltas)
+}
+
+/// Resolves a link from the manifest against the manifest's own location.
+fn resolve_link(base: &str, link: &str) -> Result<String> {
+    if let Some(path) = local_metadata_path(base) {
+        if local_metadata_path(link).is_some() {
+            return Ok(link.to_string());
+        }
+        let dir = path.parent().map(PathBuf::from).unwrap_or_default();
+        return Ok(dir.join(link).to_string_lossy().into_owned());
+    }
+
+    let base = Url::parse(base).map_err(|err| RegistryError::InvalidUrl(err.to_string()))?;
+    base.join(link)
+        .map(String::from)
+        .map_err(|err| RegistryError::InvalidUrl(err.to_string()))
+}
+
+/// Reads a file published by a repository, from disk or over HTTP.
+fn read_source(repo: &Repository, url: &str, limit: u64) -> Result<Vec<u8>> {
+    if let Some(path) = local_metadata_path(url) {
+        let len = fs::metadata(&path)
+            .with_context(|| format!("reading file metadata from {}", path.display()))?
+            .len();
+        if len > limit {
+            return Err(RegistryError::MetadataTooLarge {
+                limit,
+            });
+        }
+        return fs::read(&path).with_context(|| format!("reading {}", path.display()));
+    }
+
+    let parsed = Url::parse(url).map_err(|err| RegistryError::InvalidUrl(err.to_string()))?;
+    ensure_remote_scheme_allowed(url, parsed.scheme(), repo.signature_verification())?;
+
+    let resp = SHARED_AGENT
+        .get(url)
+        .header(CACHE_CONTROL, "no-cache")
+        .header(PRAGMA, "no-cache")
+        .call()
+        .map_err(|err| RegistryError::FailedToFetchRemote(err.to_string()))?;
+
+    if !resp.status().is_success() {
+        return Err(RegistryError::FailedToFetchRemote(format!(
+            "{url} [{}]",
+            resp.status()
+        )));
+    }
+
+    Ok(resp
+        .into_body()
+        .into_with_config()
+        .limit(limit)
+        .read_to_vec()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(from: &str, to: &str) -> DeltaLink {
+        DeltaLink {
+            from: from.to_string(),
+            to: to.to_string(),
+            url: format!("{from}-{to}.json"),
+        }
+    }
+
+    fn manifest(revision: &str, deltas: Vec<DeltaLink>) -> DeltaManifest {
+        DeltaManifest {
+            revision: revision.to_string(),
+            hash: "hash".to_string(),
+            deltas,
+        }
+    }
+
+    fn route(chain: Option<Vec<&DeltaLink>>) -> Option<Vec<String>> {
+        chain.map(|links| links.iter().map(|l| l.to.clone()).collect())
+    }
+
+    #[test]
+    fn chain_follows_links_to_the_current_revision() {
+        let m = manifest("r3", vec![link("r2", "r3"), link("r1", "r2")]);
+        assert_eq!(
+            route(m.chain_from("r1")),
+            Some(vec!["r2".to_string(), "r3".to_string()])
+        );
+        assert_eq!(route(m.chain_from("r3")), Some(vec![]));
+    }
+
+    #[test]
+    fn chain_prefers_the_shortest_route() {
+        let m = manifest(
+            "r3",
+            vec![link("r1", "r2"), link("r2", "r3"), link("r1", "r3")],
+        );
+        assert_eq!(route(m.chain_from("r1")), Some(vec!["r3".to_string()]));
+    }
+
+    #[test]
+    fn chain_is_none_without_a_route() {
+        let m = manifest("r3", vec![link("r1", "r2"), link("r2", "r1")]);
+        assert!(m.chain_from("r1").is_none());
+        assert!(m.chain_from("r0").is_none());
+    }
+
+    #[test]
+    fn chain_is_capped() {
+        let links = (0..=MAX_DELTA_CHAIN)
+            .map(|i| link(&format!("r{i}"), &format!("r{}", i + 1)))
+            .collect();
+        let m = manifest(&format!("r{}", MAX_DELTA_CHAIN + 1), links);
+        assert!(m.chain_from("r0").is_none());
+        assert_eq!(m.chain_from("r1").map(|c| c.len()), Some(MAX_DELTA_CHAIN));
+    }
+
+    #[test]
+    fn links_resolve_against_the_manifest() {
+        assert_eq!(
+            resolve_link("https://example.com/repo/deltas.json", "d/r1-r2.json").unwrap(),
+            "https://example.com/repo/d/r1-r2.json"
+        );
+        assert_eq!(
+            resolve_link(
+                "https://example.com/repo/deltas.json",
+                "https://cdn.example.com/d"
+            )
+            .unwrap(),
+            "https://cdn.example.com/d"
+        );
+        assert_eq!(
+            resolve_link("/srv/repo/deltas.json", "r1-r2.json").unwrap(),
+            "/srv/repo/r1-r2.json"
+        );
+    }
+
+    #[test]
+    fn delta_parses_plain_and_compressed() {
+        let json = br#"{"from":"r1","to":"r2","removed":[{"pkg_name":"a","version":"1"}]}"#;
+        let plain = MetadataDelta::parse(json).unwrap();
+        assert_eq!(plain.to, "r2");
+        assert_eq!(plain.removed[0].pkg_id, None);
+        assert!(plain.added.is_empty());
+
+        let compressed = zstd::encode_all(&json[..], 0).unwrap();
+        let decoded = MetadataDelta::parse(&compressed).unwrap();
+        assert_eq!(decoded.removed, plain.removed);
+    }
+}
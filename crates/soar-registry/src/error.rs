@@ -106,6 +106,15 @@ pub enum RegistryError {
     )]
     MetadataTooLarge { limit: u64 },
 
+    #[error("Unusable metadata delta: {0}")]
+    #[diagnostic(
+        code(soar_registry::invalid_delta),
+        help(
+            "The repository's deltas disagree with its manifest; the full metadata is used instead"
+        )
+    )]
+    InvalidDelta(String),
+
     #[error("{0}")]
     #[diagnostic(code(soar_registry::custom))]
     Custom(String),
@@ -12,6 +12,12 @@
 //! - SQLite databases (`.sdb` files, optionally zstd-compressed)
 //! - JSON files containing package arrays
 //!
+//! A repository can also publish deltas between metadata revisions, so a
+//! sync fetches only what changed.
+//!
+//! Publishers can check an index before shipping it with [`lint_index`],
+//! which reports what a client would skip or fail on.
+//!
 //! # Example
 //!
 //! ```no_run
@@ -19,27 +25,34 @@
 //! use soar_config::repository::Repository;
 //!
 //! async fn sync_repo(repo: &Repository, existing_etag: Option<String>) -> soar_registry::Result<()> {
-//!     if let Some((etag, content)) = fetch_metadata(repo, false, existing_etag).await? {
-//!         match content {
+//!     if let Some(fetched) = fetch_metadata(repo, false, existing_etag, None).await? {
+//!         match fetched.content {
 //!             MetadataContent::SqliteDb(bytes) => {
 //!                 // Write SQLite database to disk
 //!             }
 //!             MetadataContent::Json(packages) => {
 //!                 // Process JSON packages into a database
 //!             }
+//!             MetadataContent::Delta(deltas) => {
+//!                 // Apply the changes to the existing database
+//!             }
 //!         }
 //!     }
 //!     Ok(())
 //! }
 //! ```
 
+pub mod delta;
 pub mod error;
+pub mod lint;
 pub mod metadata;
 pub mod package;
 
+pub use delta::{DeltaManifest, MetadataDelta, PackageKey, Revision};
 pub use error::{ErrorContext, RegistryError, Result};
+pub use lint::{lint_index, lint_packages, read_index_source, LintIssue, LintReport, Severity};
 pub use metadata::{
-    fetch_metadata, parse_index, process_metadata_content, write_metadata_db, MetadataContent,
-    SQLITE_MAGIC_BYTES, SUPPORTED_FORMAT, ZST_MAGIC_BYTES,
+    fetch_metadata, parse_index, process_metadata_content, write_metadata_db, FetchedMetadata,
+    MetadataContent, SQLITE_MAGIC_BYTES, SUPPORTED_FORMAT, ZST_MAGIC_BYTES,
 };
-pub use package::RemotePackage;
+pub use package::{RemoteLaunch, RemotePackage, RemoteSnapshot};
@@ -0,0 +1,732 @@
+//! Validation of a metadata index before it is published.
+//!
+//! [`parse_index`](crate::parse_index) accepts anything that deserializes, and
+//! the import and install paths then skip what they cannot use: a package with
+//! an unsafe name is left out of the database, a file whose target escapes the
+//! package directory is not placed, a checksum in the wrong form fails the
+//! download. Each of those is found by whoever installs the package, long
+//! after the index shipped.
+//!
+//! Linting applies the same rules up front. Every finding carries a JSON
+//! pointer into the index, so a repository's CI can point at the entry to fix.
+
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fs,
+    path::PathBuf,
+};
+
+use serde::Serialize;
+use serde_json::Value;
+use soar_dl::http_client::SHARED_AGENT;
+use soar_utils::{
+    entrypoint,
+    path::{is_safe_component, is_safe_relative},
+    version::compare_versions,
+};
+use url::Url;
+
+use crate::{
+    error::{ErrorContext, RegistryError, Result},
+    metadata::{
+        decode_zstd, local_metadata_path, MAX_METADATA_SIZE, SQLITE_MAGIC_BYTES, SUPPORTED_FORMAT,
+        ZST_MAGIC_BYTES,
+    },
+    package::RemotePackage,
+};
+
+/// What a completion command can generate: completions for the shells an
+/// install links, or a manual page.
+const COMPLETION_KINDS: [&str; 4] = ["bash", "zsh", "fish", "man"];
+
+/// How much a finding matters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Works, but is probably not what the publisher meant.
+    Warning,
+    /// Skipped or failing once a client syncs or installs it.
+    Error,
+}
+
+/// One problem found in an index.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LintIssue {
+    pub severity: Severity,
+    /// JSON pointer (RFC 6901) to the offending value.
+    pub pointer: String,
+    pub message: String,
+}
+
+/// Everything found in one index.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LintReport {
+    /// How many package entries the index holds.
+    pub packages: usize,
+    pub issues: Vec<LintIssue>,
+}
+
+impl LintReport {
+    pub fn errors(&self) -> usize {
+        self.count(Severity::Error)
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.count(Severity::Warning)
+    }
+
+    /// Whether nothing was found.
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == severity)
+            .count()
+    }
+}
+
+/// Reads an index to lint from a file or an http(s) URL.
+///
+/// Nothing is verified here: linting is for the publisher, who checks what
+/// they are about to sign.
+pub fn read_index_source(source: &str) -> Result<Vec<u8>> {
+    if source.starts_with("http://") || source.starts_with("https://") {
+        Url::parse(source).map_err(|err| RegistryError::InvalidUrl(err.to_string()))?;
+        let resp = SHARED_AGENT
+            .get(source)
+            .call()
+            .map_err(|err| RegistryError::FailedToFetchRemote(err.to_string()))?;
+        if !resp.status().is_success() {
+            return Err(RegistryError::FailedToFetchRemote(format!(
+                "{source} [{}]",
+                resp.status()
+            )));
+        }
+        return Ok(resp
+            .into_body()
+            .into_with_config()
+            .limit(MAX_METADATA_SIZE)
+            .read_to_vec()?);
+    }
+
+    let path = local_metadata_path(source).unwrap_or_else(|| PathBuf::from(source));
+    let len = fs::metadata(&path)
+        .with_context(|| format!("reading file metadata from {}", path.display()))?
+        .len();
+    if len > MAX_METADATA_SIZE {
+        return Err(RegistryError::MetadataTooLarge {
+            limit: MAX_METADATA_SIZE,
+        });
+    }
+    fs::read(&path).with_context(|| format!("reading {}", path.display()))
+}
+
+/// Lints a JSON index in either shape, zstd-compressed or not.
+///
+/// An entry that does not deserialize is reported rather than failing the
+/// lint, so one run finds every broken entry. Only an index that is not JSON
+/// at all, or is a SQLite database, is an error.
+pub fn lint_index(bytes: &[u8]) -> Result<LintReport> {
+    let decoded;
+    let bytes = if bytes.len() >= 4 && bytes[..4] == ZST_MAGIC_BYTES {
+        decoded = decode_zstd(bytes)?;
+        decoded.as_slice()
+    } else {
+        bytes
+    };
+    if bytes.len() >= 4 && bytes[..4] == SQLITE_MAGIC_BYTES {
+        return Err(RegistryError::Custom(
+            "a SQLite database cannot be linted; lint the JSON it was built from".to_string(),
+        ));
+    }
+
+    let entries = match serde_json::from_slice::<Value>(bytes)? {
+        Value::Array(entries) => entries,
+        Value::Object(mut index) => {
+            let format = index.get("format").and_then(Value::as_u64);
+            if let Some(found) = format.filter(|&f| f > SUPPORTED_FORMAT as u64) {
+                return Err(RegistryError::UnsupportedFormat {
+                    found: found as u32,
+                    supported: SUPPORTED_FORMAT,
+                });
+            }
+            let mut linter = Linter::default();
+            if format.is_none() {
+                linter.error("/format", "missing or not a number");
+            }
+            return Ok(match index.remove("packages") {
+                Some(Value::Array(entries)) => linter.run("/packages", entries),
+                _ => {
+                    linter.error("/packages", "missing or not an array");
+                    linter.finish(0)
+                }
+            });
+        }
+        _ => {
+            return Err(RegistryError::Custom(
+                "an index is an array of packages or an object with `packages`".to_string(),
+            ))
+        }
+    };
+
+    Ok(Linter::default().run("", entries))
+}
+
+/// Lints packages already parsed, with pointers into an array of them.
+pub fn lint_packages(packages: &[RemotePackage]) -> LintReport {
+    let mut linter = Linter::default();
+    for (idx, package) in packages.iter().enumerate() {
+        linter.package(&format!("/{idx}"), package);
+    }
+    linter.finish(packages.len())
+}
+
+/// The identity the database keeps packages unique by.
+type Identity = (Option<String>, Option<String>, String, String);
+
+#[derive(Default)]
+struct Linter {
+    issues: Vec<LintIssue>,
+    /// Where each identity was first seen.
+    identities: HashMap<Identity, String>,
+    /// Where each package, regardless of version, was first seen.
+    packages: HashMap<(Option<String>, Option<String>, String), String>,
+}
+
+impl Linter {
+    fn run(mut self, prefix: &str, entries: Vec<Value>) -> LintReport {
+        let count = entries.len();
+        for (idx, entry) in entries.into_iter().enumerate() {
+            let pointer = format!("{prefix}/{idx}");
+            match serde_json::from_value::<RemotePackage>(entry) {
+                Ok(package) => self.package(&pointer, &package),
+                Err(err) => self.error(&pointer, format!("does not deserialize: {err}")),
+            }
+        }
+        self.finish(count)
+    }
+
+    fn finish(self, packages: usize) -> LintReport {
+        LintReport {
+            packages,
+            issues: self.issues,
+        }
+    }
+
+    fn error(&mut self, pointer: &str, message: impl Into<String>) {
+        self.push(Severity::Error, pointer, message);
+    }
+
+    fn warning(&mut self, pointer: &str, message: impl Into<String>) {
+        self.push(Severity::Warning, pointer, message);
+    }
+
+    fn push(&mut self, severity: Severity, pointer: &str, message: impl Into<String>) {
+        self.issues.push(LintIssue {
+            severity,
+            pointer: pointer.to_string(),
+            message: message.into(),
+        });
+    }
+
+    fn package(&mut self, at: &str, package: &RemotePackage) {
+        self.identity(at, package);
+
+        // The import skips a package whose name or id is not a single path
+        // component, since both are joined into its install directory.
+        if !is_safe_component(&package.pkg_name) {
+            self.error(
+                &format!("{at}/pkg_name"),
+                "not a single path component; the package is skipped on sync",
+            );
+        }
+        if let Some(pkg_id) = &package.pkg_id {
+            if !is_safe_component(pkg_id) {
+                self.error(
+                    &format!("{at}/pkg_id"),
+                    "not a single path component; the package is skipped on sync",
+                );
+            }
+        }
+        if package.version.trim().is_empty() {
+            self.error(&format!("{at}/version"), "empty");
+        }
+        if package.description.trim().is_empty() {
+            self.warning(&format!("{at}/description"), "empty");
+        }
+
+        self.artifact(at, package);
+
+        for (idx, provide) in package.provides.iter().flatten().enumerate() {
+            if !is_safe_provide(provide) {
+                self.error(
+                    &format!("{at}/provides/{idx}"),
+                    format!("`{provide}` is not a single path component; it is skipped"),
+                );
+            }
+        }
+
+        for (field, urls) in [
+            ("homepages", &package.homepages),
+            ("src_urls", &package.src_urls),
+        ] {
+            for (idx, url) in urls.iter().flatten().enumerate() {
+                self.url(&format!("{at}/{field}/{idx}"), url, false);
+            }
+        }
+        for (field, url) in [
+            ("ghcr_url", &package.ghcr_url),
+            ("build_log", &package.build_log),
+            ("build_script", &package.build_script),
+        ] {
+            if let Some(url) = url {
+                self.url(&format!("{at}/{field}"), url, false);
+            }
+        }
+        for (field, url) in [
+            ("icon", &package.icon),
+            ("desktop", &package.desktop),
+            ("appstream", &package.appstream),
+        ] {
+            if let Some(url) = url {
+                self.url(&format!("{at}/{field}"), url, true);
+            }
+        }
+
+        self.files(at, package);
+        self.extras(at, package);
+        self.completions(at, package);
+        self.launch(at, package);
+        self.snapshots(at, package);
+    }
+
+    fn identity(&mut self, at: &str, package: &RemotePackage) {
+        let identity = (
+            package.pkg_id.clone(),
+            package.pkg_family.clone(),
+            package.pkg_name.clone(),
+            package.version.clone(),
+        );
+        if let Some(first) = self.identities.get(&identity) {
+            let message = format!(
+                "duplicates {first}; only one of them is kept on sync, and which is unspecified"
+            );
+            self.error(at, message);
+            return;
+        }
+        self.identities.insert(identity, at.to_string());
+
+        let package_key = (
+            package.pkg_id.clone(),
+            package.pkg_family.clone(),
+            package.pkg_name.clone(),
+        );
+        if let Some(first) = self.packages.get(&package_key) {
+            let message = format!(
+                "same package as {first} at another version; list older builds in `snapshots`"
+            );
+            self.warning(at, message);
+            return;
+        }
+        self.packages.insert(package_key, at.to_string());
+    }
+
+    fn artifact(&mut self, at: &str, package: &RemotePackage) {
+        if package.download_url.trim().is_empty() {
+            if package.ghcr_pkg.is_none() {
+                self.error(
+                    &format!("{at}/download_url"),
+                    "empty, and there is no `ghcr_pkg` to install from",
+                );
+            }
+        } else {
+            self.url(&format!("{at}/download_url"), &package.download_url, true);
+        }
+
+        match &package.bsum {
+            Some(bsum) => self.checksum(&format!("{at}/bsum"), bsum),
+            None => {
+                self.warning(
+                    &format!("{at}/bsum"),
+                    "missing; the download is installed unverified",
+                )
+            }
+        }
+    }
+
+    fn files(&mut self, at: &str, package: &RemotePackage) {
+        let mut targets: HashMap<&str, usize> = HashMap::new();
+        for (idx, file) in package.files.iter().flatten().enumerate() {
+            let here = format!("{at}/files/{idx}");
+            if !is_safe_relative(&file.to) {
+                self.error(
+                    &format!("{here}/to"),
+                    format!("`{}` escapes the package directory; it is skipped", file.to),
+                );
+            } else if let Some(first) = targets.insert(&file.to, idx) {
+                self.error(
+                    &format!("{here}/to"),
+                    format!("`{}` is also the target of {at}/files/{first}", file.to),
+                );
+            }
+            for (alias_idx, alias) in file.alias.iter().enumerate() {
+                if !is_safe_relative(alias) {
+                    self.error(
+                        &format!("{here}/alias/{alias_idx}"),
+                        format!("`{alias}` escapes the package directory; it is skipped"),
+                    );
+                }
+            }
+        }
+    }
+
+    fn extras(&mut self, at: &str, package: &RemotePackage) {
+        for (idx, extra) in package.extra.iter().flatten().enumerate() {
+            let here = format!("{at}/extra/{idx}");
+            self.url(&format!("{here}/url"), &extra.url, true);
+            if !is_safe_component(&extra.to) {
+                self.error(
+                    &format!("{here}/to"),
+                    format!("`{}` is not a single file name; it is skipped", extra.to),
+                );
+            }
+            if let Some(blake3) = &extra.blake3 {
+                self.checksum(&format!("{here}/blake3"), blake3);
+            } else if let Some(sha256) = &extra.sha256 {
+                if !is_hex_digest(sha256) {
+                    self.error(
+                        &format!("{here}/sha256"),
+                        "not a lowercase hex sha256 digest",
+                    );
+                }
+                self.warning(
+                    &format!("{here}/sha256"),
+                    "only `blake3` is verified on install; publish that instead",
+                );
+            }
+        }
+    }
+
+    fn launch(&mut self, at: &str, package: &RemotePackage) {
+        let Some(launch) = &package.launch else {
+            return;
+        };
+        for name in launch.env.keys() {
+            if !entrypoint::is_variable_name(name) {
+                self.error(
+                    &format!("{at}/launch/env/{name}"),
+                    "not a variable name a shell can set",
+                );
+            }
+        }
+        if launch
+            .pre_launch
+            .as_deref()
+            .is_some_and(|command| command.trim().is_empty())
+        {
+            self.error(
+                &format!("{at}/launch/pre_launch"),
+                "empty; there is no command to run",
+            );
+        }
+    }
+
+    fn completions(&mut self, at: &str, package: &RemotePackage) {
+        for (shell, command) in package.completion_commands.iter().flatten() {
+            let here = format!("{at}/completion_commands/{shell}");
+            if !COMPLETION_KINDS.contains(&shell.as_str()) {
+                self.warning(
+                    &here,
+                    format!(
+                        "soar generates completions for bash, zsh and fish, or a `man` page, not \
+                         `{shell}`"
+                    ),
+                );
+            } else if command.trim().is_empty() {
+                self.error(&here, "empty; there is no command to run");
+            }
+        }
+    }
+
+    fn snapshots(&mut self, at: &str, package: &RemotePackage) {
+        let mut ids = HashSet::new();
+        let mut previous: Option<&str> = None;
+        for (idx, snapshot) in package.snapshots.iter().flatten().enumerate() {
+            let here = format!("{at}/snapshots/{idx}");
+            if snapshot.version.trim().is_empty() {
+                self.error(&format!("{here}/version"), "empty");
+            } else {
+                if compare_versions(&snapshot.version, &package.version) == Ordering::Greater {
+                    self.warning(
+                        &format!("{here}/version"),
+                        format!(
+                            "`{}` is newer than the current version `{}`",
+                            snapshot.version, package.version
+                        ),
+                    );
+                }
+                if let Some(previous) = previous {
+                    if compare_versions(&snapshot.version, previous) == Ordering::Greater {
+                        self.warning(
+                            &format!("{here}/version"),
+                            format!(
+                                "`{}` is newer than the snapshot before it; list newest first",
+                                snapshot.version
+                            ),
+                        );
+                    }
+                }
+                previous = Some(&snapshot.version);
+            }
+
+            if let Some(id) = &snapshot.id {
+                if package.build_id.as_ref() == Some(id) || !ids.insert(id) {
+                    self.error(
+                        &format!("{here}/id"),
+                        format!("`{id}` names another build of this package"),
+                    );
+                }
+            }
+            if snapshot.ghcr_tag.is_some() && package.ghcr_pkg.is_none() {
+                self.error(
+                    &format!("{here}/ghcr_tag"),
+                    "set, but the package has no `ghcr_pkg` to tag",
+                );
+            }
+            if let Some(url) = &snapshot.download_url {
+                self.url(&format!("{here}/download_url"), url, true);
+            }
+            if let Some(bsum) = &snapshot.bsum {
+                self.checksum(&format!("{here}/bsum"), bsum);
+            }
+        }
+    }
+
+    /// Checks a URL. One that is `fetched` is downloaded on install, so
+    /// cleartext http there is worth a warning.
+    fn url(&mut self, pointer: &str, url: &str, fetched: bool) {
+        match Url::parse(url) {
+            Ok(parsed) => {
+                match parsed.scheme() {
+                    "https" => {}
+                    "http" if fetched => {
+                        self.warning(pointer, "fetched over cleartext http");
+                    }
+                    "http" => {}
+                    scheme => {
+                        self.error(pointer, format!("unsupported URL scheme `{scheme}`"));
+                    }
+                }
+            }
+            Err(err) => self.error(pointer, format!("not a valid URL: {err}")),
+        }
+    }
+
+    /// Checks a blake3 checksum, which installs compare as lowercase hex.
+    fn checksum(&mut self, pointer: &str, sum: &str) {
+        if !is_hex_digest(sum) {
+            self.error(pointer, "not a lowercase hex blake3 digest");
+        }
+    }
+}
+
+/// Whether `sum` is a 256-bit digest in lowercase hex.
+fn is_hex_digest(sum: &str) -> bool {
+    sum.len() == 64 && sum.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Whether every name a `provides` entry links stays in the bin directory,
+/// read the way the import reads it.
+fn is_safe_provide(provide: &str) -> bool {
+    let provide = provide.strip_prefix('@').unwrap_or(provide);
+    let (name, target) = ["==", "=>", ":"]
+        .iter()
+        .find_map(|sep| provide.split_once(sep))
+        .map_or((provide, None), |(name, target)| (name, Some(target)));
+    is_safe_component(name) && target.is_none_or(is_safe_component)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUM: &str = "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262";
+
+    fn pointers(report: &LintReport, severity: Severity) -> Vec<&str> {
+        report
+            .issues
+            .iter()
+            .filter(|issue| issue.severity == severity)
+            .map(|issue| issue.pointer.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn a_good_index_is_clean() {
+        let index = format!(
+            r#"{{"format": 1, "packages": [{{
+                "pkg_name": "bat",
+                "description": "cat with wings",
+                "version": "0.24.0",
+                "download_url": "https://example.com/bat",
+                "bsum": "{SUM}",
+                "provides": ["bat", "batcat==bat"],
+                "files": [{{"source": "bat", "to": "bin/bat", "alias": ["bin/batcat"]}}],
+                "completion_commands": {{"zsh": "bin/bat --completion zsh"}},
+                "snapshots": ["0.23.0", {{"version": "0.22.1", "id": "b7"}}]
+            }}]}}"#
+        );
+
+        let report = lint_index(index.as_bytes()).unwrap();
+        assert_eq!(report.packages, 1);
+        assert!(report.is_clean(), "{:?}", report.issues);
+    }
+
+    #[test]
+    fn broken_entries_are_reported_with_their_pointer() {
+        let index = r#"[
+            {"pkg_name": "no-version", "description": "x", "download_url": "https://e/x"},
+            {
+                "pkg_name": "../escape",
+                "description": "x",
+                "version": "1",
+                "download_url": "ftp://e/x",
+                "bsum": "ABC",
+                "files": [{"to": "bin/x"}, {"to": "../x"}, {"to": "bin/x"}],
+                "extra": [{"url": "https://e/l", "to": "a/b"}]
+            }
+        ]"#;
+
+        let report = lint_index(index.as_bytes()).unwrap();
+        assert_eq!(report.packages, 2);
+        assert_eq!(
+            pointers(&report, Severity::Error),
+            [
+                "/0",
+                "/1/pkg_name",
+                "/1/download_url",
+                "/1/bsum",
+                "/1/files/1/to",
+                "/1/files/2/to",
+                "/1/extra/0/to",
+            ]
+        );
+    }
+
+    #[test]
+    fn duplicates_are_errors_and_other_versions_warnings() {
+        let package = |version: &str| {
+            RemotePackage {
+                pkg_name: "bat".to_string(),
+                description: "x".to_string(),
+                version: version.to_string(),
+                download_url: "https://e/bat".to_string(),
+                bsum: Some(SUM.to_string()),
+                ..Default::default()
+            }
+        };
+
+        let report = lint_packages(&[package("1.0"), package("1.0"), package("0.9")]);
+        assert_eq!(pointers(&report, Severity::Error), ["/1"]);
+        assert_eq!(pointers(&report, Severity::Warning), ["/2"]);
+        assert!(report.issues[0].message.contains("/0"));
+    }
+
+    #[test]
+    fn snapshots_are_listed_newest_first() {
+        let index = format!(
+            r#"[{{
+                "pkg_name": "bat",
+                "description": "x",
+                "version": "1.2",
+                "download_url": "https://e/bat",
+                "bsum": "{SUM}",
+                "snapshots": ["1.3", "1.0", "1.1", {{"version": "0.9", "ghcr_tag": "0.9"}}]
+            }}]"#
+        );
+
+        let report = lint_index(index.as_bytes()).unwrap();
+        assert_eq!(
+            pointers(&report, Severity::Warning),
+            ["/0/snapshots/0/version", "/0/snapshots/2/version"]
+        );
+        assert_eq!(
+            pointers(&report, Severity::Error),
+            ["/0/snapshots/3/ghcr_tag"]
+        );
+    }
+
+    #[test]
+    fn launch_settings_need_settable_names_and_a_command() {
+        let index = format!(
+            r#"[{{
+                "pkg_name": "app",
+                "description": "x",
+                "version": "1.2",
+                "download_url": "https://e/app",
+                "bsum": "{SUM}",
+                "launch": {{
+                    "env": {{"QT_QPA_PLATFORM": "xcb", "BAD-NAME": "x"}},
+                    "args": ["--flag"],
+                    "pre_launch": " "
+                }}
+            }}]"#
+        );
+
+        let report = lint_index(index.as_bytes()).unwrap();
+        assert_eq!(
+            pointers(&report, Severity::Error),
+            ["/0/launch/env/BAD-NAME", "/0/launch/pre_launch"]
+        );
+    }
+
+    #[test]
+    fn completion_commands_name_a_linked_shell() {
+        let index = format!(
+            r#"[{{
+                "pkg_name": "bat",
+                "description": "x",
+                "version": "1.2",
+                "download_url": "https://e/bat",
+                "bsum": "{SUM}",
+                "completion_commands": {{
+                    "bash": " ", "fish": "bat --completion fish", "man": "bat --man", "nu": "x"
+                }}
+            }}]"#
+        );
+
+        let report = lint_index(index.as_bytes()).unwrap();
+        assert_eq!(
+            pointers(&report, Severity::Error),
+            ["/0/completion_commands/bash"]
+        );
+        assert_eq!(
+            pointers(&report, Severity::Warning),
+            ["/0/completion_commands/nu"]
+        );
+    }
+
+    #[test]
+    fn a_newer_format_is_refused() {
+        let err = lint_index(br#"{"format": 99, "packages": []}"#).unwrap_err();
+        assert!(matches!(
+            err,
+            RegistryError::UnsupportedFormat {
+                found: 99,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn provides_are_read_like_the_import() {
+        assert!(is_safe_provide("@bat"));
+        assert!(is_safe_provide("bat=>batcat"));
+        assert!(!is_safe_provide("bat:../batcat"));
+        assert!(!is_safe_provide("../bat"));
+    }
+}
@@ -7,7 +7,7 @@ use std::{
     fs::{self, File},
     io::{self, BufRead, BufReader, BufWriter, Write},
     path::{Path, PathBuf},
-    time::UNIX_EPOCH,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
 use minisign_verify::{PublicKey, Signature};
@@ -23,6 +23,7 @@ use ureq::http::{
 use url::Url;
 
 use crate::{
+    delta::{fetch_deltas, fetch_manifest, DeltaManifest, MetadataDelta, Revision},
     error::{ErrorContext, RegistryError, Result},
     package::RemotePackage,
 };
@@ -47,6 +48,9 @@ pub const MAX_METADATA_SIZE: u64 = 256 * 1024 * 1024;
 /// - Pre-built SQLite databases (more efficient for large repositories)
 /// - JSON arrays of packages (simpler format)
 ///
+/// A repository publishing deltas can also send just the changes since the
+/// revision the local database holds.
+///
 /// The caller is responsible for handling each variant appropriately,
 /// typically by either writing the SQLite bytes directly to disk or
 /// importing JSON packages into a new database.
@@ -55,6 +59,20 @@ pub enum MetadataContent {
     SqliteDb(Vec<u8>),
     /// Parsed package metadata from JSON format.
     Json(Vec<RemotePackage>),
+    /// Deltas to apply to the local database, in order.
+    Delta(Vec<MetadataDelta>),
+}
+
+/// Metadata fetched from a repository, ready to be stored.
+pub struct FetchedMetadata {
+    /// ETag of the full metadata, or its modification time for a local file.
+    pub etag: String,
+    pub content: MetadataContent,
+    /// The revision the repository's delta manifest says `content` is at.
+    ///
+    /// Only repositories publishing deltas have one, and it is a claim: the
+    /// caller records it once the stored packages hash to it.
+    pub revision: Option<Revision>,
 }
 
 /// Fetches repository metadata from a remote source.
@@ -62,15 +80,23 @@ pub enum MetadataContent {
 /// This function retrieves package metadata for a configured repository, handling
 /// caching via ETags and respecting the repository's sync interval.
 ///
+/// Each channel keeps its own database, so `repo` can be a view from
+/// [`Repository::for_channel`] as well as a configured repository.
+///
+/// When the repository sets `delta_url` and the local database is at a known
+/// revision, only the deltas since that revision are fetched. Without a usable
+/// chain of deltas, the full metadata is.
+///
 /// # Arguments
 ///
 /// * `repo` - The repository configuration
 /// * `force` - If `true`, bypasses cache validation and fetches fresh metadata
 /// * `existing_etag` - Optional etag from a previous fetch, read from the database
+/// * `existing_revision` - Optional revision the database was verified at
 ///
 /// # Returns
 ///
-/// * `Ok(Some((etag, content)))` - New metadata was fetched successfully
+/// * `Ok(Some(fetched))` - New metadata was fetched successfully
 /// * `Ok(None)` - Cached metadata is still valid (not modified)
 /// * `Err(_)` - An error occurred during fetching or processing
 ///
@@ -91,9 +117,9 @@ pub enum MetadataContent {
 /// use soar_config::repository::Repository;
 ///
 /// async fn sync(repo: &Repository, etag: Option<String>) -> soar_registry::Result<()> {
-///     if let Some((new_etag, content)) = fetch_metadata(repo, false, etag).await? {
-///         let db_path = repo.get_path().unwrap().join("metadata.db");
-///         if let MetadataContent::SqliteDb(bytes) = content {
+///     if let Some(fetched) = fetch_metadata(repo, false, etag, None).await? {
+///         let db_path = repo.metadata_db_path().unwrap();
+///         if let MetadataContent::SqliteDb(bytes) = fetched.content {
 ///             write_metadata_db(&bytes, &db_path)?;
 ///         }
 ///     }
@@ -104,14 +130,18 @@ pub async fn fetch_metadata(
     repo: &Repository,
     force: bool,
     existing_etag: Option<String>,
-) -> Result<Option<(String, MetadataContent)>> {
-    let repo_path = repo.get_path().map_err(|e| {
+    existing_revision: Option<String>,
+) -> Result<Option<FetchedMetadata>> {
+    let metadata_db = repo.metadata_db_path().map_err(|e| {
         RegistryError::IoError {
             action: "getting repository path".to_string(),
             source: io::Error::other(e.to_string()),
         }
     })?;
-    let metadata_db = repo_path.join("metadata.db");
+    let repo_path = metadata_db
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
 
     if !metadata_db.exists() {
         fs::create_dir_all(&repo_path)
@@ -141,11 +171,103 @@ pub async fn fetch_metadata(
         String::new()
     };
 
+    let manifest = repo.delta_url.as_deref().and_then(|delta_url| {
+        fetch_manifest(repo, delta_url)
+            .inspect_err(|err| {
+                warn!(
+                    "delta manifest for '{}' is unavailable, syncing in full: {err}",
+                    repo.name
+                )
+            })
+            .ok()
+    });
+
+    if let Some(manifest) = &manifest {
+        let existing_revision = existing_revision.filter(|_| metadata_db.exists());
+        if let Some(deltas) = fetch_delta_chain(repo, manifest, existing_revision.as_deref()) {
+            if deltas.is_empty() {
+                // Nothing changed, but the check counts as a sync: without
+                // this the manifest would be fetched again on every run
+                // instead of once per sync interval.
+                mark_synced(&metadata_db);
+                return Ok(None);
+            }
+            return Ok(Some(FetchedMetadata {
+                etag,
+                content: MetadataContent::Delta(deltas),
+                revision: Some(manifest.current()),
+            }));
+        }
+    }
+
+    let fetched = fetch_full_metadata(repo, &metadata_db, &etag, force)?;
+    Ok(fetched.map(|(etag, content)| {
+        FetchedMetadata {
+            etag,
+            content,
+            revision: manifest.as_ref().map(DeltaManifest::current),
+        }
+    }))
+}
+
+/// Resets the sync interval of an up-to-date metadata database.
+fn mark_synced(metadata_db: &Path) {
+    let touched = File::options()
+        .write(true)
+        .open(metadata_db)
+        .and_then(|file| file.set_modified(SystemTime::now()));
+    if let Err(err) = touched {
+        debug!(
+            path = %metadata_db.display(),
+            "failed to update metadata modification time: {err}"
+        );
+    }
+}
+
+/// Fetches the deltas from the local revision to the manifest's.
+///
+/// Returns `None` when the full metadata has to be fetched instead, and no
+/// deltas when the local revision is already current.
+fn fetch_delta_chain(
+    repo: &Repository,
+    manifest: &DeltaManifest,
+    existing_revision: Option<&str>,
+) -> Option<Vec<MetadataDelta>> {
+    let existing_revision = existing_revision?;
+    let Some(chain) = manifest.chain_from(existing_revision) else {
+        debug!(
+            repo_name = repo.name,
+            from = existing_revision,
+            to = manifest.revision,
+            "no delta chain to the current revision"
+        );
+        return None;
+    };
+
+    // The manifest was fetched from `delta_url`, so it is set here.
+    let delta_url = repo.delta_url.as_deref()?;
+    fetch_deltas(repo, delta_url, &chain)
+        .inspect_err(|err| {
+            warn!(
+                "deltas for '{}' are unavailable, syncing in full: {err}",
+                repo.name
+            )
+        })
+        .ok()
+}
+
+/// Fetches the full metadata, or `None` when it has not changed.
+fn fetch_full_metadata(
+    repo: &Repository,
+    metadata_db: &Path,
+    etag: &str,
+    force: bool,
+) -> Result<Option<(String, MetadataContent)>> {
     // A repository URL can point at a local file (`file://` or a filesystem
     // path) or a remote http(s) endpoint. Local sources are read from disk;
     // remote sources are fetched over HTTP.
     if let Some(path) = local_metadata_path(&repo.url) {
-        return fetch_local_metadata(repo, &path, &metadata_db, &etag, force);
+        return fetch_local_metadata(repo, &path, metadata_db, etag, force);
     }
 
     let parsed_url =
@@ -203,14 +325,14 @@ pub async fn fetch_metadata(
         fetch_signature_text(&format!("{}.sig", repo.url))
     })?;
 
-    let metadata_content = process_metadata_content(content, &metadata_db)?;
+    let metadata_content = process_metadata_content(content, metadata_db)?;
 
     Ok(Some((etag, metadata_content)))
 }
 
 /// Resolves a repository URL to a local filesystem path when it is a local
 /// source (a `file://` URL or a filesystem path), or `None` for http(s) URLs.
-fn local_metadata_path(url: &str) -> Option<PathBuf> {
+pub(crate) fn local_metadata_path(url: &str) -> Option<PathBuf> {
     let trimmed = url.trim();
     if let Some(rest) = trimmed.strip_prefix("file://") {
         return resolve_path(rest).ok();
@@ -230,7 +352,11 @@ fn local_metadata_path(url: &str) -> Option<PathBuf> {
 /// `https` is always allowed. Cleartext `http` is only allowed when the metadata
 /// will be authenticated by signature verification, so a network attacker cannot
 /// substitute unverifiable metadata. Any other scheme is rejected.
-fn ensure_remote_scheme_allowed(url: &str, scheme: &str, signature_verified: bool) -> Result<()> {
+pub(crate) fn ensure_remote_scheme_allowed(
+    url: &str,
+    scheme: &str,
+    signature_verified: bool,
+) -> Result<()> {
     match scheme {
         "https" => Ok(()),
         "http" if signature_verified => Ok(()),
@@ -288,7 +414,7 @@ fn fetch_local_metadata(
 }
 
 /// Reads the detached signature published next to a local metadata file.
-fn read_local_signature(metadata_path: &Path) -> std::result::Result<String, String> {
+pub(crate) fn read_local_signature(metadata_path: &Path) -> std::result::Result<String, String> {
     let mut sig_path = metadata_path.as_os_str().to_os_string();
     sig_path.push(".sig");
     let sig_path = PathBuf::from(sig_path);
@@ -303,7 +429,7 @@ fn read_local_signature(metadata_path: &Path) -> std::result::Result<String, Str
 /// fetched bytes, before the metadata is decompressed, parsed, or persisted. A
 /// missing or invalid signature is a hard error so a tampered metadata source
 /// cannot supply both the package `download_url` and its expected checksum.
-fn verify_metadata_signature(
+pub(crate) fn verify_metadata_signature(
     repo: &Repository,
     content: &[u8],
     load_signature: impl FnOnce() -> std::result::Result<String, String>,
@@ -353,7 +479,7 @@ fn verify_metadata_signature(
 }
 
 /// Fetches the textual contents of a detached minisign signature.
-fn fetch_signature_text(url: &str) -> std::result::Result<String, String> {
+pub(crate) fn fetch_signature_text(url: &str) -> std::result::Result<String, String> {
     let resp = SHARED_AGENT
         .get(url)
         .header(CACHE_CONTROL, "no-cache")
@@ -449,6 +575,24 @@ pub fn process_metadata_content(
     }
 }
 
+/// Decompresses zstd content held in memory, within [`MAX_METADATA_SIZE`].
+pub(crate) fn decode_zstd(bytes: &[u8]) -> Result<Vec<u8>> {
+    let decoder = zstd::Decoder::new(bytes)
+        .map_err(|e| RegistryError::Custom(format!("creating zstd decoder: {e}")))?;
+    let mut decoded = Vec::new();
+    let read = io::Read::read_to_end(
+        &mut io::Read::take(decoder, MAX_METADATA_SIZE + 1),
+        &mut decoded,
+    )
+    .with_context(|| "decoding zstd content".to_string())?;
+    if read as u64 > MAX_METADATA_SIZE {
+        return Err(RegistryError::MetadataTooLarge {
+            limit: MAX_METADATA_SIZE,
+        });
+    }
+    Ok(decoded)
+}
+
 /// The highest index format this build understands.
 pub const SUPPORTED_FORMAT: u32 = 1;
 
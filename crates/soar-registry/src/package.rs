@@ -5,7 +5,7 @@
 //! quirks in the metadata format, including flexible boolean parsing and
 //! optional number fields.
 
-use std::fmt;
+use std::{collections::BTreeMap, fmt};
 
 use serde::{
     de::{self, Visitor},
@@ -234,7 +234,8 @@ pub struct RemotePackage {
     pub portable: Option<bool>,
 
     pub repology: Option<Vec<String>>,
-    pub snapshots: Option<Vec<String>>,
+    /// Past builds the repository still serves, newest first.
+    pub snapshots: Option<Vec<RemoteSnapshot>>,
     pub replaces: Option<Vec<String>>,
     /// Executables inside the artifact, as source path -> installed name.
     /// Pinned side files to install alongside the artifact.
@@ -244,6 +245,15 @@ pub struct RemotePackage {
     /// artifact is the package, which is how the older format always behaved.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub files: Option<Vec<RemoteFile>>,
+    /// Commands printing the package's completions, by shell, or its manual
+    /// page under `man`, for a package that ships none. They run sandboxed in
+    /// the package directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub completion_commands: Option<BTreeMap<String, String>>,
+    /// How the package's commands are started, for a program that needs
+    /// variables or flags to run well.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub launch: Option<RemoteLaunch>,
 }
 
 #[cfg(test)]
@@ -294,6 +304,28 @@ mod tests {
         let pkg: RemotePackage = serde_json::from_str(json).unwrap();
         assert_eq!(pkg.disabled, Some(true));
     }
+
+    #[test]
+    fn test_snapshots_in_either_shape() {
+        let json = r#"{
+            "pkg_name": "test",
+            "description": "test",
+            "version": "1.1.0",
+            "download_url": "https://example.com",
+            "snapshots": [
+                "1.0.0",
+                {"version": "0.9.0", "id": "b41", "ghcr_tag": "0.9.0-b41", "size": "1024"}
+            ]
+        }"#;
+
+        let pkg: RemotePackage = serde_json::from_str(json).unwrap();
+        let snapshots = pkg.snapshots.unwrap();
+        assert_eq!(snapshots[0].version, "1.0.0");
+        assert_eq!(snapshots[0].id, None);
+        assert_eq!(snapshots[1].id.as_deref(), Some("b41"));
+        assert_eq!(snapshots[1].ghcr_tag.as_deref(), Some("0.9.0-b41"));
+        assert_eq!(snapshots[1].size, Some(1024));
+    }
 }
 
 /// One file the package installs, as published in the index.
@@ -311,6 +343,21 @@ pub struct RemoteFile {
     pub alias: Vec<String>,
 }
 
+/// How a package's commands are started, as published in the index.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct RemoteLaunch {
+    /// Variables set for the commands. Values may refer to other variables
+    /// and to `$INSTALL_DIR`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Arguments put before the user's.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+    /// Shell command run first; the command does not start when it fails.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pre_launch: Option<String>,
+}
+
 /// A pinned side file as published in the index.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct RemoteExtra {
@@ -321,3 +368,83 @@ pub struct RemoteExtra {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub sha256: Option<String>,
 }
+
+/// A past build the repository still serves, as published in the index.
+///
+/// The first indexes listed snapshots as bare version strings, fetched by
+/// putting the version into the package's `{{version}}` placeholders. Those
+/// still read, as a snapshot with only a version. A snapshot can instead say
+/// where its build is, and the checksum it has, itself.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(from = "SnapshotRepr")]
+pub struct RemoteSnapshot {
+    pub version: String,
+    /// The build's id, which `pkg@<id>` selects it by.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<String>,
+    /// The OCI tag of the build, in place of the tag in `ghcr_pkg`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ghcr_tag: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub download_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bsum: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+}
+
+/// Either shape a snapshot is published in.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SnapshotRepr {
+    Version(String),
+    Build {
+        version: String,
+        #[serde(default)]
+        id: Option<String>,
+        #[serde(default)]
+        date: Option<String>,
+        #[serde(default)]
+        ghcr_tag: Option<String>,
+        #[serde(default)]
+        download_url: Option<String>,
+        #[serde(default)]
+        bsum: Option<String>,
+        #[serde(default, deserialize_with = "optional_number")]
+        size: Option<u64>,
+    },
+}
+
+impl From<SnapshotRepr> for RemoteSnapshot {
+    fn from(repr: SnapshotRepr) -> Self {
+        match repr {
+            SnapshotRepr::Version(version) => {
+                Self {
+                    version,
+                    ..Self::default()
+                }
+            }
+            SnapshotRepr::Build {
+                version,
+                id,
+                date,
+                ghcr_tag,
+                download_url,
+                bsum,
+                size,
+            } => {
+                Self {
+                    version,
+                    id,
+                    date,
+                    ghcr_tag,
+                    download_url,
+                    bsum,
+                    size,
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,45 @@
+//! Cooperative cancellation.
+//!
+//! Long-running work checks a [`CancellationToken`] between steps and stops
+//! at the next one once it is cancelled, undoing what it had started. Nothing
+//! is interrupted midway through a write.
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// A flag shared by the work and whoever may stop it; clones share it.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the work holding this token to stop. It cannot be taken back.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clones_share_cancellation() {
+        let token = CancellationToken::new();
+        let held = token.clone();
+        assert!(!held.is_cancelled());
+
+        token.cancel();
+        assert!(held.is_cancelled());
+        assert!(!CancellationToken::new().is_cancelled());
+    }
+}
@@ -1,9 +1,13 @@
-//! Reading named sections out of an ELF file.
+//! Reading named sections and dynamic linking details out of an ELF file.
 //!
-//! An AppImage is an ELF, and the one section soar cares about is `.upd_info`,
-//! which names where updates come from. Only 64-bit little-endian files are
-//! read, which is every AppImage soar installs; anything else reports no
-//! section rather than guessing at its layout.
+//! An AppImage is an ELF, and the section soar cares about is `.upd_info`,
+//! which names where updates come from. Beyond that, [`inspect`] reports what a
+//! binary needs from the system it runs on: its architecture, its dynamic
+//! loader, its shared libraries and the symbol versions it takes from them.
+//!
+//! Only 64-bit little-endian files are read in full, which is every binary soar
+//! installs on the hosts it supports; anything else reports its architecture
+//! and nothing more rather than guessing at its layout.
 
 use std::{
     fs::File,
@@ -62,6 +66,228 @@ pub fn section_data(path: impl AsRef<Path>, name: &str) -> Option<Vec<u8>> {
     None
 }
 
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const PT_INTERP: u32 = 3;
+
+const DT_NULL: u64 = 0;
+const DT_NEEDED: u64 = 1;
+const DT_STRTAB: u64 = 5;
+const DT_STRSZ: u64 = 10;
+const DT_RPATH: u64 = 15;
+const DT_RUNPATH: u64 = 29;
+const DT_VERDEF: u64 = 0x6fff_fffc;
+const DT_VERDEFNUM: u64 = 0x6fff_fffd;
+const DT_VERNEED: u64 = 0x6fff_fffe;
+const DT_VERNEEDNUM: u64 = 0x6fff_ffff;
+
+/// `vd_flags` marking the definition that names the library itself.
+const VER_FLG_BASE: u16 = 1;
+
+/// Guards the version chains, which a corrupt file could make circular.
+const MAX_VERSIONS: u32 = 4096;
+
+/// What an ELF file needs from the system that runs it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ElfInfo {
+    /// `e_machine`: the architecture the file was built for.
+    pub machine: u16,
+    /// The dynamic loader named by `PT_INTERP`. A static binary has none.
+    pub interpreter: Option<String>,
+    /// Shared libraries from `DT_NEEDED`, in load order.
+    pub needed: Vec<String>,
+    /// Library search paths from `DT_RUNPATH`, or `DT_RPATH` without one,
+    /// with `$ORIGIN` left unexpanded.
+    pub search_paths: Vec<String>,
+    /// Symbol versions the file requires, as `(library, version)` pairs such
+    /// as `("libc.so.6", "GLIBC_2.34")`.
+    pub required_versions: Vec<(String, String)>,
+    /// Symbol versions a shared library defines, such as `GLIBC_2.34`.
+    pub defined_versions: Vec<String>,
+}
+
+/// What the ELF at `path` needs to run.
+///
+/// Returns `None` when the file is not an ELF or cannot be read. For a file
+/// that is not 64-bit little-endian only [`ElfInfo::machine`] is filled in.
+pub fn inspect(path: impl AsRef<Path>) -> Option<ElfInfo> {
+    let mut file = File::open(path).ok()?;
+
+    let mut ident = [0u8; 16];
+    file.read_exact(&mut ident).ok()?;
+    if ident[..4] != ELF_MAGIC {
+        return None;
+    }
+    let machine = read_at(&mut file, 0x12, 2)?;
+    let machine = if ident[5] == DATA_LE {
+        u16::from_le_bytes([machine[0], machine[1]])
+    } else {
+        u16::from_be_bytes([machine[0], machine[1]])
+    };
+    let mut info = ElfInfo {
+        machine,
+        ..Default::default()
+    };
+    if ident[4] != CLASS_64 || ident[5] != DATA_LE {
+        return Some(info);
+    }
+
+    let phoff = read_u64(&mut file, 0x20)?;
+    let phentsize = read_u16(&mut file, 0x36)? as u64;
+    let phnum = read_u16(&mut file, 0x38)? as u64;
+
+    // (vaddr, offset, filesz) of each loaded segment, to find the dynamic
+    // tables, which are addressed by where they are loaded.
+    let mut loads = Vec::new();
+    let mut dynamic = None;
+    for i in 0..phnum {
+        let entry = phoff.checked_add(i.checked_mul(phentsize)?)?;
+        let kind = read_u32(&mut file, entry)?;
+        let offset = read_u64(&mut file, entry + 0x08)?;
+        let vaddr = read_u64(&mut file, entry + 0x10)?;
+        let filesz = read_u64(&mut file, entry + 0x20)?;
+        match kind {
+            PT_LOAD => loads.push((vaddr, offset, filesz)),
+            PT_DYNAMIC => dynamic = Some((offset, filesz)),
+            PT_INTERP => {
+                let mut path = read_at(&mut file, offset, filesz)?;
+                while path.last() == Some(&0) {
+                    path.pop();
+                }
+                info.interpreter = Some(String::from_utf8_lossy(&path).into_owned());
+            }
+            _ => {}
+        }
+    }
+
+    let Some((offset, size)) = dynamic else {
+        return Some(info);
+    };
+    let entries = read_at(&mut file, offset, size)?;
+    let mut tags: Vec<(u64, u64)> = Vec::new();
+    for entry in entries.chunks_exact(16) {
+        let tag = u64::from_le_bytes(entry[..8].try_into().ok()?);
+        if tag == DT_NULL {
+            break;
+        }
+        tags.push((tag, u64::from_le_bytes(entry[8..].try_into().ok()?)));
+    }
+    let value = |wanted: u64| tags.iter().find(|(t, _)| *t == wanted).map(|(_, v)| *v);
+    let file_offset = |vaddr: u64| {
+        loads
+            .iter()
+            .find(|(start, _, size)| vaddr >= *start && vaddr < start + size)
+            .map(|(start, offset, _)| vaddr - start + offset)
+    };
+
+    let (Some(strtab), Some(strsz)) = (value(DT_STRTAB).and_then(file_offset), value(DT_STRSZ))
+    else {
+        return Some(info);
+    };
+    let strings = read_at(&mut file, strtab, strsz)?;
+    let string = |offset: u64| section_name(&strings, offset as usize).map(str::to_string);
+
+    info.needed = tags
+        .iter()
+        .filter(|(t, _)| *t == DT_NEEDED)
+        .filter_map(|(_, v)| string(*v))
+        .collect();
+    info.search_paths = value(DT_RUNPATH)
+        .or_else(|| value(DT_RPATH))
+        .and_then(string)
+        .map(|paths| {
+            paths
+                .split(':')
+                .filter(|p| !p.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if let (Some(mut entry), Some(count)) = (
+        value(DT_VERNEED).and_then(file_offset),
+        value(DT_VERNEEDNUM),
+    ) {
+        for _ in 0..count.min(MAX_VERSIONS as u64) {
+            let aux_count = read_u16(&mut file, entry + 2)?;
+            let library = string(read_u32(&mut file, entry + 4)? as u64)?;
+            let mut aux = entry + read_u32(&mut file, entry + 8)? as u64;
+            for _ in 0..aux_count.min(MAX_VERSIONS as u16) {
+                if let Some(version) = string(read_u32(&mut file, aux + 8)? as u64) {
+                    info.required_versions.push((library.clone(), version));
+                }
+                let next = read_u32(&mut file, aux + 12)?;
+                if next == 0 {
+                    break;
+                }
+                aux += next as u64;
+            }
+            let next = read_u32(&mut file, entry + 12)?;
+            if next == 0 {
+                break;
+            }
+            entry += next as u64;
+        }
+    }
+
+    if let (Some(mut entry), Some(count)) =
+        (value(DT_VERDEF).and_then(file_offset), value(DT_VERDEFNUM))
+    {
+        for _ in 0..count.min(MAX_VERSIONS as u64) {
+            let flags = read_u16(&mut file, entry + 2)?;
+            // The first auxiliary entry names the version; any after it name
+            // the versions it inherits from.
+            let aux = entry + read_u32(&mut file, entry + 12)? as u64;
+            if flags & VER_FLG_BASE == 0 {
+                if let Some(version) = string(read_u32(&mut file, aux)? as u64) {
+                    info.defined_versions.push(version);
+                }
+            }
+            let next = read_u32(&mut file, entry + 16)?;
+            if next == 0 {
+                break;
+            }
+            entry += next as u64;
+        }
+    }
+
+    Some(info)
+}
+
+/// The conventional name of an `e_machine` value, as `uname -m` prints it.
+pub fn machine_name(machine: u16) -> Option<&'static str> {
+    Some(match machine {
+        3 => "i686",
+        8 => "mips",
+        20 => "powerpc",
+        21 => "ppc64",
+        22 => "s390x",
+        40 => "arm",
+        62 => "x86_64",
+        183 => "aarch64",
+        243 => "riscv64",
+        258 => "loongarch64",
+        _ => return None,
+    })
+}
+
+/// The `e_machine` value of binaries built for this host.
+pub fn host_machine() -> Option<u16> {
+    Some(match std::env::consts::ARCH {
+        "x86" => 3,
+        "mips" | "mips64" => 8,
+        "powerpc" => 20,
+        "powerpc64" => 21,
+        "s390x" => 22,
+        "arm" => 40,
+        "x86_64" => 62,
+        "aarch64" => 183,
+        "riscv64" => 243,
+        "loongarch64" => 258,
+        _ => return None,
+    })
+}
+
 /// The NUL-terminated name starting at `offset` in the section name table.
 fn section_name(names: &[u8], offset: usize) -> Option<&str> {
     let rest = names.get(offset..)?;
@@ -141,6 +367,85 @@ mod tests {
         out
     }
 
+    /// A minimal dynamically linked 64-bit little-endian ELF for `machine`,
+    /// loaded at address zero so file offsets and addresses coincide.
+    fn dynamic_elf(machine: u16, interp: &str, needed: &[&str], runpath: &str) -> Vec<u8> {
+        let phoff = 64u64;
+        let phentsize = 56u64;
+        let interp_offset = phoff + 3 * phentsize;
+        let interp = format!("{interp}\0");
+
+        let mut strings = vec![0u8];
+        let mut add = |s: &str| {
+            let offset = strings.len() as u64;
+            strings.extend_from_slice(s.as_bytes());
+            strings.push(0);
+            offset
+        };
+        let needed: Vec<u64> = needed.iter().map(|n| add(n)).collect();
+        let runpath = add(runpath);
+        let libc = add("libc.so.6");
+        let glibc = add("GLIBC_2.34");
+        let strtab = interp_offset + interp.len() as u64;
+
+        // One Verneed for libc.so.6 with a single Vernaux for GLIBC_2.34.
+        let verneed = strtab + strings.len() as u64;
+        let mut versions = Vec::new();
+        versions.extend_from_slice(&1u16.to_le_bytes());
+        versions.extend_from_slice(&1u16.to_le_bytes());
+        versions.extend_from_slice(&(libc as u32).to_le_bytes());
+        versions.extend_from_slice(&16u32.to_le_bytes());
+        versions.extend_from_slice(&0u32.to_le_bytes());
+        versions.extend_from_slice(&0u32.to_le_bytes());
+        versions.extend_from_slice(&0u16.to_le_bytes());
+        versions.extend_from_slice(&2u16.to_le_bytes());
+        versions.extend_from_slice(&(glibc as u32).to_le_bytes());
+        versions.extend_from_slice(&0u32.to_le_bytes());
+
+        let dynamic_offset = verneed + versions.len() as u64;
+        let mut dynamic = Vec::new();
+        let mut tag = |t: u64, v: u64| {
+            dynamic.extend_from_slice(&t.to_le_bytes());
+            dynamic.extend_from_slice(&v.to_le_bytes());
+        };
+        for n in &needed {
+            tag(DT_NEEDED, *n);
+        }
+        tag(DT_RUNPATH, runpath);
+        tag(DT_STRTAB, strtab);
+        tag(DT_STRSZ, strings.len() as u64);
+        tag(DT_VERNEED, verneed);
+        tag(DT_VERNEEDNUM, 1);
+        tag(DT_NULL, 0);
+        let total = dynamic_offset + dynamic.len() as u64;
+
+        let mut out = vec![0u8; 64];
+        out[..4].copy_from_slice(&ELF_MAGIC);
+        out[4] = CLASS_64;
+        out[5] = DATA_LE;
+        out[0x12..0x14].copy_from_slice(&machine.to_le_bytes());
+        out[0x20..0x28].copy_from_slice(&phoff.to_le_bytes());
+        out[0x36..0x38].copy_from_slice(&(phentsize as u16).to_le_bytes());
+        out[0x38..0x3A].copy_from_slice(&3u16.to_le_bytes());
+        for (kind, offset, size) in [
+            (PT_LOAD, 0, total),
+            (PT_INTERP, interp_offset, interp.len() as u64),
+            (PT_DYNAMIC, dynamic_offset, dynamic.len() as u64),
+        ] {
+            let mut e = vec![0u8; phentsize as usize];
+            e[0..4].copy_from_slice(&kind.to_le_bytes());
+            e[0x08..0x10].copy_from_slice(&offset.to_le_bytes());
+            e[0x10..0x18].copy_from_slice(&offset.to_le_bytes());
+            e[0x20..0x28].copy_from_slice(&size.to_le_bytes());
+            out.extend_from_slice(&e);
+        }
+        out.extend_from_slice(interp.as_bytes());
+        out.extend_from_slice(&strings);
+        out.extend_from_slice(&versions);
+        out.extend_from_slice(&dynamic);
+        out
+    }
+
     fn write(bytes: &[u8]) -> (tempfile::TempDir, std::path::PathBuf) {
         let dir = tempdir().unwrap();
         let path = dir.path().join("app");
@@ -177,4 +482,41 @@ mod tests {
         let (_dir, plain) = write(b"#!/bin/sh\necho hi\n");
         assert_eq!(section_data(&plain, ".upd_info"), None);
     }
+
+    #[test]
+    fn inspects_what_a_dynamic_binary_needs() {
+        let (_dir, path) = write(&dynamic_elf(
+            183,
+            "/lib/ld-linux-aarch64.so.1",
+            &["libfoo.so.1", "libc.so.6"],
+            "$ORIGIN/../lib:/opt/foo",
+        ));
+        let info = inspect(&path).unwrap();
+
+        assert_eq!(info.machine, 183);
+        assert_eq!(machine_name(info.machine), Some("aarch64"));
+        assert_eq!(
+            info.interpreter.as_deref(),
+            Some("/lib/ld-linux-aarch64.so.1")
+        );
+        assert_eq!(info.needed, ["libfoo.so.1", "libc.so.6"]);
+        assert_eq!(info.search_paths, ["$ORIGIN/../lib", "/opt/foo"]);
+        assert_eq!(
+            info.required_versions,
+            [("libc.so.6".to_string(), "GLIBC_2.34".to_string())]
+        );
+        assert!(info.defined_versions.is_empty());
+    }
+
+    #[test]
+    fn a_file_that_is_not_elf_is_not_inspected() {
+        let (_dir, plain) = write(b"#!/bin/sh\necho hi\n");
+        assert_eq!(inspect(&plain), None);
+    }
+
+    #[test]
+    fn the_running_binary_matches_the_host() {
+        let info = inspect(std::env::current_exe().unwrap()).unwrap();
+        assert_eq!(Some(info.machine), host_machine());
+    }
 }
@@ -0,0 +1,716 @@
+//! What kind of program a file is, and what it needs in order to run.
+//!
+//! Not every command a package ships is an ELF. Shell and Python scripts name
+//! their interpreter in a `#!` line, a Python zipapp needs `python3` and a
+//! Java archive needs `java`. [`classify`] tells these apart by their contents,
+//! so a package laid out with a script or a `.jar` as its command can be made
+//! runnable and checked for the interpreter it relies on.
+
+use std::{
+    env,
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom},
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+/// The longest `#!` line read. Linux itself stops at 256 bytes.
+const SHEBANG_MAX: usize = 256;
+
+const ZIP_LOCAL_HEADER: [u8; 4] = *b"PK\x03\x04";
+const ZIP_CENTRAL_HEADER: [u8; 4] = *b"PK\x01\x02";
+const ZIP_END_OF_DIRECTORY: [u8; 4] = *b"PK\x05\x06";
+
+/// The end-of-central-directory record, less its trailing comment.
+const ZIP_END_LEN: u64 = 22;
+
+/// A runnable file, by how it gets started.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Entrypoint {
+    /// A native executable.
+    Elf,
+    /// A file starting with `#!`, run by `interpreter` with `args` before the
+    /// file itself. `interpreter` is a bare name when the line goes through
+    /// `/usr/bin/env`, and a path otherwise.
+    Script {
+        interpreter: String,
+        args: Vec<String>,
+    },
+    /// A Python zip application, with `__main__.py` at its root.
+    Zipapp,
+    /// A Java archive, with a `META-INF/MANIFEST.MF`.
+    Jar,
+}
+
+impl Entrypoint {
+    /// The program that runs it, as it would be looked up: `python3` for a
+    /// zipapp, `java` for a jar, and a script's interpreter. `None` for an ELF.
+    pub fn interpreter(&self) -> Option<&str> {
+        match self {
+            Entrypoint::Elf => None,
+            Entrypoint::Script {
+                interpreter, ..
+            } => Some(interpreter),
+            Entrypoint::Zipapp => Some("python3"),
+            Entrypoint::Jar => Some("java"),
+        }
+    }
+
+    /// Whether the file runs by itself once it is executable.
+    pub fn is_self_executing(&self) -> bool {
+        matches!(self, Entrypoint::Elf | Entrypoint::Script { .. })
+    }
+
+    /// The interpreter's command name, without a directory.
+    pub fn interpreter_name(&self) -> Option<&str> {
+        self.interpreter()
+            .map(|interpreter| interpreter.rsplit('/').next().unwrap_or(interpreter))
+    }
+
+    /// Where the interpreter is on this host, searching `PATH` and then
+    /// `extra_dirs` for a bare name. A script naming an absolute interpreter
+    /// that is missing falls back to a command of the same name, which is what
+    /// a launcher runs it with.
+    pub fn locate_interpreter(&self, extra_dirs: &[PathBuf]) -> Option<PathBuf> {
+        let interpreter = self.interpreter()?;
+        if interpreter.contains('/') && is_executable(Path::new(interpreter)) {
+            return Some(PathBuf::from(interpreter));
+        }
+        find_program(self.interpreter_name()?, extra_dirs)
+    }
+
+    /// The command line that runs the file at `path`, or `None` when it runs
+    /// fine as it is.
+    ///
+    /// A zipapp or jar is never executable by itself. A script is, unless its
+    /// `#!` line names an interpreter at a path this host does not have, in
+    /// which case it is run with whatever provides that command.
+    pub fn invocation(&self, path: &Path) -> Option<Vec<String>> {
+        let mut argv = match self {
+            Entrypoint::Elf => return None,
+            Entrypoint::Script {
+                interpreter,
+                args,
+            } => {
+                if !interpreter.contains('/') || is_executable(Path::new(interpreter)) {
+                    return None;
+                }
+                let mut argv = vec![self.interpreter_name()?.to_string()];
+                argv.extend(args.iter().cloned());
+                argv
+            }
+            Entrypoint::Zipapp => vec!["python3".to_string()],
+            Entrypoint::Jar => vec!["java".to_string(), "-jar".to_string()],
+        };
+        argv.push(path.to_string_lossy().into_owned());
+        Some(argv)
+    }
+
+    /// A `/bin/sh` launcher running the file at `path` as [`invocation`]
+    /// describes, or `None` when it needs none.
+    ///
+    /// [`invocation`]: Entrypoint::invocation
+    pub fn launcher(&self, path: &Path) -> Option<String> {
+        let argv = self.invocation(path)?;
+        let what = match self {
+            Entrypoint::Zipapp => "a Python zip application",
+            Entrypoint::Jar => "a Java archive",
+            _ => "a script whose interpreter is elsewhere here",
+        };
+        let command: Vec<String> = argv.iter().map(|arg| quote(arg)).collect();
+        Some(format!(
+            "#!/bin/sh\n\
+             # Generated by soar: runs {what}.\n\
+             exec {} \"$@\"\n",
+            command.join(" "),
+        ))
+    }
+}
+
+/// The soar subcommand a sandbox launcher runs its program through.
+///
+/// soar takes it before anything else reads the command line, so the
+/// program's own arguments reach it untouched.
+pub const SANDBOX_EXEC: &str = "sandbox-exec";
+
+/// A `/bin/sh` launcher running `program` through soar under the runtime
+/// sandbox profile at `profile`.
+///
+/// soar is looked up on `PATH` each time the launcher runs, with `soar` as
+/// the fallback: the binary that wrote the launcher can move or vanish, as an
+/// update replaces a soar installed through soar.
+pub fn sandbox_launcher(soar: &Path, profile: &Path, program: &Path) -> String {
+    let name = program
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    format!(
+        "#!/bin/sh\n\
+         # Generated by soar: runs {} in its sandbox.\n\
+         soar=$(command -v soar) || soar={}\n\
+         exec \"$soar\" {SANDBOX_EXEC} {} -- {} \"$@\"\n",
+        quote(&name),
+        quote(&soar.to_string_lossy()),
+        quote(&profile.to_string_lossy()),
+        quote(&program.to_string_lossy()),
+    )
+}
+
+/// A `/bin/sh` launcher running `program` with the launch settings a package
+/// was given: `env` exported, `pre_launch` run first, and `args` placed
+/// before the arguments it was called with.
+///
+/// Environment values may refer to other variables as `$NAME` or `${NAME}`,
+/// which are expanded when the launcher runs; nothing else in them is.
+/// `$INSTALL_DIR`, which is not exported, names `install_dir` for them and for
+/// `pre_launch`. A failing `pre_launch` stops the program from starting. Each
+/// name in `env` must pass [`is_variable_name`].
+pub fn settings_launcher<'a>(
+    program: &Path,
+    install_dir: &Path,
+    env: impl IntoIterator<Item = (&'a str, &'a str)>,
+    args: &[String],
+    pre_launch: Option<&str>,
+) -> String {
+    let name = program
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let mut script = format!(
+        "#!/bin/sh\n\
+         # Generated by soar: runs {} with its launch settings.\n\
+         INSTALL_DIR={}\n",
+        quote(&name),
+        quote(&install_dir.to_string_lossy()),
+    );
+    for (key, value) in env {
+        script.push_str(&format!("export {key}={}\n", quote_expanding(value)));
+    }
+    if let Some(pre_launch) = pre_launch {
+        script.push_str(&format!("{{\n{}\n}} || exit $?\n", pre_launch.trim_end()));
+    }
+    if !args.is_empty() {
+        let args = args.iter().map(|arg| quote(arg)).collect::<Vec<_>>();
+        script.push_str(&format!("set -- {} \"$@\"\n", args.join(" ")));
+    }
+    script.push_str(&format!(
+        "exec {} \"$@\"\n",
+        quote(&program.to_string_lossy())
+    ));
+    script
+}
+
+/// The files a [`version_shim`] looks for in each directory, in order.
+pub const VERSION_FILES: [&str; 2] = [".soar-version", ".tool-versions"];
+
+/// A `/bin/sh` shim running the version of `pkg_name` a project selects, or
+/// `default` where none does.
+///
+/// The shim walks up from the current directory to the first of
+/// [`VERSION_FILES`] naming the package. As in a `.tool-versions` file, each
+/// line gives a package and its version. `versions` pairs every installed
+/// version with what its command runs; a version that is not among them is
+/// an error rather than a fall back to `default`.
+pub fn version_shim(pkg_name: &str, default: &Path, versions: &[(String, PathBuf)]) -> String {
+    let name = default
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    let files = VERSION_FILES
+        .iter()
+        .map(|file| format!("\"$dir/{file}\""))
+        .collect::<Vec<_>>();
+    let mut script = format!(
+        "#!/bin/sh\n\
+         # Generated by soar: runs {} at the version a project selects.\n\
+         pkg={}\n\
+         dir=${{PWD%/}}\n\
+         while :; do\n\
+         for file in {}; do\n\
+         [ -f \"$file\" ] || continue\n\
+         version=$(awk -v pkg=\"$pkg\" '$1 == pkg {{ print $2; exit }}' \"$file\")\n\
+         case $version in\n\
+         '') continue ;;\n",
+        quote(&name),
+        quote(pkg_name),
+        files.join(" "),
+    );
+    for (version, program) in versions {
+        script.push_str(&format!(
+            "{}) exec {} \"$@\" ;;\n",
+            quote(version),
+            quote(&program.to_string_lossy())
+        ));
+    }
+    script.push_str(&format!(
+        "esac\n\
+         echo \"$pkg $version, selected by $file, is not installed\" >&2\n\
+         exit 127\n\
+         done\n\
+         case $dir in\n\
+         */*) dir=${{dir%/*}} ;;\n\
+         *) break ;;\n\
+         esac\n\
+         done\n\
+         exec {} \"$@\"\n",
+        quote(&default.to_string_lossy()),
+    ));
+    script
+}
+
+/// Whether `name` is an environment variable a shell can set, which is what a
+/// [`settings_launcher`] can export.
+pub fn is_variable_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The file a launcher written by [`Entrypoint::launcher`],
+/// [`sandbox_launcher`] or [`settings_launcher`] runs, or `None` when
+/// `script` is not one. For a [`version_shim`], that is its default.
+pub fn launched_file(script: &str) -> Option<PathBuf> {
+    if !script
+        .lines()
+        .nth(1)
+        .is_some_and(|line| line.starts_with("# Generated by soar: runs"))
+    {
+        return None;
+    }
+    // A settings launcher's `pre_launch` comes first and may exec on its own.
+    let exec = script
+        .lines()
+        .rev()
+        .find_map(|line| line.strip_prefix("exec "))?;
+    // A sandbox launcher runs whichever soar it looked up.
+    let exec = exec.strip_prefix("\"$soar\" ").unwrap_or(exec);
+    let words = unquote(exec)?;
+    match words.as_slice() {
+        [.., file, last] if last == "$@" => Some(PathBuf::from(file)),
+        _ => None,
+    }
+}
+
+/// What kind of program the file at `path` is, or `None` when it is not one.
+pub fn classify(path: impl AsRef<Path>) -> Option<Entrypoint> {
+    let mut file = File::open(path).ok()?;
+    let mut head = [0u8; SHEBANG_MAX];
+    let mut len = 0;
+    while len < head.len() {
+        match file.read(&mut head[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(_) => return None,
+        }
+    }
+    let head = &head[..len];
+
+    if head.starts_with(b"\x7fELF") {
+        return Some(Entrypoint::Elf);
+    }
+    if let Some(line) = head.strip_prefix(b"#!") {
+        return parse_shebang(line);
+    }
+    if head.starts_with(&ZIP_LOCAL_HEADER) {
+        let names = zip_entry_names(&mut file)?;
+        if names.iter().any(|name| name == "__main__.py") {
+            return Some(Entrypoint::Zipapp);
+        }
+        if names
+            .iter()
+            .any(|name| name.eq_ignore_ascii_case("META-INF/MANIFEST.MF"))
+        {
+            return Some(Entrypoint::Jar);
+        }
+    }
+    None
+}
+
+/// Whether the file is a zip meant to be run whole, which must not be
+/// unpacked the way an archive of files is.
+pub fn is_zip_program(path: impl AsRef<Path>) -> bool {
+    matches!(classify(path), Some(Entrypoint::Zipapp | Entrypoint::Jar))
+}
+
+/// The executable `name` in `PATH`, or else in one of `extra_dirs`.
+pub fn find_program(name: &str, extra_dirs: &[PathBuf]) -> Option<PathBuf> {
+    let path = env::var_os("PATH").unwrap_or_default();
+    env::split_paths(&path)
+        .chain(extra_dirs.iter().cloned())
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .map(|dir| dir.join(name))
+        .find(|candidate| is_executable(candidate))
+}
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+/// The interpreter and its arguments from what follows `#!`.
+///
+/// `/usr/bin/env` is looked through to the command it runs, skipping its own
+/// options and any `NAME=value` assignments, as `env -S` lines use.
+fn parse_shebang(line: &[u8]) -> Option<Entrypoint> {
+    let end = line.iter().position(|&b| b == b'\n').unwrap_or(line.len());
+    let line = std::str::from_utf8(&line[..end]).ok()?;
+    let mut words = line.split_whitespace();
+    let program = words.next()?;
+
+    if program.rsplit('/').next() != Some("env") {
+        return Some(Entrypoint::Script {
+            interpreter: program.to_string(),
+            args: words.map(str::to_string).collect(),
+        });
+    }
+
+    let mut words = words.skip_while(|word| word.starts_with('-') || word.contains('='));
+    let interpreter = words.next()?.to_string();
+    Some(Entrypoint::Script {
+        interpreter,
+        args: words.map(str::to_string).collect(),
+    })
+}
+
+/// The names of a zip's entries, read from its central directory.
+///
+/// The directory is located from the end of the file, so a zip with data
+/// before it (a zipapp with a stub prepended, say) reads the same.
+fn zip_entry_names(file: &mut File) -> Option<Vec<String>> {
+    let size = file.seek(SeekFrom::End(0)).ok()?;
+    let tail_len = size.min(ZIP_END_LEN + u64::from(u16::MAX));
+    file.seek(SeekFrom::Start(size - tail_len)).ok()?;
+    let mut tail = vec![0u8; tail_len as usize];
+    file.read_exact(&mut tail).ok()?;
+
+    let end = tail
+        .windows(4)
+        .rposition(|window| window == ZIP_END_OF_DIRECTORY)?;
+    let record = tail.get(end..end + ZIP_END_LEN as usize)?;
+    let count = u16::from_le_bytes([record[10], record[11]]) as usize;
+    let dir_size = u32::from_le_bytes(record[12..16].try_into().ok()?) as u64;
+
+    let dir_start = (size - tail_len + end as u64).checked_sub(dir_size)?;
+    file.seek(SeekFrom::Start(dir_start)).ok()?;
+    let mut dir = vec![0u8; dir_size as usize];
+    file.read_exact(&mut dir).ok()?;
+
+    let mut names = Vec::with_capacity(count);
+    let mut offset = 0;
+    for _ in 0..count {
+        let header = dir.get(offset..offset + 46)?;
+        if header[..4] != ZIP_CENTRAL_HEADER {
+            return None;
+        }
+        let field = |at: usize| u16::from_le_bytes([header[at], header[at + 1]]) as usize;
+        let (name_len, extra_len, comment_len) = (field(28), field(30), field(32));
+        let name = dir.get(offset + 46..offset + 46 + name_len)?;
+        names.push(String::from_utf8_lossy(name).into_owned());
+        offset += 46 + name_len + extra_len + comment_len;
+    }
+    Some(names)
+}
+
+fn quote(value: &str) -> String {
+    if !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:+,@%".contains(c))
+    {
+        return value.to_string();
+    }
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// `value` in double quotes, leaving `$NAME` and `${NAME}` to be expanded
+/// and any other `$`, backquote, or backslash as written.
+fn quote_expanding(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '$' if chars
+                .peek()
+                .is_some_and(|&next| next == '{' || next == '_' || next.is_ascii_alphabetic()) =>
+            {
+                quoted.push('$')
+            }
+            '$' | '`' | '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Split a command line written with [`quote`] back into its words. A
+/// double-quoted word is taken verbatim, and anything else `quote` never
+/// writes fails.
+fn unquote(line: &str) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    let mut chars = line.trim().chars().peekable();
+    while chars.peek().is_some() {
+        let mut word = String::new();
+        while let Some(c) = chars.next() {
+            match c {
+                ' ' => break,
+                '\'' => {
+                    loop {
+                        match chars.next()? {
+                            '\'' => break,
+                            c => word.push(c),
+                        }
+                    }
+                }
+                '\\' => word.push(chars.next()?),
+                '"' => {
+                    let quoted: String = chars.by_ref().take_while(|&c| c != '"').collect();
+                    if quoted.contains(['$', '`', '\\']) && quoted != "$@" {
+                        return None;
+                    }
+                    word.push_str(&quoted);
+                }
+                '$' | '`' => return None,
+                c => word.push(c),
+            }
+        }
+        while chars.peek() == Some(&' ') {
+            chars.next();
+        }
+        words.push(word);
+    }
+    Some(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tempfile::tempdir;
+
+    use super::*;
+
+    /// A stored (uncompressed) zip holding empty entries with these names.
+    fn zip(names: &[&str]) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut dir = Vec::new();
+        for name in names {
+            let offset = data.len() as u32;
+            data.extend_from_slice(&ZIP_LOCAL_HEADER);
+            data.extend_from_slice(&[0; 22]);
+            data.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            data.extend_from_slice(&[0; 2]);
+            data.extend_from_slice(name.as_bytes());
+
+            dir.extend_from_slice(&ZIP_CENTRAL_HEADER);
+            dir.extend_from_slice(&[0; 24]);
+            dir.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            dir.extend_from_slice(&[0; 12]);
+            dir.extend_from_slice(&offset.to_le_bytes());
+            dir.extend_from_slice(name.as_bytes());
+        }
+        let dir_offset = data.len() as u32;
+        data.extend_from_slice(&dir);
+        data.extend_from_slice(&ZIP_END_OF_DIRECTORY);
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&(names.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(names.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(dir.len() as u32).to_le_bytes());
+        data.extend_from_slice(&dir_offset.to_le_bytes());
+        data.extend_from_slice(&[0; 2]);
+        data
+    }
+
+    fn write(dir: &Path, name: &str, content: &[u8]) -> PathBuf {
+        let path = dir.join(name);
+        File::create(&path).unwrap().write_all(content).unwrap();
+        path
+    }
+
+    #[test]
+    fn shebangs_name_their_interpreter() {
+        let dir = tempdir().unwrap();
+        let sh = write(dir.path(), "a", b"#!/bin/sh -e\necho hi\n");
+        assert_eq!(
+            classify(&sh),
+            Some(Entrypoint::Script {
+                interpreter: "/bin/sh".into(),
+                args: vec!["-e".into()],
+            })
+        );
+
+        let env = write(
+            dir.path(),
+            "b",
+            b"#!/usr/bin/env -S PYTHONUNBUFFERED=1 python3 -u\n",
+        );
+        let env = classify(&env).unwrap();
+        assert_eq!(env.interpreter(), Some("python3"));
+        assert_eq!(env.launcher(Path::new("/x")), None);
+    }
+
+    #[test]
+    fn zips_are_told_apart_by_their_entries() {
+        let dir = tempdir().unwrap();
+        let pyz = write(dir.path(), "tool", &zip(&["pkg/mod.py", "__main__.py"]));
+        let jar = write(
+            dir.path(),
+            "app",
+            &zip(&["META-INF/", "META-INF/MANIFEST.MF", "Main.class"]),
+        );
+        let plain = write(dir.path(), "data.zip", &zip(&["README"]));
+
+        assert_eq!(classify(&pyz), Some(Entrypoint::Zipapp));
+        assert_eq!(classify(&jar), Some(Entrypoint::Jar));
+        assert_eq!(classify(&plain), None);
+        assert!(is_zip_program(&jar));
+        assert!(!is_zip_program(&plain));
+    }
+
+    #[test]
+    fn launchers_run_the_file_through_its_interpreter() {
+        let path = Path::new("/opt/pkgs/my app/app.jar");
+        assert_eq!(
+            Entrypoint::Jar.launcher(path).unwrap(),
+            "#!/bin/sh\n# Generated by soar: runs a Java archive.\nexec java -jar '/opt/pkgs/my app/app.jar' \"$@\"\n"
+        );
+        assert!(Entrypoint::Zipapp
+            .launcher(Path::new("/p/tool.pyz"))
+            .unwrap()
+            .contains("exec python3 /p/tool.pyz \"$@\""));
+
+        let moved = Entrypoint::Script {
+            interpreter: "/nonexistent/bin/perl".into(),
+            args: vec!["-w".into()],
+        };
+        assert_eq!(moved.interpreter_name(), Some("perl"));
+        assert!(moved
+            .launcher(Path::new("/p/tool"))
+            .unwrap()
+            .contains("exec perl -w /p/tool \"$@\""));
+        assert_eq!(Entrypoint::Elf.launcher(path), None);
+    }
+
+    #[test]
+    fn launchers_lead_back_to_their_file() {
+        let path = Path::new("/opt/pkgs/it's here/app.jar");
+        let launcher = Entrypoint::Jar.launcher(path).unwrap();
+        assert_eq!(launched_file(&launcher), Some(path.to_path_buf()));
+
+        let sandboxed = sandbox_launcher(
+            Path::new("/usr/bin/soar"),
+            Path::new("/p/.soar_sandbox.toml"),
+            Path::new("/p/my tool"),
+        );
+        assert!(sandboxed.contains("soar=$(command -v soar) || soar=/usr/bin/soar\n"));
+        assert!(sandboxed
+            .contains("exec \"$soar\" sandbox-exec /p/.soar_sandbox.toml -- '/p/my tool' \"$@\""));
+        assert_eq!(launched_file(&sandboxed), Some(PathBuf::from("/p/my tool")));
+
+        // An extracted AppImage's launcher runs a path from the environment.
+        let appimage = "#!/bin/sh\n# Generated by soar: runs an AppImage extracted for systems without FUSE.\nexec \"$APPDIR/AppRun\" \"$@\"\n";
+        assert_eq!(launched_file(appimage), None);
+        assert_eq!(
+            launched_file("#!/bin/sh\nexec java -jar x.jar \"$@\"\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn settings_launchers_export_run_and_prepend() {
+        let launcher = settings_launcher(
+            Path::new("/p/.launchers/my tool"),
+            Path::new("/p"),
+            [("LIB", "$INSTALL_DIR/lib"), ("PRICE", "5$ `x` \"q\"")],
+            &["--config".into(), "a b".into()],
+            Some("mkdir -p \"$HOME/.cache/t\"\nexec true"),
+        );
+        assert_eq!(
+            launcher,
+            "#!/bin/sh\n\
+             # Generated by soar: runs 'my tool' with its launch settings.\n\
+             INSTALL_DIR=/p\n\
+             export LIB=\"$INSTALL_DIR/lib\"\n\
+             export PRICE=\"5\\$ \\`x\\` \\\"q\\\"\"\n\
+             {\n\
+             mkdir -p \"$HOME/.cache/t\"\n\
+             exec true\n\
+             } || exit $?\n\
+             set -- --config 'a b' \"$@\"\n\
+             exec '/p/.launchers/my tool' \"$@\"\n"
+        );
+        assert_eq!(
+            launched_file(&launcher),
+            Some(PathBuf::from("/p/.launchers/my tool"))
+        );
+
+        let bare = settings_launcher(Path::new("/p/tool"), Path::new("/p"), [], &[], None);
+        assert!(bare.ends_with("INSTALL_DIR=/p\nexec /p/tool \"$@\"\n"));
+    }
+
+    #[test]
+    fn version_shims_run_what_the_nearest_project_selects() {
+        let dir = tempdir().unwrap();
+        let program = |version: &str| {
+            let path = dir.path().join(format!("kubectl-{version}"));
+            fs::write(&path, format!("#!/bin/sh\necho {version} \"$@\"\n")).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+            path
+        };
+        let shim = dir.path().join("kubectl");
+        fs::write(
+            &shim,
+            version_shim(
+                "kubectl",
+                &program("1.30"),
+                &[
+                    ("1.28".into(), program("1.28")),
+                    ("1.30".into(), program("1.30")),
+                ],
+            ),
+        )
+        .unwrap();
+        assert_eq!(
+            launched_file(&fs::read_to_string(&shim).unwrap()),
+            Some(program("1.30"))
+        );
+
+        let project = dir.path().join("project");
+        let nested = project.join("src/deep");
+        fs::create_dir_all(&nested).unwrap();
+        let run = |cwd: &Path| {
+            let output = std::process::Command::new("sh")
+                .arg(&shim)
+                .arg("get")
+                .current_dir(cwd)
+                .env("PWD", cwd)
+                .output()
+                .unwrap();
+            (
+                output.status.code(),
+                String::from_utf8_lossy(&output.stdout).trim().to_string(),
+            )
+        };
+
+        assert_eq!(run(&nested), (Some(0), "1.30 get".into()));
+
+        fs::write(project.join(".tool-versions"), "kubectl 1.28\n").unwrap();
+        assert_eq!(run(&nested), (Some(0), "1.28 get".into()));
+
+        // A nearer file that does not name the package is passed over.
+        fs::write(nested.join(".soar-version"), "terraform 1.5.0\n").unwrap();
+        assert_eq!(run(&nested), (Some(0), "1.28 get".into()));
+
+        fs::write(nested.join(".soar-version"), "# pinned\nkubectl 1.27\n").unwrap();
+        assert_eq!(run(&nested).0, Some(127));
+    }
+
+    #[test]
+    fn other_files_are_not_programs() {
+        let dir = tempdir().unwrap();
+        assert_eq!(classify(write(dir.path(), "t", b"plain text\n")), None);
+        assert_eq!(classify(write(dir.path(), "e", b"")), None);
+        assert_eq!(classify(dir.path().join("missing")), None);
+    }
+}
@@ -1,5 +1,7 @@
 pub mod bytes;
+pub mod cancel;
 pub mod elf;
+pub mod entrypoint;
 pub mod error;
 pub mod fs;
 pub mod hash;
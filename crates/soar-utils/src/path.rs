@@ -29,6 +29,29 @@ pub fn is_safe_component(name: &str) -> bool {
     matches!(components.next(), Some(Component::Normal(_))) && components.next().is_none()
 }
 
+/// Returns `true` if `path` is a relative path that stays inside the directory
+/// it is joined to.
+///
+/// Unlike [`is_safe_component`], `path` may have several components, so this
+/// suits values that name a file below a directory, such as the targets of a
+/// package's `files`. Every component must be a normal name: `.`, `..` and a
+/// leading `/` are all rejected.
+///
+/// # Example
+///
+/// ```
+/// use soar_utils::path::is_safe_relative;
+///
+/// assert!(is_safe_relative("share/man/man1/bat.1"));
+/// assert!(!is_safe_relative("bin/../../etc/passwd"));
+/// ```
+pub fn is_safe_relative(path: &str) -> bool {
+    !path.is_empty()
+        && Path::new(path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+}
+
 /// Resolves a path string that may contain environment variables
 ///
 /// This method expands environment variables in the format `$VAR` or `${VAR}`, resolves tilde
@@ -179,6 +202,34 @@ pub fn icons_dir(system: bool) -> PathBuf {
     }
 }
 
+/// Returns the shared MIME database directory, whose `packages` subdirectory
+/// holds MIME type definitions
+pub fn mime_dir(system: bool) -> PathBuf {
+    if system {
+        PathBuf::from("/usr/local/share/mime")
+    } else {
+        xdg_data_home().join("mime")
+    }
+}
+
+/// Returns the AppStream metainfo directory
+pub fn metainfo_dir(system: bool) -> PathBuf {
+    if system {
+        PathBuf::from("/usr/local/share/metainfo")
+    } else {
+        xdg_data_home().join("metainfo")
+    }
+}
+
+/// Returns the `mimeapps.list` holding default applications
+pub fn mimeapps_list(system: bool) -> PathBuf {
+    if system {
+        PathBuf::from("/etc/xdg/mimeapps.list")
+    } else {
+        xdg_config_home().join("mimeapps.list")
+    }
+}
+
 fn expand_variables(path: &str) -> PathResult<String> {
     let mut result = String::with_capacity(path.len());
     let mut chars = path.chars().peekable();
@@ -275,6 +326,17 @@ mod tests {
         assert!(!super::is_safe_component("/etc/passwd"));
     }
 
+    #[test]
+    fn test_is_safe_relative() {
+        assert!(super::is_safe_relative("bin/bat"));
+        assert!(super::is_safe_relative("share/man/man1/bat.1"));
+
+        assert!(!super::is_safe_relative(""));
+        assert!(!super::is_safe_relative("/usr/bin/bat"));
+        assert!(!super::is_safe_relative("../bat"));
+        assert!(!super::is_safe_relative("bin/../../bat"));
+    }
+
     use std::env;
 
     use serial_test::serial;
@@ -517,4 +579,26 @@ mod tests {
         let icons = icons_dir(true);
         assert_eq!(icons, PathBuf::from("/usr/local/share/icons/hicolor"));
     }
+
+    #[test]
+    #[serial]
+    fn test_mime_dirs() {
+        // User mode
+        env::set_var("XDG_DATA_HOME", "/tmp/data");
+        env::set_var("XDG_CONFIG_HOME", "/tmp/config");
+        assert_eq!(mime_dir(false), PathBuf::from("/tmp/data/mime"));
+        assert_eq!(metainfo_dir(false), PathBuf::from("/tmp/data/metainfo"));
+        assert_eq!(
+            mimeapps_list(false),
+            PathBuf::from("/tmp/config/mimeapps.list")
+        );
+
+        // System mode
+        assert_eq!(mime_dir(true), PathBuf::from("/usr/local/share/mime"));
+        assert_eq!(
+            metainfo_dir(true),
+            PathBuf::from("/usr/local/share/metainfo")
+        );
+        assert_eq!(mimeapps_list(true), PathBuf::from("/etc/xdg/mimeapps.list"));
+    }
 }
@@ -80,6 +80,8 @@ export default defineConfig({
             { text: 'Search Packages', link: '/search' },
             { text: 'List Packages', link: '/list' },
             { text: 'Use Package', link: '/use' },
+            { text: 'Command Alternatives', link: '/alternatives' },
+            { text: 'File Associations', link: '/mime' },
             { text: 'Run Package', link: '/run' },
             { text: 'Inspect Packages', link: '/inspection' },
           ],
@@ -98,6 +100,7 @@ export default defineConfig({
           items: [
             { text: 'Health', link: '/health' },
             { text: 'Maintenance', link: '/maintenance' },
+            { text: 'Daemon', link: '/daemon' },
           ],
         },
         {